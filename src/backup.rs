@@ -0,0 +1,126 @@
+//! Backup versions of saved files.
+//!
+//! Before a file is overwritten, its previous version is copied into a `.rx-backups`
+//! directory next to it. Backups are numbered from the most recent one, eg.
+//! `.rx-backups/sprite.1.png`, so that they keep the file extension and can be
+//! opened like any other image.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time;
+
+/// Name of the backup directory.
+pub const DIR: &str = ".rx-backups";
+
+/// Path of the `n`th most recent backup of a file, starting from `1`.
+pub fn path(path: &Path, n: usize) -> PathBuf {
+    let dir = path.parent().unwrap_or_else(|| Path::new("")).join(DIR);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    match path.extension() {
+        Some(ext) => dir.join(format!("{}.{}.{}", stem, n, ext.to_string_lossy())),
+        None => dir.join(format!("{}.{}", stem, n)),
+    }
+}
+
+/// Back up a file before it's overwritten, keeping at most `keep` versions.
+/// Returns `false` if there was nothing to back up.
+pub fn rotate(file: &Path, keep: usize) -> io::Result<bool> {
+    if keep == 0 || !file.is_file() {
+        return Ok(false);
+    }
+    // Make room for the new backup, and drop the ones left over from when more
+    // versions were kept.
+    for n in keep.. {
+        let old = self::path(file, n);
+
+        if !old.exists() {
+            break;
+        }
+        fs::remove_file(old)?;
+    }
+    for n in (1..keep).rev() {
+        let from = self::path(file, n);
+
+        if from.exists() {
+            fs::rename(from, self::path(file, n + 1))?;
+        }
+    }
+    let newest = self::path(file, 1);
+
+    if let Some(dir) = newest.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::copy(file, newest)?;
+
+    Ok(true)
+}
+
+/// The backups of a file, from the most recent, with their modification time.
+pub fn list(file: &Path) -> Vec<(usize, time::SystemTime)> {
+    (1..)
+        .map(|n| (n, self::path(file, n)))
+        .take_while(|(_, path)| path.is_file())
+        .map(|(n, path)| {
+            let modified = fs::metadata(path)
+                .and_then(|m| m.modified())
+                .unwrap_or(time::UNIX_EPOCH);
+            (n, modified)
+        })
+        .collect()
+}
+
+/// Restore the `n`th backup of a file. The current version of the file
+/// is backed up first, so that restoring can itself be undone.
+pub fn restore(file: &Path, n: usize, keep: usize) -> io::Result<()> {
+    let backup = self::path(file, n);
+
+    if !backup.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("backup {} of `{}` not found", n, file.display()),
+        ));
+    }
+    let contents = fs::read(backup)?;
+
+    self::rotate(file, keep)?;
+    fs::write(file, contents)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backups() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("sprite.png");
+
+        assert!(!rotate(&file, 2).unwrap());
+
+        for version in &["a", "b", "c"] {
+            rotate(&file, 2).unwrap();
+            fs::write(&file, version).unwrap();
+        }
+        assert_eq!(
+            path(&file, 1),
+            tmp.path().join(".rx-backups").join("sprite.1.png")
+        );
+        assert_eq!(list(&file).len(), 2);
+        assert_eq!(fs::read(path(&file, 1)).unwrap(), b"b");
+        assert_eq!(fs::read(path(&file, 2)).unwrap(), b"a");
+
+        restore(&file, 2, 2).unwrap();
+        assert_eq!(fs::read(&file).unwrap(), b"a");
+        assert_eq!(fs::read(path(&file, 1)).unwrap(), b"c");
+        assert!(restore(&file, 9, 2).is_err());
+
+        // Keeping fewer versions prunes the older ones.
+        rotate(&file, 1).unwrap();
+        assert_eq!(list(&file).len(), 1);
+        assert_eq!(fs::read(path(&file, 1)).unwrap(), b"a");
+    }
+}
@@ -11,6 +11,7 @@ use crate::util::vector_angle;
 use std::collections::BTreeSet;
 use std::f32::consts::PI;
 use std::fmt;
+use std::mem;
 
 /// Input state of the brush.
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -29,35 +30,131 @@ pub enum BrushState {
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
 pub enum BrushMode {
     /// Erase pixels.
-    Erase,
-    /// Draw on all frames at once.
-    Multi,
+    Erase(
+        /// erase to the background color, instead of transparent
+        bool,
+    ),
+    /// Draw on multiple frames at once.
+    Multi(Frames),
+    /// Paint the x-mirrored stroke on the opposite frame, ie. frame `n - 1 - i`
+    /// when painting on frame `i` of `n`.
+    Mirror,
     /// Pixel-perfect mode.
     Perfect,
     /// X-Symmetry mode.
-    XSym,
+    XSym(
+        /// axis position, relative to the frame (defaults to the frame center)
+        Option<u32>,
+    ),
     /// Y-Symmetry mode.
-    YSym,
+    YSym(
+        /// axis position, relative to the frame (defaults to the frame center)
+        Option<u32>,
+    ),
     /// X-Ray mode.
     XRay,
+    /// Paint only where pixels are transparent, behind existing pixels.
+    Behind,
+    /// Paint only where pixels are already opaque.
+    Replace,
+    /// Mirror the stroke across the two 2:1 isometric axes through the frame center.
+    IsoSym,
+    /// Mirror the stroke across the x and y axes and both diagonals through the frame center.
+    OctSym,
+    /// Start strokes with a straight line from the end of the last stroke.
+    Connect,
     /// Confine stroke to a straight line from the starting point
     Line(
-        /// snap angle (degrees)
-        Option<u32>,
+        /// snap angle
+        Option<Snap>,
     ),
+    /// Draw an ellipse outline around the starting point, through the brush position.
+    Circle,
+}
+
+/// Snap angle of line strokes.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Snap {
+    /// Snap to multiples of the given angle, in degrees.
+    Degrees(u32),
+    /// Snap to the horizontal, vertical and 2:1 isometric directions.
+    Iso,
+    /// Snap to the ray through the nearest vanishing point.
+    Vanishing,
+}
+
+impl fmt::Display for Snap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Degrees(n) => n.fmt(f),
+            Self::Iso => "iso".fmt(f),
+            Self::Vanishing => "vp".fmt(f),
+        }
+    }
 }
 
 impl fmt::Display for BrushMode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Erase => "erase".fmt(f),
-            Self::Multi => "multi".fmt(f),
+            Self::Erase(false) => "erase".fmt(f),
+            Self::Erase(true) => "erase bg".fmt(f),
+            Self::Multi(Frames::Rest) => "multi".fmt(f),
+            Self::Multi(frames) => write!(f, "multi {}", frames),
+            Self::Mirror => "mirror".fmt(f),
             Self::Perfect => "perfect".fmt(f),
-            Self::XSym => "xsym".fmt(f),
-            Self::YSym => "ysym".fmt(f),
+            Self::XSym(Some(x)) => write!(f, "xsym {}", x),
+            Self::XSym(None) => "xsym".fmt(f),
+            Self::YSym(Some(y)) => write!(f, "ysym {}", y),
+            Self::YSym(None) => "ysym".fmt(f),
             Self::XRay => "xray".fmt(f),
-            Self::Line(Some(snap)) => write!(f, "{} degree snap line", snap),
+            Self::Behind => "behind".fmt(f),
+            Self::Replace => "replace".fmt(f),
+            Self::IsoSym => "isosym".fmt(f),
+            Self::OctSym => "octsym".fmt(f),
+            Self::Connect => "connect".fmt(f),
+            Self::Line(Some(Snap::Degrees(snap))) => write!(f, "{} degree snap line", snap),
+            Self::Line(Some(Snap::Iso)) => write!(f, "iso snap line"),
+            Self::Line(Some(Snap::Vanishing)) => write!(f, "vp snap line"),
             Self::Line(None) => write!(f, "line"),
+            Self::Circle => "circle".fmt(f),
+        }
+    }
+}
+
+/// Frames painted on in multi-frame mode, in addition to the frame under
+/// the brush.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Frames {
+    /// From the current frame through the last frame.
+    Rest,
+    /// All frames.
+    All,
+    /// From the first to the last frame given, inclusive.
+    Range(u32, u32),
+    /// Every n-th frame, counting from the current frame.
+    Every(u32),
+}
+
+impl Frames {
+    /// Check whether the frame at `index` is painted on, when painting on the
+    /// `current` frame.
+    pub fn contains(&self, index: u32, current: u32) -> bool {
+        match *self {
+            Self::Rest => index >= current,
+            Self::All => true,
+            Self::Range(a, b) => index >= a.min(b) && index <= a.max(b),
+            Self::Every(n) => (index as i64 - current as i64) % n.max(1) as i64 == 0,
+        }
+    }
+}
+
+impl fmt::Display for Frames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rest => Ok(()),
+            Self::All => "all".fmt(f),
+            Self::Range(a, b) => write!(f, "{} {}", a, b),
+            Self::Every(n) => write!(f, "every {}", n),
         }
     }
 }
@@ -68,6 +165,60 @@ pub enum Align {
     BottomLeft,
 }
 
+/// Random variation of the brush color, applied to each stamped pixel.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct Jitter {
+    /// Maximum number of palette entries away from the brush color.
+    pub color: usize,
+    /// Maximum amount of alpha taken away from the brush color.
+    pub alpha: u8,
+}
+
+impl Jitter {
+    /// Whether any jitter is set.
+    pub fn is_set(&self) -> bool {
+        self.color > 0 || self.alpha > 0
+    }
+
+    /// The color of the pixel at `p`, varied between the palette entries next to
+    /// `color`. Colors that aren't in the palette only have their alpha varied.
+    /// The same pixel and seed always give the same color, so that strokes
+    /// replay identically.
+    pub fn apply(&self, color: Rgba8, palette: &[Rgba8], p: Point2<i32>, seed: u32) -> Rgba8 {
+        let h = Self::hash(p, seed);
+        let mut c = color;
+
+        // Colors are picked evenly from the range around `color`, clamped to the
+        // palette, so that entries near either end aren't picked more often.
+        if self.color > 0 {
+            if let Some(i) = palette.iter().position(|x| *x == color) {
+                let from = i.saturating_sub(self.color);
+                let to = (i + self.color).min(palette.len() - 1);
+
+                c = palette[from + (h & 0xffff) as usize % (to - from + 1)];
+            }
+        }
+        if self.alpha > 0 {
+            let max = self.alpha.min(c.a) as u32;
+            c.a -= ((h >> 16) % (max + 1)) as u8;
+        }
+        c
+    }
+
+    fn hash(p: Point2<i32>, seed: u32) -> u32 {
+        let mut h = (p.x as u32).wrapping_mul(0x27d4_eb2d)
+            ^ (p.y as u32).wrapping_mul(0x1656_67b1)
+            ^ seed.wrapping_mul(0x9e37_79b9);
+
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x2c1b_3c6d);
+        h ^= h >> 12;
+        h = h.wrapping_mul(0x297a_2d39);
+        h ^= h >> 15;
+        h
+    }
+}
+
 /// Brush context.
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Brush {
@@ -79,13 +230,34 @@ pub struct Brush {
     pub stroke: Vec<Point2<i32>>,
     /// Current stroke color.
     pub color: Rgba8,
+    /// Distance in pixels between the points of a stroke, or `1` for a
+    /// contiguous stroke.
+    pub spacing: usize,
+    /// Snap angle of line strokes in degrees, overriding the angle of the line
+    /// mode. Usually set while a modifier key is held.
+    pub snap: Option<Snap>,
+    /// Random variation of the stroke color.
+    pub jitter: Jitter,
+    /// Where the last stroke ended, for connecting the next one to it.
+    pub last: Option<Point2<i32>>,
+    /// Vanishing points for perspective lines, in layer coordinates. At most two
+    /// points are kept.
+    pub vanishing: Vec<Point2<i32>>,
+    /// Size of the grid the stroke points are snapped to, or `0` to not snap.
+    pub grid: usize,
+    /// Where the current stroke started.
+    origin: Point2<i32>,
 
     /// Currently active brush modes.
     modes: BTreeSet<BrushMode>,
+    /// Current brush path, before spacing is applied.
+    path: Vec<Point2<i32>>,
     /// Current brush position.
     curr: Point2<i32>,
     /// Previous brush position.
     prev: Point2<i32>,
+    /// Number of strokes started, used to vary the jitter between strokes.
+    strokes: u32,
 }
 
 impl Default for Brush {
@@ -95,9 +267,18 @@ impl Default for Brush {
             state: BrushState::NotDrawing,
             stroke: Vec::with_capacity(32),
             color: Rgba8::TRANSPARENT,
+            spacing: 1,
+            snap: None,
+            jitter: Jitter::default(),
+            last: None,
+            vanishing: Vec::new(),
+            grid: 0,
+            origin: Point2::new(0, 0),
             modes: BTreeSet::new(),
+            path: Vec::with_capacity(32),
             curr: Point2::new(0, 0),
             prev: Point2::new(0, 0),
+            strokes: 0,
         }
     }
 }
@@ -108,39 +289,103 @@ impl Brush {
         self.modes.contains(&m)
     }
 
+    /// Return the active brush modes.
+    pub fn modes(&self) -> impl Iterator<Item = &BrushMode> {
+        self.modes.iter()
+    }
+
     /// Activate the given brush mode.
     pub fn set(&mut self, m: BrushMode) -> bool {
-        if let BrushMode::Line(_) = m {
-            // only one line sub-mode may be active at a time
-            if let Some(line_mode) = self.line_mode() {
-                self.unset(line_mode);
-            }
+        // Only one sub-mode of each mode may be active at a time,
+        // eg. a single line snap angle or symmetry axis.
+        if let Some(mode) = self.mode_like(m) {
+            self.modes.remove(&mode);
         }
+        // Painting behind and replacing pixels are mutually exclusive, and
+        // so are lines and circles.
+        match m {
+            BrushMode::Behind => self.modes.remove(&BrushMode::Replace),
+            BrushMode::Replace => self.modes.remove(&BrushMode::Behind),
+            BrushMode::Line(_) => self.modes.remove(&BrushMode::Circle),
+            BrushMode::Circle => self
+                .line_mode()
+                .is_some_and(|mode| self.modes.remove(&mode)),
+            _ => false,
+        };
         self.modes.insert(m)
     }
 
     /// De-activate the given brush mode.
     pub fn unset(&mut self, m: BrushMode) -> bool {
-        match self.line_mode() {
-            Some(line_mode) if matches!(m, BrushMode::Line(_)) => self.modes.remove(&line_mode),
-            _ => self.modes.remove(&m),
+        match self.mode_like(m) {
+            Some(mode) => self.modes.remove(&mode),
+            None => false,
         }
     }
 
     /// Toggle the given brush mode.
     pub fn toggle(&mut self, m: BrushMode) {
-        if self.is_set(m) {
+        if self.mode_like(m).is_some() {
             self.unset(m);
         } else {
             self.set(m);
         }
     }
 
+    /// Set the axis of the active symmetry modes, relative to the frame.
+    pub fn set_axis(&mut self, x: u32, y: u32) {
+        if self.mode_like(BrushMode::XSym(None)).is_some() {
+            self.set(BrushMode::XSym(Some(x)));
+        }
+        if self.mode_like(BrushMode::YSym(None)).is_some() {
+            self.set(BrushMode::YSym(Some(y)));
+        }
+    }
+
+    /// Return the x-symmetry axis position, relative to the frame, if x-symmetry is active.
+    /// The axis lies on the edge between two pixels.
+    pub fn x_axis(&self, fw: u32) -> Option<f32> {
+        match self.mode_like(BrushMode::XSym(None)) {
+            Some(BrushMode::XSym(Some(x))) => Some(x as f32),
+            Some(_) => Some(fw as f32 / 2.),
+            None => None,
+        }
+    }
+
+    /// Return the y-symmetry axis position, relative to the frame, if y-symmetry is active.
+    /// The axis lies on the edge between two pixels.
+    pub fn y_axis(&self, fh: u32) -> Option<f32> {
+        match self.mode_like(BrushMode::YSym(None)) {
+            Some(BrushMode::YSym(Some(y))) => Some(y as f32),
+            Some(_) => Some(fh as f32 / 2.),
+            None => None,
+        }
+    }
+
+    /// Check whether a mode painting on other frames is active, ie. multi-frame
+    /// mode, regardless of its frames, or mirror mode.
+    pub fn is_multi(&self) -> bool {
+        self.mode_like(BrushMode::Multi(Frames::Rest)).is_some() || self.is_set(BrushMode::Mirror)
+    }
+
+    /// Check whether an erase mode is active, regardless of what it erases to.
+    pub fn is_erasing(&self) -> bool {
+        self.mode_like(BrushMode::Erase(false)).is_some()
+    }
+
     /// Check whether the brush is currently drawing.
     pub fn is_drawing(&self) -> bool {
         !matches!(self.state, BrushState::NotDrawing)
     }
 
+    /// Whether a stroke is in progress, ie. input is pressed.
+    fn is_stroking(&self) -> bool {
+        matches!(
+            self.state,
+            BrushState::DrawStarted(_) | BrushState::Drawing(_)
+        )
+    }
+
     #[allow(dead_code)]
     pub fn reset(&mut self) {
         self.modes.clear();
@@ -151,6 +396,7 @@ impl Brush {
         if let BrushState::DrawEnded(_) = self.state {
             self.state = BrushState::NotDrawing;
             self.stroke.clear();
+            self.path.clear();
         }
     }
 
@@ -158,35 +404,95 @@ impl Brush {
     pub fn start_drawing(&mut self, p: LayerCoords<i32>, color: Rgba8, extent: ViewExtent) {
         self.state = BrushState::DrawStarted(extent);
         self.color = color;
+        self.strokes = self.strokes.wrapping_add(1);
         self.stroke = Vec::with_capacity(32);
+        self.path = Vec::with_capacity(32);
         self.draw(p);
     }
 
+    /// Start drawing with a straight line from the end of the last stroke, as if the
+    /// stroke had started there. Without a last stroke, this is like `start_drawing`.
+    pub fn start_line(&mut self, p: LayerCoords<i32>, color: Rgba8, extent: ViewExtent) {
+        match self.last {
+            Some(last) => {
+                self.start_drawing(LayerCoords::new(last.x, last.y), color, extent);
+                self.draw(p);
+            }
+            None => self.start_drawing(p, color, extent),
+        }
+    }
+
+    /// Whether strokes are shapes, ie. lines or circles.
+    pub fn is_shape(&self) -> bool {
+        self.line_mode().is_some() || self.is_set(BrushMode::Circle)
+    }
+
     /// If a line mode is active, return it
     fn line_mode(&self) -> Option<BrushMode> {
+        self.mode_like(BrushMode::Line(None))
+    }
+
+    /// If a mode of the same kind as the given mode is active, return it,
+    /// regardless of its parameters.
+    fn mode_like(&self, m: BrushMode) -> Option<BrushMode> {
         self.modes
             .iter()
-            .filter(|mode| matches!(mode, BrushMode::Line(_)))
+            .find(|mode| mem::discriminant(*mode) == mem::discriminant(&m))
             .cloned()
-            .next()
+    }
+
+    /// Set the line snap angle override, and update the line being drawn, if any.
+    pub fn set_snap(&mut self, snap: Option<Snap>) {
+        self.snap = snap;
+
+        if self.is_stroking() && (self.line_mode().is_some() || self.is_set(BrushMode::Circle)) {
+            self.draw(LayerCoords::new(self.curr.x, self.curr.y));
+        }
+    }
+
+    /// The start and end points of the line being drawn, in line mode.
+    pub fn current_line(&self) -> Option<(Point2<i32>, Point2<i32>)> {
+        if !self.is_stroking() {
+            return None;
+        }
+        self.line_mode()?;
+
+        Some((*self.path.first()?, *self.path.last()?))
     }
 
     /// Draw. Called while input is pressed.
     pub fn draw(&mut self, p: LayerCoords<i32>) {
         self.prev = if let BrushState::DrawStarted(_) = self.state {
+            self.origin = *p;
             *p
         } else {
             self.curr
         };
         self.curr = *p;
 
-        if let Some(BrushMode::Line(snap)) = self.line_mode() {
-            let start = *self.stroke.first().unwrap_or(&p);
-            self.stroke.clear();
+        if self.is_set(BrushMode::Circle) {
+            let (rx, ry) = (
+                (self.curr.x - self.origin.x).abs(),
+                (self.curr.y - self.origin.y).abs(),
+            );
+            // With a snap angle, eg. while the snap key is held, draw a circle.
+            let (rx, ry) = if self.snap.is_some() {
+                (rx.max(ry), rx.max(ry))
+            } else {
+                (rx, ry)
+            };
+            self.path.clear();
 
-            let end = match snap {
+            Brush::ellipse(self.origin, rx, ry, &mut self.path);
+        } else if let Some(BrushMode::Line(snap)) = self.line_mode() {
+            let start = *self.path.first().unwrap_or(&p);
+            self.path.clear();
+
+            let end = match self.snap.or(snap) {
                 None => self.curr,
-                Some(snap) => {
+                Some(Snap::Iso) => Brush::snap_iso(start, self.curr),
+                Some(Snap::Vanishing) => Brush::snap_vanishing(start, self.curr, &self.vanishing),
+                Some(Snap::Degrees(snap)) => {
                     let snap_rad = snap as f32 * PI / 180.0;
                     let curr: Vector2<f32> = self.curr.map(|x| x as f32).into();
                     let start: Vector2<f32> = start.map(|x| x as f32).into();
@@ -198,14 +504,28 @@ impl Brush {
                 }
             };
 
-            Brush::line(start, end, &mut self.stroke);
+            Brush::line(start, end, &mut self.path);
         } else {
-            Brush::line(self.prev, self.curr, &mut self.stroke);
-            self.stroke.dedup();
+            Brush::line(self.prev, self.curr, &mut self.path);
+            self.path.dedup();
         }
 
         if self.is_set(BrushMode::Perfect) {
-            self.stroke = Brush::filter(&self.stroke);
+            self.path = Brush::filter(&self.path);
+        }
+        // Only keep every n-th point of the path, counting from the start of
+        // the stroke, so that points already drawn stay put.
+        self.stroke = self
+            .path
+            .iter()
+            .step_by(self.spacing.max(1))
+            .cloned()
+            .collect();
+
+        if self.grid > 1 {
+            let stroke = self.stroke.iter().map(|p| self.snap_to_grid(*p)).collect();
+            self.stroke = stroke;
+            self.stroke.dedup();
         }
 
         match self.state {
@@ -217,11 +537,23 @@ impl Brush {
         }
     }
 
+    /// Snap a point to the brush grid. Points are moved to the middle of their grid
+    /// cell, so that a brush as big as the grid covers the cell exactly.
+    pub fn snap_to_grid(&self, p: Point2<i32>) -> Point2<i32> {
+        if self.grid <= 1 {
+            return p;
+        }
+        let n = self.grid as i32;
+
+        p.map(|c| c.div_euclid(n) * n + n / 2)
+    }
+
     /// Stop drawing. Called when input is released.
     pub fn stop_drawing(&mut self) {
         match self.state {
             BrushState::DrawStarted(ex) | BrushState::Drawing(ex) => {
                 self.state = BrushState::DrawEnded(ex);
+                self.last = Some(self.curr);
             }
             _ => unreachable!(),
         }
@@ -232,31 +564,164 @@ impl Brush {
         let mut pixels = vec![*p];
         let ViewExtent { fw, fh, nframes } = extent;
 
-        if self.is_set(BrushMode::XSym) {
+        // Symmetry axes are relative to the frame, and mirror pixels
+        // across the edge they sit on, ie. `2 * axis - p - 1`. Even-sized
+        // brushes are centered on the corner of `p` rather than its center,
+        // so they mirror to `2 * axis - p`.
+        let odd = (self.size % 2) as i32;
+        // Mirrored pixels that land outside of the frame they were mirrored in,
+        // eg. with an axis off-center, are dropped instead of bleeding into the
+        // neighbouring frames.
+        let within = |frame_offset: i32, p: Point2<i32>| {
+            (frame_offset..frame_offset + fw as i32).contains(&p.x) && (0..fh as i32).contains(&p.y)
+        };
+
+        if let Some(axis) = self.x_axis(fw) {
+            let axis2 = (axis * 2.) as i32;
+
             for p in pixels.clone() {
-                let frame_index = p.x / fw as i32;
+                let frame_offset = p.x.div_euclid(fw as i32) * fw as i32;
+                let m = Point2::new(frame_offset + axis2 - (p.x - frame_offset) - odd, p.y);
 
-                pixels.push(Point2::new(
-                    (frame_index + 1) * fw as i32 - (p.x - frame_index * fw as i32) - 1,
-                    p.y,
-                ));
+                if within(frame_offset, m) {
+                    pixels.push(m);
+                }
             }
         }
-        if self.is_set(BrushMode::YSym) {
+        if let Some(axis) = self.y_axis(fh) {
+            let axis2 = (axis * 2.) as i32;
+
             for p in pixels.clone() {
-                pixels.push(Point2::new(p.x, fh as i32 - p.y - 1));
+                pixels.push(Point2::new(p.x, axis2 - p.y - odd));
             }
         }
-        if self.is_set(BrushMode::Multi) {
+        if self.is_set(BrushMode::IsoSym) {
+            // Isometric axes have a 2:1 slope, so reflecting across them maps a
+            // vertical step to two horizontal pixels, and two horizontal steps to
+            // a vertical pixel. Relative to the frame center, `(x, y)` maps to
+            // `(2y, x/2)` and `(-2y, -x/2)`, and the two combined give `(-x, -y)`.
+            let (cx, cy) = (fw as f32 / 2., fh as f32 / 2.);
+
             for p in pixels.clone() {
-                let frame_index = p.x / fw as i32;
-                for i in 0..nframes as i32 - frame_index {
-                    let offset = Vector2::new((i as u32 * fw) as i32, 0);
-                    pixels.push(p + offset);
+                let frame_offset = p.x.div_euclid(fw as i32) * fw as i32;
+                let x = (p.x - frame_offset) as f32 + 0.5 - cx;
+                let y = p.y as f32 + 0.5 - cy;
+
+                for (mx, my) in [(2. * y, x / 2.), (-2. * y, -x / 2.)] {
+                    let my = (cy + my).floor() as i32;
+                    let mx = (cx + mx - 0.5).floor() as i32;
+
+                    for m in [
+                        Point2::new(frame_offset + mx, my),
+                        Point2::new(frame_offset + mx + 1, my),
+                    ] {
+                        if within(frame_offset, m) {
+                            pixels.push(m);
+                        }
+                    }
+                }
+                let m = Point2::new(
+                    frame_offset + (cx - x).floor() as i32,
+                    (cy - y).floor() as i32,
+                );
+                if within(frame_offset, m) {
+                    pixels.push(m);
+                }
+            }
+        }
+        if self.is_set(BrushMode::OctSym) {
+            // Relative to the frame center, `(x, y)` maps to every combination of
+            // `(±x, ±y)` and `(±y, ±x)`. Like with the other symmetry modes, pixels
+            // are mirrored across their center, or their corner for even-sized brushes.
+            let (cx, cy) = (fw as f32 / 2., fh as f32 / 2.);
+            let half = odd as f32 / 2.;
+
+            for p in pixels.clone() {
+                let frame_offset = p.x.div_euclid(fw as i32) * fw as i32;
+                let x = (p.x - frame_offset) as f32 + half - cx;
+                let y = p.y as f32 + half - cy;
+
+                for (mx, my) in [
+                    (-x, y),
+                    (x, -y),
+                    (-x, -y),
+                    (y, x),
+                    (-y, x),
+                    (y, -x),
+                    (-y, -x),
+                ] {
+                    let m = Point2::new(
+                        frame_offset + (cx + mx - half).floor() as i32,
+                        (cy + my - half).floor() as i32,
+                    );
+                    // The diagonal mirrors leave the frame when it isn't square.
+                    if within(frame_offset, m) {
+                        pixels.push(m);
+                    }
                 }
             }
         }
-        pixels.iter().map(|p| ViewCoords::new(p.x, p.y)).collect()
+        if let Some(BrushMode::Multi(frames)) = self.mode_like(BrushMode::Multi(Frames::Rest)) {
+            for p in pixels.clone() {
+                let current = p.x.div_euclid(fw as i32);
+                let x = p.x - current * fw as i32;
+
+                for i in (0..nframes as i32).filter(|i| *i != current) {
+                    if current >= 0 && frames.contains(i as u32, current as u32) {
+                        pixels.push(Point2::new(i * fw as i32 + x, p.y));
+                    }
+                }
+            }
+        }
+        if self.is_set(BrushMode::Mirror) {
+            for p in pixels.clone() {
+                let current = p.x.div_euclid(fw as i32);
+                let x = p.x - current * fw as i32;
+
+                if (0..nframes as i32).contains(&current) {
+                    let opposite = nframes as i32 - 1 - current;
+                    pixels.push(Point2::new(opposite * fw as i32 + fw as i32 - x - odd, p.y));
+                }
+            }
+        }
+        // Mirrors can land on the same pixel, eg. on the isometric axes. These
+        // are only kept once, so that translucent colors aren't blended twice.
+        let mut unique: Vec<Point2<i32>> = Vec::with_capacity(pixels.len());
+        for p in pixels {
+            if !unique.contains(&p) {
+                unique.push(p);
+            }
+        }
+        unique.iter().map(|p| ViewCoords::new(p.x, p.y)).collect()
+    }
+
+    /// Return the pixels covered by the brush's stroke, for all brush heads.
+    pub fn pixels(&self) -> Vec<Point2<i32>> {
+        let extent = match self.state {
+            BrushState::DrawStarted(extent)
+            | BrushState::Drawing(extent)
+            | BrushState::DrawEnded(extent) => extent,
+            BrushState::NotDrawing => return Vec::new(),
+        };
+        let size = self.size as i32;
+        let mut pixels = Vec::new();
+
+        // Brush heads are aligned like the shapes returned by `output`,
+        // with `Align::BottomLeft`.
+        for p in &self.stroke {
+            for head in self.expand(ViewCoords::new(p.x, p.y), extent) {
+                let (x, y) = (head.x - size / 2, head.y - size / 2);
+
+                for j in y..y + size {
+                    for i in x..x + size {
+                        pixels.push(Point2::new(i, j));
+                    }
+                }
+            }
+        }
+        pixels.sort_by_key(|p| (p.y, p.x));
+        pixels.dedup();
+        pixels
     }
 
     /// Return the brush's output strokes as shapes.
@@ -280,7 +745,7 @@ impl Brush {
                             ZDepth::ZERO,
                             stroke,
                             fill,
-                            scale,
+                            Vector2::new(scale, scale),
                             align,
                         )
                     })
@@ -290,6 +755,35 @@ impl Brush {
         }
     }
 
+    /// The color of the stroke at the given pixel, with the jitter applied.
+    pub fn color_at(&self, p: Point2<i32>, palette: &[Rgba8]) -> Rgba8 {
+        if self.jitter.is_set() {
+            self.jitter.apply(self.color, palette, p, self.strokes)
+        } else {
+            self.color
+        }
+    }
+
+    /// Return the brush's output strokes as single pixel shapes, with the jitter
+    /// applied to each pixel's color.
+    pub fn jittered(&self, palette: &[Rgba8]) -> Vec<Shape> {
+        self.pixels()
+            .into_iter()
+            .map(|p| {
+                let color = self.color_at(p, palette);
+                let (x, y) = (p.x as f32, p.y as f32);
+
+                Shape::Rectangle(
+                    Rect::new(x, y, x + 1., y + 1.),
+                    ZDepth::ZERO,
+                    Rotation::ZERO,
+                    Stroke::NONE,
+                    Fill::Solid(color.into()),
+                )
+            })
+            .collect()
+    }
+
     /// Return the shape that should be painted when the brush is at the given
     /// position with the given parameters. Takes an `Origin` which describes
     /// whether to align the position to the bottom-left of the shape, or the
@@ -300,7 +794,7 @@ impl Brush {
         z: ZDepth,
         stroke: Stroke,
         fill: Fill,
-        scale: f32,
+        scale: Vector2<f32>,
         align: Align,
     ) -> Shape {
         let x = p.x;
@@ -309,12 +803,12 @@ impl Brush {
         let size = self.size as f32;
 
         let offset = match align {
-            Align::Center => size * scale / 2.,
-            Align::BottomLeft => (self.size / 2) as f32 * scale,
+            Align::Center => scale * (size / 2.),
+            Align::BottomLeft => scale * (self.size / 2) as f32,
         };
 
         Shape::Rectangle(
-            Rect::new(x, y, x + size * scale, y + size * scale) - Vector2::new(offset, offset),
+            Rect::new(x, y, x + size * scale.x, y + size * scale.y) - offset,
             z,
             Rotation::ZERO,
             stroke,
@@ -324,6 +818,64 @@ impl Brush {
 
     ///////////////////////////////////////////////////////////////////////////
 
+    /// Snap the end of a line to the closest horizontal, vertical or 2:1 isometric
+    /// direction. The end is placed on a whole step of the direction, so that
+    /// isometric lines are made of pairs of pixels.
+    fn snap_iso(start: Point2<i32>, end: Point2<i32>) -> Point2<i32> {
+        const DIRECTIONS: [(i32, i32); 8] = [
+            (1, 0),
+            (2, 1),
+            (0, 1),
+            (-2, 1),
+            (-1, 0),
+            (-2, -1),
+            (0, -1),
+            (2, -1),
+        ];
+        let (dx, dy) = ((end.x - start.x) as f32, (end.y - start.y) as f32);
+        let dot = |(x, y): (i32, i32)| dx * x as f32 + dy * y as f32;
+        let len = |(x, y): (i32, i32)| ((x * x + y * y) as f32).sqrt();
+
+        let (x, y) = DIRECTIONS
+            .iter()
+            .copied()
+            .max_by(|a, b| (dot(*a) / len(*a)).total_cmp(&(dot(*b) / len(*b))))
+            .expect("there is at least one direction");
+        let steps = (dot((x, y)) / (x * x + y * y) as f32).round() as i32;
+
+        Point2::new(start.x + x * steps, start.y + y * steps)
+    }
+
+    /// Add a vanishing point, replacing the oldest one if there are already two.
+    pub fn add_vanishing_point(&mut self, p: Point2<i32>) {
+        if self.vanishing.len() >= 2 {
+            self.vanishing.remove(0);
+        }
+        self.vanishing.push(p);
+    }
+
+    /// Snap the end of a line to the ray from its start through a vanishing point.
+    /// With two vanishing points, the one most in line with the stroke is used.
+    fn snap_vanishing(start: Point2<i32>, end: Point2<i32>, points: &[Point2<i32>]) -> Point2<i32> {
+        let (dx, dy) = ((end.x - start.x) as f32, (end.y - start.y) as f32);
+        let rays = points
+            .iter()
+            .map(|p| ((p.x - start.x) as f32, (p.y - start.y) as f32))
+            .filter(|(x, y)| *x != 0. || *y != 0.);
+        let alignment = |(x, y): (f32, f32)| (dx * x + dy * y).abs() / (x * x + y * y).sqrt();
+
+        match rays.max_by(|a, b| alignment(*a).total_cmp(&alignment(*b))) {
+            Some((x, y)) => {
+                let t = (dx * x + dy * y) / (x * x + y * y);
+                Point2::new(
+                    start.x + (x * t).round() as i32,
+                    start.y + (y * t).round() as i32,
+                )
+            }
+            None => end,
+        }
+    }
+
     /// Draw a line between two points. Uses Bresenham's line algorithm.
     pub fn line(mut p0: Point2<i32>, p1: Point2<i32>, canvas: &mut Vec<Point2<i32>>) {
         let dx = i32::abs(p1.x - p0.x);
@@ -354,6 +906,66 @@ impl Brush {
         }
     }
 
+    /// Draw an ellipse outline with the given center and radii. Uses the midpoint
+    /// ellipse algorithm. Points are ordered by angle around the center.
+    pub fn ellipse(c: Point2<i32>, rx: i32, ry: i32, canvas: &mut Vec<Point2<i32>>) {
+        if rx == 0 || ry == 0 {
+            return Brush::line(
+                Point2::new(c.x - rx, c.y - ry),
+                Point2::new(c.x + rx, c.y + ry),
+                canvas,
+            );
+        }
+        let mut points = Vec::new();
+        let mut plot = |x: i64, y: i64| {
+            let (x, y) = (x as i32, y as i32);
+
+            for (sx, sy) in [(1, 1), (-1, 1), (-1, -1), (1, -1)] {
+                points.push(Point2::new(c.x + sx * x, c.y + sy * y));
+            }
+        };
+        let (rx2, ry2) = (rx as i64 * rx as i64, ry as i64 * ry as i64);
+        let (mut x, mut y) = (0, ry as i64);
+        let (mut px, mut py) = (0, 2 * rx2 * y);
+
+        // Where the slope of the curve is less than one, step along x.
+        let mut d = ry2 - rx2 * ry as i64 + rx2 / 4;
+        while px < py {
+            plot(x, y);
+            x += 1;
+            px += 2 * ry2;
+
+            if d < 0 {
+                d += ry2 + px;
+            } else {
+                y -= 1;
+                py -= 2 * rx2;
+                d += ry2 + px - py;
+            }
+        }
+        // Then step along y. The decision variable is scaled by four to keep
+        // it an integer.
+        let mut d = ry2 * (2 * x + 1) * (2 * x + 1) + 4 * rx2 * (y - 1) * (y - 1) - 4 * rx2 * ry2;
+        while y >= 0 {
+            plot(x, y);
+            y -= 1;
+            py -= 2 * rx2;
+
+            if d > 0 {
+                d += 4 * (rx2 - py);
+            } else {
+                x += 1;
+                px += 2 * ry2;
+                d += 4 * (rx2 - py + px);
+            }
+        }
+        let angle = |p: &Point2<i32>| ((p.y - c.y) as f32).atan2((p.x - c.x) as f32);
+
+        points.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+        points.dedup();
+        canvas.extend(points);
+    }
+
     /// Paint a circle into a pixel buffer.
     #[allow(dead_code)]
     fn paint(
@@ -406,6 +1018,8 @@ impl Brush {
         }
 
         filtered.extend(stroke.last().cloned());
+        // The last point may already be in, eg. with single point strokes.
+        filtered.dedup();
 
         filtered
     }
@@ -415,6 +1029,384 @@ impl Brush {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_expand_symmetry() {
+        let extent = ViewExtent::new(8, 8, 2);
+        let expand = |b: &Brush, x, y| -> Vec<(i32, i32)> {
+            b.expand(ViewCoords::new(x, y), extent)
+                .into_iter()
+                .map(|p| (p.x, p.y))
+                .collect()
+        };
+        let mut b = Brush::default();
+
+        b.set(BrushMode::XSym(None));
+        assert_eq!(expand(&b, 1, 2), vec![(1, 2), (6, 2)]);
+        assert_eq!(expand(&b, 9, 2), vec![(9, 2), (14, 2)]);
+
+        b.set(BrushMode::XSym(Some(2)));
+        assert!(!b.is_set(BrushMode::XSym(None)));
+        assert_eq!(expand(&b, 1, 2), vec![(1, 2), (2, 2)]);
+        assert_eq!(expand(&b, 8, 2), vec![(8, 2), (11, 2)]);
+
+        // Pixels mirrored past the frame edge are dropped.
+        b.set(BrushMode::XSym(Some(6)));
+        assert_eq!(expand(&b, 1, 2), vec![(1, 2)]);
+        assert_eq!(expand(&b, 7, 2), vec![(7, 2), (4, 2)]);
+        assert_eq!(expand(&b, 9, 2), vec![(9, 2)]);
+        b.set(BrushMode::XSym(Some(2)));
+
+        b.toggle(BrushMode::XSym(None));
+        assert_eq!(b.x_axis(8), None);
+
+        b.set(BrushMode::YSym(None));
+        b.set_axis(3, 5);
+        assert!(b.is_set(BrushMode::YSym(Some(5))));
+        assert_eq!(expand(&b, 1, 2), vec![(1, 2), (1, 7)]);
+
+        // Even-sized brushes are centered on the corner of the pixel, so the
+        // mirrored brush lines up with the original across the axis.
+        b.size = 2;
+        assert_eq!(expand(&b, 1, 2), vec![(1, 2), (1, 8)]);
+
+        // Isometric axes map a vertical step to two horizontal pixels.
+        let mut b = Brush::default();
+        b.set(BrushMode::IsoSym);
+        assert_eq!(expand(&b, 5, 4), vec![(5, 4), (4, 4), (2, 3), (3, 3)]);
+        assert_eq!(expand(&b, 13, 4), vec![(13, 4), (12, 4), (10, 3), (11, 3)]);
+        // Mirrors that fall outside of the frame don't paint the next one.
+        assert_eq!(expand(&b, 1, 0), vec![(1, 0), (6, 7)]);
+        assert_eq!(expand(&b, 9, 0), vec![(9, 0), (14, 7)]);
+
+        // Eight-way symmetry mirrors across both axes and both diagonals.
+        let mut b = Brush::default();
+        b.set(BrushMode::OctSym);
+        assert_eq!(
+            expand(&b, 1, 2),
+            vec![
+                (1, 2),
+                (6, 2),
+                (1, 5),
+                (6, 5),
+                (2, 1),
+                (5, 1),
+                (2, 6),
+                (5, 6)
+            ]
+        );
+        assert_eq!(
+            expand(&b, 9, 2),
+            vec![
+                (9, 2),
+                (14, 2),
+                (9, 5),
+                (14, 5),
+                (10, 1),
+                (13, 1),
+                (10, 6),
+                (13, 6)
+            ]
+        );
+
+        b.size = 2;
+        assert_eq!(
+            expand(&b, 1, 2),
+            vec![
+                (1, 2),
+                (7, 2),
+                (1, 6),
+                (7, 6),
+                (2, 1),
+                (6, 1),
+                (2, 7),
+                (6, 7)
+            ]
+        );
+
+        // In frames that aren't square, only the mirrors within the frame are kept.
+        let extent = ViewExtent::new(4, 8, 2);
+        let expand = |b: &Brush, x, y| -> Vec<(i32, i32)> {
+            b.expand(ViewCoords::new(x, y), extent)
+                .into_iter()
+                .map(|p| (p.x, p.y))
+                .collect()
+        };
+        b.size = 1;
+        assert_eq!(expand(&b, 1, 0), vec![(1, 0), (2, 0), (1, 7), (2, 7)]);
+        assert_eq!(expand(&b, 5, 0), vec![(5, 0), (6, 0), (5, 7), (6, 7)]);
+        assert_eq!(expand(&b, 1, 3), vec![(1, 3), (2, 3), (1, 4), (2, 4)]);
+    }
+
+    #[test]
+    fn test_expand_multi() {
+        let extent = ViewExtent::new(4, 4, 6);
+        let frames = |b: &Brush, x| -> Vec<i32> {
+            b.expand(ViewCoords::new(x, 0), extent)
+                .into_iter()
+                .map(|p| p.x / 4)
+                .collect()
+        };
+        let mut b = Brush::default();
+
+        b.set(BrushMode::Multi(Frames::Rest));
+        assert_eq!(frames(&b, 9), vec![2, 3, 4, 5]);
+
+        b.set(BrushMode::Multi(Frames::All));
+        assert_eq!(frames(&b, 9), vec![2, 0, 1, 3, 4, 5]);
+
+        b.set(BrushMode::Multi(Frames::Range(1, 3)));
+        assert_eq!(frames(&b, 1), vec![0, 1, 2, 3]);
+
+        b.set(BrushMode::Multi(Frames::Every(2)));
+        assert_eq!(frames(&b, 13), vec![3, 1, 5]);
+        assert!(b.is_multi());
+    }
+
+    #[test]
+    fn test_expand_mirror() {
+        let extent = ViewExtent::new(8, 8, 4);
+        let mut b = Brush::default();
+
+        b.set(BrushMode::Mirror);
+        // Frame 0 mirrors onto frame 3, and frame 2 onto frame 1.
+        assert_eq!(
+            b.expand(ViewCoords::new(1, 2), extent),
+            vec![ViewCoords::new(1, 2), ViewCoords::new(30, 2)]
+        );
+        assert_eq!(
+            b.expand(ViewCoords::new(18, 2), extent),
+            vec![ViewCoords::new(18, 2), ViewCoords::new(13, 2)]
+        );
+        assert!(b.is_multi());
+    }
+
+    #[test]
+    fn test_jitter() {
+        let palette = [Rgba8::BLACK, Rgba8::RED, Rgba8::WHITE];
+        let jitter = Jitter {
+            color: 1,
+            alpha: 0x40,
+        };
+        let mut colors = BTreeSet::new();
+
+        for x in 0..64 {
+            let p = Point2::new(x, 0);
+            let c = jitter.apply(Rgba8::RED, &palette, p, 1);
+
+            assert_eq!(c, jitter.apply(Rgba8::RED, &palette, p, 1));
+            assert!(c.a >= 0xff - 0x40);
+            colors.insert(c.alpha(0xff));
+        }
+        assert_eq!(colors.len(), 3);
+
+        // Near the ends of the palette and of the alpha range, jitter stays even.
+        let (mut black, mut clear) = (0, 0);
+        for x in 0..256 {
+            let p = Point2::new(x, 0);
+
+            black +=
+                (jitter.apply(Rgba8::BLACK, &palette, p, 1).alpha(0xff) == Rgba8::BLACK) as usize;
+            clear += (jitter.apply(Rgba8::BLUE.alpha(0x20), &palette, p, 1).a == 0) as usize;
+        }
+        assert!((96..160).contains(&black), "{} black pixels", black);
+        assert!(clear < 32, "{} clear pixels", clear);
+
+        // Colors outside the palette keep their color.
+        let c = jitter.apply(Rgba8::BLUE, &palette, Point2::new(0, 0), 1);
+        assert_eq!(c.alpha(0xff), Rgba8::BLUE);
+        assert!(!Jitter::default().is_set());
+    }
+
+    #[test]
+    fn test_spacing() {
+        let mut b = Brush::default();
+        let extent = ViewExtent::new(16, 16, 1);
+
+        b.spacing = 3;
+        b.start_drawing(LayerCoords::new(0, 0), Rgba8::WHITE, extent);
+        b.draw(LayerCoords::new(4, 0));
+        b.draw(LayerCoords::new(7, 0));
+
+        assert_eq!(
+            b.stroke,
+            vec![Point2::new(0, 0), Point2::new(3, 0), Point2::new(6, 0)]
+        );
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut b = Brush::default();
+        let extent = ViewExtent::new(16, 16, 1);
+
+        b.size = 4;
+        b.grid = 4;
+        b.start_drawing(LayerCoords::new(1, 1), Rgba8::WHITE, extent);
+        b.draw(LayerCoords::new(9, 3));
+
+        assert_eq!(
+            b.stroke,
+            vec![Point2::new(2, 2), Point2::new(6, 2), Point2::new(10, 2)]
+        );
+        let pixels = b.pixels();
+
+        assert_eq!(pixels.len(), 48);
+        assert_eq!(pixels.first(), Some(&Point2::new(0, 0)));
+        assert_eq!(pixels.last(), Some(&Point2::new(11, 3)));
+        assert_eq!(b.snap_to_grid(Point2::new(-1, 4)), Point2::new(-2, 6));
+    }
+
+    #[test]
+    fn test_pixels() {
+        let mut b = Brush::default();
+        let extent = ViewExtent::new(16, 16, 1);
+
+        assert!(b.pixels().is_empty());
+
+        b.size = 2;
+        b.start_drawing(LayerCoords::new(1, 1), Rgba8::WHITE, extent);
+        b.draw(LayerCoords::new(2, 1));
+
+        assert_eq!(
+            b.pixels(),
+            vec![
+                Point2::new(0, 0),
+                Point2::new(1, 0),
+                Point2::new(2, 0),
+                Point2::new(0, 1),
+                Point2::new(1, 1),
+                Point2::new(2, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_start_line() {
+        let extent = ViewExtent::new(8, 8, 1);
+        let mut b = Brush::default();
+
+        // Without a previous stroke, only the point is drawn.
+        b.start_line(LayerCoords::new(1, 1), Rgba8::WHITE, extent);
+        assert_eq!(b.stroke, vec![Point2::new(1, 1)]);
+        b.stop_drawing();
+        b.update();
+
+        b.start_line(LayerCoords::new(4, 1), Rgba8::WHITE, extent);
+        assert_eq!(
+            b.stroke,
+            (1..=4).map(|x| Point2::new(x, 1)).collect::<Vec<_>>()
+        );
+        b.stop_drawing();
+        assert_eq!(b.last, Some(Point2::new(4, 1)));
+    }
+
+    #[test]
+    fn test_line_snap() {
+        let mut b = Brush::default();
+        let extent = ViewExtent::new(16, 16, 1);
+
+        b.set(BrushMode::Line(None));
+        b.start_drawing(LayerCoords::new(0, 0), Rgba8::WHITE, extent);
+        b.draw(LayerCoords::new(10, 1));
+        assert_eq!(
+            b.current_line(),
+            Some((Point2::new(0, 0), Point2::new(10, 1)))
+        );
+
+        b.set_snap(Some(Snap::Degrees(45)));
+        assert_eq!(
+            b.current_line(),
+            Some((Point2::new(0, 0), Point2::new(10, 0)))
+        );
+
+        b.set_snap(None);
+        assert_eq!(
+            b.current_line(),
+            Some((Point2::new(0, 0), Point2::new(10, 1)))
+        );
+
+        b.set_snap(Some(Snap::Iso));
+        b.draw(LayerCoords::new(7, 4));
+        assert_eq!(
+            b.current_line(),
+            Some((Point2::new(0, 0), Point2::new(8, 4)))
+        );
+        assert_eq!(
+            &b.stroke[..5],
+            &[
+                Point2::new(0, 0),
+                Point2::new(1, 0),
+                Point2::new(2, 1),
+                Point2::new(3, 1),
+                Point2::new(4, 2)
+            ]
+        );
+
+        b.add_vanishing_point(Point2::new(40, 40));
+        b.add_vanishing_point(Point2::new(20, 10));
+        b.add_vanishing_point(Point2::new(-20, 10));
+        assert_eq!(b.vanishing.len(), 2);
+
+        b.set_snap(Some(Snap::Vanishing));
+        b.draw(LayerCoords::new(9, 6));
+        assert_eq!(
+            b.current_line(),
+            Some((Point2::new(0, 0), Point2::new(10, 5)))
+        );
+
+        b.stop_drawing();
+        assert_eq!(b.current_line(), None);
+    }
+
+    #[test]
+    fn test_circle() {
+        let extent = ViewExtent::new(16, 16, 1);
+        let mut b = Brush::default();
+
+        b.set(BrushMode::Line(None));
+        b.set(BrushMode::Circle);
+        assert!(b.line_mode().is_none());
+
+        b.start_drawing(LayerCoords::new(8, 8), Rgba8::WHITE, extent);
+        b.draw(LayerCoords::new(9, 9));
+        assert_eq!(
+            b.stroke,
+            vec![
+                Point2::new(8, 7),
+                Point2::new(9, 8),
+                Point2::new(8, 9),
+                Point2::new(7, 8),
+            ]
+        );
+        b.draw(LayerCoords::new(9, 8));
+        assert_eq!(
+            b.stroke,
+            vec![Point2::new(7, 8), Point2::new(8, 8), Point2::new(9, 8)]
+        );
+
+        // Every point of a circle is at about the same distance from the center.
+        b.draw(LayerCoords::new(13, 13));
+        assert_eq!(b.stroke.len(), 28);
+        for p in &b.stroke {
+            let d = (((p.x - 8).pow(2) + (p.y - 8).pow(2)) as f32).sqrt();
+            assert!((d - 5.).abs() < 0.5, "{:?} is {} away", p, d);
+        }
+
+        // Ellipses fit within their radii, and snap to circles.
+        b.draw(LayerCoords::new(12, 10));
+        assert!(b
+            .stroke
+            .iter()
+            .all(|p| (p.x - 8).abs() <= 4 && (p.y - 8).abs() <= 2));
+        assert!(b.stroke.contains(&Point2::new(4, 8)));
+        assert!(b.stroke.contains(&Point2::new(8, 10)));
+
+        b.set_snap(Some(Snap::Degrees(45)));
+        assert!(b.stroke.contains(&Point2::new(8, 12)));
+
+        b.draw(LayerCoords::new(8, 8));
+        assert_eq!(b.stroke, vec![Point2::new(8, 8)]);
+    }
+
     #[test]
     fn test_paint() {
         let z = Rgba8::TRANSPARENT;
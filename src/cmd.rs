@@ -1,10 +1,15 @@
 use crate::autocomplete::{self, Autocomplete, FileCompleter, FileCompleterOpts};
-use crate::brush::BrushMode;
+use crate::brush::{BrushMode, Snap};
 use crate::history::History;
+use crate::palette::ColorRef;
 use crate::parser::*;
 use crate::platform;
-use crate::session::{Direction, Input, Mode, PanState, Tool, VisualState};
+use crate::profile::{Profile, TileFormat};
+use crate::session::{
+    Direction, FloodOptions, Input, Mode, NineSlice, PanState, Tool, VisualState,
+};
 use crate::view::layer::LayerId;
+use crate::view::{ExportFormat, ExportOptions, Playback, ScaleFilter};
 
 use memoir::traits::Parse;
 use memoir::*;
@@ -30,6 +35,24 @@ pub enum Axis {
     Vertical,
 }
 
+/// The part of a view a command applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    /// The frame under the cursor.
+    Frame,
+    /// Every frame of the view.
+    View,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Frame => write!(f, "frame"),
+            Self::View => write!(f, "view"),
+        }
+    }
+}
+
 /// User command. Most of the interactions available to
 /// the user are modeled as commands that are processed
 /// by the session.
@@ -40,45 +63,90 @@ pub enum Command {
     BrushSet(BrushMode),
     BrushToggle(BrushMode),
     BrushSize(Op),
+    BrushSpacing(usize),
+    BrushSnap(Option<Snap>),
+    BrushJitterColor(usize),
+    BrushJitterAlpha(u8),
+    BrushColor(ColorRef),
+    ColorRecent(usize),
+    ColorPicker,
+    ColorRamp(Direction),
+    ColorBudget,
     BrushUnset(BrushMode),
+    BrushAxis,
+    BrushVanishingPoint,
+    BrushVanishingClear,
 
     #[allow(dead_code)]
     Crop(Rect<u32>),
+    CropAuto(u32),
     ChangeDir(Option<String>),
     Echo(Value),
+    Theme(Option<String>),
 
     // Files
     Edit(Vec<String>),
     EditFrames(Vec<String>),
-    Export(Option<u32>, String),
+    Import(String, Option<(i32, i32)>),
+    Export(String, ExportOptions),
+    ExportAll(Option<String>, Option<u32>),
+    RecordStart(String, usize),
+    RecordStop,
+    CheckpointSave(String),
+    CheckpointRevert(String),
+    CheckpointDiff(Option<String>),
     Write(Option<String>),
     WriteFrames(Option<String>),
     WriteQuit,
+    Backups(Option<usize>),
     Quit,
     QuitAll,
     ForceQuit,
     ForceQuitAll,
     Source(Option<String>),
 
+    // Shared sessions
+    NetHost(String),
+    NetJoin(String),
+    NetLeave,
+
     // Frames
     FrameAdd,
     FrameClone(i32),
-    FrameRemove,
+    FrameInsert(usize),
+    FrameDup(usize),
+    FrameRemove(Option<usize>),
+    FrameMove(usize, usize),
+    FrameCopy(usize, usize),
+    FramePaste(Option<usize>),
     FramePrev,
     FrameNext,
     FrameResize(u32, u32),
 
+    // Animation
+    AnimationFps(u32),
+    AnimationPlayback(Playback),
+    AnimationRange(Option<(usize, usize)>),
+
     // Palette
     PaletteAdd(Rgba8),
     PaletteClear,
+    PaletteGroup(String),
+    PaletteView(bool),
     PaletteGradient(Rgba8, Rgba8, usize),
     PaletteSample,
     PaletteSort,
+    PaletteHighlight(Option<usize>),
+    PaletteCycle(Option<(usize, usize, usize)>),
     PaletteWrite(String),
 
     // Navigation
     Pan(i32, i32),
     Zoom(Op),
+    ZoomFit,
+    Search(Option<Rgba8>),
+    SearchNext,
+    SearchPrev,
 
     // TODO: These operate on the active layer. We should have a command
     // to set the active layer.
@@ -87,6 +155,7 @@ pub enum Command {
     PaintBackground(i32, i32),
     PaintPalette(usize, i32, i32),
     PaintLine(Rgba8, i32, i32, i32, i32),
+    PaintFilter(String),
 
     // Selection
     SelectionMove(i32, i32),
@@ -100,9 +169,13 @@ pub enum Command {
     SelectionErase,
     SelectionJump(Direction),
     SelectionFlip(Axis),
+    Flip(Axis, Scope),
+    Rotate(u32, Scope),
+    SelectionShift(i32, i32, bool),
 
     // Settings
     Set(String, Value),
+    Settings(Option<String>),
     Toggle(String),
     Reset,
     Map(Box<KeyMapping>),
@@ -116,14 +189,46 @@ pub enum Command {
     Mode(Mode),
     Tool(Tool),
     ToolPrev,
+    Flood(FloodOptions),
 
     Undo,
     Redo,
+    UndoOlder,
+    UndoNewer,
+    UndoGoto(usize),
+    UndoTree,
+    UndoBegin,
+    UndoEnd,
+    UndoGroup(Vec<Command>),
 
     // View
     ViewCenter,
     ViewNext,
     ViewPrev,
+    JumpBack,
+    JumpForward,
+    ViewStats,
+    ViewLink(String),
+    ViewUnlink,
+    Preview(Option<NineSlice>),
+    PreviewWindow(u32),
+    PreviewBackdrop(Option<Value>),
+
+    // Grid & guides
+    Grid(u32, Option<Rgba8>),
+    GuideAdd(Axis, Option<i32>),
+    GuideClear,
+
+    // Tiles
+    TileCopy(usize, usize),
+    TileSwap(usize, usize),
+    TileDuplicates,
+
+    // Split
+    Split,
+    SplitClose,
+    SplitFocus,
+    SplitResize(Op),
 
     // Layers
     LayerAdd,
@@ -142,14 +247,113 @@ impl Command {
                 | Self::Pan(_, _)
                 | Self::Undo
                 | Self::Redo
+                | Self::UndoOlder
+                | Self::UndoNewer
                 | Self::ViewNext
+                | Self::JumpBack
+                | Self::JumpForward
+                | Self::SearchNext
+                | Self::SearchPrev
                 | Self::ViewPrev
                 | Self::SelectionMove(_, _)
                 | Self::SelectionJump(_)
                 | Self::SelectionResize(_, _)
                 | Self::SelectionOffset(_, _)
+                | Self::FramePrev
+                | Self::FrameNext
+        )
+    }
+
+    /// Whether the command changes the pixels or frames of a view, or writes it
+    /// to disk. These are disabled in read-only mode.
+    pub fn is_edit(&self) -> bool {
+        matches!(
+            self,
+            Self::Crop(_)
+                | Self::CropAuto(_)
+                | Self::CheckpointRevert(_)
+                | Self::Write(_)
+                | Self::ExportAll(_, _)
+                | Self::WriteFrames(_)
+                | Self::WriteQuit
+                | Self::Backups(Some(_))
+                | Self::FrameAdd
+                | Self::FrameClone(_)
+                | Self::FrameInsert(_)
+                | Self::FrameDup(_)
+                | Self::FrameRemove(_)
+                | Self::FrameMove(_, _)
+                | Self::FrameCopy(_, _)
+                | Self::FramePaste(_)
+                | Self::FrameResize(_, _)
+                | Self::PaintColor(_, _, _)
+                | Self::PaintForeground(_, _)
+                | Self::PaintBackground(_, _)
+                | Self::PaintPalette(_, _, _)
+                | Self::PaintLine(_, _, _, _, _)
+                | Self::PaintFilter(_)
+                | Self::Import(_, _)
+                | Self::SelectionPaste
+                | Self::SelectionCut
+                | Self::SelectionFill(_)
+                | Self::SelectionErase
+                | Self::SelectionFlip(_)
+                | Self::Flip(_, _)
+                | Self::Rotate(_, _)
+                | Self::SelectionShift(_, _, _)
+                | Self::Slice(_)
+                | Self::Fill(_)
+                | Self::Undo
+                | Self::Redo
+                | Self::UndoOlder
+                | Self::UndoNewer
+                | Self::UndoGoto(_)
+                | Self::TileCopy(_, _)
+                | Self::TileSwap(_, _)
+                | Self::LayerAdd
+                | Self::LayerRemove(_)
+                | Self::LayerExtend(_)
+        )
+    }
+
+    /// Whether the command is exchanged with the other peers of a shared session.
+    /// Anything else received from a peer is dropped.
+    pub fn is_shared(&self) -> bool {
+        matches!(
+            self,
+            Self::PaintColor(_, _, _)
+                | Self::PaintLine(_, _, _, _, _)
+                | Self::FrameAdd
+                | Self::FrameClone(_)
+                | Self::FrameInsert(_)
+                | Self::FrameDup(_)
+                | Self::FrameRemove(_)
+                | Self::FrameMove(_, _)
+                | Self::FrameResize(_, _)
+                | Self::Flip(_, Scope::View)
+                | Self::Rotate(_, Scope::View)
+                | Self::CropAuto(_)
         )
     }
+
+    /// Apply `f` to the file paths of the command, if any.
+    pub fn map_paths(self, f: impl Fn(&str) -> String) -> Self {
+        match self {
+            Self::ChangeDir(Some(p)) => Self::ChangeDir(Some(f(&p))),
+            Self::Edit(ps) => Self::Edit(ps.iter().map(|p| f(p)).collect()),
+            Self::EditFrames(ps) => Self::EditFrames(ps.iter().map(|p| f(p)).collect()),
+            Self::Import(p, pos) => Self::Import(f(&p), pos),
+            Self::Export(p, opts) => Self::Export(f(&p), opts),
+            Self::ExportAll(Some(dir), scale) => Self::ExportAll(Some(f(&dir)), scale),
+            Self::RecordStart(p, n) => Self::RecordStart(f(&p), n),
+            Self::Write(Some(p)) => Self::Write(Some(f(&p))),
+            Self::WriteFrames(Some(dir)) => Self::WriteFrames(Some(f(&dir))),
+            Self::Source(Some(p)) => Self::Source(Some(f(&p))),
+            Self::PaletteWrite(p) => Self::PaletteWrite(f(&p)),
+            Self::ViewLink(p) => Self::ViewLink(f(&p)),
+            cmd => cmd,
+        }
+    }
 }
 
 impl fmt::Display for Command {
@@ -161,12 +365,34 @@ impl fmt::Display for Command {
             Self::BrushSize(Op::Incr) => write!(f, "Increase brush size"),
             Self::BrushSize(Op::Decr) => write!(f, "Decrease brush size"),
             Self::BrushSize(Op::Set(s)) => write!(f, "Set brush size to {}", s),
+            Self::BrushSpacing(n) => write!(f, "Set brush spacing to {}", n),
+            Self::BrushSnap(Some(Snap::Degrees(n))) => write!(f, "Snap lines to {} degrees", n),
+            Self::BrushSnap(Some(Snap::Iso)) => write!(f, "Snap lines to isometric angles"),
+            Self::BrushSnap(Some(Snap::Vanishing)) => {
+                write!(f, "Snap lines to the nearest vanishing point")
+            }
+            Self::BrushSnap(None) => write!(f, "Snap lines to the line mode angle"),
+            Self::BrushColor(c) => write!(f, "Set brush color to {}", c),
+            Self::BrushJitterColor(n) => write!(f, "Set brush color jitter to {}", n),
+            Self::BrushJitterAlpha(n) => write!(f, "Set brush alpha jitter to {}", n),
+            Self::ColorRecent(i) => write!(f, "Pick recent color #{}", i),
+            Self::ColorPicker => write!(f, "Open the color picker"),
+            Self::ColorBudget => write!(f, "List frames over the color budget"),
+            Self::ColorRamp(Direction::Forward) => write!(f, "Next color in palette ramp"),
+            Self::ColorRamp(Direction::Backward) => write!(f, "Previous color in palette ramp"),
             Self::BrushUnset(m) => write!(f, "Unset brush `{}` mode", m),
+            Self::BrushAxis => write!(f, "Move brush symmetry axes to cursor"),
+            Self::BrushVanishingPoint => write!(f, "Place a vanishing point at the cursor"),
+            Self::BrushVanishingClear => write!(f, "Clear vanishing points"),
             Self::Crop(_) => write!(f, "Crop view"),
+            Self::CropAuto(_) => write!(f, "Crop the view frames to their content"),
             Self::ChangeDir(_) => write!(f, "Change the current working directory"),
             Self::Echo(_) => write!(f, "Echo a value"),
+            Self::Theme(Some(name)) => write!(f, "Load the `{}` UI theme", name),
+            Self::Theme(None) => write!(f, "Show the current UI theme"),
             Self::Edit(_) => write!(f, "Edit path(s)"),
             Self::EditFrames(_) => write!(f, "Edit path(s) as animation frames"),
+            Self::Import(path, _) => write!(f, "Import {} into the active view", path),
             Self::Fill(Some(c)) => write!(f, "Fill view with {color}", color = c),
             Self::Fill(None) => write!(f, "Fill view with background color"),
             Self::ForceQuit => write!(f, "Quit view without saving"),
@@ -177,12 +403,32 @@ impl fmt::Display for Command {
             Self::Mode(m) => write!(f, "Switch to {} mode", m),
             Self::FrameAdd => write!(f, "Add a blank frame to the view"),
             Self::FrameClone(i) => write!(f, "Clone frame {} and add it to the view", i),
-            Self::FrameRemove => write!(f, "Remove the last frame of the view"),
+            Self::FrameInsert(i) => write!(f, "Insert a blank frame at {}", i),
+            Self::FrameDup(i) => write!(f, "Duplicate frame {}", i),
+            Self::FrameRemove(None) => write!(f, "Remove the last frame of the view"),
+            Self::FrameRemove(Some(i)) => write!(f, "Remove frame {} of the view", i),
+            Self::FrameMove(from, to) => write!(f, "Move frame {} to {}", from, to),
+            Self::FrameCopy(view, i) => write!(f, "Copy frame {} of view {}", i, view),
+            Self::FramePaste(None) => write!(f, "Paste the copied frame"),
+            Self::FramePaste(Some(i)) => write!(f, "Paste the copied frame over frame {}", i),
             Self::FramePrev => write!(f, "Navigate to previous frame"),
             Self::FrameNext => write!(f, "Navigate to next frame"),
+            Self::AnimationFps(n) => write!(f, "Set animation frame rate to {} fps", n),
+            Self::AnimationPlayback(m) => write!(f, "Set animation playback to {}", m),
+            Self::AnimationRange(Some((a, b))) => {
+                write!(f, "Restrict animation to frames {} to {}", a, b)
+            }
+            Self::AnimationRange(None) => write!(f, "Play back all animation frames"),
             Self::Noop => write!(f, "No-op"),
             Self::PaletteAdd(c) => write!(f, "Add {color} to palette", color = c),
+            Self::Search(Some(c)) => write!(f, "Search for {} in the active view", c),
+            Self::Search(None) => write!(f, "Clear the search"),
+            Self::SearchNext => write!(f, "Go to the next search match"),
+            Self::SearchPrev => write!(f, "Go to the previous search match"),
             Self::PaletteClear => write!(f, "Clear palette"),
+            Self::PaletteGroup(name) => write!(f, "Start palette group `{}`", name),
+            Self::PaletteView(true) => write!(f, "Give the active view its own palette"),
+            Self::PaletteView(false) => write!(f, "Use the session palette in the active view"),
             Self::PaletteGradient(cs, ce, n) => write!(
                 f,
                 "Create {} colors gradient from {} to {}",
@@ -192,6 +438,14 @@ impl fmt::Display for Command {
             ),
             Self::PaletteSample => write!(f, "Sample palette from view"),
             Self::PaletteSort => write!(f, "Sort palette colors"),
+            Self::PaletteHighlight(Some(i)) => {
+                write!(f, "Highlight the pixels using palette color #{}", i)
+            }
+            Self::PaletteHighlight(None) => write!(f, "Clear the palette color highlight"),
+            Self::PaletteCycle(Some((a, b, _))) => {
+                write!(f, "Cycle palette colors #{} to #{} in the preview", a, b)
+            }
+            Self::PaletteCycle(None) => write!(f, "Stop cycling palette colors"),
             Self::Pan(x, 0) if *x > 0 => write!(f, "Pan workspace right"),
             Self::Pan(x, 0) if *x < 0 => write!(f, "Pan workspace left"),
             Self::Pan(0, y) if *y > 0 => write!(f, "Pan workspace up"),
@@ -205,23 +459,77 @@ impl fmt::Display for Command {
             Self::Tool(Tool::Brush) => write!(f, "Brush tool"),
             Self::Tool(Tool::Sampler) => write!(f, "Color sampler tool"),
             Self::Tool(Tool::FloodFill) => write!(f, "Flood fill tool"),
+            Self::Flood(o) if o.global => {
+                write!(f, "Flood fill tool, filling matching colors everywhere")
+            }
+            Self::Flood(_) => write!(f, "Flood fill tool"),
             Self::ToolPrev => write!(f, "Switch to previous tool"),
             Self::Set(s, v) => write!(f, "Set {setting} to {val}", setting = s, val = v),
+            Self::Settings(Some(s)) => write!(f, "Show the value of {}", s),
+            Self::Settings(None) => write!(f, "Show all settings"),
             Self::Slice(Some(n)) => write!(f, "Slice view into {} frame(s)", n),
             Self::Slice(None) => write!(f, "Reset view slices"),
             Self::Source(_) => write!(f, "Source an rx script (eg. a palette)"),
+            Self::NetHost(addr) => write!(f, "Host a shared session on {}", addr),
+            Self::NetJoin(addr) => write!(f, "Join the shared session at {}", addr),
+            Self::NetLeave => write!(f, "Leave the shared session"),
+            Self::RecordStart(path, _) => write!(f, "Record a timelapse to {}", path),
+            Self::RecordStop => write!(f, "Stop recording the timelapse"),
+            Self::CheckpointSave(name) => write!(f, "Save checkpoint `{}`", name),
+            Self::CheckpointRevert(name) => write!(f, "Revert to checkpoint `{}`", name),
+            Self::CheckpointDiff(Some(name)) => write!(f, "Show changes since `{}`", name),
+            Self::CheckpointDiff(None) => write!(f, "Hide checkpoint changes"),
+            Self::Grid(n, _) => write!(f, "Show {n}x{n} grid", n = n),
+            Self::GuideAdd(Axis::Horizontal, _) => write!(f, "Add horizontal guide"),
+            Self::GuideAdd(Axis::Vertical, _) => write!(f, "Add vertical guide"),
+            Self::GuideClear => write!(f, "Clear all guides"),
+            Self::Split => write!(f, "Split workspace"),
+            Self::SplitClose => write!(f, "Close split pane"),
+            Self::SplitFocus => write!(f, "Swap active view with split pane view"),
+            Self::SplitResize(Op::Incr) => write!(f, "Grow split pane"),
+            Self::SplitResize(Op::Decr) => write!(f, "Shrink split pane"),
+            Self::SplitResize(Op::Set(r)) => write!(f, "Set split pane ratio to {:.2}", r),
             Self::SwapColors => write!(f, "Swap foreground & background colors"),
             Self::Toggle(s) => write!(f, "Toggle {setting} on/off", setting = s),
             Self::Undo => write!(f, "Undo view edit"),
+            Self::UndoOlder => write!(f, "Go to previous view edit in time"),
+            Self::UndoNewer => write!(f, "Go to next view edit in time"),
+            Self::UndoGoto(edit) => write!(f, "Go to view edit #{}", edit),
+            Self::UndoTree => write!(f, "Show view edit history"),
+            Self::UndoBegin => write!(f, "Start grouping view edits into one undo step"),
+            Self::UndoEnd => write!(f, "Stop grouping view edits"),
+            Self::UndoGroup(_) => write!(f, "Run commands as one undo step"),
             Self::ViewCenter => write!(f, "Center active view"),
+            Self::ViewStats => write!(f, "Show active view statistics"),
+            Self::ViewLink(path) => write!(f, "Link active view with `{}`", path),
+            Self::ViewUnlink => write!(f, "Unlink active view"),
+            Self::Preview(Some(p)) => write!(
+                f,
+                "Preview the current frame nine-sliced at {}x{}",
+                p.width, p.height
+            ),
+            Self::Preview(None) => write!(f, "Hide the preview"),
+            Self::PreviewWindow(0) => write!(f, "Close the preview window"),
+            Self::PreviewWindow(z) => write!(f, "Show the active view in a window at {}x", z),
+            Self::PreviewBackdrop(None) => write!(f, "Clear the animation preview backdrop"),
+            Self::PreviewBackdrop(Some(_)) => write!(f, "Set the animation preview backdrop"),
+            Self::TileCopy(src, dst) => write!(f, "Copy tile #{} over tile #{}", src, dst),
+            Self::TileSwap(a, b) => write!(f, "Swap tiles #{} and #{}", a, b),
+            Self::TileDuplicates => write!(f, "Find duplicate tiles"),
+            Self::JumpBack => write!(f, "Jump back to the previous position"),
+            Self::JumpForward => write!(f, "Jump forward to the next position"),
             Self::ViewNext => write!(f, "Go to next view"),
             Self::ViewPrev => write!(f, "Go to previous view"),
             Self::Write(None) => write!(f, "Write view to disk"),
             Self::Write(Some(_)) => write!(f, "Write view to disk as..."),
+            Self::ExportAll(_, _) => write!(f, "Write or export all modified views"),
             Self::WriteQuit => write!(f, "Write file to disk and quit"),
+            Self::Backups(None) => write!(f, "List backups of the view file"),
+            Self::Backups(Some(n)) => write!(f, "Restore backup {} of the view file", n),
             Self::Zoom(Op::Incr) => write!(f, "Zoom in view"),
             Self::Zoom(Op::Decr) => write!(f, "Zoom out view"),
             Self::Zoom(Op::Set(z)) => write!(f, "Set view zoom to {:.1}", z),
+            Self::ZoomFit => write!(f, "Zoom view to fit the workspace"),
             Self::Reset => write!(f, "Reset all settings to default"),
             Self::SelectionFill(None) => write!(f, "Fill selection with foreground color"),
             Self::SelectionYank => write!(f, "Yank (copy) selection"),
@@ -244,20 +552,67 @@ impl fmt::Display for Command {
             Self::SelectionErase => write!(f, "Erase selection contents"),
             Self::SelectionFlip(Axis::Horizontal) => write!(f, "Flip selection horizontally"),
             Self::SelectionFlip(Axis::Vertical) => write!(f, "Flip selection vertically"),
+            Self::Flip(Axis::Horizontal, s) => write!(f, "Flip the {} horizontally", s),
+            Self::Flip(Axis::Vertical, s) => write!(f, "Flip the {} vertically", s),
+            Self::Rotate(n, s) => write!(f, "Rotate the {} by {} degrees", s, n),
+            Self::SelectionShift(x, y, false) => {
+                write!(f, "Shift selection contents by {:2},{:2}", x, y)
+            }
+            Self::SelectionShift(x, y, true) => {
+                write!(f, "Wrap-shift selection contents by {:2},{:2}", x, y)
+            }
             Self::PaintColor(_, x, y) => write!(f, "Paint {:2},{:2}", x, y),
+            Self::PaintFilter(cmd) => write!(f, "Filter pixels through `{}`", cmd),
             _ => write!(f, "..."),
         }
     }
 }
 
+/// Format a color the way the color parser reads it back, with the alpha as a
+/// fraction, eg. `#ff0000/0.502`. The fraction is biased so that it truncates
+/// back to the same alpha.
+fn format_color(c: Rgba8) -> String {
+    let rgb = Rgba8::new(c.r, c.g, c.b, 0xff);
+
+    if c.a == 0xff {
+        format!("{}", rgb)
+    } else {
+        format!("{}/{:.3}", rgb, (c.a as f64 + 0.5) / 255.)
+    }
+}
+
 impl From<Command> for String {
     fn from(cmd: Command) -> Self {
         match cmd {
             Command::Brush => format!("brush"),
             Command::BrushSet(m) => format!("brush/set {}", m),
+            Command::BrushAxis => format!("brush/axis"),
+            Command::BrushVanishingPoint => format!("brush/vp"),
+            Command::BrushVanishingClear => format!("brush/vp clear"),
             Command::BrushSize(Op::Incr) => format!("brush/size +"),
             Command::BrushSize(Op::Decr) => format!("brush/size -"),
             Command::BrushSize(Op::Set(s)) => format!("brush/size {}", s),
+            Command::BrushSpacing(n) => format!("brush/spacing {}", n),
+            Command::BrushSnap(Some(n)) => format!("brush/snap {}", n),
+            Command::BrushSnap(None) => format!("brush/snap"),
+            Command::BrushColor(c) => format!("brush/color {}", c),
+            Command::BrushJitterColor(n) => format!("brush/jitter color {}", n),
+            Command::BrushJitterAlpha(n) => format!("brush/jitter alpha {}", n),
+            Command::ColorRecent(i) => format!("color/recent {}", i),
+            Command::ColorPicker => format!("color/picker"),
+            Command::ColorBudget => format!("color/budget"),
+            Command::ColorRamp(Direction::Forward) => format!("color/next-in-ramp"),
+            Command::ColorRamp(Direction::Backward) => format!("color/prev-in-ramp"),
+            Command::Flood(o) => {
+                let mut s = String::from("flood");
+                if o.global {
+                    s.push_str(" --global");
+                }
+                if o.tolerance > 0 {
+                    s = format!("{} --tolerance {}", s, o.tolerance);
+                }
+                s
+            }
             Command::BrushUnset(m) => format!("brush/unset {}", m),
             Command::Echo(_) => unimplemented!(),
             Command::Edit(_) => unimplemented!(),
@@ -269,35 +624,189 @@ impl From<Command> for String {
             Command::Mode(m) => format!("mode {}", m),
             Command::FrameAdd => format!("f/add"),
             Command::FrameClone(i) => format!("f/clone {}", i),
-            Command::FrameRemove => format!("f/remove"),
-            Command::Export(None, path) => format!("export {}", path),
-            Command::Export(Some(s), path) => format!("export @{}x {}", s, path),
+            Command::FrameInsert(i) => format!("f/insert {}", i),
+            Command::FrameDup(i) => format!("f/dup {}", i),
+            Command::FrameRemove(None) => format!("f/remove"),
+            Command::FrameRemove(Some(i)) => format!("f/remove {}", i),
+            Command::FrameMove(from, to) => format!("f/move {} {}", from, to),
+            Command::FrameCopy(view, i) => format!("f/copy {} {}", view, i),
+            Command::FramePaste(None) => format!("f/paste"),
+            Command::FramePaste(Some(i)) => format!("f/paste {}", i),
+            Command::AnimationFps(n) => format!("anim/fps {}", n),
+            Command::AnimationPlayback(m) => format!("anim/playback {}", m),
+            Command::AnimationRange(Some((a, b))) => format!("anim/range {} {}", a, b),
+            Command::AnimationRange(None) => format!("anim/range"),
+            Command::Export(path, opts) => {
+                let ExportOptions {
+                    scale,
+                    filter,
+                    layout,
+                    profile,
+                    format,
+                    pipe,
+                    frames,
+                    selection,
+                } = opts;
+                let mut s = match scale {
+                    Some(scale) => format!("export @{}x {}", scale, path),
+                    None => format!("export {}", path),
+                };
+                if let Some(filter) = filter {
+                    s.push_str(&format!(" --filter {}", filter));
+                }
+                if let Some((cols, rows)) = layout.grid {
+                    s.push_str(&format!(" --layout {}x{}", cols, rows));
+                }
+                if layout.padding > 0 {
+                    s.push_str(&format!(" --padding {}", layout.padding));
+                }
+                if layout.spacing > 0 {
+                    s.push_str(&format!(" --spacing {}", layout.spacing));
+                }
+                if let Some(profile) = profile {
+                    s.push_str(&format!(" --profile {}", profile));
+                }
+                match format {
+                    Some(ExportFormat::Tiles(t)) => s.push_str(&format!(" --tiles {}", t)),
+                    Some(f) => s.push_str(&format!(" --format {}", f)),
+                    None => {}
+                }
+                if let Some(cmd) = pipe {
+                    s.push_str(&format!(" --pipe \"{}\"", cmd));
+                }
+                match frames {
+                    Some((a, b)) if a == b => s.push_str(&format!(" --frame {}", a)),
+                    Some((a, b)) => s.push_str(&format!(" --frames {}..{}", a, b)),
+                    None => {}
+                }
+                if selection {
+                    s.push_str(" --selection");
+                }
+                s
+            }
+            Command::ExportAll(dir, scale) => {
+                let mut s = String::from("export-all");
+
+                if let Some(dir) = dir {
+                    s.push_str(&format!(" --dir {}", dir));
+                }
+                if let Some(scale) = scale {
+                    s.push_str(&format!(" --scale {}", scale));
+                }
+                s
+            }
             Command::Noop => format!(""),
             Command::PaletteAdd(c) => format!("p/add {}", c),
+            Command::Search(Some(c)) => format!("search {}", c),
+            Command::Search(None) => format!("search"),
+            Command::SearchNext => format!("search/next"),
+            Command::SearchPrev => format!("search/prev"),
             Command::PaletteClear => format!("p/clear"),
+            Command::PaletteGroup(name) => format!("p/group {}", name),
+            Command::PaletteView(true) => format!("p/view"),
+            Command::PaletteView(false) => format!("p/view off"),
             Command::PaletteWrite(_) => format!("p/write"),
             Command::PaletteSample => format!("p/sample"),
+            Command::PaletteHighlight(Some(i)) => format!("p/highlight {}", i),
+            Command::PaletteHighlight(None) => format!("p/highlight"),
+            Command::PaletteCycle(Some((a, b, n))) => format!("p/cycle {} {} {}", a, b, n),
+            Command::PaletteCycle(None) => format!("p/cycle"),
             Command::PaletteGradient(cs, ce, n) => format!("p/gradient {} {} {}", cs, ce, n),
             Command::Pan(x, y) => format!("pan {} {}", x, y),
             Command::Quit => format!("q"),
             Command::Redo => format!("redo"),
             Command::FrameResize(w, h) => format!("f/resize {} {}", w, h),
             Command::Set(s, v) => format!("set {} = {}", s, v),
+            Command::Settings(Some(s)) => format!("set {}?", s),
+            Command::Settings(None) => format!("set"),
             Command::Slice(Some(n)) => format!("slice {}", n),
             Command::Slice(None) => format!("slice"),
             Command::Source(Some(path)) => format!("source {}", path),
+            Command::Source(None) => format!("source"),
+            Command::Import(path, Some((x, y))) => format!("import {} {} {}", path, x, y),
+            Command::Import(path, None) => format!("import {}", path),
+            Command::NetHost(addr) => format!("net/host {}", addr),
+            Command::NetJoin(addr) => format!("net/join {}", addr),
+            Command::NetLeave => format!("net/leave"),
+            Command::Flip(axis, scope) => {
+                let axis = if axis == Axis::Horizontal { "x" } else { "y" };
+                format!("flip {} --{}", axis, scope)
+            }
+            Command::Rotate(n, scope) => format!("rotate {} --{}", n, scope),
+            Command::CropAuto(0) => format!("crop --auto"),
+            Command::CropAuto(n) => format!("crop --auto --padding {}", n),
+            Command::PaintColor(rgba, x, y) => {
+                format!("paint/color {} {} {}", self::format_color(rgba), x, y)
+            }
+            Command::PaintLine(rgba, x1, y1, x2, y2) => format!(
+                "paint/line {} {} {} {} {}",
+                self::format_color(rgba),
+                x1,
+                y1,
+                x2,
+                y2
+            ),
+            Command::PaintFilter(cmd) => format!("paint/filter \"{}\"", cmd),
+            Command::Theme(Some(name)) => format!("theme {}", name),
+            Command::Theme(None) => format!("theme"),
+            Command::RecordStart(path, n) => format!("record/start {} {}", path, n),
+            Command::RecordStop => format!("record/stop"),
+            Command::CheckpointSave(name) => format!("checkpoint/save {}", name),
+            Command::CheckpointRevert(name) => format!("checkpoint/revert {}", name),
+            Command::CheckpointDiff(Some(name)) => format!("checkpoint/diff {}", name),
+            Command::CheckpointDiff(None) => format!("checkpoint/diff"),
+            Command::Grid(n, None) => format!("set grid {}", n),
+            Command::Grid(n, Some(c)) => format!("set grid {} {}", n, c),
+            Command::Split => format!("split"),
+            Command::SplitClose => format!("split/close"),
+            Command::SplitFocus => format!("split/focus"),
+            Command::SplitResize(Op::Incr) => format!("split/resize +"),
+            Command::SplitResize(Op::Decr) => format!("split/resize -"),
+            Command::SplitResize(Op::Set(r)) => format!("split/resize {}", r),
             Command::SwapColors => format!("swap"),
             Command::Toggle(s) => format!("toggle {}", s),
             Command::Undo => format!("undo"),
+            Command::UndoOlder => format!("undo/older"),
+            Command::UndoNewer => format!("undo/newer"),
+            Command::UndoGoto(edit) => format!("undo/goto {}", edit),
+            Command::UndoTree => format!("undo/tree"),
+            Command::UndoBegin => format!("undo/begin"),
+            Command::UndoEnd => format!("undo/end"),
+            Command::UndoGroup(cmds) => format!(
+                "undo/group {{ {} }}",
+                cmds.iter()
+                    .map(|c| format!(":{}", String::from(c.clone())))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
             Command::ViewCenter => format!("v/center"),
+            Command::ViewStats => format!("stats"),
+            Command::ViewLink(path) => format!("v/link {}", path),
+            Command::ViewUnlink => format!("v/unlink"),
+            Command::Preview(Some(p)) => format!("preview {}", p),
+            Command::Preview(None) => format!("preview"),
+            Command::PreviewWindow(z) => format!("preview/window {}", z),
+            Command::PreviewBackdrop(Some(Value::Rgba8(c))) => {
+                format!("preview/backdrop {}", self::format_color(c))
+            }
+            Command::PreviewBackdrop(Some(v)) => format!("preview/backdrop {}", v),
+            Command::PreviewBackdrop(None) => format!("preview/backdrop"),
+            Command::TileCopy(src, dst) => format!("tile/copy {} {}", src, dst),
+            Command::TileSwap(a, b) => format!("tile/swap {} {}", a, b),
+            Command::TileDuplicates => format!("tile/dupes"),
+            Command::JumpBack => format!("jump/back"),
+            Command::JumpForward => format!("jump/forward"),
             Command::ViewNext => format!("v/next"),
             Command::ViewPrev => format!("v/prev"),
             Command::Write(None) => format!("w"),
             Command::Write(Some(path)) => format!("w {}", path),
             Command::WriteQuit => format!("wq"),
+            Command::Backups(None) => format!("backups"),
+            Command::Backups(Some(n)) => format!("backups {}", n),
             Command::Zoom(Op::Incr) => format!("v/zoom +"),
             Command::Zoom(Op::Decr) => format!("v/zoom -"),
             Command::Zoom(Op::Set(z)) => format!("v/zoom {}", z),
+            Command::ZoomFit => format!("zoom fit"),
             _ => unimplemented!(),
         }
     }
@@ -408,6 +917,13 @@ impl Value {
         panic!("expected {:?} to be a `Rgba8`", self);
     }
 
+    pub fn to_str(&self) -> &str {
+        match self {
+            Value::Str(s) | Value::Ident(s) => s,
+            _ => panic!("expected {:?} to be a `string`", self),
+        }
+    }
+
     pub fn description(&self) -> &'static str {
         match self {
             Self::Bool(_) => "on / off",
@@ -470,6 +986,10 @@ impl Parse for Value {
         let str_val = quoted().map(Value::Str).label("<string>");
         let rgba8_val = color().map(Value::Rgba8);
         let u32_tuple_val = tuple::<u32>(natural(), natural()).map(|(x, y)| Value::U32Tuple(x, y));
+        let ratio_val = natural::<u32>()
+            .skip(symbol(':'))
+            .then(natural::<u32>())
+            .map(|(x, y)| Value::U32Tuple(x, y));
         let u32_val = natural::<u32>().map(Value::U32);
         let f64_tuple_val =
             tuple::<f32>(rational(), rational()).map(|(x, y)| Value::F32Tuple(x, y));
@@ -483,6 +1003,7 @@ impl Parse for Value {
         greediest(vec![
             rgba8_val,
             u32_tuple_val,
+            ratio_val,
             f64_tuple_val,
             u32_val,
             f64_val,
@@ -738,6 +1259,28 @@ impl Commands {
     }
 }
 
+/// An option of the `export` command.
+#[derive(Debug, Clone)]
+enum ExportOption {
+    Scale(u32),
+    Filter(ScaleFilter),
+    Layout(u32, u32),
+    Padding(u32),
+    Spacing(u32),
+    Profile(Profile),
+    Format(ExportFormat),
+    Pipe(String),
+    Frames(usize, usize),
+    Selection,
+}
+
+/// An option of the `flood` command.
+#[derive(Debug, Clone)]
+enum FloodOption {
+    Global,
+    Tolerance(u8),
+}
+
 impl Default for Commands {
     fn default() -> Self {
         Self::new()
@@ -748,8 +1291,157 @@ impl Default for Commands {
                 p.value(Command::ForceQuitAll)
             })
             .command("export", "Export view", |p| {
-                p.then(optional(scale().skip(whitespace())).then(path()))
-                    .map(|(_, (scale, path))| Command::Export(scale, path))
+                // Options may come before or after the path.
+                fn leading(name: &'static str) -> Parser<()> {
+                    hush(peek(string(name)).then(whitespace()))
+                }
+                fn trailing(name: &'static str) -> Parser<()> {
+                    hush(peek(whitespace().then(string(name))).then(whitespace()))
+                }
+                // Flags don't take a value, so there's no whitespace after them.
+                fn leading_flag(name: &'static str) -> Parser<()> {
+                    hush(string(name))
+                }
+                fn trailing_flag(name: &'static str) -> Parser<()> {
+                    hush(peek(whitespace().then(string(name))))
+                }
+                let options = |option: fn(&'static str) -> Parser<()>,
+                               flag: fn(&'static str) -> Parser<()>| {
+                    choice(vec![
+                        flag("--selection").value(ExportOption::Selection),
+                        option("--scale")
+                            .then(natural::<u32>().label("<scale>"))
+                            .map(|(_, n)| ExportOption::Scale(n)),
+                        option("--filter")
+                            .then(
+                                choice(vec![
+                                    string("scale2x").value(ScaleFilter::Scale2x),
+                                    string("scale3x").value(ScaleFilter::Scale3x),
+                                ])
+                                .label("<filter>"),
+                            )
+                            .map(|(_, f)| ExportOption::Filter(f)),
+                        option("--layout")
+                            .then(
+                                natural::<u32>()
+                                    .skip(symbol('x'))
+                                    .then(natural::<u32>())
+                                    .label("<cols>x<rows>"),
+                            )
+                            .map(|(_, (cols, rows))| ExportOption::Layout(cols, rows)),
+                        option("--padding")
+                            .then(natural::<u32>().label("<padding>"))
+                            .map(|(_, n)| ExportOption::Padding(n)),
+                        option("--spacing")
+                            .then(natural::<u32>().label("<spacing>"))
+                            .map(|(_, n)| ExportOption::Spacing(n)),
+                        option("--profile")
+                            .then(
+                                choice(vec![
+                                    string("pico8").value(Profile::Pico8),
+                                    string("tic80").value(Profile::Tic80),
+                                    string("gb").value(Profile::GameBoy),
+                                ])
+                                .label("<profile>"),
+                            )
+                            .map(|(_, p)| ExportOption::Profile(p)),
+                        option("--tiles")
+                            .then(
+                                choice(vec![
+                                    string("gb").value(TileFormat::GameBoy),
+                                    string("nes").value(TileFormat::Nes),
+                                    string("snes").value(TileFormat::Snes),
+                                    string("genesis").value(TileFormat::Genesis),
+                                ])
+                                .label("<format>"),
+                            )
+                            .map(|(_, f)| ExportOption::Format(ExportFormat::Tiles(f))),
+                        option("--format")
+                            .then(
+                                choice(vec![
+                                    string("c-array").value(ExportFormat::Array { indexed: false }),
+                                    string("c-indexed")
+                                        .value(ExportFormat::Array { indexed: true }),
+                                    string("xpm").value(ExportFormat::Xpm),
+                                    string("godot").value(ExportFormat::SpriteFrames),
+                                ])
+                                .label("<format>"),
+                            )
+                            .map(|(_, f)| ExportOption::Format(f)),
+                        option("--pipe")
+                            .then(quoted().label("<command>"))
+                            .map(|(_, cmd)| ExportOption::Pipe(cmd)),
+                        option("--frames")
+                            .then(
+                                natural::<usize>()
+                                    .skip(string(".."))
+                                    .then(natural::<usize>())
+                                    .label("<from>..<to>"),
+                            )
+                            .map(|(_, (a, b))| ExportOption::Frames(a, b)),
+                        option("--frame")
+                            .then(natural::<usize>().label("<frame>"))
+                            .map(|(_, n)| ExportOption::Frames(n, n)),
+                    ])
+                };
+
+                p.then(optional(many::<_, Vec<_>>(
+                    options(leading, leading_flag).skip(whitespace()),
+                )))
+                .then(optional(scale().skip(whitespace())).then(path()))
+                .then(optional(many::<_, Vec<_>>(options(
+                    trailing,
+                    trailing_flag,
+                ))))
+                .map(|(((_, before), (scale, path)), after)| {
+                    let mut opts = ExportOptions {
+                        scale,
+                        ..ExportOptions::default()
+                    };
+
+                    for opt in before.into_iter().chain(after).flatten() {
+                        match opt {
+                            ExportOption::Scale(n) => opts.scale = Some(n),
+                            ExportOption::Filter(f) => opts.filter = Some(f),
+                            ExportOption::Layout(cols, rows) => {
+                                opts.layout.grid = Some((cols, rows))
+                            }
+                            ExportOption::Padding(n) => opts.layout.padding = n,
+                            ExportOption::Spacing(n) => opts.layout.spacing = n,
+                            ExportOption::Profile(p) => opts.profile = Some(p),
+                            ExportOption::Format(f) => opts.format = Some(f),
+                            ExportOption::Pipe(cmd) => opts.pipe = Some(cmd),
+                            ExportOption::Frames(a, b) => opts.frames = Some((a, b)),
+                            ExportOption::Selection => opts.selection = true,
+                        }
+                    }
+                    Command::Export(path, opts)
+                })
+            })
+            .command("export-all", "Write or export all modified views", |p| {
+                let option = choice(vec![
+                    string("--dir")
+                        .skip(whitespace())
+                        .then(path().label("<dir>"))
+                        .map(|(_, dir)| (Some(dir), None)),
+                    string("--scale")
+                        .skip(whitespace())
+                        .then(natural::<u32>().label("<scale>"))
+                        .map(|(_, n)| (None, Some(n))),
+                ]);
+
+                p.then(optional(many::<_, Vec<_>>(
+                    option.skip(optional(whitespace())),
+                )))
+                .map(|(_, opts)| {
+                    let (dir, scale) = opts
+                        .into_iter()
+                        .flatten()
+                        .fold((None, None), |(dir, scale), (d, s)| {
+                            (d.or(dir), s.or(scale))
+                        });
+                    Command::ExportAll(dir, scale)
+                })
             })
             .command("wq", "Write & quit view", |p| p.value(Command::WriteQuit))
             .command("x", "Write & quit view", |p| p.value(Command::WriteQuit))
@@ -761,25 +1453,55 @@ impl Default for Commands {
                 p.then(optional(path()))
                     .map(|(_, dir)| Command::WriteFrames(dir))
             })
+            .command("backups", "List or restore backups of the view file", |p| {
+                p.then(optional(natural::<usize>().label("<n>")))
+                    .map(|(_, n)| Command::Backups(n))
+            })
             .command("e", "Edit path(s)", |p| {
                 p.then(paths()).map(|(_, paths)| Command::Edit(paths))
             })
             .command("e/frames", "Edit frames as view", |p| {
                 p.then(paths()).map(|(_, paths)| Command::EditFrames(paths))
             })
+            .command(
+                "import",
+                "Import an image into the active view as a paste, centered on the cursor, \
+                 or with its top-left corner at the given coordinates",
+                |p| {
+                    p.then(path())
+                        .then(optional(whitespace().then(tuple::<i32>(
+                            integer().label("<x>"),
+                            integer().label("<y>"),
+                        ))))
+                        .map(|((_, path), pos)| Command::Import(path, pos.map(|(_, p)| p)))
+                },
+            )
             .command("help", "Display help", |p| {
                 p.value(Command::Mode(Mode::Help))
             })
-            .command("set", "Set setting to value", |p| {
-                p.then(setting())
-                    .skip(optional(whitespace()))
-                    .then(optional(
-                        symbol('=')
-                            .skip(optional(whitespace()))
-                            .then(Value::parser())
-                            .map(|(_, v)| v),
-                    ))
-                    .map(|((_, k), v)| Command::Set(k, v.unwrap_or(Value::Bool(true))))
+            .command("set", "Set setting to value, or show settings", |p| {
+                p.then(optional(
+                    // Shorthand for showing the grid, eg. `set grid 16 #333333`.
+                    peek(
+                        string("grid")
+                            .skip(whitespace())
+                            .then(natural::<u32>().label("<spacing>")),
+                    )
+                    .then(optional(whitespace().then(color()).map(|(_, c)| c)))
+                    .map(|((_, n), c)| Command::Grid(n, c))
+                    // Query a setting, eg. `set grid?`.
+                    .or(peek(setting().skip(symbol('?'))).map(|k| Command::Settings(Some(k))))
+                    .or(setting()
+                        .skip(optional(whitespace()))
+                        .then(optional(
+                            symbol('=')
+                                .skip(optional(whitespace()))
+                                .then(Value::parser())
+                                .map(|(_, v)| v),
+                        ))
+                        .map(|(k, v)| Command::Set(k, v.unwrap_or(Value::Bool(true))))),
+                ))
+                .map(|(_, cmd)| cmd.unwrap_or(Command::Settings(None)))
             })
             .command("unset", "Set setting to `off`", |p| {
                 p.then(setting())
@@ -797,12 +1519,50 @@ impl Default for Commands {
             })
             .command(
                 "source",
-                "Source an rx script (eg. palette or config)",
+                "Source an rx script (eg. palette or config), or the init script again",
                 |p| p.then(optional(path())).map(|(_, p)| Command::Source(p)),
             )
+            .command(
+                "record/start",
+                "Record a timelapse of the active view, every <edits> edits",
+                |p| {
+                    p.then(path())
+                        .then(optional(peek(
+                            whitespace().then(natural::<usize>().label("<edits>")),
+                        )))
+                        .map(|((_, path), n)| {
+                            Command::RecordStart(path, n.map(|(_, n)| n).unwrap_or(1))
+                        })
+                },
+            )
+            .command(
+                "record/stop",
+                "Stop recording and write the timelapse",
+                |p| p.value(Command::RecordStop),
+            )
+            .command("checkpoint/save", "Save a named checkpoint", |p| {
+                p.then(token().label("<name>"))
+                    .map(|(_, name)| Command::CheckpointSave(name))
+            })
+            .command("checkpoint/revert", "Revert to a named checkpoint", |p| {
+                p.then(token().label("<name>"))
+                    .map(|(_, name)| Command::CheckpointRevert(name))
+            })
+            .command(
+                "checkpoint/diff",
+                "Show the pixels changed since a checkpoint",
+                |p| {
+                    p.then(optional(token().label("<name>")))
+                        .map(|(_, name)| Command::CheckpointDiff(name))
+                },
+            )
             .command("cd", "Change current directory", |p| {
                 p.then(optional(path())).map(|(_, p)| Command::ChangeDir(p))
             })
+            .command("theme", "Load a UI theme by name or path", |p| {
+                p.then(optional(path().label("<theme>")))
+                    .map(|(_, t)| Command::Theme(t))
+            })
             .command("zoom", "Zoom view", |p| {
                 p.then(
                     peek(rational::<f32>().label("<level>"))
@@ -816,8 +1576,9 @@ impl Default for Commands {
                         .or(symbol('+')
                             .value(Command::Zoom(Op::Incr))
                             .or(symbol('-').value(Command::Zoom(Op::Decr)))
+                            .or(string("fit").value(Command::ZoomFit))
                             .or(fail("couldn't parse zoom parameter")))
-                        .label("+/-"),
+                        .label("+/-/fit"),
                 )
                 .map(|(_, cmd)| cmd)
             })
@@ -834,6 +1595,48 @@ impl Default for Commands {
                 )
                 .map(|(_, cmd)| cmd)
             })
+            .command(
+                "brush/spacing",
+                "Set the distance between brush stroke points",
+                |p| {
+                    p.then(natural::<usize>().label("<n>"))
+                        .map(|(_, n)| Command::BrushSpacing(n))
+                },
+            )
+            .command(
+                "brush/color",
+                "Set the brush color, eg. `#ff0000` or a palette group color like `skin.2`",
+                |p| p.then(color_ref()).map(|(_, c)| Command::BrushColor(c)),
+            )
+            .command(
+                "color/recent",
+                "Pick a recently painted color, `0` being the most recent",
+                |p| {
+                    p.then(natural::<usize>().label("<index>"))
+                        .map(|(_, i)| Command::ColorRecent(i))
+                },
+            )
+            .command(
+                "color/picker",
+                "Pick a foreground color with arrows, <shift>-arrows for hue, and <enter>, \
+                 or <shift>-<enter> to also add it to the palette",
+                |p| p.value(Command::ColorPicker),
+            )
+            .command(
+                "color/budget",
+                "List the frames with more colors than `max-colors` allows",
+                |p| p.value(Command::ColorBudget),
+            )
+            .command(
+                "color/next-in-ramp",
+                "Set the foreground color to the next color of its palette ramp",
+                |p| p.value(Command::ColorRamp(Direction::Forward)),
+            )
+            .command(
+                "color/prev-in-ramp",
+                "Set the foreground color to the previous color of its palette ramp",
+                |p| p.value(Command::ColorRamp(Direction::Backward)),
+            )
             .command(
                 "brush/set",
                 "Set brush mode, eg. `xsym` for x-symmetry",
@@ -850,11 +1653,76 @@ impl Default for Commands {
                 p.then(param::<BrushMode>())
                     .map(|(_, m)| Command::BrushToggle(m))
             })
+            .command(
+                "brush/snap",
+                "Snap lines to the given angle, eg. while a key is held, or to the line mode angle",
+                |p| p.then(optional(snap())).map(|(_, s)| Command::BrushSnap(s)),
+            )
+            .command(
+                "brush/jitter",
+                "Randomly vary each brush pixel between <amount> adjacent palette colors, \
+                 or by up to <amount> alpha",
+                |p| {
+                    p.then(
+                        choice(vec![
+                            string("color")
+                                .skip(whitespace())
+                                .then(natural::<usize>().label("<amount>"))
+                                .map(|(_, n)| Command::BrushJitterColor(n)),
+                            string("alpha")
+                                .skip(whitespace())
+                                .then(natural::<u8>().label("<amount>"))
+                                .map(|(_, n)| Command::BrushJitterAlpha(n)),
+                        ])
+                        .label("color/alpha"),
+                    )
+                    .map(|(_, cmd)| cmd)
+                },
+            )
+            .command(
+                "brush/axis",
+                "Move the brush symmetry axes to the cursor",
+                |p| p.value(Command::BrushAxis),
+            )
+            .command(
+                "brush/vp",
+                "Place a vanishing point at the cursor for `vp` line snapping, replacing the \
+                 oldest of two, or clear them",
+                |p| {
+                    p.then(optional(string("clear"))).map(|(_, clear)| {
+                        if clear.is_some() {
+                            Command::BrushVanishingClear
+                        } else {
+                            Command::BrushVanishingPoint
+                        }
+                    })
+                },
+            )
             .command("brush", "Switch to brush", |p| {
                 p.value(Command::Tool(Tool::Brush))
             })
             .command("flood", "Switch to flood fill tool", |p| {
-                p.value(Command::Tool(Tool::FloodFill))
+                let options = choice(vec![
+                    string("--global").value(FloodOption::Global),
+                    string("--tolerance")
+                        .skip(whitespace())
+                        .then(natural::<u8>().label("<0..255>"))
+                        .map(|(_, n)| FloodOption::Tolerance(n)),
+                ])
+                .skip(optional(whitespace()));
+
+                p.then(optional(many::<_, Vec<_>>(options)))
+                    .map(|(_, opts)| {
+                        let mut flood = FloodOptions::default();
+
+                        for opt in opts.unwrap_or_default() {
+                            match opt {
+                                FloodOption::Global => flood.global = true,
+                                FloodOption::Tolerance(n) => flood.tolerance = n,
+                            }
+                        }
+                        Command::Flood(flood)
+                    })
             })
             .command("mode", "Set session mode, eg. `visual` or `normal`", |p| {
                 p.then(param::<Mode>()).map(|(_, m)| Command::Mode(m))
@@ -877,6 +1745,139 @@ impl Default for Commands {
             .command("v/center", "Center the active view", |p| {
                 p.value(Command::ViewCenter)
             })
+            .command(
+                "v/link",
+                "Link the active view with another, to mirror brush strokes between them",
+                |p| {
+                    p.then(path().label("<path>"))
+                        .map(|(_, path)| Command::ViewLink(path))
+                },
+            )
+            .command("v/unlink", "Unlink the active view from all views", |p| {
+                p.value(Command::ViewUnlink)
+            })
+            .command("stats", "Show active view statistics", |p| {
+                p.value(Command::ViewStats)
+            })
+            .command("preview", "Preview the current frame nine-sliced", |p| {
+                let margins = peek(whitespace().then(string("--margins")))
+                    .then(whitespace())
+                    .then(natural::<u32>().label("<margins>"))
+                    .map(|(_, n)| n);
+                let nine_slice = string("9slice")
+                    .skip(whitespace())
+                    .then(
+                        natural::<u32>()
+                            .skip(symbol('x'))
+                            .then(natural::<u32>())
+                            .label("<width>x<height>"),
+                    )
+                    .then(optional(margins))
+                    .map(|((_, (width, height)), margins)| NineSlice {
+                        width,
+                        height,
+                        margins,
+                    });
+
+                p.then(optional(nine_slice))
+                    .map(|(_, p)| Command::Preview(p))
+            })
+            .command(
+                "preview/window",
+                "Show the active view in a separate window, or `0` to close it",
+                |p| {
+                    p.then(optional(natural::<u32>().label("<zoom>")))
+                        .map(|(_, z)| Command::PreviewWindow(z.unwrap_or(1)))
+                },
+            )
+            .command(
+                "preview/backdrop",
+                "Show an image or a color behind the animation preview, or nothing",
+                |p| {
+                    p.then(optional(
+                        color()
+                            .map(Value::Rgba8)
+                            .or(path().map(Value::Str))
+                            .label("<path|color>"),
+                    ))
+                    .map(|(_, v)| Command::PreviewBackdrop(v))
+                },
+            )
+            .command(
+                "tile/copy",
+                "Copy a tile over another, in tileset mode",
+                |p| {
+                    p.then(tuple::<usize>(
+                        natural().label("<src>"),
+                        natural().label("<dst>"),
+                    ))
+                    .map(|(_, (src, dst))| Command::TileCopy(src, dst))
+                },
+            )
+            .command("tile/swap", "Swap two tiles, in tileset mode", |p| {
+                p.then(tuple::<usize>(
+                    natural().label("<tile>"),
+                    natural().label("<tile>"),
+                ))
+                .map(|(_, (a, b))| Command::TileSwap(a, b))
+            })
+            .command("tile/dupes", "Find duplicate tiles, in tileset mode", |p| {
+                p.value(Command::TileDuplicates)
+            })
+            .command("jump/back", "Jump back to the previous position", |p| {
+                p.value(Command::JumpBack)
+            })
+            .command("jump/forward", "Jump forward to the next position", |p| {
+                p.value(Command::JumpForward)
+            })
+            .command(
+                "guide/h",
+                "Add a horizontal guide at <y> or the cursor",
+                |p| {
+                    p.then(optional(integer::<i32>().label("<y>")))
+                        .map(|(_, y)| Command::GuideAdd(Axis::Horizontal, y))
+                },
+            )
+            .command(
+                "guide/v",
+                "Add a vertical guide at <x> or the cursor",
+                |p| {
+                    p.then(optional(integer::<i32>().label("<x>")))
+                        .map(|(_, x)| Command::GuideAdd(Axis::Vertical, x))
+                },
+            )
+            .command("guide/clear", "Clear all guides of the active view", |p| {
+                p.value(Command::GuideClear)
+            })
+            .command("split", "Split the workspace with the active view", |p| {
+                p.value(Command::Split)
+            })
+            .command("split/close", "Close the split pane", |p| {
+                p.value(Command::SplitClose)
+            })
+            .command(
+                "split/focus",
+                "Swap the active view with the split pane view",
+                |p| p.value(Command::SplitFocus),
+            )
+            .command("split/resize", "Resize the split pane", |p| {
+                p.then(
+                    peek(rational::<f32>().label("<ratio>"))
+                        .try_map(|r| {
+                            if r > 0. && r < 1. {
+                                Ok(Command::SplitResize(Op::Set(r)))
+                            } else {
+                                Err("split ratio must be between 0.0 and 1.0")
+                            }
+                        })
+                        .or(symbol('+')
+                            .value(Command::SplitResize(Op::Incr))
+                            .or(symbol('-').value(Command::SplitResize(Op::Decr)))
+                            .or(fail("couldn't parse split ratio parameter")))
+                        .label("+/-"),
+                )
+                .map(|(_, cmd)| cmd)
+            })
             .command("v/clear", "Clear the active view", |p| {
                 p.value(Command::Fill(Some(Rgba8::TRANSPARENT)))
             })
@@ -913,12 +1914,41 @@ impl Default for Commands {
             .command("map/clear!", "Clear all key mappings", |p| {
                 p.value(Command::MapClear)
             })
+            .command(
+                "search",
+                "Highlight pixels of a color in the active view",
+                |p| p.then(optional(color())).map(|(_, c)| Command::Search(c)),
+            )
+            .command("search/next", "Go to the next search match", |p| {
+                p.value(Command::SearchNext)
+            })
+            .command("search/prev", "Go to the previous search match", |p| {
+                p.value(Command::SearchPrev)
+            })
             .command("p/add", "Add a color to the palette", |p| {
                 p.then(color()).map(|(_, rgba)| Command::PaletteAdd(rgba))
             })
             .command("p/clear", "Clear the color palette", |p| {
                 p.value(Command::PaletteClear)
             })
+            .command(
+                "p/group",
+                "Start a named palette group, which colors are added to",
+                |p| {
+                    p.then(identifier().label("<name>"))
+                        .map(|(_, name)| Command::PaletteGroup(name))
+                },
+            )
+            .command(
+                "p/view",
+                "Give the active view its own palette, or `off` to use the session palette",
+                |p| {
+                    p.then(optional(
+                        string("on").value(true).or(string("off").value(false)),
+                    ))
+                    .map(|(_, on)| Command::PaletteView(on.unwrap_or(true)))
+                },
+            )
             .command("p/gradient", "Add a gradient to the palette", |p| {
                 p.then(tuple::<Rgba8>(
                     color().label("<from>"),
@@ -936,11 +1966,99 @@ impl Default for Commands {
             .command("p/sort", "Sort the palette colors", |p| {
                 p.value(Command::PaletteSort)
             })
+            .command(
+                "p/highlight",
+                "Highlight the pixels using a palette color, or clear the highlight",
+                |p| {
+                    p.then(optional(natural::<usize>().label("<index>")))
+                        .map(|(_, i)| Command::PaletteHighlight(i))
+                },
+            )
+            .command(
+                "p/cycle",
+                "Cycle a range of palette colors in the animation preview, one step every \
+                 <frames> animation frames, or stop cycling",
+                |p| {
+                    p.then(optional(
+                        natural::<usize>()
+                            .label("<from>")
+                            .skip(whitespace())
+                            .then(natural::<usize>().label("<to>"))
+                            .then(optional(
+                                whitespace().then(natural::<usize>().label("<frames>")),
+                            )),
+                    ))
+                    .map(|(_, range)| {
+                        Command::PaletteCycle(
+                            range.map(|((a, b), n)| (a, b, n.map(|(_, n)| n).unwrap_or(1))),
+                        )
+                    })
+                },
+            )
             .command("p/write", "Write the color palette to a file", |p| {
                 p.then(path()).map(|(_, path)| Command::PaletteWrite(path))
             })
             .command("undo", "Undo the last edit", |p| p.value(Command::Undo))
             .command("redo", "Redo the last edit", |p| p.value(Command::Redo))
+            .command(
+                "undo/older",
+                "Go to the previous edit in time, across branches",
+                |p| p.value(Command::UndoOlder),
+            )
+            .command(
+                "undo/newer",
+                "Go to the next edit in time, across branches",
+                |p| p.value(Command::UndoNewer),
+            )
+            .command("undo/goto", "Go to the given edit", |p| {
+                p.then(natural::<usize>().label("<edit>"))
+                    .map(|(_, edit)| Command::UndoGoto(edit))
+            })
+            .command("undo/tree", "Show the edit history branches", |p| {
+                p.value(Command::UndoTree)
+            })
+            .command(
+                "undo/begin",
+                "Start grouping edits, so that they are undone as one",
+                |p| p.value(Command::UndoBegin),
+            )
+            .command("undo/end", "Stop grouping edits", |p| {
+                p.value(Command::UndoEnd)
+            })
+            .command(
+                "undo/group",
+                "Run commands separated by `;`, as a single undo step",
+                |p| {
+                    // Prevent stack overflow.
+                    let cmds = Parser::new(
+                        |input| {
+                            let i = match input.bytes().position(|c| c == b'}') {
+                                Some(i) => i,
+                                None => return Err(("unclosed '{' delimiter".into(), input)),
+                            };
+                            let parser = Commands::default().parser();
+                            let mut cmds = Vec::new();
+
+                            for cmd in input[..i].split(';').map(str::trim) {
+                                match parser.parse(cmd) {
+                                    Ok((cmd, rest)) if rest.trim().is_empty() => cmds.push(cmd),
+                                    Ok((_, rest)) => {
+                                        return Err((
+                                            format!("expected {:?}, got {:?}", ';', rest).into(),
+                                            rest,
+                                        ))
+                                    }
+                                    Err(err) => return Err(err),
+                                }
+                            }
+                            Ok((cmds, &input[i..]))
+                        },
+                        "<cmd>",
+                    );
+                    p.then(between('{', '}', cmds))
+                        .map(|(_, cmds)| Command::UndoGroup(cmds))
+                },
+            )
             .command("f/add", "Add a blank frame to the active view", |p| {
                 p.value(Command::FrameAdd)
             })
@@ -948,10 +2066,43 @@ impl Default for Commands {
                 p.then(optional(integer::<i32>().label("<index>")))
                     .map(|(_, index)| Command::FrameClone(index.unwrap_or(-1)))
             })
+            .command("f/insert", "Insert a blank frame at an index", |p| {
+                p.then(natural::<usize>().label("<index>"))
+                    .map(|(_, index)| Command::FrameInsert(index))
+            })
+            .command("f/dup", "Duplicate a frame in place", |p| {
+                p.then(natural::<usize>().label("<index>"))
+                    .map(|(_, index)| Command::FrameDup(index))
+            })
             .command(
                 "f/remove",
-                "Remove the last frame from the active view",
-                |p| p.value(Command::FrameRemove),
+                "Remove a frame, or the last frame from the active view",
+                |p| {
+                    p.then(optional(natural::<usize>().label("<index>")))
+                        .map(|(_, index)| Command::FrameRemove(index))
+                },
+            )
+            .command("f/move", "Move a frame to another index", |p| {
+                p.then(tuple::<usize>(
+                    natural().label("<from>"),
+                    natural().label("<to>"),
+                ))
+                .map(|(_, (from, to))| Command::FrameMove(from, to))
+            })
+            .command("f/copy", "Copy a frame of any view", |p| {
+                p.then(tuple::<usize>(
+                    natural().label("<view>"),
+                    natural().label("<index>"),
+                ))
+                .map(|(_, (view, index))| Command::FrameCopy(view, index))
+            })
+            .command(
+                "f/paste",
+                "Paste the copied frame over a frame, or the frame under the cursor",
+                |p| {
+                    p.then(optional(natural::<usize>().label("<index>")))
+                        .map(|(_, index)| Command::FramePaste(index))
+                },
             )
             .command("f/prev", "Navigate to previous frame", |p| {
                 p.value(Command::FramePrev)
@@ -959,6 +2110,46 @@ impl Default for Commands {
             .command("f/next", "Navigate to next frame", |p| {
                 p.value(Command::FrameNext)
             })
+            .command("anim/fps", "Set the animation frame rate", |p| {
+                p.then(natural::<u32>().label("<fps>"))
+                    .map(|(_, n)| Command::AnimationFps(n))
+            })
+            .command(
+                "anim/playback",
+                "Set the animation playback, eg. `pingpong`",
+                |p| {
+                    p.then(param::<Playback>())
+                        .map(|(_, m)| Command::AnimationPlayback(m))
+                },
+            )
+            .command(
+                "anim/range",
+                "Restrict animation playback to a frame range",
+                |p| {
+                    p.then(optional(tuple::<usize>(
+                        natural().label("<from>"),
+                        natural().label("<to>"),
+                    )))
+                    .map(|(_, r)| Command::AnimationRange(r))
+                },
+            )
+            .command(
+                "net/host",
+                "Host a shared session on an address, eg. 0.0.0.0:7777",
+                |p| {
+                    p.then(token().label("<address>"))
+                        .map(|(_, addr)| Command::NetHost(addr))
+                },
+            )
+            .command("net/join", "Join a shared session at an address", |p| {
+                p.then(token().label("<address>"))
+                    .map(|(_, addr)| Command::NetJoin(addr))
+            })
+            .command(
+                "net/leave",
+                "Leave or stop hosting the shared session",
+                |p| p.value(Command::NetLeave),
+            )
             .command("f/resize", "Resize the active view frame(s)", |p| {
                 p.then(tuple::<u32>(
                     natural().label("<width>"),
@@ -1022,6 +2213,48 @@ impl Default for Commands {
                 p.then(optional(color()))
                     .map(|(_, rgba)| Command::SelectionFill(rgba))
             })
+            .command(
+                "flip",
+                "Flip every frame of the view, or the frame under the cursor with `--frame`",
+                |p| {
+                    p.then(word().label("x/y"))
+                        .try_map(|(_, t)| match t.as_str() {
+                            "x" => Ok(Axis::Horizontal),
+                            "y" => Ok(Axis::Vertical),
+                            _ => Err(format!("unknown axis {:?}, must be 'x' or 'y'", t)),
+                        })
+                        .then(scope())
+                        .map(|(axis, scope)| Command::Flip(axis, scope))
+                },
+            )
+            .command(
+                "rotate",
+                "Rotate every frame of the view clockwise, or the frame under the cursor \
+                 with `--frame`",
+                |p| {
+                    p.then(natural::<u32>().label("90/180/270"))
+                        .try_map(|(_, n)| match n {
+                            90 | 180 | 270 => Ok(n),
+                            _ => Err(format!("invalid angle {}, must be 90, 180 or 270", n)),
+                        })
+                        .then(scope())
+                        .map(|(n, scope)| Command::Rotate(n, scope))
+                },
+            )
+            .command(
+                "crop",
+                "Crop every frame of the view to the bounds of its content, with `--auto`",
+                |p| {
+                    let padding = peek(whitespace().then(string("--padding")))
+                        .then(whitespace())
+                        .then(natural::<u32>().label("<padding>"))
+                        .map(|(_, n)| n);
+
+                    p.then(string("--auto"))
+                        .then(optional(padding))
+                        .map(|(_, padding)| Command::CropAuto(padding.unwrap_or(0)))
+                },
+            )
             .command("selection/flip", "Flip selection", |p| {
                 p.then(word().label("x/y"))
                     .try_map(|(_, t)| match t.as_str() {
@@ -1030,6 +2263,18 @@ impl Default for Commands {
                         _ => Err(format!("unknown axis {:?}, must be 'x' or 'y'", t)),
                     })
             })
+            .command(
+                "selection/shift",
+                "Shift selection contents, or wrap them around its edges with `--wrap`",
+                |p| {
+                    p.then(tuple::<i32>(
+                        integer().label("<dx>"),
+                        integer().label("<dy>"),
+                    ))
+                    .then(optional(peek(whitespace().then(string("--wrap")))))
+                    .map(|((_, (x, y)), wrap)| Command::SelectionShift(x, y, wrap.is_some()))
+                },
+            )
             .command("paint/color", "Paint color", |p| {
                 p.then(color())
                     .skip(whitespace())
@@ -1052,6 +2297,15 @@ impl Default for Commands {
                         Command::PaintLine(color, x1, y1, x2, y2)
                     })
             })
+            .command(
+                "paint/filter",
+                "Filter the selection, or the active layer, through a shell command. \
+                 It reads `<x> <y> <color>` lines, and writes the pixels to paint in the same form",
+                |p| {
+                    p.then(quoted().label("<command>"))
+                        .map(|(_, cmd)| Command::PaintFilter(cmd))
+                },
+            )
             .command("paint/fg", "Paint foreground color", |p| {
                 p.then(tuple::<i32>(integer().label("<x>"), integer().label("<y>")))
                     .map(|(_, (x, y))| Command::PaintForeground(x, y))
@@ -1097,9 +2351,12 @@ impl autocomplete::Completer for CommandCompleter {
                     input,
                     FileCompleterOpts { directories: true },
                 ),
-                Command::Source(path) | Command::Write(path) => {
+                Command::Source(path) | Command::Write(path) | Command::Theme(path) => {
                     self.complete_path(path.as_ref(), input, Default::default())
                 }
+                Command::Import(path, _) => {
+                    self.complete_path(Some(&path), input, Default::default())
+                }
                 Command::Edit(paths) | Command::EditFrames(paths) => {
                     self.complete_path(paths.last(), input, Default::default())
                 }
@@ -1134,6 +2391,7 @@ impl CommandCompleter {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::view::SheetLayout;
     use std::{fs, fs::File};
 
     #[test]
@@ -1375,6 +2633,20 @@ mod test {
             p.parse(":set scale = 1.0"),
             Ok((Command::Set("scale".to_owned(), Value::F64(1.0)), ""))
         );
+        assert_eq!(p.parse(":set"), Ok((Command::Settings(None), "")));
+
+        // Colors are formatted so that they parse back the same, including alpha.
+        for a in 0..=255 {
+            let cmd = Command::PaintColor(Rgba8::new(0xff, 0, 0x80, a), 4, -5);
+            assert_eq!(
+                p.parse(&format!(":{}", String::from(cmd.clone()))),
+                Ok((cmd, ""))
+            );
+        }
+        assert_eq!(
+            p.parse(":set grid?"),
+            Ok((Command::Settings(Some("grid".to_owned())), ""))
+        );
         assert_eq!(
             p.parse(":set foo=value"),
             Ok((
@@ -1417,6 +2689,354 @@ mod test {
         assert!(p.parse(":zoom -1.0").is_err());
     }
 
+    #[test]
+    fn test_grid_command() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(
+            p.parse(":set grid 16 #333333"),
+            Ok((
+                Command::Grid(16, Some(Rgba8::new(0x33, 0x33, 0x33, 0xff))),
+                ""
+            ))
+        );
+        assert_eq!(p.parse(":set grid 8"), Ok((Command::Grid(8, None), "")));
+        assert_eq!(
+            p.parse(":set grid = off"),
+            Ok((Command::Set("grid".to_owned(), Value::Bool(false)), ""))
+        );
+        assert_eq!(
+            p.parse(":set grid/spacing = 4 4"),
+            Ok((
+                Command::Set("grid/spacing".to_owned(), Value::U32Tuple(4, 4)),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn test_export_command() {
+        let p = Commands::default().line_parser();
+        let export = |path: &str, opts| Ok((Command::Export(String::from(path), opts), ""));
+
+        assert_eq!(
+            p.parse(":export out.png"),
+            export("out.png", ExportOptions::default())
+        );
+        assert_eq!(
+            p.parse(":export @4x out.png"),
+            export(
+                "out.png",
+                ExportOptions {
+                    scale: Some(4),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export out.png --scale 8"),
+            export(
+                "out.png",
+                ExportOptions {
+                    scale: Some(8),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert!(p.parse(":export out.png --scale").is_err());
+        assert_eq!(
+            p.parse(":export out.png --scale 2 --filter scale2x"),
+            export(
+                "out.png",
+                ExportOptions {
+                    scale: Some(2),
+                    filter: Some(ScaleFilter::Scale2x),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert!(p.parse(":export out.png --filter hq4x").is_err());
+        assert_eq!(
+            p.parse(":export sheet.png --layout 4x2 --padding 1 --spacing 2 --scale 2"),
+            export(
+                "sheet.png",
+                ExportOptions {
+                    scale: Some(2),
+                    layout: SheetLayout {
+                        grid: Some((4, 2)),
+                        padding: 1,
+                        spacing: 2,
+                    },
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export --profile gb tiles.2bpp"),
+            export(
+                "tiles.2bpp",
+                ExportOptions {
+                    profile: Some(Profile::GameBoy),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export sheet.png --profile pico8"),
+            export(
+                "sheet.png",
+                ExportOptions {
+                    profile: Some(Profile::Pico8),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export sprites.bin --tiles genesis"),
+            export(
+                "sprites.bin",
+                ExportOptions {
+                    format: Some(ExportFormat::Tiles(TileFormat::Genesis)),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export --format c-indexed sprite.h"),
+            export(
+                "sprite.h",
+                ExportOptions {
+                    format: Some(ExportFormat::Array { indexed: true }),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export out.png --pipe \"oxipng -o 4\""),
+            export(
+                "out.png",
+                ExportOptions {
+                    pipe: Some(String::from("oxipng -o 4")),
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export out.png --selection --scale 2"),
+            export(
+                "out.png",
+                ExportOptions {
+                    scale: Some(2),
+                    selection: true,
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        assert_eq!(
+            p.parse(":export --selection out.png"),
+            export(
+                "out.png",
+                ExportOptions {
+                    selection: true,
+                    ..ExportOptions::default()
+                }
+            )
+        );
+        for (input, frames) in &[
+            (":export --frame 2 out.png", (2, 2)),
+            (":export out.png --frames 1..3", (1, 3)),
+        ] {
+            let opts = ExportOptions {
+                frames: Some(*frames),
+                ..ExportOptions::default()
+            };
+            assert_eq!(p.parse(input), export("out.png", opts.clone()));
+            assert_eq!(
+                p.parse(&format!(
+                    ":{}",
+                    String::from(Command::Export("out.png".into(), opts))
+                )),
+                p.parse(input)
+            );
+        }
+
+        assert_eq!(
+            p.parse(":export-all"),
+            Ok((Command::ExportAll(None, None), ""))
+        );
+        assert_eq!(
+            p.parse(":export-all --scale 2 --dir out"),
+            Ok((Command::ExportAll(Some("out".into()), Some(2)), ""))
+        );
+        assert_eq!(
+            p.parse(&format!(
+                ":{}",
+                String::from(Command::ExportAll(Some("out".into()), Some(2)))
+            )),
+            p.parse(":export-all --dir out --scale 2")
+        );
+    }
+
+    #[test]
+    fn test_frame_commands() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(p.parse(":f/insert 0"), Ok((Command::FrameInsert(0), "")));
+        assert_eq!(p.parse(":f/dup 2"), Ok((Command::FrameDup(2), "")));
+        assert_eq!(p.parse(":f/remove"), Ok((Command::FrameRemove(None), "")));
+        assert_eq!(p.parse(":f/copy 1 2"), Ok((Command::FrameCopy(1, 2), "")));
+        assert_eq!(
+            p.parse(":brush/color skin.2"),
+            Ok((
+                Command::BrushColor(ColorRef::Named(String::from("skin"), 2)),
+                ""
+            ))
+        );
+        assert_eq!(
+            p.parse(":brush/color #ff0000"),
+            Ok((Command::BrushColor(ColorRef::Color(Rgba8::RED)), ""))
+        );
+        assert_eq!(p.parse(":f/paste"), Ok((Command::FramePaste(None), "")));
+        assert_eq!(
+            p.parse(":f/remove 1"),
+            Ok((Command::FrameRemove(Some(1)), ""))
+        );
+        assert_eq!(p.parse(":f/move 3 0"), Ok((Command::FrameMove(3, 0), "")));
+    }
+
+    #[test]
+    fn test_preview_command() {
+        let p = Commands::default().line_parser();
+        let preview = NineSlice {
+            width: 128,
+            height: 48,
+            margins: Some(4),
+        };
+
+        assert_eq!(
+            p.parse(":preview 9slice 128x48 --margins 4"),
+            Ok((Command::Preview(Some(preview)), ""))
+        );
+        assert_eq!(
+            p.parse(":preview 9slice 128x48"),
+            Ok((
+                Command::Preview(Some(NineSlice {
+                    margins: None,
+                    ..preview
+                })),
+                ""
+            ))
+        );
+        assert_eq!(p.parse(":preview"), Ok((Command::Preview(None), "")));
+        assert_eq!(
+            p.parse(":preview/window"),
+            Ok((Command::PreviewWindow(1), ""))
+        );
+        assert_eq!(
+            p.parse(":preview/window 0"),
+            Ok((Command::PreviewWindow(0), ""))
+        );
+        assert_eq!(
+            String::from(Command::Preview(Some(preview))),
+            "preview 9slice 128x48 --margins 4"
+        );
+        assert_eq!(
+            p.parse(":preview/backdrop #336699"),
+            Ok((
+                Command::PreviewBackdrop(Some(Value::Rgba8(Rgba8::new(0x33, 0x66, 0x99, 0xff)))),
+                ""
+            ))
+        );
+        assert_eq!(
+            p.parse(":preview/backdrop scene.png"),
+            Ok((
+                Command::PreviewBackdrop(Some(Value::Str(String::from("scene.png")))),
+                ""
+            ))
+        );
+        assert_eq!(
+            p.parse(":selection/shift 4 -2 --wrap"),
+            Ok((Command::SelectionShift(4, -2, true), ""))
+        );
+        assert_eq!(
+            p.parse(":selection/shift 1 0"),
+            Ok((Command::SelectionShift(1, 0, false), ""))
+        );
+        assert_eq!(
+            p.parse(":preview/backdrop"),
+            Ok((Command::PreviewBackdrop(None), ""))
+        );
+    }
+
+    #[test]
+    fn test_guide_commands() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(
+            p.parse(":guide/h 12"),
+            Ok((Command::GuideAdd(Axis::Horizontal, Some(12)), ""))
+        );
+        assert_eq!(
+            p.parse(":guide/v"),
+            Ok((Command::GuideAdd(Axis::Vertical, None), ""))
+        );
+    }
+
+    #[test]
+    fn test_crop_command() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(p.parse(":crop --auto"), Ok((Command::CropAuto(0), "")));
+        assert_eq!(
+            p.parse(":crop --auto --padding 2"),
+            Ok((Command::CropAuto(2), ""))
+        );
+        assert_eq!(
+            String::from(Command::CropAuto(2)),
+            "crop --auto --padding 2"
+        );
+        assert!(p.parse(":crop").is_err());
+    }
+
+    #[test]
+    fn test_undo_group_command() {
+        let p = Commands::default().line_parser();
+        let group = Command::UndoGroup(vec![
+            Command::FrameAdd,
+            Command::Flip(Axis::Vertical, Scope::View),
+        ]);
+
+        assert_eq!(
+            p.parse(":undo/group { :f/add; :flip y }"),
+            Ok((group.clone(), ""))
+        );
+        assert_eq!(
+            p.parse(&format!(":{}", String::from(group.clone()))),
+            Ok((group, ""))
+        );
+        assert!(p.parse(":undo/group { :f/add :flip y }").is_err());
+        assert!(p.parse(":undo/group { :flip x").is_err());
+    }
+
+    #[test]
+    fn test_transform_commands() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(
+            p.parse(":flip x"),
+            Ok((Command::Flip(Axis::Horizontal, Scope::View), ""))
+        );
+        assert_eq!(
+            p.parse(":flip y --frame"),
+            Ok((Command::Flip(Axis::Vertical, Scope::Frame), ""))
+        );
+        assert_eq!(
+            p.parse(":rotate 90 --view"),
+            Ok((Command::Rotate(90, Scope::View), ""))
+        );
+        assert!(p.parse(":rotate 45").is_err());
+    }
+
     #[test]
     fn test_vfill_commands() {
         let p = Commands::default().line_parser();
@@ -1456,6 +3076,111 @@ mod test {
         assert_eq!(rest, "");
     }
 
+    #[test]
+    fn test_flood_command() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(
+            p.parse(":flood"),
+            Ok((Command::Flood(FloodOptions::default()), ""))
+        );
+        assert_eq!(
+            p.parse(":flood --tolerance 16 --global"),
+            Ok((
+                Command::Flood(FloodOptions {
+                    global: true,
+                    tolerance: 16
+                }),
+                ""
+            ))
+        );
+        assert_eq!(
+            String::from(Command::Flood(FloodOptions {
+                global: true,
+                tolerance: 16
+            })),
+            "flood --global --tolerance 16"
+        );
+    }
+
+    #[test]
+    fn test_brush_erase_commands() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(
+            p.parse(":brush/set erase"),
+            Ok((Command::BrushSet(BrushMode::Erase(false)), ""))
+        );
+        assert_eq!(
+            p.parse(":brush/set erase bg"),
+            Ok((Command::BrushSet(BrushMode::Erase(true)), ""))
+        );
+        assert_eq!(
+            String::from(Command::BrushSet(BrushMode::Erase(true))),
+            "brush/set erase bg"
+        );
+    }
+
+    #[test]
+    fn test_brush_iso_commands() {
+        let p = Commands::default().line_parser();
+
+        assert_eq!(
+            p.parse(":brush/set line iso"),
+            Ok((Command::BrushSet(BrushMode::Line(Some(Snap::Iso))), ""))
+        );
+        assert_eq!(
+            p.parse(":brush/set line 15"),
+            Ok((
+                Command::BrushSet(BrushMode::Line(Some(Snap::Degrees(15)))),
+                ""
+            ))
+        );
+        assert_eq!(
+            p.parse(":brush/set isosym"),
+            Ok((Command::BrushSet(BrushMode::IsoSym), ""))
+        );
+        assert_eq!(
+            p.parse(":brush/set octsym"),
+            Ok((Command::BrushSet(BrushMode::OctSym), ""))
+        );
+        assert_eq!(
+            p.parse(":brush/set circle"),
+            Ok((Command::BrushSet(BrushMode::Circle), ""))
+        );
+        assert_eq!(
+            p.parse(":brush/snap iso"),
+            Ok((Command::BrushSnap(Some(Snap::Iso)), ""))
+        );
+        assert_eq!(
+            String::from(Command::BrushSnap(Some(Snap::Iso))),
+            "brush/snap iso"
+        );
+        assert_eq!(
+            p.parse(":brush/set line vp"),
+            Ok((
+                Command::BrushSet(BrushMode::Line(Some(Snap::Vanishing))),
+                ""
+            ))
+        );
+        assert_eq!(
+            p.parse(":brush/vp clear"),
+            Ok((Command::BrushVanishingClear, ""))
+        );
+        assert_eq!(
+            p.parse(":brush/jitter color 2"),
+            Ok((Command::BrushJitterColor(2), ""))
+        );
+        assert_eq!(
+            p.parse(":brush/jitter alpha 64"),
+            Ok((Command::BrushJitterAlpha(64), ""))
+        );
+        assert_eq!(
+            String::from(Command::BrushJitterAlpha(64)),
+            "brush/jitter alpha 64"
+        );
+    }
+
     #[test]
     fn tes_value_parser() {
         let p = Value::parser();
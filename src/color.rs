@@ -5,6 +5,7 @@ pub const BLACK: Rgba8 = Rgba8::new(0x00, 0x00, 0x00, 0xff);
 pub const TRANSPARENT: Rgba8 = Rgba8::new(0x00, 0x00, 0x00, 0x00);
 pub const GREY: Rgba8 = Rgba8::new(0x88, 0x88, 0x88, 0xff);
 pub const DARK_GREY: Rgba8 = Rgba8::new(0x55, 0x55, 0x55, 0xff);
+pub const CHECKER_GREY: Rgba8 = Rgba8::new(0x66, 0x66, 0x66, 0xff);
 pub const LIGHT_GREY: Rgba8 = Rgba8::new(0xaa, 0xaa, 0xaa, 0xff);
 pub const RED: Rgba8 = Rgba8::new(0xff, 0x33, 0x66, 0xff);
 pub const YELLOW: Rgba8 = Rgba8::new(0xff, 0xff, 0x66, 0xff);
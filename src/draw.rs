@@ -1,12 +1,15 @@
 use crate::brush::{Align, Brush, BrushMode};
+use crate::cmd::Axis;
 use crate::color;
 use crate::execution::Execution;
 use crate::font::{TextAlign, TextBatch};
+use crate::picker::{self, Picker};
 use crate::platform;
 use crate::session;
 use crate::session::{Mode, Session, Tool, VisualState};
 use crate::sprite;
-use crate::view::{layer::LayerCoords, View};
+use crate::statusline;
+use crate::view::{layer::LayerCoords, Guide, Playback, View, ViewCoords};
 
 use crate::gfx::math::{Matrix4, Vector2};
 use crate::gfx::rect::Rect;
@@ -23,7 +26,9 @@ pub const VIEW_LAYER: ZDepth = ZDepth(-0.7);
 pub const BRUSH_LAYER: ZDepth = ZDepth(-0.6);
 pub const GRID_LAYER: ZDepth = ZDepth(-0.5);
 pub const UI_LAYER: ZDepth = ZDepth(-0.4);
+pub const SPLIT_LAYER: ZDepth = ZDepth(-0.35);
 pub const TEXT_LAYER: ZDepth = ZDepth(-0.3);
+pub const MINIMAP_LAYER: ZDepth = ZDepth(-0.25);
 pub const PALETTE_LAYER: ZDepth = ZDepth(-0.2);
 pub const HELP_LAYER: ZDepth = ZDepth(-0.1);
 pub const CURSOR_LAYER: ZDepth = ZDepth(0.0);
@@ -33,13 +38,21 @@ pub const XRAY_MIN_ZOOM: f32 = 16.0;
 pub const GLYPH_WIDTH: f32 = 8.;
 pub const GLYPH_HEIGHT: f32 = 14.;
 
-#[rustfmt::skip]
-pub const CHECKER: [u8; 16] = [
-    0x55, 0x55, 0x55, 0xff,
-    0x66, 0x66, 0x66, 0xff,
-    0x66, 0x66, 0x66, 0xff,
-    0x55, 0x55, 0x55, 0xff,
-];
+/// The 2x2 checker texture, from the `checker/dark` and `checker/light` settings.
+pub fn checker(session: &Session) -> [Rgba8; 4] {
+    let dark = session.settings["checker/dark"].to_rgba8();
+    let light = session.settings["checker/light"].to_rgba8();
+
+    [dark, light, light, dark]
+}
+
+/// Whether anything is drawn behind views, ie. the checker, a backdrop or the matte.
+pub fn has_backdrop(session: &Session) -> bool {
+    session.settings["checker"].is_set()
+        || session.settings["matte"].is_set()
+        || session.settings["backdrop"].to_rgba8().a > 0
+}
+
 const CHECKER_REPEAT: f32 = 4.;
 const LINE_HEIGHT: f32 = GLYPH_HEIGHT + 4.;
 const MARGIN: f32 = 10.;
@@ -96,7 +109,7 @@ pub mod cursors {
                     self::OMNI
                 }
                 _ => {
-                    if b.is_set(BrushMode::Erase) {
+                    if b.is_erasing() {
                         self::ERASE
                     } else {
                         self::CROSSHAIR
@@ -118,6 +131,9 @@ mod checker {
 
 pub struct Context {
     pub ui_batch: shape2d::Batch,
+    /// UI chrome, such as the palette, drawn at the `ui/scale`.
+    pub chrome_batch: shape2d::Batch,
+    /// Text, drawn at the `ui/scale`.
     pub text_batch: TextBatch,
     pub overlay_batch: TextBatch,
     pub cursor_sprite: sprite::Sprite,
@@ -136,15 +152,30 @@ impl Context {
         self::draw_brush(session, &session.brush, &mut self.ui_batch);
         self::draw_paste(session, &mut self.paste_batch);
         self::draw_grid(session, &mut self.ui_batch);
-        self::draw_ui(session, &mut self.ui_batch, &mut self.text_batch);
+        self::draw_guides(session, &mut self.ui_batch);
+        self::draw_checkpoint_diff(session, &mut self.ui_batch);
+        self::draw_search(session, &mut self.ui_batch);
+        self::draw_highlight(session, &mut self.ui_batch);
+        self::draw_tiles(session, &mut self.ui_batch);
+        self::draw_ui(
+            session,
+            &mut self.ui_batch,
+            &mut self.chrome_batch,
+            &mut self.text_batch,
+        );
         self::draw_overlay(session, avg_frametime, &mut self.overlay_batch, execution);
-        self::draw_palette(session, &mut self.ui_batch);
+        self::draw_palette(session, &mut self.chrome_batch);
+        self::draw_picker(session, &mut self.chrome_batch);
+        self::draw_minimap(session, &mut self.ui_batch);
+        self::draw_rulers(session, &mut self.ui_batch);
+        self::draw_preview_border(session, &mut self.ui_batch);
         self::draw_cursor(session, &mut self.cursor_sprite, &mut self.tool_batch);
         self::draw_checker(session, &mut self.checker_batch);
     }
 
     pub fn clear(&mut self) {
         self.ui_batch.clear();
+        self.chrome_batch.clear();
         self.text_batch.clear();
         self.overlay_batch.clear();
         self.cursor_sprite.clear();
@@ -154,19 +185,26 @@ impl Context {
     }
 }
 
-fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch) {
+/// Draw the UI. Shapes drawn on `canvas` are in session coordinates, while the
+/// `chrome` shapes and the `text` are in UI coordinates.
+fn draw_ui(
+    session: &Session,
+    canvas: &mut shape2d::Batch,
+    chrome: &mut shape2d::Batch,
+    text: &mut TextBatch,
+) {
     let view = session.active_view();
     let layer = view.active_layer_id;
+    let u = session.ui_scale();
+    let (width, _) = session.ui_size();
 
     if let Some(selection) = session.selection {
         let fill = match session.mode {
-            Mode::Visual(VisualState::Selecting { .. }) => {
-                Rgba8::new(color::RED.r, color::RED.g, color::RED.b, 0x55)
-            }
+            Mode::Visual(VisualState::Selecting { .. }) => session.theme.selection.alpha(0x55),
             // TODO: Handle different modes differently.
             _ => Rgba8::TRANSPARENT,
         };
-        let stroke = color::RED;
+        let stroke = session.theme.selection;
 
         let r = selection.abs().bounds();
         let offset = session.offset + view.offset + view.layer_offset(layer, view.zoom);
@@ -174,29 +212,31 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
         {
             // Selection dimensions.
             let s = selection;
-            let z = view.zoom;
+            let z = view.scale();
             let t = format!("{}x{}", r.width(), r.height());
             let (x, align) = if s.x2 > s.x1 {
-                ((s.x2 + 1) as f32 * z, TextAlign::Right)
+                ((s.x2 + 1) as f32 * z.x, TextAlign::Right)
             } else {
-                ((s.x2 as f32) * z, TextAlign::Left)
+                ((s.x2 as f32) * z.x, TextAlign::Left)
             };
             let y = if s.y2 >= s.y1 {
-                (s.y2 + 1) as f32 * z + 1.
+                (s.y2 + 1) as f32 * z.y + 1.
             } else {
-                (s.y2) as f32 * z - self::LINE_HEIGHT + 1.
+                (s.y2) as f32 * z.y - self::LINE_HEIGHT + 1.
             };
             text.add(
                 &t,
-                x + offset.x,
-                y + offset.y,
+                (x + offset.x) / u,
+                (y + offset.y) / u,
                 self::TEXT_LAYER,
                 stroke,
                 align,
             );
         }
 
-        let t = Matrix4::from_translation(offset.extend(0.)) * Matrix4::from_scale(view.zoom);
+        let s = view.scale();
+        let t = Matrix4::from_translation(offset.extend(0.))
+            * Matrix4::from_nonuniform_scale(s.x, s.y, 1.);
 
         // Selection stroke.
         canvas.add(Shape::Rectangle(
@@ -222,18 +262,16 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
 
     for v in session.views.iter() {
         let offset = v.offset + session.offset;
+        let z = v.scale();
 
         // Frame lines
         for n in 1..v.animation.len() {
             let n = n as f32;
-            let x = n * v.zoom * v.fw as f32 + offset.x;
+            let x = n * z.x * v.fw as f32 + offset.x;
             canvas.add(Shape::Line(
                 Line::new(
                     [x, offset.y],
-                    [
-                        x,
-                        v.zoom * (v.fh as usize * v.layers.len()) as f32 + offset.y,
-                    ],
+                    [x, z.y * (v.fh as usize * v.layers.len()) as f32 + offset.y],
                 ),
                 self::UI_LAYER,
                 Rotation::ZERO,
@@ -243,9 +281,9 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
         // Layer lines
         for n in 1..v.layers.len() {
             let n = n as f32;
-            let y = n * v.zoom * v.fh as f32 + offset.y;
+            let y = n * z.y * v.fh as f32 + offset.y;
             canvas.add(Shape::Line(
-                Line::new([offset.x, y], [v.zoom * v.width() as f32 + offset.x, y]),
+                Line::new([offset.x, y], [z.x * v.width() as f32 + offset.x, y]),
                 self::UI_LAYER,
                 Rotation::ZERO,
                 Stroke::new(1.0, Rgba::new(1., 1., 1., 0.6)),
@@ -255,16 +293,18 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
         let r = v.rect();
         let border_color = if session.is_active(v.id) {
             match session.mode {
-                // TODO: (rgx) Use `Rgba8::alpha`.
-                Mode::Visual(_) => {
-                    Rgba8::new(color::RED.r, color::RED.g, color::RED.b, 0xdd).into()
-                }
-                _ => color::WHITE.into(),
+                Mode::Visual(_) => session.theme.selection.alpha(0xdd).into(),
+                _ => session.theme.border.into(),
             }
         } else if session.hover_view.map(|(id, _)| id) == Some(v.id) {
-            Rgba::new(0.7, 0.7, 0.7, 1.0)
+            // Half-way between the active and inactive border.
+            let (a, b) = (
+                Rgba::from(session.theme.border),
+                Rgba::from(session.theme.border_inactive),
+            );
+            Rgba::new((a.r + b.r) / 2., (a.g + b.g) / 2., (a.b + b.b) / 2., 1.0)
         } else {
-            Rgba::new(0.5, 0.5, 0.5, 1.0)
+            session.theme.border_inactive.into()
         };
         canvas.add(Shape::Rectangle(
             Rect::new(r.x1 - 1., r.y1 - 1., r.x2 + 1., r.y2 + 1.) + session.offset,
@@ -278,68 +318,146 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
             // View info
             text.add(
                 &format!("{}x{}x{}", v.fw, v.fh, v.animation.len()),
-                offset.x,
-                offset.y - self::LINE_HEIGHT,
+                offset.x / u,
+                offset.y / u - self::LINE_HEIGHT,
                 self::TEXT_LAYER,
-                color::GREY,
+                session.theme.text_dim,
                 TextAlign::Left,
             );
+
+            // Animation info, above the animation preview
+            if session.settings["animation"].is_set() && v.animation.len() > 1 {
+                let mut info = format!("{}/{}", v.animation.index + 1, v.animation.len());
+                if v.animation.playback != Playback::Forward {
+                    info = format!("{} {}", info, v.animation.playback);
+                }
+                if let Some((a, b)) = v.animation.range {
+                    info = format!("{} [{}-{}]", info, a, b);
+                }
+                text.add(
+                    &info,
+                    (offset.x - v.fw as f32 * z.x) / u,
+                    (offset.y + v.fh as f32 * z.y + 1.) / u,
+                    self::TEXT_LAYER,
+                    session.theme.text_dim,
+                    TextAlign::Left,
+                );
+            }
         }
     }
-    if session.settings["ui/status"].is_set() {
+    if let Some(split) = session.split {
+        let r = split.rect(session.width, session.height);
+
+        // Split pane border
+        canvas.add(Shape::Line(
+            Line::new([r.x1, r.y1], [r.x1, r.y2]),
+            self::TEXT_LAYER,
+            Rotation::ZERO,
+            Stroke::new(1.0, session.theme.border_inactive.into()),
+        ));
+
+        if let Some(v) = session.views.get(split.view) {
+            // Split pane status
+            text.add(
+                &format!("{} {:>5}%", v.status(), (split.zoom * 100.) as u32),
+                r.x1 / u + MARGIN,
+                r.y1 / u + MARGIN + self::LINE_HEIGHT * 2.,
+                self::TEXT_LAYER,
+                session.theme.text_dim,
+                TextAlign::Left,
+            );
+        }
+    }
+
+    let statusline = session.settings["ui/statusline"].to_str();
+
+    if session.settings["ui/status"].is_set() && !statusline.is_empty() {
+        // User-defined status line
+        let (left, right) = statusline::render(statusline, |item| session.status_item(item));
+
+        text.add(
+            &left,
+            MARGIN,
+            MARGIN + self::LINE_HEIGHT,
+            self::TEXT_LAYER,
+            session.theme.text,
+            TextAlign::Left,
+        );
+        text.add(
+            &right,
+            width - MARGIN,
+            MARGIN + self::LINE_HEIGHT,
+            self::TEXT_LAYER,
+            session.theme.text,
+            TextAlign::Right,
+        );
+    } else if session.settings["ui/status"].is_set() {
         // Active view status
         text.add(
             &view.status(),
             MARGIN,
             MARGIN + self::LINE_HEIGHT,
             self::TEXT_LAYER,
-            Rgba8::WHITE,
+            session.theme.text,
             TextAlign::Left,
         );
 
         // Session status
         text.add(
             &format!("{:>5}%", (view.zoom * 100.) as u32),
-            session.width - MARGIN,
+            width - MARGIN,
             MARGIN + self::LINE_HEIGHT,
             self::TEXT_LAYER,
-            Rgba8::WHITE,
+            session.theme.text,
             TextAlign::Right,
         );
 
-        if session.width >= 600. {
+        // Color budget warning, left of the zoom.
+        if let Some(warning) = session.budget_status() {
+            text.add(
+                &warning,
+                width - MARGIN - self::GLYPH_WIDTH * 8.,
+                MARGIN + self::LINE_HEIGHT,
+                self::TEXT_LAYER,
+                color::YELLOW,
+                TextAlign::Right,
+            );
+        }
+
+        if width >= 600. {
             let cursor = session.view_coords(view.id, session.cursor);
-            let hover_color = session
-                .hover_color
-                .map_or(String::new(), |c| Rgb8::from(c).to_string());
+            // While drawing a line, show its length and angle instead of the color.
+            let hover_color = session.line_status().unwrap_or_else(|| {
+                session
+                    .hover_color
+                    .map_or(String::new(), |c| Rgb8::from(c).to_string())
+            });
             text.add(
                 &format!("{:>4},{:<4} {}", cursor.x, cursor.y, hover_color),
-                (session.width * 0.5).floor(),
+                (width * 0.5).floor(),
                 MARGIN + self::LINE_HEIGHT,
                 self::TEXT_LAYER,
-                Rgba8::WHITE,
+                session.theme.text,
                 TextAlign::Left,
             );
         }
     }
 
     if session.settings["ui/switcher"].is_set() {
-        if session.width >= 400. {
+        if width >= 400. {
             // Fg color
-            canvas.add(Shape::Rectangle(
-                Rect::origin(11., 11.).with_origin(
-                    (session.width * 0.4).floor(),
-                    self::LINE_HEIGHT + self::MARGIN + 2.,
-                ),
+            chrome.add(Shape::Rectangle(
+                Rect::origin(11., 11.)
+                    .with_origin((width * 0.4).floor(), self::LINE_HEIGHT + self::MARGIN + 2.),
                 self::UI_LAYER,
                 Rotation::ZERO,
                 Stroke::new(1.0, Rgba::WHITE),
                 Fill::Solid(session.fg.into()),
             ));
             // Bg color
-            canvas.add(Shape::Rectangle(
+            chrome.add(Shape::Rectangle(
                 Rect::origin(11., 11.).with_origin(
-                    (session.width * 0.4).floor() + 25.,
+                    (width * 0.4).floor() + 25.,
                     self::LINE_HEIGHT + self::MARGIN + 2.,
                 ),
                 self::UI_LAYER,
@@ -358,7 +476,7 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
             MARGIN,
             MARGIN,
             self::TEXT_LAYER,
-            Rgba8::WHITE,
+            session.theme.cmdline,
             TextAlign::Left,
         );
         if session.settings["ui/cursor"].is_set() {
@@ -367,7 +485,7 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
                 MARGIN + session.cmdline.cursor as f32 * self::GLYPH_WIDTH,
                 MARGIN,
                 self::TEXT_LAYER,
-                Rgba8::RED,
+                session.theme.cursor,
             );
         }
     } else if !session.message.is_execution()
@@ -385,6 +503,25 @@ fn draw_ui(session: &Session, canvas: &mut shape2d::Batch, text: &mut TextBatch)
         );
     }
 
+    // Background job progress
+    let mut pending = session.jobs.pending();
+    if let Some(name) = pending.next() {
+        let rest = pending.count();
+        let s = if rest > 0 {
+            format!("exporting {}... (+{})", name, rest)
+        } else {
+            format!("exporting {}...", name)
+        };
+        text.add(
+            &s,
+            width - MARGIN,
+            MARGIN,
+            self::TEXT_LAYER,
+            session.theme.text_dim,
+            TextAlign::Right,
+        );
+    }
+
     if session.settings["debug"].is_set() && session.settings["debug/crosshair"].is_set() {
         canvas.add(Shape::Line(
             Line::new(
@@ -485,19 +622,15 @@ fn draw_palette(session: &Session, batch: &mut shape2d::Batch) {
     }
 
     let p = &session.palette;
-    let height = p.height;
-    for (i, color) in p.colors.iter().rev().cloned().enumerate() {
-        let x = if i >= height {
-            (i / height) as f32 * p.cellsize
-        } else {
-            0.
-        };
-        let y = (i % height) as f32 * p.cellsize;
+    let rows = p.rows();
+    for (color, (column, row)) in p.entries().cloned().zip(p.cells()) {
+        let x = column as f32 * p.cellsize;
+        let y = (rows - row - 1) as f32 * p.cellsize;
 
         let mut stroke = shape2d::Stroke::NONE;
         if let (Tool::Sampler, Some(c)) = (&session.tool, p.hover) {
             if c == color {
-                stroke = shape2d::Stroke::new(1., Rgba::WHITE);
+                stroke = shape2d::Stroke::new(1., session.theme.palette.into());
             }
         }
 
@@ -511,60 +644,507 @@ fn draw_palette(session: &Session, batch: &mut shape2d::Batch) {
     }
 }
 
-fn draw_checker(session: &Session, batch: &mut sprite2d::Batch) {
-    if session.settings["checker"].is_set() {
-        for v in session.views.iter() {
-            let ratio = v.width() as f32 / v.height() as f32;
-            let rx = CHECKER_REPEAT * v.zoom * ratio * v.layers.len() as f32;
-            let ry = CHECKER_REPEAT * v.zoom * v.layers.len() as f32;
+fn draw_picker(session: &Session, batch: &mut shape2d::Batch) {
+    let picker = match &session.picker {
+        Some(picker) => picker,
+        None => return,
+    };
+    let (width, height) = session.ui_size();
+    let r = Picker::rect(width, height);
+    let (square, slider) = (Picker::square(r), Picker::slider(r));
+    let cell = Picker::CELL_SIZE;
+    let max = (Picker::STEPS - 1) as f32;
+    let n = Picker::HUE_STEPS;
+    let hue_cell = slider.height() / n as f32;
+    let outline = Stroke::new(1., session.theme.palette.into());
+
+    // The background is drawn below the picker cells, and the selection
+    // outlines above them.
+    batch.add(Shape::Rectangle(
+        Rect::new(
+            r.x1 - Picker::GAP,
+            r.y1 - Picker::GAP,
+            r.x2 + Picker::GAP,
+            r.y2 + Picker::GAP,
+        ),
+        self::PALETTE_LAYER,
+        Rotation::ZERO,
+        Stroke::new(1., session.theme.border.into()),
+        Fill::Solid(color::BLACK.into()),
+    ));
 
-            batch.add(
-                checker::rect(),
-                v.rect() + session.offset,
-                self::CHECKER_LAYER,
-                Rgba::TRANSPARENT,
-                1.,
-                Repeat::new(rx, ry),
-            );
+    for j in 0..Picker::STEPS {
+        for i in 0..Picker::STEPS {
+            let c = picker::hsv_to_rgb(picker.hue, i as f32 / max, j as f32 / max);
+            let (x, y) = (square.x1 + i as f32 * cell, square.y1 + j as f32 * cell);
+
+            batch.add(Shape::Rectangle(
+                Rect::new(x, y, x + cell, y + cell),
+                self::HELP_LAYER,
+                Rotation::ZERO,
+                Stroke::NONE,
+                Fill::Solid(c.into()),
+            ));
         }
     }
+    for k in 0..n {
+        let c = picker::hsv_to_rgb(k as f32 * 360. / n as f32, 1., 1.);
+        let y = slider.y1 + k as f32 * hue_cell;
+
+        batch.add(Shape::Rectangle(
+            Rect::new(slider.x1, y, slider.x2, y + hue_cell),
+            self::HELP_LAYER,
+            Rotation::ZERO,
+            Stroke::NONE,
+            Fill::Solid(c.into()),
+        ));
+    }
+    batch.add(Shape::Rectangle(
+        Picker::swatch(r),
+        self::HELP_LAYER,
+        Rotation::ZERO,
+        Stroke::NONE,
+        Fill::Solid(picker.color().into()),
+    ));
+
+    let (i, j) = (
+        (picker.saturation * max).round(),
+        (picker.value * max).round(),
+    );
+    let (x, y) = (square.x1 + i * cell, square.y1 + j * cell);
+    let k = (picker.hue / 360. * n as f32).round() as usize % n;
+    let y_hue = slider.y1 + k as f32 * hue_cell;
+
+    for rect in &[
+        Rect::new(x, y, x + cell, y + cell),
+        Rect::new(slider.x1, y_hue, slider.x2, y_hue + hue_cell),
+    ] {
+        batch.add(Shape::Rectangle(
+            *rect,
+            self::CURSOR_LAYER,
+            Rotation::ZERO,
+            outline,
+            Fill::Empty,
+        ));
+    }
 }
 
-fn draw_grid(session: &Session, batch: &mut shape2d::Batch) {
-    if session.settings["grid"].is_set() {
-        let color = session.settings["grid/color"].to_rgba8().alpha(0xcc);
-        let (gx, gy) = session.settings["grid/spacing"].clone().into();
+fn draw_checker(session: &Session, batch: &mut sprite2d::Batch) {
+    if !self::has_backdrop(session) {
+        return;
+    }
+    // A backdrop color is mixed over the checker by its alpha. Without the
+    // checker, it's drawn solid. The matte hides both while it's on.
+    let backdrop = session.settings["backdrop"].to_rgba8();
+    let backdrop = if session.settings["matte"].is_set() {
+        session.settings["matte/color"].to_rgba8().alpha(0xff)
+    } else if session.settings["checker"].is_set() {
+        backdrop
+    } else {
+        backdrop.alpha(0xff)
+    };
+    let size = session.settings["checker/size"].to_u64() as f32;
+
+    for v in session.views.iter() {
+        let (rx, ry) = if size > 0. {
+            // Each checker square is `size` view pixels wide.
+            let r = v.rect();
+            (
+                r.width() / v.scale().x / size / 2.,
+                r.height() / v.scale().y / size / 2.,
+            )
+        } else {
+            let ratio = v.width() as f32 * v.pixel_ratio.x / (v.height() as f32 * v.pixel_ratio.y);
+            (
+                CHECKER_REPEAT * v.zoom * ratio * v.layers.len() as f32,
+                CHECKER_REPEAT * v.zoom * v.layers.len() as f32,
+            )
+        };
+
+        batch.add(
+            checker::rect(),
+            v.rect() + session.offset,
+            self::CHECKER_LAYER,
+            backdrop.into(),
+            1.,
+            Repeat::new(rx, ry),
+        );
+    }
+}
 
+fn draw_minimap(session: &Session, batch: &mut shape2d::Batch) {
+    if let Some(r) = session.minimap_rect() {
         let v = session.active_view();
-        let t = session.offset + v.offset;
-        let w = v.width();
-        let h = v.height();
-        let m = Matrix4::from_translation(t.extend(0.)) * Matrix4::from_scale(v.zoom);
+        // The minimap keeps the view's pixel ratio, so both axes are scaled apart.
+        let (sx, sy) = (r.width() / v.width() as f32, r.height() / v.height() as f32);
+        let visible = session.visible_rect();
 
-        // Grid columns.
-        for x in (0..).step_by(gx as usize).skip(1).take_while(|x| *x < w) {
-            let h = h as f32;
-            let x = x as f32;
+        // Minimap border
+        batch.add(Shape::Rectangle(
+            Rect::new(r.x1 - 1., r.y1 - 1., r.x2 + 1., r.y2 + 1.),
+            self::MINIMAP_LAYER,
+            Rotation::ZERO,
+            Stroke::new(1.0, Rgba::new(0.5, 0.5, 0.5, 1.0)),
+            Fill::Empty,
+        ));
+        // Visible region
+        if visible.width() > 0. && visible.height() > 0. {
+            batch.add(Shape::Rectangle(
+                Rect::new(
+                    r.x1 + visible.x1 * sx,
+                    r.y1 + visible.y1 * sy,
+                    r.x1 + visible.x2 * sx,
+                    r.y1 + visible.y2 * sy,
+                ),
+                self::MINIMAP_LAYER,
+                Rotation::ZERO,
+                Stroke::new(1.0, color::RED.into()),
+                Fill::Empty,
+            ));
+        }
+    }
+}
 
+/// Draw the rulers, with a tick at every grid line of the active view, and the
+/// guide being dragged from them.
+fn draw_rulers(session: &Session, batch: &mut shape2d::Batch) {
+    let (horizontal, vertical) = match session.ruler_rects() {
+        Some(rects) => rects,
+        None => return,
+    };
+    let v = session.active_view();
+    let offset = session.offset + v.offset;
+    let s = v.scale();
+    let (gx, gy): (u32, u32) = session.settings["grid/spacing"].clone().into();
+    let tick = Stroke::new(1., color::GREY.into());
+
+    for r in [horizontal, vertical].iter() {
+        batch.add(Shape::Rectangle(
+            *r,
+            self::UI_LAYER,
+            Rotation::ZERO,
+            Stroke::new(1., session.theme.border_inactive.into()),
+            Fill::Solid(color::BLACK.into()),
+        ));
+    }
+    for x in (0..=v.width()).step_by(gx.max(1) as usize) {
+        let x = offset.x + x as f32 * s.x;
+
+        if x >= horizontal.x1 && x <= horizontal.x2 {
             batch.add(Shape::Line(
-                Line::new([x, 0.], [x, h]).transform(m),
-                self::GRID_LAYER,
+                Line::new(
+                    [x, horizontal.y1],
+                    [x, horizontal.y1 + horizontal.height() / 2.],
+                ),
+                self::UI_LAYER,
                 Rotation::ZERO,
-                Stroke::new(1., color.into()),
+                tick,
             ));
         }
-        // Grid rows.
-        for y in (0..).step_by(gy as usize).skip(1).take_while(|y| *y < h) {
-            let w = w as f32;
-            let y = y as f32;
+    }
+    for y in (0..=v.fh).step_by(gy.max(1) as usize) {
+        let y = offset.y + y as f32 * s.y;
 
+        if y >= vertical.y1 && y <= vertical.y2 {
             batch.add(Shape::Line(
-                Line::new([0., y], [w, y]).transform(m),
+                Line::new([vertical.x1, y], [vertical.x1 + vertical.width() / 2., y]),
+                self::UI_LAYER,
+                Rotation::ZERO,
+                tick,
+            ));
+        }
+    }
+
+    let color = session.settings["guide/color"].to_rgba8();
+    let c = session.cursor;
+    let line = match session.guide_drag {
+        Some(Axis::Horizontal) => Line::new([0., c.y], [session.width, c.y]),
+        Some(Axis::Vertical) => Line::new([c.x, 0.], [c.x, session.height]),
+        None => return,
+    };
+    batch.add(Shape::Line(
+        line,
+        self::UI_LAYER,
+        Rotation::ZERO,
+        Stroke::new(1., color.into()),
+    ));
+}
+
+fn draw_preview_border(session: &Session, batch: &mut shape2d::Batch) {
+    if let Some(r) = session.preview_rect() {
+        batch.add(Shape::Rectangle(
+            Rect::new(r.x1 - 1., r.y1 - 1., r.x2 + 1., r.y2 + 1.),
+            self::MINIMAP_LAYER,
+            Rotation::ZERO,
+            Stroke::new(1.0, session.theme.border_inactive.into()),
+            Fill::Empty,
+        ));
+    }
+}
+
+/// Nine-slice the current frame of the active view into the preview area.
+/// The batch is drawn once per layer, with the layer texture.
+pub fn draw_preview(session: &Session) -> Option<sprite2d::Batch> {
+    let preview = session.preview?;
+    let r = session.preview_rect()?;
+    let v = session.active_view();
+    let frame = *v.animation.val();
+    let scale = r.width() / preview.width as f32;
+    let m = preview.margins(v.fw, v.fh) as f32;
+
+    // Slice edges, in the frame, and in the preview area.
+    let (dx, dy) = (
+        (m * scale).min(r.width() / 2.),
+        (m * scale).min(r.height() / 2.),
+    );
+    let src_x = [frame.x1, frame.x1 + m, frame.x2 - m, frame.x2];
+    let src_y = [frame.y1, frame.y1 + m, frame.y2 - m, frame.y2];
+    let dst_x = [r.x1, r.x1 + dx, r.x2 - dx, r.x2];
+    let dst_y = [r.y1, r.y1 + dy, r.y2 - dy, r.y2];
+
+    let mut batch = sprite2d::Batch::new(v.width(), v.fh);
+
+    for j in 0..3 {
+        for i in 0..3 {
+            let src = Rect::new(src_x[i], src_y[j], src_x[i + 1], src_y[j + 1]);
+            let dst = Rect::new(dst_x[i], dst_y[j], dst_x[i + 1], dst_y[j + 1]);
+
+            if src.width() > 0. && src.height() > 0. && dst.width() > 0. && dst.height() > 0. {
+                batch.add(
+                    src,
+                    dst,
+                    self::MINIMAP_LAYER,
+                    Rgba::TRANSPARENT,
+                    1.,
+                    Repeat::default(),
+                );
+            }
+        }
+    }
+    Some(batch)
+}
+
+fn draw_guides(session: &Session, batch: &mut shape2d::Batch) {
+    let color = session.settings["guide/color"].to_rgba8();
+
+    for v in session.views.iter() {
+        let t = session.offset + v.offset;
+        let m = Matrix4::from_translation(t.extend(0.)) * v.transform();
+        let (w, h) = (v.width() as f32, v.height() as f32);
+
+        for g in v.guides.iter() {
+            match *g {
+                Guide::Vertical(x) => {
+                    batch.add(Shape::Line(
+                        Line::new([x as f32, 0.], [x as f32, h]).transform(m),
+                        self::GRID_LAYER,
+                        Rotation::ZERO,
+                        Stroke::new(1., color.into()),
+                    ));
+                }
+                // Horizontal guides are repeated on every layer.
+                Guide::Horizontal(y) => {
+                    for l in 0..v.layers.len() {
+                        let y = (y + (v.fh as usize * l) as i32) as f32;
+
+                        batch.add(Shape::Line(
+                            Line::new([0., y], [w, y]).transform(m),
+                            self::GRID_LAYER,
+                            Rotation::ZERO,
+                            Stroke::new(1., color.into()),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Highlight the pixels of the active layer that changed since a checkpoint.
+fn draw_checkpoint_diff(session: &Session, batch: &mut shape2d::Batch) {
+    let color = color::RED.alpha(0x88);
+
+    for v in session.views.iter() {
+        let changed = match &v.diff {
+            Some(name) => v.resource.checkpoint_diff(name, v.active_layer_id),
+            None => None,
+        };
+        let offset = session.offset + v.offset + v.layer_offset(v.active_layer_id, v.zoom);
+        let t = Matrix4::from_translation(offset.extend(0.)) * v.transform();
+
+        for p in changed.into_iter().flatten() {
+            let (x, y) = (p.x as f32, p.y as f32);
+
+            batch.add(Shape::Rectangle(
+                Rect::new(x, y, x + 1., y + 1.).transform(t),
+                self::GRID_LAYER,
+                Rotation::ZERO,
+                Stroke::NONE,
+                Fill::Solid(color.into()),
+            ));
+        }
+    }
+}
+
+/// Outline the pixels of the active layer that match the view's search color.
+/// Only the matches within the visible part of the view are drawn.
+fn draw_search(session: &Session, batch: &mut shape2d::Batch) {
+    let color = session.theme.selection;
+
+    for v in session.views.iter() {
+        if v.search.is_none() {
+            continue;
+        }
+        let visible = session.view_visible_rect(v);
+        let offset = session.offset + v.offset + v.layer_offset(v.active_layer_id, v.zoom);
+        let t = Matrix4::from_translation(offset.extend(0.)) * v.transform();
+        // The visible rectangle is in view coordinates, where layers are stacked.
+        let ly = (v.fh as usize * v.active_layer_id) as f32;
+
+        for p in session.search_matches(v.id) {
+            let (x, y) = (p.x as f32, p.y as f32);
+
+            if !visible.intersects(Rect::new(x, y + ly, x + 1., y + ly + 1.)) {
+                continue;
+            }
+            batch.add(Shape::Rectangle(
+                Rect::new(x, y, x + 1., y + 1.).transform(t),
                 self::GRID_LAYER,
                 Rotation::ZERO,
                 Stroke::new(1., color.into()),
+                Fill::Empty,
             ));
         }
+    }
+}
+
+/// Shade the pixels of the active layer using the hovered palette color, or
+/// else the color set with `:p/highlight`. Like search matches, only the
+/// visible pixels are shaded.
+fn draw_highlight(session: &Session, batch: &mut shape2d::Batch) {
+    if session.palette.hover.or(session.highlight).is_none() {
+        return;
+    }
+    let v = session.active_view();
+    let visible = session.visible_rect();
+    let offset = session.offset + v.offset + v.layer_offset(v.active_layer_id, v.zoom);
+    let t = Matrix4::from_translation(offset.extend(0.)) * v.transform();
+    let fill = session.theme.selection.alpha(0x88);
+    let ly = (v.fh as usize * v.active_layer_id) as f32;
+
+    for p in session.highlight_matches() {
+        let (x, y) = (p.x as f32, p.y as f32);
+
+        if !visible.intersects(Rect::new(x, y + ly, x + 1., y + ly + 1.)) {
+            continue;
+        }
+        batch.add(Shape::Rectangle(
+            Rect::new(x, y, x + 1., y + 1.).transform(t),
+            self::GRID_LAYER,
+            Rotation::ZERO,
+            Stroke::NONE,
+            Fill::Solid(fill.into()),
+        ));
+    }
+}
+
+/// Draw the tile boundaries of the active layer, in tileset mode.
+fn draw_tiles(session: &Session, batch: &mut shape2d::Batch) {
+    let size = session.settings["tileset"].to_u64() as u32;
+
+    if size == 0 {
+        return;
+    }
+    let color = session.settings["tileset/color"].to_rgba8().alpha(0xcc);
+    let v = session.active_view();
+    let (cols, rows) = v.tiles(size);
+    let (w, h) = (v.width(), v.fh);
+    let offset = session.offset + v.offset + v.layer_offset(v.active_layer_id, v.zoom);
+    let m = Matrix4::from_translation(offset.extend(0.)) * v.transform();
+    let stroke = Stroke::new(1., color.into());
+
+    // Tile columns.
+    for x in (1..=cols).map(|n| n * size).filter(|x| *x < w) {
+        let x = x as f32;
+
+        batch.add(Shape::Line(
+            Line::new([x, 0.], [x, h as f32]).transform(m),
+            self::GRID_LAYER,
+            Rotation::ZERO,
+            stroke,
+        ));
+    }
+    // Tile rows, from the top.
+    for y in (1..=rows).map(|n| n * size).filter(|y| *y < h) {
+        let y = (h - y) as f32;
+
+        batch.add(Shape::Line(
+            Line::new([0., y], [w as f32, y]).transform(m),
+            self::GRID_LAYER,
+            Rotation::ZERO,
+            stroke,
+        ));
+    }
+}
+
+fn draw_grid(session: &Session, batch: &mut shape2d::Batch) {
+    if session.settings["grid"].is_set() {
+        let color = session.settings["grid/color"].to_rgba8().alpha(0xcc);
+        let major_color = session.settings["grid/major/color"].to_rgba8().alpha(0xcc);
+        let (gx, gy): (u32, u32) = session.settings["grid/spacing"].clone().into();
+        let (mx, my): (u32, u32) = session.settings["grid/major/spacing"].clone().into();
+
+        let v = session.active_view();
+        let t = session.offset + v.offset;
+        let w = v.width();
+        let h = v.height();
+        let m = Matrix4::from_translation(t.extend(0.)) * v.transform();
+
+        // Returns the line color at the given position, if there is a line there.
+        // Major lines take precedence over minor lines.
+        let line = |n: u32, minor: u32, major: u32| {
+            if major > 0 && n.is_multiple_of(major) {
+                Some(major_color)
+            } else if minor > 0 && n.is_multiple_of(minor) {
+                Some(color)
+            } else {
+                None
+            }
+        };
+
+        if session.settings["grid/iso"].is_set() && gx > 0 && gy > 0 {
+            draw_iso_grid(w, h, (gx, gy), m, batch, |n| line(n, gx, mx));
+            return;
+        }
+
+        // Grid columns.
+        for x in 1..w {
+            if let Some(c) = line(x, gx, mx) {
+                let h = h as f32;
+                let x = x as f32;
+
+                batch.add(Shape::Line(
+                    Line::new([x, 0.], [x, h]).transform(m),
+                    self::GRID_LAYER,
+                    Rotation::ZERO,
+                    Stroke::new(1., c.into()),
+                ));
+            }
+        }
+        // Grid rows.
+        for y in 1..h {
+            if let Some(c) = line(y, gy, my) {
+                let w = w as f32;
+                let y = y as f32;
+
+                batch.add(Shape::Line(
+                    Line::new([0., y], [w, y]).transform(m),
+                    self::GRID_LAYER,
+                    Rotation::ZERO,
+                    Stroke::new(1., c.into()),
+                ));
+            }
+        }
 
         // Draw center lines.
         if w % gx == 0 && h % gy == 0 {
@@ -587,6 +1167,49 @@ fn draw_grid(session: &Session, batch: &mut shape2d::Batch) {
     }
 }
 
+/// Draw an isometric grid of diamonds `gx` wide and `gy` high, over a `w` by `h` view.
+/// The grid lines go through every multiple of `gx` on the bottom edge of the view.
+fn draw_iso_grid(
+    w: u32,
+    h: u32,
+    (gx, gy): (u32, u32),
+    m: Matrix4<f32>,
+    batch: &mut shape2d::Batch,
+    line: impl Fn(u32) -> Option<Rgba8>,
+) {
+    let (w, h) = (w as f32, h as f32);
+    let slope = gy as f32 / gx as f32;
+    // Horizontal distance covered by a line going from the bottom to the top of the view.
+    let run = h / slope;
+    let steps = (run / gx as f32).ceil() as i32 * gx as i32;
+
+    for b in (-steps..=w as i32 + steps).step_by(gx as usize) {
+        let c = if let Some(c) = line(b.unsigned_abs()) {
+            c
+        } else {
+            continue;
+        };
+        let x = b as f32;
+
+        // Rising and falling lines crossing the bottom of the view at `x`.
+        for (x0, x1, y0, y1) in [(x, x + run, 0., h), (x - run, x, h, 0.)] {
+            let (a, b) = (x0.max(0.), x1.min(w));
+
+            if a >= b {
+                continue;
+            }
+            let y = |x: f32| y0 + (y1 - y0) * (x - x0) / run;
+
+            batch.add(Shape::Line(
+                Line::new([a, y(a)], [b, y(b)]).transform(m),
+                self::GRID_LAYER,
+                Rotation::ZERO,
+                Stroke::new(1., c.into()),
+            ));
+        }
+    }
+}
+
 fn draw_cursor(session: &Session, inverted: &mut sprite::Sprite, batch: &mut sprite2d::Batch) {
     if !session.settings["ui/cursor"].is_set() {
         return;
@@ -632,7 +1255,7 @@ fn draw_brush(session: &Session, brush: &Brush, shapes: &mut shape2d::Batch) {
     }
     let v = session.active_view();
     let c = session.cursor;
-    let z = v.zoom;
+    let z = v.scale();
 
     match session.mode {
         Mode::Visual(VisualState::Selecting { .. }) => {
@@ -643,7 +1266,7 @@ fn draw_brush(session: &Session, brush: &Brush, shapes: &mut shape2d::Batch) {
             if v.contains(c - session.offset).is_some() {
                 let c = session.snap(c, v.offset.x, v.offset.y, z);
                 shapes.add(Shape::Rectangle(
-                    Rect::new(c.x, c.y, c.x + z, c.y + z),
+                    Rect::new(c.x, c.y, c.x + z.x, c.y + z.y),
                     self::UI_LAYER,
                     Rotation::ZERO,
                     Stroke::new(1.0, color::RED.into()),
@@ -656,9 +1279,53 @@ fn draw_brush(session: &Session, brush: &Brush, shapes: &mut shape2d::Batch) {
                 let view_coords = session.active_view_coords(c);
                 let layer_coords = session.active_layer_coords(c);
 
+                // Draw symmetry axes on every frame.
+                {
+                    let t = session.offset + v.offset;
+                    let m = Matrix4::from_translation(t.extend(0.)) * v.transform();
+                    let stroke = Stroke::new(1., color::RED.alpha(0x99).into());
+                    let (w, h) = (v.width() as f32, v.height() as f32);
+
+                    if let Some(axis) = brush.x_axis(v.fw) {
+                        for n in 0..v.animation.len() {
+                            let x = (n as u32 * v.fw) as f32 + axis;
+                            shapes.add(Shape::Line(
+                                Line::new([x, 0.], [x, h]).transform(m),
+                                self::GRID_LAYER,
+                                Rotation::ZERO,
+                                stroke,
+                            ));
+                        }
+                    }
+                    if let Some(axis) = brush.y_axis(v.fh) {
+                        shapes.add(Shape::Line(
+                            Line::new([0., axis], [w, axis]).transform(m),
+                            self::GRID_LAYER,
+                            Rotation::ZERO,
+                            stroke,
+                        ));
+                    }
+                    // Draw vanishing points as crosses centered on their pixel.
+                    for p in &brush.vanishing {
+                        let (x, y) = (p.x as f32 + 0.5, p.y as f32 + 0.5);
+
+                        for line in &[
+                            Line::new([x - 2., y], [x + 2., y]),
+                            Line::new([x, y - 2.], [x, y + 2.]),
+                        ] {
+                            shapes.add(Shape::Line(
+                                line.transform(m),
+                                self::GRID_LAYER,
+                                Rotation::ZERO,
+                                stroke,
+                            ));
+                        }
+                    }
+                }
+
                 // Draw enabled brush
                 if v.contains(c - session.offset).is_some() {
-                    let (stroke, fill) = if brush.is_set(BrushMode::Erase) {
+                    let (stroke, fill) = if brush.is_erasing() {
                         // When erasing, we draw a stroke that is the inverse of the underlying
                         // color at the cursor. Note that this isn't perfect, since it uses
                         // the current snapshot to get the color, so it may be incorrect
@@ -684,13 +1351,20 @@ fn draw_brush(session: &Session, brush: &Brush, shapes: &mut shape2d::Batch) {
                         (Stroke::NONE, Fill::Solid(session.fg.into()))
                     };
 
-                    for p in brush.expand(view_coords.into(), v.extent()) {
+                    let brush_coords: ViewCoords<i32> = session
+                        .active_view_coords(c + session.brush_offset())
+                        .into();
+                    let brush_coords = brush.snap_to_grid(*brush_coords);
+
+                    for p in
+                        brush.expand(ViewCoords::new(brush_coords.x, brush_coords.y), v.extent())
+                    {
                         shapes.add(brush.shape(
                             *session.session_coords(v.id, p.into()),
                             self::BRUSH_LAYER,
                             stroke,
                             fill,
-                            v.zoom,
+                            z,
                             Align::BottomLeft,
                         ));
                     }
@@ -704,8 +1378,7 @@ fn draw_brush(session: &Session, brush: &Brush, shapes: &mut shape2d::Batch) {
 
                         if let Some(xray) = v.color_at(v.active_layer_id, p).cloned() {
                             if xray != session.fg {
-                                let center = *session.session_coords(v.id, view_coords)
-                                    + Vector2::new(z / 2., z / 2.);
+                                let center = *session.session_coords(v.id, view_coords) + z * 0.5;
 
                                 shapes.add(
                                     Shape::circle(center, self::XRAY_RADIUS, 16)
@@ -717,7 +1390,7 @@ fn draw_brush(session: &Session, brush: &Brush, shapes: &mut shape2d::Batch) {
                     }
                 // Draw disabled brush
                 } else {
-                    let color = if brush.is_set(BrushMode::Erase) {
+                    let color = if brush.is_erasing() {
                         color::GREY
                     } else {
                         session.fg
@@ -727,7 +1400,7 @@ fn draw_brush(session: &Session, brush: &Brush, shapes: &mut shape2d::Batch) {
                         self::UI_LAYER,
                         Stroke::new(1.0, color.into()),
                         Fill::Empty,
-                        v.zoom,
+                        z,
                         Align::Center,
                     ));
                 }
@@ -755,7 +1428,8 @@ pub fn draw_view_animation<R>(session: &Session, v: &View<R>) -> sprite2d::Batch
         v.width(),
         v.fh,
         *v.animation.val(),
-        Rect::new(-(v.fw as f32), 0., 0., v.fh as f32) * v.zoom + (session.offset + v.offset),
+        Rect::new(-(v.fw as f32), 0., 0., v.fh as f32).transform(v.transform())
+            + (session.offset + v.offset),
         self::VIEW_LAYER,
         Rgba::TRANSPARENT,
         1.,
@@ -763,13 +1437,47 @@ pub fn draw_view_animation<R>(session: &Session, v: &View<R>) -> sprite2d::Batch
     )
 }
 
+/// The backdrop behind the animation preview of each animated view. It's drawn
+/// behind every layer animation, and behind the composite animation, if any.
+pub fn draw_preview_backdrop(session: &Session) -> Option<sprite2d::Batch> {
+    let backdrop = session.preview_backdrop.as_ref()?;
+
+    if !session.settings["animation"].is_set() {
+        return None;
+    }
+    let (w, h) = (backdrop.width as f32, backdrop.height as f32);
+    let mut batch = sprite2d::Batch::new(backdrop.width, backdrop.height);
+
+    for v in session.views.iter().filter(|v| v.animation.len() > 1) {
+        let rect = Rect::new(-(v.fw as f32), 0., 0., v.fh as f32).transform(v.transform())
+            + (session.offset + v.offset);
+        let composite = Vector2::new(0., -(v.fh as f32 * v.scale().y));
+        let offsets = (0..v.layers.len())
+            .map(|i| v.layer_offset(i, v.zoom))
+            .chain(Some(composite).filter(|_| v.layers.len() > 1));
+
+        for offset in offsets {
+            batch.add(
+                Rect::origin(w, h),
+                rect + offset,
+                self::CHECKER_LAYER,
+                Rgba::TRANSPARENT,
+                1.,
+                Repeat::new(v.fw as f32 / w, v.fh as f32 / h),
+            );
+        }
+    }
+    Some(batch)
+}
+
 pub fn draw_view_composites<R>(session: &Session, v: &View<R>) -> sprite2d::Batch {
     let mut batch = sprite2d::Batch::new(v.width(), v.fh);
 
     for frame in v.animation.frames.iter() {
         batch.add(
             *frame,
-            (*frame - Vector2::new(0., v.fh as f32)) * v.zoom + (session.offset + v.offset),
+            (*frame - Vector2::new(0., v.fh as f32)).transform(v.transform())
+                + (session.offset + v.offset),
             self::VIEW_LAYER,
             Rgba::TRANSPARENT,
             1.,
@@ -781,15 +1489,17 @@ pub fn draw_view_composites<R>(session: &Session, v: &View<R>) -> sprite2d::Batc
 }
 
 pub fn draw_help(session: &Session, text: &mut TextBatch, shape: &mut shape2d::Batch) {
+    let (width, height) = session.ui_size();
+
     shape.add(Shape::Rectangle(
-        Rect::origin(session.width as f32, session.height as f32),
+        Rect::origin(width, height),
         ZDepth(0.0),
         Rotation::ZERO,
         Stroke::new(1., color::RED.into()),
         Fill::Empty,
     ));
     shape.add(Shape::Rectangle(
-        Rect::origin(session.width as f32, session.height as f32),
+        Rect::origin(width, height),
         self::HELP_LAYER,
         Rotation::ZERO,
         Stroke::NONE,
@@ -806,7 +1516,7 @@ pub fn draw_help(session: &Session, text: &mut TextBatch, shape: &mut shape2d::B
             platform::Key::Escape,
         ),
         left_margin,
-        session.height as f32 - self::MARGIN - self::LINE_HEIGHT,
+        height - self::MARGIN - self::LINE_HEIGHT,
         self::HELP_LAYER,
         color::LIGHT_GREY,
         TextAlign::Left,
@@ -821,7 +1531,7 @@ pub fn draw_help(session: &Session, text: &mut TextBatch, shape: &mut shape2d::B
         .filter_map(|kb| kb.display.as_ref().map(|d| (d, kb)))
         .partition(|(_, kb)| kb.modes.contains(&Mode::Normal));
 
-    let mut line = (0..(session.height as usize - self::LINE_HEIGHT as usize * 4))
+    let mut line = (0..(height as usize - self::LINE_HEIGHT as usize * 4))
         .rev()
         .step_by(self::LINE_HEIGHT as usize);
 
@@ -888,11 +1598,26 @@ pub fn draw_help(session: &Session, text: &mut TextBatch, shape: &mut shape2d::B
                 color::RED,
                 TextAlign::Left,
             );
+            // Show the current value of the setting after its description.
+            if let Some(v) = l
+                .split_whitespace()
+                .next()
+                .and_then(|k| session.settings.get(k))
+            {
+                text.add(
+                    &format!("{}", v),
+                    left_margin + (l.len() + 2) as f32 * self::GLYPH_WIDTH,
+                    y as f32,
+                    self::HELP_LAYER,
+                    color::LIGHT_GREEN,
+                    TextAlign::Left,
+                );
+            }
         }
     }
 
     for (i, l) in session.help().iter().enumerate() {
-        let y = session.height as f32 - (i + 4) as f32 * self::LINE_HEIGHT;
+        let y = height - (i + 4) as f32 * self::LINE_HEIGHT;
 
         text.add(
             l,
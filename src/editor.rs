@@ -0,0 +1,1492 @@
+//! A windowless editor, for driving rx from other programs.
+//!
+//! ```no_run
+//! use rx::Editor;
+//!
+//! let mut editor = Editor::new().unwrap();
+//!
+//! editor.command("f/resize 16 16").unwrap();
+//! editor.command("paint/color #ff0000 0 0").unwrap();
+//!
+//! let (w, h, pixels) = editor.pixels();
+//! assert_eq!(pixels.len(), (w * h) as usize);
+//! ```
+//!
+//! Input can also be fed as it would come from a window, to drive tools that
+//! work with the mouse:
+//!
+//! ```no_run
+//! use rx::Editor;
+//!
+//! let mut editor = Editor::new().unwrap();
+//!
+//! editor.command("f/resize 16 16").unwrap();
+//! editor.cursor(0, 0);
+//! editor.mouse_down();
+//! editor.cursor(15, 0);
+//! editor.mouse_up();
+//! ```
+use crate::cmd::Value;
+use crate::event::Event;
+use crate::execution::Execution;
+use crate::gfx::Rgba8;
+use crate::image;
+use crate::platform;
+use crate::session::{ExitReason, Session, State};
+use crate::software;
+use crate::view::FileStatus;
+
+use directories as dirs;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time;
+
+/// An rx session without a window, rendered in software.
+pub struct Editor {
+    session: Session,
+    execution: Execution,
+    renderer: software::Renderer,
+}
+
+impl Editor {
+    /// Create an editor with a blank view. The user's initialization script
+    /// isn't sourced, so that editors behave the same everywhere.
+    pub fn new() -> io::Result<Self> {
+        let proj_dirs = dirs::ProjectDirs::from("io", "cloudhead", "rx")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "config directory not found"))?;
+        let base_dirs = dirs::BaseDirs::new()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "home directory not found"))?;
+        let cwd = std::env::current_dir()?;
+        let options = crate::Options::default();
+
+        let session = Session::new(options.width, options.height, cwd, proj_dirs, base_dirs)
+            .with_blank(
+                FileStatus::NoFile,
+                Session::DEFAULT_VIEW_W,
+                Session::DEFAULT_VIEW_H,
+            )
+            .init(Some(PathBuf::from("-")))?;
+
+        let mut editor = Self {
+            session,
+            execution: Execution::normal()?,
+            renderer: software::Renderer::new(),
+        };
+        editor.tick();
+
+        Ok(editor)
+    }
+
+    /// Open the given paths as views.
+    pub fn open<P: AsRef<Path>>(&mut self, paths: &[P]) -> io::Result<()> {
+        self.session.edit(paths)?;
+        self.tick();
+
+        Ok(())
+    }
+
+    /// Open the given paths as the frames of a single view. The frames are
+    /// ordered by file name, and must all be the same size.
+    pub fn open_frames<P: AsRef<Path>>(&mut self, paths: &[P]) -> io::Result<()> {
+        self.session.edit_frames(paths)?;
+        self.tick();
+
+        Ok(())
+    }
+
+    /// Read a PNG image into a new view, which isn't backed by a file.
+    pub fn read<R: io::Read>(&mut self, reader: R) -> io::Result<()> {
+        let (buffer, w, h) = image::read(reader)?;
+        let pixels = Rgba8::align(&buffer).to_vec();
+
+        self.session.open_pixels(w, h, pixels);
+        self.tick();
+
+        Ok(())
+    }
+
+    /// Write the active view's active layer as a PNG image.
+    pub fn write<W: io::Write>(&self, out: W) -> io::Result<()> {
+        let (w, h, pixels) = self.pixels();
+
+        image::write(out, w, h, 1, &pixels, &image::Metadata::default())
+    }
+
+    /// Run a command, as it would be typed in the command line, without the
+    /// leading `:`. The command's effects are rendered, and exports are written,
+    /// before returning.
+    pub fn command(&mut self, cmd: &str) -> Result<(), String> {
+        self.session.source_line(cmd)?;
+        self.session.wait_jobs();
+        self.tick();
+
+        match &self.session.state {
+            State::Closing(ExitReason::Error(e)) => Err(e.clone()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Feed an input event to the session, as if it came from the window, and
+    /// render its effects. Events are written as in recordings, eg.
+    /// `cursor/moved 64 32`, `mouse/input pressed` or `keyboard/input v released`.
+    /// Cursor positions are in window coordinates.
+    pub fn event(&mut self, input: &str) -> Result<(), String> {
+        let event = Event::from_str(input).map_err(|e| e.to_string())?;
+        self.update(vec![event]);
+
+        Ok(())
+    }
+
+    /// Move the cursor over a pixel of the active layer. Like in [`Editor::pixels`],
+    /// the origin is at the top-left. Pixels outside of the layer can be given, to
+    /// move the cursor off the canvas.
+    pub fn cursor(&mut self, x: i32, y: i32) {
+        let position = self.window_position(x, y);
+        self.update(vec![Event::CursorMoved(position)]);
+    }
+
+    /// Press the left mouse button.
+    pub fn mouse_down(&mut self) {
+        self.update(vec![Event::MouseInput(
+            platform::MouseButton::Left,
+            platform::InputState::Pressed,
+        )]);
+    }
+
+    /// Release the left mouse button.
+    pub fn mouse_up(&mut self) {
+        self.update(vec![Event::MouseInput(
+            platform::MouseButton::Left,
+            platform::InputState::Released,
+        )]);
+    }
+
+    /// Press and release a key, written as in key mappings, eg. `v` or `<space>`.
+    pub fn key(&mut self, key: &str) -> Result<(), String> {
+        self.event(&format!("keyboard/input {} pressed", key))?;
+        self.event(&format!("keyboard/input {} released", key))
+    }
+
+    /// Whether the session was closed, eg. with `:q`.
+    pub fn is_closed(&self) -> bool {
+        matches!(self.session.state, State::Closing(_))
+    }
+
+    /// Width, height and pixels of the active view's active layer, top row first.
+    /// This includes changes that weren't recorded in the view history yet.
+    pub fn pixels(&self) -> (u32, u32, Vec<Rgba8>) {
+        let v = self.session.active_view();
+        let (w, h, pixels) = self
+            .renderer
+            .pixels(v.id, v.active_layer_id)
+            .expect("the active view is rendered");
+
+        (w, h, pixels.to_vec())
+    }
+
+    /// The current value of a setting, if it exists.
+    pub fn setting(&self, name: &str) -> Option<&Value> {
+        self.session.settings.get(name)
+    }
+
+    /// The underlying session.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    fn tick(&mut self) {
+        self.update(vec![]);
+    }
+
+    fn update(&mut self, mut events: Vec<Event>) {
+        let effects = self.session.update(
+            &mut events,
+            &mut self.execution,
+            time::Duration::default(),
+            time::Duration::default(),
+        );
+        self.renderer.frame(&mut self.session, effects);
+        self.session.cleanup();
+    }
+
+    /// Window position of the center of a pixel of the active layer. This is the
+    /// inverse of converting the cursor to session and then layer coordinates.
+    fn window_position(&self, x: i32, y: i32) -> platform::LogicalPosition {
+        let session = &self.session;
+        let v = session.active_view();
+        let (w, h) = v.layer_size();
+        let s = v.scale();
+
+        // Layer coordinates have their origin at the bottom-left.
+        let (mut px, mut py) = (x as f32 + 0.5, (h as i32 - 1 - y) as f32 + 0.5);
+        if v.flip_x {
+            px = w as f32 - px;
+        }
+        if v.flip_y {
+            py = h as f32 - py;
+        }
+        let offset = session.offset + v.offset + v.layer_offset(v.active_layer_id, v.zoom);
+        let (sx, sy) = ((px * s.x + offset.x).floor(), (py * s.y + offset.y).floor());
+        let scale = session.settings["scale"].to_f64();
+
+        platform::LogicalPosition::new(
+            (sx as f64 + 0.5) * scale,
+            (session.height as f64 - sy as f64 - 0.5) * scale,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cmd::Axis;
+    use crate::gfx::math::Vector2;
+    use crate::pixels;
+    use crate::session::{Backdrop, Mode, Selection, VisualState};
+    use crate::view::layer::LayerCoords;
+    use crate::view::Guide;
+
+    /// Save a single frame image in the given directory, and return its path.
+    fn save_image(dir: &Path, name: &str, w: u32, h: u32, pixels: &[Rgba8]) -> PathBuf {
+        let path = dir.join(name);
+        image::save_as(&path, w, h, 1, pixels, &image::Metadata::default()).unwrap();
+
+        path
+    }
+
+    /// Save an image in a new temporary directory, and open it in a new editor.
+    /// The directory is removed when dropped, so it's returned with the editor.
+    fn open_image(name: &str, w: u32, h: u32, pixels: &[Rgba8]) -> (Editor, tempfile::TempDir) {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = save_image(tmp.path(), name, w, h, pixels);
+        let mut editor = Editor::new().unwrap();
+
+        editor.open(&[path]).unwrap();
+
+        (editor, tmp)
+    }
+
+    /// The message currently shown by the editor.
+    fn message(editor: &Editor) -> String {
+        editor.session().message.to_string()
+    }
+
+    #[test]
+    fn test_editor_commands() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 2 2").unwrap();
+        editor.command("paint/color #ff0000 1 0").unwrap();
+
+        let (w, h, pixels) = editor.pixels();
+        assert_eq!((w, h), (2, 2));
+        assert_eq!(pixels[1], Rgba8::RED);
+
+        assert!(editor.command("not-a-command").is_err());
+        assert!(!editor.is_closed());
+
+        editor.command("q!").unwrap();
+        assert!(editor.is_closed());
+    }
+
+    #[test]
+    fn test_editor_zoom_fit() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 16 8").unwrap();
+        editor.command("zoom fit").unwrap();
+
+        let session = editor.session();
+        let v = session.active_view();
+
+        // The default workspace is 1280x720, which leaves 1232x672 inside the margins.
+        assert_eq!(v.zoom, 77.);
+        assert!(v.width() as f32 * v.zoom <= session.width);
+    }
+
+    #[test]
+    fn test_editor_pixel_ratio() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 16 8").unwrap();
+        editor.command("set pixel-ratio = 4:2").unwrap();
+        editor.command("zoom fit").unwrap();
+
+        let session = editor.session();
+        let v = session.active_view();
+        let r = v.rect();
+
+        assert_eq!(v.pixel_ratio, Vector2::new(2., 1.));
+        assert_eq!(r.width(), r.height() * 4.);
+        assert!(r.width() <= session.width);
+
+        editor.command("set pixel-ratio = 0:1").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+        assert_eq!(
+            editor.session().active_view().pixel_ratio,
+            Vector2::new(2., 1.)
+        );
+    }
+
+    #[test]
+    fn test_editor_search() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 2 1").unwrap();
+        editor.command("search #ff0000").unwrap();
+        assert_eq!(message(&editor), "Pattern not found: #ff0000");
+        editor.command("search #000000/0").unwrap();
+        assert_eq!(message(&editor), "2 match(es) for #00000000");
+
+        // Matches are visited in reading order, and wrap around.
+        editor.command("search/next").unwrap();
+        assert_eq!(message(&editor), "Match 1 of 2 at 0,0");
+        editor.command("search/next").unwrap();
+        assert_eq!(message(&editor), "Match 2 of 2 at 1,0");
+        editor.command("search/next").unwrap();
+        assert_eq!(message(&editor), "Match 1 of 2 at 0,0");
+        editor.command("search/prev").unwrap();
+        assert_eq!(message(&editor), "Match 2 of 2 at 1,0");
+
+        // Matches are found again when the pixels change.
+        let id = editor.session().views.active_id;
+        assert_eq!(editor.session().search_matches(id).len(), 2);
+
+        editor.cursor(0, 0);
+        editor.mouse_down();
+        editor.mouse_up();
+        assert_eq!(
+            editor.session().search_matches(id),
+            &[LayerCoords::new(1, 0)]
+        );
+
+        editor.command("search").unwrap();
+        assert!(editor.session().search_matches(id).is_empty());
+    }
+
+    #[test]
+    fn test_editor_tiles() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("tile/dupes").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+
+        editor.command("set tileset = 2").unwrap();
+        editor.command("f/resize 6 2").unwrap();
+        editor.command("paint/color #ff0000 0 1").unwrap();
+        // Record the painted pixel in the view history.
+        editor.command("tile/copy 2 2").unwrap();
+        editor.command("tile/copy 0 2").unwrap();
+
+        let (_, _, pixels) = editor.pixels();
+        assert_eq!((pixels[6], pixels[10]), (Rgba8::RED, Rgba8::RED));
+
+        editor.command("tile/dupes").unwrap();
+        assert_eq!(message(&editor), "Duplicate tiles: #0 = #2");
+        editor.command("tile/swap 0 3").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+    }
+
+    #[test]
+    fn test_editor_frame_copy() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/paste 0").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+
+        editor.command("f/resize 2 2").unwrap();
+        editor.command("f/add").unwrap();
+        editor.command("f/add").unwrap();
+        editor.command("paint/color #ff0000 1 1").unwrap();
+        // Record the painted pixel in the view history.
+        editor.command("f/move 1 2").unwrap();
+
+        editor.command("f/copy 1 0").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+        editor.command("f/copy 0 0").unwrap();
+        editor.command("f/paste 2").unwrap();
+
+        let (w, _, pixels) = editor.pixels();
+        assert_eq!(w, 6);
+        assert_eq!((pixels[7], pixels[11]), (Rgba8::RED, Rgba8::RED));
+        assert_eq!(pixels[9], Rgba8::TRANSPARENT);
+
+        // Pasted frames replace the target frame, transparent pixels included.
+        editor.command("f/copy 0 1").unwrap();
+        editor.command("f/paste 2").unwrap();
+
+        let (_, _, pixels) = editor.pixels();
+        assert_eq!((pixels[7], pixels[11]), (Rgba8::RED, Rgba8::TRANSPARENT));
+    }
+
+    #[test]
+    fn test_editor_view_palette() {
+        let mut editor = Editor::new().unwrap();
+        let colors = editor.session().palette.colors.clone();
+
+        editor.command("p/view").unwrap();
+        editor.command("p/clear").unwrap();
+        editor.command("p/add #ff0000").unwrap();
+
+        let session = editor.session();
+        assert_eq!(session.palette.colors.as_slice(), &[Rgba8::RED]);
+        assert_eq!(
+            session
+                .active_view()
+                .palette
+                .as_ref()
+                .map(|p| p.colors.len()),
+            Some(1)
+        );
+
+        editor.command("p/view off").unwrap();
+
+        let session = editor.session();
+        assert_eq!(session.palette.colors, colors);
+        assert!(session.active_view().palette.is_none());
+    }
+
+    #[test]
+    fn test_editor_palette_lock() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 2 1").unwrap();
+        editor.command("p/clear").unwrap();
+        editor.command("p/add #ff0000").unwrap();
+        editor.command("p/add #0000ff").unwrap();
+        editor.command("set palette/lock = on").unwrap();
+        editor.command("paint/color #ee1122 0 0").unwrap();
+        editor.command("brush/color #1100ee").unwrap();
+
+        let (_, _, pixels) = editor.pixels();
+        assert_eq!(pixels[0], Rgba8::RED);
+        assert_eq!(editor.session().fg, Rgba8::BLUE);
+    }
+
+    #[test]
+    fn test_editor_palette_lock_paste() {
+        let color = Rgba8::new(0xee, 0x11, 0x22, 0xff);
+        let (mut editor, _tmp) = open_image("lock.png", 2, 1, &[color, Rgba8::TRANSPARENT]);
+
+        editor.command("p/clear").unwrap();
+        editor.command("p/add #ff0000").unwrap();
+
+        editor.key("v").unwrap();
+        editor.cursor(0, 0);
+        editor.mouse_down();
+        editor.mouse_up();
+        editor.command("selection/yank").unwrap();
+        editor.command("selection/move 1 0").unwrap();
+
+        editor.command("set palette/lock = on").unwrap();
+        editor.command("selection/paste").unwrap();
+
+        assert_eq!(editor.pixels().2, vec![color, Rgba8::RED]);
+
+        // Copied frames are snapped too.
+        let (mut editor, _tmp) = open_image("lock.png", 2, 1, &[color, Rgba8::TRANSPARENT]);
+
+        editor.command("slice 2").unwrap();
+        editor.command("p/clear").unwrap();
+        editor.command("p/add #ff0000").unwrap();
+        editor.command("f/copy 0 0").unwrap();
+
+        editor.command("set palette/lock = on").unwrap();
+        editor.command("f/paste 1").unwrap();
+
+        assert_eq!(editor.pixels().2, vec![color, Rgba8::RED]);
+    }
+
+    #[test]
+    fn test_editor_palette_highlight() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 2 1").unwrap();
+        editor.command("p/clear").unwrap();
+        editor.command("p/add #000000/0").unwrap();
+        editor.command("p/highlight 1").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+
+        editor.command("p/highlight 0").unwrap();
+        assert_eq!(message(&editor), "2 pixel(s) using #00000000");
+        assert_eq!(editor.session().highlight, Some(Rgba8::TRANSPARENT));
+        assert_eq!(editor.session().highlight_matches().len(), 2);
+
+        editor.cursor(1, 0);
+        editor.mouse_down();
+        editor.mouse_up();
+        assert_eq!(
+            editor.session().highlight_matches(),
+            &[LayerCoords::new(0, 0)]
+        );
+
+        editor.command("p/highlight").unwrap();
+        assert_eq!(editor.session().highlight, None);
+        assert!(editor.session().highlight_matches().is_empty());
+    }
+
+    #[test]
+    fn test_editor_export_tiles() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 8 8").unwrap();
+        editor.command("p/clear").unwrap();
+        editor.command("p/add #000000").unwrap();
+        editor.command("p/add #ff0000").unwrap();
+        editor.command("f/add").unwrap();
+        editor.command("f/add").unwrap();
+        editor.command("paint/color #ff0000 0 0").unwrap();
+        // Record the painted pixel in the view history.
+        editor.command("f/move 1 2").unwrap();
+        editor.command("f/remove 2").unwrap();
+        editor.command("f/remove 1").unwrap();
+
+        let path = tmp.path().join("tiles.chr");
+        editor
+            .command(&format!("export {}", path.display()))
+            .unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), 16);
+        assert_eq!(data[0], 0b1000_0000);
+        assert_eq!(&data[1..], &[0; 15]);
+
+        // Colors that aren't in the palette are written as the closest color.
+        editor.command("p/clear").unwrap();
+        editor.command("p/add #000000").unwrap();
+        editor.command("p/add #ee1111").unwrap();
+        editor
+            .command(&format!("export {}", path.display()))
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        for c in &["#00ff00", "#0000ff", "#ffffff"] {
+            editor.command(&format!("p/add {}", c)).unwrap();
+        }
+        editor
+            .command(&format!("export {}", path.display()))
+            .unwrap();
+        assert_eq!(
+            message(&editor),
+            "Error: the palette has 5 colors, but only 4 can be indexed with 2 bits per pixel"
+        );
+
+        // Game Boy tiles are in the Game Boy shades, whatever the palette.
+        let path = tmp.path().join("tiles.2bpp");
+        editor
+            .command(&format!("export {}", path.display()))
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap().len(), 16);
+    }
+
+    #[test]
+    fn test_editor_export_array() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 2 1").unwrap();
+        editor.command("zoom 4").unwrap();
+
+        // Arrays are saved at the view's size, whatever the zoom.
+        let path = tmp.path().join("my-sprite.h");
+        editor
+            .command(&format!("export {}", path.display()))
+            .unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("#define MY_SPRITE_WIDTH 2\n"));
+
+        #[cfg(unix)]
+        {
+            editor.command("set on-export = \"false\"").unwrap();
+            editor
+                .command(&format!("export {}", path.display()))
+                .unwrap();
+            assert!(message(&editor).ends_with("`false` failed with exit status: 1"));
+            editor.command("set on-export = \"\"").unwrap();
+        }
+
+        let path = tmp.path().join("sprite.xpm");
+        editor
+            .command(&format!("export {}", path.display()))
+            .unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("/* XPM */\n"));
+    }
+
+    #[test]
+    fn test_editor_export_sprite_frames() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 4 2").unwrap();
+        editor.command("f/add").unwrap();
+        editor.command("f/add").unwrap();
+        editor.command("anim/playback pingpong").unwrap();
+
+        // The view's playback mode and the export scale are used.
+        let path = tmp.path().join("hero.tres");
+        editor
+            .command(&format!("export @2x {}", path.display()))
+            .unwrap();
+
+        let tres = std::fs::read_to_string(&path).unwrap();
+        assert!(tres.contains("region = Rect2(16, 0, 8, 4)"));
+        assert_eq!(tres.matches("\"texture\": SubResource").count(), 4);
+        assert!(tmp.path().join("hero.png").exists());
+    }
+
+    #[test]
+    fn test_editor_echo() {
+        let mut editor = Editor::new().unwrap();
+
+        editor
+            .command("echo \"zoom: %zoom, %%zoom, %nope\"")
+            .unwrap();
+        assert_eq!(message(&editor), "zoom: 100%, %zoom, %nope");
+    }
+
+    #[test]
+    fn test_editor_path_variables() {
+        let (mut editor, tmp) = open_image("hero.png", 2, 2, &[Rgba8::RED; 4]);
+
+        editor.command("export %d/%f-%F.png").unwrap();
+        assert!(tmp.path().join("hero-0.png").exists());
+
+        editor.command("export %d/%{f}_%{date}.png").unwrap();
+        let date = chrono::Local::now().format("%Y-%m-%d");
+        assert!(tmp.path().join(format!("hero_{}.png", date)).exists());
+
+        // Unknown variables are left as they are.
+        editor.command("export %d/%nope.png").unwrap();
+        assert!(tmp.path().join("%nope.png").exists());
+    }
+
+    #[test]
+    fn test_editor_read_write() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 3 2").unwrap();
+        editor.command("paint/color #ff0000 1 0").unwrap();
+
+        let mut png = Vec::new();
+        editor.write(&mut png).unwrap();
+
+        let mut other = Editor::new().unwrap();
+        other.read(png.as_slice()).unwrap();
+        assert_eq!(other.pixels(), editor.pixels());
+        assert_eq!(other.session().views.iter().count(), 1);
+
+        assert!(other.read(&b"not a png"[..]).is_err());
+    }
+
+    #[test]
+    fn test_editor_ui_scale() {
+        let mut editor = Editor::new().unwrap();
+        let (w, h) = (editor.session().width, editor.session().height);
+
+        editor.command("set ui/scale = 2").unwrap();
+        assert_eq!(editor.session().ui_size(), (w / 2., h / 2.));
+        // Views aren't affected by the UI scale.
+        assert_eq!(editor.setting("scale"), Some(&Value::F64(1.)));
+
+        // The palette is centered in UI coordinates.
+        let p = &editor.session().palette;
+        assert_eq!(p.y, h / 4. - p.rows() as f32 * p.cellsize / 2.);
+
+        editor.command("set ui/scale = 5").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+        assert_eq!(editor.session().ui_size(), (w / 2., h / 2.));
+    }
+
+    #[test]
+    fn test_editor_settings() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("set scale = 2").unwrap();
+        assert_eq!(editor.setting("scale"), Some(&Value::F64(2.)));
+
+        editor.command("set scale = 8").unwrap();
+        assert!(message(&editor).starts_with("Error"));
+        assert_eq!(editor.setting("scale"), Some(&Value::F64(2.)));
+
+        editor.command("set animation/delay?").unwrap();
+        assert_eq!(message(&editor), "animation/delay = 160");
+        editor.command("set nope?").unwrap();
+        assert_eq!(message(&editor), "Error: no such setting `nope`");
+        assert!(editor.setting("nope").is_none());
+
+        editor.command("set").unwrap();
+        assert!(editor.session().settings.iter().any(|(k, _)| k == "grid"));
+    }
+
+    #[test]
+    fn test_editor_shared_session() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("net/leave").unwrap();
+        assert_eq!(message(&editor), "Error: the session isn't shared");
+    }
+
+    #[test]
+    fn test_editor_readonly() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 2 2").unwrap();
+        editor.command("set readonly = on").unwrap();
+        editor.command("paint/color #ff0000 1 0").unwrap();
+        assert_eq!(
+            message(&editor),
+            "Error: read-only mode, `:set readonly = off` to edit"
+        );
+        editor.command("f/add").unwrap();
+        editor.command("zoom +").unwrap();
+        editor.command("import missing.png").unwrap();
+        assert_eq!(
+            message(&editor),
+            "Error: read-only mode, `:set readonly = off` to edit"
+        );
+
+        let (w, _, pixels) = editor.pixels();
+        assert_eq!(w, 2);
+        assert_eq!(pixels[1], Rgba8::TRANSPARENT);
+
+        editor.command("set readonly = off").unwrap();
+        editor.command("paint/color #ff0000 1 0").unwrap();
+        assert_eq!(editor.pixels().2[1], Rgba8::RED);
+    }
+
+    #[test]
+    fn test_editor_open_frames() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (red, blue) = ([Rgba8::RED; 4], [Rgba8::BLUE; 4]);
+        let walk2 = save_image(tmp.path(), "walk_2.png", 2, 2, &blue);
+        let walk1 = save_image(tmp.path(), "walk_1.png", 2, 2, &red);
+
+        let mut editor = Editor::new().unwrap();
+        editor.open_frames(&[&walk2, &walk1]).unwrap();
+
+        let v = editor.session().active_view();
+        assert_eq!((v.fw, v.fh, v.animation.len()), (2, 2, 2));
+
+        let (w, _, pixels) = editor.pixels();
+        assert_eq!(w, 4);
+        assert_eq!(
+            &pixels[..4],
+            &[Rgba8::RED, Rgba8::RED, Rgba8::BLUE, Rgba8::BLUE]
+        );
+
+        let walk3 = save_image(tmp.path(), "walk_3.png", 1, 4, &red);
+        assert!(editor.open_frames(&[&walk1, &walk3]).is_err());
+    }
+
+    #[test]
+    fn test_editor_export_frames() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 2 1").unwrap();
+        editor.command("f/add").unwrap();
+        editor.command("f/add").unwrap();
+
+        let path = tmp.path().join("frames.h");
+        editor
+            .command(&format!("export --frames 1..2 {}", path.display()))
+            .unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("#define FRAMES_WIDTH 4\n"));
+
+        editor
+            .command(&format!("export {} --frame 0", path.display()))
+            .unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .starts_with("#define FRAMES_WIDTH 2\n"));
+
+        editor
+            .command(&format!("export {} --frame 3", path.display()))
+            .unwrap();
+        assert!(message(&editor).ends_with("frame range must be within 0..2"));
+    }
+
+    #[test]
+    fn test_editor_export_layout_too_big() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut editor = Editor::new().unwrap();
+        let path = tmp.path().join("sheet.png");
+
+        editor.command("f/resize 2 1").unwrap();
+        editor
+            .command(&format!("export --padding 4294967295 {}", path.display()))
+            .unwrap();
+        assert!(message(&editor).ends_with("a 1x1 layout of 2x1 frames at scale 1 is too big"));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_editor_export_selection() {
+        let (r, g, b, k) = (Rgba8::RED, Rgba8::GREEN, Rgba8::BLUE, Rgba8::BLACK);
+        let (mut editor, tmp) = open_image("sheet.png", 4, 2, &[r, g, b, r, k, k, g, k]);
+        let out = tmp.path().join("sprite.png");
+
+        editor
+            .command(&format!("export {} --selection", out.display()))
+            .unwrap();
+        assert!(message(&editor).ends_with("there is no selection to export"));
+
+        editor.command("visual").unwrap();
+        editor.command("selection/expand").unwrap();
+        editor.command("selection/offset -1 0").unwrap();
+        editor
+            .command(&format!("export {} --selection", out.display()))
+            .unwrap();
+
+        let (pixels, w, h, _) = image::load(&out).unwrap();
+        assert_eq!((w, h), (2, 2));
+        assert_eq!(Rgba8::align(&pixels), &[g, b, k, g]);
+    }
+
+    #[test]
+    fn test_editor_export_filter() {
+        let mut sprite = vec![Rgba8::TRANSPARENT; 4 * 4];
+        sprite[4 + 1] = Rgba8::RED;
+        sprite[2 * 4 + 2] = Rgba8::RED;
+
+        let (mut editor, tmp) = open_image("line.png", 4, 4, &sprite);
+        let out = tmp.path().join("line-2x.png");
+
+        editor
+            .command(&format!("export {} --filter scale2x", out.display()))
+            .unwrap();
+
+        let (output, w, h, _) = image::load(&out).unwrap();
+        assert_eq!((w, h), (8, 8));
+        assert_eq!(
+            Rgba8::align(&output),
+            pixels::scale2x(&sprite, 4, 4).as_slice()
+        );
+
+        editor
+            .command(&format!(
+                "export {} --filter scale2x --scale 3",
+                out.display()
+            ))
+            .unwrap();
+        assert!(message(&editor).ends_with("`scale2x` can't scale by 3"));
+    }
+
+    #[test]
+    fn test_editor_import() {
+        let (r, g, t) = (Rgba8::RED, Rgba8::GREEN, Rgba8::TRANSPARENT);
+        let (mut editor, tmp) = open_image("sheet.png", 4, 2, &[t; 8]);
+        let sprite = save_image(tmp.path(), "sprite.png", 2, 1, &[r, g]);
+
+        editor
+            .command(&format!("import {} 1 0", sprite.display()))
+            .unwrap();
+        assert_eq!(editor.session().mode, Mode::Visual(VisualState::Pasting));
+        editor.command("selection/paste").unwrap();
+        assert_eq!(editor.pixels().2, vec![t, r, g, t, t, t, t, t]);
+
+        editor.command("import missing.png").unwrap();
+        assert!(message(&editor).starts_with("Error importing missing.png"));
+    }
+
+    #[test]
+    fn test_editor_animation_delay() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("anim.png");
+        let meta = image::Metadata {
+            nframes: Some(2),
+            delay: Some(80),
+            ..image::Metadata::default()
+        };
+        image::save_as(&path, 2, 1, 1, &[Rgba8::RED, Rgba8::BLUE], &meta).unwrap();
+
+        let mut editor = Editor::new().unwrap();
+        editor.open(&[&path]).unwrap();
+
+        // The delay is the view's own, and other views keep the setting.
+        let delay = time::Duration::from_millis(80);
+        assert_eq!(editor.session().active_view().animation.delay, Some(delay));
+        assert_eq!(editor.setting("animation/delay"), Some(&Value::U32(160)));
+        assert_eq!(editor.session().animation_wait(), Some(delay));
+
+        editor.command("set animation/delay = 100").unwrap();
+        assert_eq!(editor.session().active_view().animation.delay, None);
+    }
+
+    #[test]
+    fn test_editor_animation_strokes() {
+        let mut editor = Editor::new().unwrap();
+        let fg = editor.session().fg;
+        let t = Rgba8::TRANSPARENT;
+
+        assert_eq!(
+            editor.setting("animation/strokes"),
+            Some(&Value::Bool(false))
+        );
+
+        editor.command("set animation/strokes = on").unwrap();
+        editor.command("f/resize 2 1").unwrap();
+        editor.command("f/add").unwrap();
+        assert_eq!(
+            editor.setting("animation/strokes"),
+            Some(&Value::Bool(true))
+        );
+        assert!(editor.session().animation_wait().is_some());
+
+        // While the stroke is being drawn, it's only a draft.
+        editor.cursor(0, 0);
+        editor.mouse_down();
+        editor.cursor(1, 0);
+        assert!(editor.session().brush.is_drawing());
+        assert_eq!(editor.pixels().2, vec![t; 4]);
+
+        // Once it's finished, it's painted on the frame, and the animation keeps playing.
+        editor.mouse_up();
+        assert_eq!(editor.pixels().2, vec![fg, fg, t, t]);
+        assert!(editor.session().animation_wait().is_some());
+    }
+
+    #[test]
+    fn test_editor_preview_backdrop() {
+        let tmp = tempfile::tempdir().unwrap();
+        let pixels = [Rgba8::RED, Rgba8::BLUE];
+        let path = save_image(tmp.path(), "scene.png", 2, 1, &pixels);
+        let mut editor = Editor::new().unwrap();
+
+        editor
+            .command(&format!("preview/backdrop {}", path.display()))
+            .unwrap();
+        let backdrop = editor.session().preview_backdrop.clone().unwrap();
+        assert_eq!((backdrop.width, backdrop.height), (2, 1));
+        assert_eq!(backdrop.pixels, pixels);
+
+        editor.command("preview/backdrop #ff0000").unwrap();
+        assert_eq!(
+            editor.session().preview_backdrop,
+            Some(Backdrop::color(Rgba8::RED))
+        );
+
+        editor.command("preview/backdrop").unwrap();
+        assert_eq!(editor.session().preview_backdrop, None);
+    }
+
+    #[test]
+    fn test_editor_view_link() {
+        let pixels = [Rgba8::RED; 4];
+        let (mut editor, tmp) = open_image("hero.png", 2, 2, &pixels);
+        let normals = save_image(tmp.path(), "hero_n.png", 2, 2, &pixels);
+
+        editor.open(&[normals]).unwrap();
+
+        let active = editor.session().views.active_id;
+        editor.command("v/link hero.png").unwrap();
+        editor.command("v/link hero.png").unwrap();
+
+        let linked = editor.session().linked_views(active);
+        assert_eq!(linked.len(), 1);
+        assert_eq!(editor.session().linked_views(linked[0]), vec![active]);
+
+        editor.command("v/link hero_n.png").unwrap();
+        assert!(message(&editor).ends_with("can't be linked to itself"));
+
+        editor.command("v/link villain.png").unwrap();
+        assert!(message(&editor).ends_with("no view is showing `villain.png`"));
+
+        // Strokes are blended onto the linked view like onto the active one.
+        editor.command("brush/color #0000ff/0.5").unwrap();
+        editor.cursor(0, 1);
+        editor.mouse_down();
+        editor.mouse_up();
+        editor.command("brush/set erase").unwrap();
+        editor.cursor(1, 0);
+        editor.mouse_down();
+        editor.mouse_up();
+
+        let (r, t) = (Rgba8::RED, Rgba8::TRANSPARENT);
+        let mix = Rgba8::BLUE.alpha(127).over(r);
+        assert_eq!(
+            editor.pixels().2,
+            vec![r, t, mix, r],
+            "the stroke is blended once"
+        );
+
+        editor.command("v/prev").unwrap();
+        assert_eq!(editor.session().views.active_id, linked[0]);
+        assert_eq!(editor.pixels().2, vec![r, t, mix, r]);
+
+        editor.command("v/unlink").unwrap();
+        assert!(editor.session().links.is_empty());
+    }
+
+    #[test]
+    fn test_editor_backups() {
+        let (mut editor, _tmp) = open_image("tile.png", 1, 1, &[Rgba8::RED]);
+
+        editor.command("set backup = on").unwrap();
+        editor.command("paint/color #00ff00 0 0").unwrap();
+        editor.command("w").unwrap();
+        editor.command("backups").unwrap();
+        assert!(message(&editor).contains("Backups of"));
+
+        editor.command("backups 1").unwrap();
+        assert!(message(&editor).ends_with("restored"));
+        assert_eq!(editor.pixels().2, vec![Rgba8::RED]);
+
+        editor.command("backups 3").unwrap();
+        assert!(message(&editor).ends_with("not found"));
+    }
+
+    #[test]
+    fn test_editor_color_budget() {
+        let (r, g, b) = (Rgba8::RED, Rgba8::GREEN, Rgba8::BLUE);
+        let (mut editor, _tmp) = open_image("walk.png", 4, 1, &[r, g, b, b]);
+
+        editor.command("slice 2").unwrap();
+        editor.command("color/budget").unwrap();
+        assert!(message(&editor).ends_with("isn't set"));
+
+        editor.command("set max-colors = 1").unwrap();
+        assert_eq!(editor.session().frames_over_budget(), vec![(0, 2)]);
+        assert_eq!(
+            editor.session().status_item("budget").unwrap(),
+            "1 frame(s) over 1 colors"
+        );
+        editor.command("color/budget").unwrap();
+        assert!(message(&editor).ends_with(": 0 (2)"));
+
+        editor.command("set max-colors = 2").unwrap();
+        editor.command("color/budget").unwrap();
+        assert!(editor.session().frames_over_budget().is_empty());
+        assert!(message(&editor).ends_with("No frames over 2 colors"));
+        assert_eq!(editor.session().budget_status(), None);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_editor_paint_filter() {
+        let (r, k) = (Rgba8::RED, Rgba8::BLACK);
+        let (mut editor, _tmp) = open_image("water.png", 2, 2, &[r; 4]);
+
+        // Scanlines: darken every other row, starting from the top.
+        editor
+            .command(r#"paint/filter "sed -n 's/^\([0-9]* [0-9]*[02468]\) .*/\1 #000000/p'""#)
+            .unwrap();
+        assert!(message(&editor).ends_with("2 pixel(s) painted"));
+        assert_eq!(editor.pixels().2, vec![k, k, r, r]);
+
+        editor.command(r#"paint/filter "echo 0 0 red""#).unwrap();
+        assert!(message(&editor).contains("invalid pixel on line 1"));
+    }
+
+    #[test]
+    fn test_editor_flip_rotate() {
+        let (r, g, b) = (Rgba8::RED, Rgba8::GREEN, Rgba8::BLUE);
+        let (mut editor, _tmp) = open_image("arrow.png", 3, 2, &[r, g, b, b, b, b]);
+
+        editor.command("flip x").unwrap();
+        assert_eq!(editor.pixels(), (3, 2, vec![b, g, r, b, b, b]));
+
+        editor.command("rotate 90 --view").unwrap();
+        assert_eq!(editor.pixels(), (2, 3, vec![b, b, b, g, b, r]));
+
+        editor.command("undo").unwrap();
+        assert_eq!(editor.pixels(), (3, 2, vec![b, g, r, b, b, b]));
+    }
+
+    #[test]
+    fn test_editor_crop_auto() {
+        let (t, r, g) = (Rgba8::TRANSPARENT, Rgba8::RED, Rgba8::GREEN);
+        let mut pixels = vec![t; 8 * 4];
+
+        // Two 4x4 frames, with content at different places in each frame.
+        pixels[8 + 1] = r;
+        pixels[2 * 8 + 5] = g;
+
+        let (mut editor, _tmp) = open_image("sprite.png", 8, 4, &pixels);
+        editor.command("slice 2").unwrap();
+
+        editor.command("crop --auto").unwrap();
+        assert_eq!(editor.pixels(), (2, 2, vec![r, t, t, g]));
+        assert!(message(&editor).ends_with("Cropped frames to 1x2"));
+
+        editor.command("undo").unwrap();
+        assert_eq!(editor.pixels(), (8, 4, pixels));
+
+        // Padding doesn't grow frames past their original size.
+        editor.command("crop --auto --padding 1").unwrap();
+        let v = editor.session().active_view();
+        assert_eq!((v.fw, v.fh), (3, 4));
+
+        editor.command("undo").unwrap();
+        editor.command("crop --auto --padding 4294967295").unwrap();
+        let v = editor.session().active_view();
+        assert_eq!((v.fw, v.fh), (4, 4));
+    }
+
+    #[test]
+    fn test_editor_undo_group() {
+        let (r, g, b, t) = (Rgba8::RED, Rgba8::GREEN, Rgba8::BLUE, Rgba8::TRANSPARENT);
+        let (mut editor, _tmp) = open_image("square.png", 2, 2, &[r, g, b, t]);
+
+        editor.command("undo/begin").unwrap();
+        editor.command("flip x").unwrap();
+        editor.command("flip y").unwrap();
+        editor.command("undo/end").unwrap();
+        editor.command("flip x").unwrap();
+        assert_eq!(editor.pixels(), (2, 2, vec![b, t, r, g]));
+
+        editor.command("undo").unwrap();
+        assert_eq!(editor.pixels(), (2, 2, vec![t, b, g, r]));
+        editor.command("undo").unwrap();
+        assert_eq!(editor.pixels(), (2, 2, vec![r, g, b, t]));
+        editor.command("redo").unwrap();
+        assert_eq!(editor.pixels(), (2, 2, vec![t, b, g, r]));
+
+        editor
+            .command("undo/group { :flip y; :paint/color #ff0000 0 0 }")
+            .unwrap();
+        assert_eq!(editor.pixels(), (2, 2, vec![r, r, t, b]));
+        editor.command("undo").unwrap();
+        assert_eq!(editor.pixels(), (2, 2, vec![t, b, g, r]));
+    }
+
+    #[test]
+    fn test_editor_undo_scope() {
+        let (r, b) = (Rgba8::RED, Rgba8::BLUE);
+        let (mut editor, tmp) = open_image("a.png", 2, 1, &[r, b]);
+
+        editor
+            .open(&[save_image(tmp.path(), "b.png", 2, 1, &[r, b])])
+            .unwrap();
+        editor.command("flip x").unwrap();
+        editor.command("v/prev").unwrap();
+        editor.command("flip x").unwrap();
+        editor.command("v/next").unwrap();
+
+        // Undo in the view that was edited last, even if it isn't active.
+        editor.command("set undo/scope = session").unwrap();
+        editor.command("undo").unwrap();
+        assert_eq!(editor.pixels(), (2, 1, vec![b, r]));
+        editor.command("undo").unwrap();
+        assert_eq!(editor.pixels(), (2, 1, vec![r, b]));
+        editor.command("redo").unwrap();
+        assert_eq!(editor.pixels(), (2, 1, vec![b, r]));
+
+        editor.command("v/prev").unwrap();
+        assert_eq!(editor.pixels(), (2, 1, vec![r, b]));
+
+        // With the default scope, only the active view is affected.
+        editor.command("set undo/scope = view").unwrap();
+        editor.command("redo").unwrap();
+        assert_eq!(editor.pixels(), (2, 1, vec![b, r]));
+
+        editor.command("set undo/scope = file").unwrap();
+        assert_eq!(
+            message(&editor),
+            "Error: undo scope must be `view` or `session`"
+        );
+        assert_eq!(
+            editor.setting("undo/scope"),
+            Some(&Value::Ident(String::from("view")))
+        );
+    }
+
+    #[test]
+    fn test_editor_selection_shift() {
+        let (r, g, b, t) = (Rgba8::RED, Rgba8::GREEN, Rgba8::BLUE, Rgba8::TRANSPARENT);
+        let (mut editor, _tmp) = open_image("tile.png", 3, 2, &[r, g, b, b, b, b]);
+
+        editor.command("visual").unwrap();
+        editor.command("selection/expand").unwrap();
+
+        editor.command("selection/shift 1 0 --wrap").unwrap();
+        assert_eq!(editor.pixels().2, vec![b, r, g, b, b, b]);
+
+        editor.command("selection/shift 0 1").unwrap();
+        assert_eq!(editor.pixels().2, vec![b, b, b, t, t, t]);
+    }
+
+    #[test]
+    fn test_editor_export_all() {
+        let (r, b) = (Rgba8::RED, Rgba8::BLUE);
+        let (mut editor, tmp) = open_image("a.png", 2, 1, &[r, b]);
+
+        editor
+            .open(&[save_image(tmp.path(), "b.png", 2, 1, &[r, b])])
+            .unwrap();
+        editor.command("flip x").unwrap();
+
+        // Only modified views are exported.
+        let out = tmp.path().join("out");
+        editor
+            .command(&format!("export-all --dir {} --scale 2", out.display()))
+            .unwrap();
+        assert_eq!(
+            message(&editor),
+            format!("1 view(s) exported to `{}`", out.display())
+        );
+        assert!(!out.join("a.png").exists());
+
+        let (_, w, h, _) = image::load(out.join("b.png")).unwrap();
+        assert_eq!((w, h), (4, 2));
+
+        editor.command("export-all --scale 2").unwrap();
+        assert_eq!(message(&editor), "Error: `--scale` requires `--dir`");
+
+        editor
+            .command(&format!("export-all --dir {} --scale 0", out.display()))
+            .unwrap();
+        assert_eq!(message(&editor), "Error: export scale must be at least 1");
+
+        // Without a directory, views are written in place.
+        editor.command("export-all").unwrap();
+        assert_eq!(message(&editor), "1 view(s) written");
+
+        let (pixels, _, _, _) = image::load(tmp.path().join("b.png")).unwrap();
+        assert_eq!(&pixels[..4], &[b.r, b.g, b.b, b.a]);
+
+        editor.command("export-all").unwrap();
+        assert_eq!(message(&editor), "No modified views");
+    }
+
+    #[test]
+    fn test_editor_export_all_same_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+
+        for dir in &[&a, &b] {
+            std::fs::create_dir(dir).unwrap();
+            save_image(dir, "sprite.png", 1, 1, &[Rgba8::RED]);
+        }
+
+        let mut editor = Editor::new().unwrap();
+        editor
+            .open(&[a.join("sprite.png"), b.join("sprite.png")])
+            .unwrap();
+        editor.command("flip x").unwrap();
+        editor.command("v/prev").unwrap();
+        editor.command("flip x").unwrap();
+
+        // Neither view is exported, since one would overwrite the other.
+        let out = tmp.path().join("out");
+        editor
+            .command(&format!("export-all --dir {}", out.display()))
+            .unwrap();
+        assert!(message(&editor).ends_with(
+            "0 of 2 view(s) exported, more than one view would be exported as `sprite.png`"
+        ));
+        assert!(!out.join("sprite.png").exists());
+    }
+
+    #[test]
+    fn test_editor_window_title() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = save_image(tmp.path(), "hero.png", 2, 1, &[Rgba8::RED, Rgba8::BLUE]);
+
+        let mut editor = Editor::new().unwrap();
+        assert_eq!(editor.session().window_title(), "rx");
+
+        editor.open(&[&path]).unwrap();
+        assert_eq!(editor.session().window_title(), "hero.png - rx");
+
+        editor.command("flip x").unwrap();
+        assert_eq!(editor.session().window_title(), "hero.png* - rx");
+
+        editor
+            .command("set window/title = \"%modified%name (%zoom)\"")
+            .unwrap();
+        assert_eq!(editor.session().window_title(), "*hero.png (100%)");
+    }
+
+    #[test]
+    fn test_editor_input_brush() {
+        let mut editor = Editor::new().unwrap();
+        let fg = editor.session().fg;
+        let t = Rgba8::TRANSPARENT;
+
+        editor.command("f/resize 4 3").unwrap();
+        editor.cursor(0, 1);
+        editor.mouse_down();
+        editor.cursor(3, 1);
+        editor.mouse_up();
+
+        let (_, _, pixels) = editor.pixels();
+        assert_eq!(
+            pixels,
+            vec![t, t, t, t, fg, fg, fg, fg, t, t, t, t],
+            "the stroke covers the middle row"
+        );
+
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 4 3").unwrap();
+        editor.command("brush/set xsym").unwrap();
+        editor.cursor(0, 0);
+        editor.mouse_down();
+        editor.mouse_up();
+
+        let (_, _, pixels) = editor.pixels();
+        assert_eq!(&pixels[..4], &[fg, t, t, fg]);
+        assert!(pixels[4..].iter().all(|p| *p == t));
+
+        // Pressing off the canvas doesn't start a stroke, even when moving onto it.
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 4 3").unwrap();
+        editor.cursor(-1, 4);
+        editor.mouse_down();
+        editor.cursor(1, 1);
+        editor.mouse_up();
+
+        assert!(editor.pixels().2.iter().all(|p| *p == t));
+    }
+
+    #[test]
+    fn test_editor_input_brush_replace() {
+        let mut editor = Editor::new().unwrap();
+        let b = Rgba8::BLUE;
+
+        editor.command("f/resize 3 1").unwrap();
+        editor.command("brush/color #0000ff").unwrap();
+        editor.cursor(0, 0);
+        editor.mouse_down();
+        editor.cursor(2, 0);
+        editor.mouse_up();
+        assert_eq!(editor.pixels().2, vec![b, b, b]);
+
+        // Each pixel is blended once, however long the stroke is being drawn for.
+        editor.command("brush/color #ff0000/0.5").unwrap();
+        editor.command("brush/set replace").unwrap();
+        editor.cursor(0, 0);
+        editor.mouse_down();
+        editor.cursor(1, 0);
+        editor.cursor(2, 0);
+        editor.mouse_up();
+
+        let (_, _, pixels) = editor.pixels();
+        let mix = Rgba8 {
+            a: 0xff,
+            ..editor.session().fg.over(b)
+        };
+        assert_eq!(pixels, vec![mix, mix, mix]);
+    }
+
+    #[test]
+    fn test_editor_input_flood() {
+        let mut editor = Editor::new().unwrap();
+        let (r, b, t) = (Rgba8::RED, Rgba8::BLUE, Rgba8::TRANSPARENT);
+
+        editor.command("f/resize 3 3").unwrap();
+        editor.command("brush/color #ff0000").unwrap();
+        editor.cursor(1, 0);
+        editor.mouse_down();
+        editor.cursor(1, 2);
+        editor.mouse_up();
+
+        editor.command("brush/color #0000ff").unwrap();
+        editor.key("g").unwrap();
+        editor.cursor(0, 2);
+        editor.mouse_down();
+        editor.mouse_up();
+
+        let (_, _, pixels) = editor.pixels();
+        assert_eq!(pixels, vec![b, r, t, b, r, t, b, r, t]);
+    }
+
+    #[test]
+    fn test_editor_input_selection() {
+        let mut editor = Editor::new().unwrap();
+        let fg = editor.session().fg;
+
+        editor.command("f/resize 4 4").unwrap();
+        editor.key("v").unwrap();
+        assert_eq!(
+            editor.session().mode,
+            Mode::Visual(VisualState::selecting())
+        );
+
+        editor.cursor(1, 1);
+        editor.mouse_down();
+        editor.cursor(2, 2);
+        editor.mouse_up();
+
+        assert_eq!(
+            editor.session().selection.map(|s| s.abs()),
+            Some(Selection::new(1, 1, 3, 3))
+        );
+        editor.command("selection/fill").unwrap();
+
+        let (_, _, pixels) = editor.pixels();
+        for (i, p) in pixels.iter().enumerate() {
+            let (x, y) = (i % 4, i / 4);
+            let selected = (1..3).contains(&x) && (1..3).contains(&y);
+
+            assert_eq!(*p == fg, selected, "pixel at {},{}", x, y);
+        }
+        assert!(editor.event("mouse/input sideways").is_err());
+    }
+
+    #[test]
+    fn test_editor_input_snap_line() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 8 3").unwrap();
+        editor.command("guide/v 6").unwrap();
+        editor.command("set snap = on").unwrap();
+        editor.command("brush/set line").unwrap();
+
+        // The end of the line snaps to the guide.
+        editor.cursor(0, 1);
+        editor.mouse_down();
+        editor.cursor(3, 1);
+        editor.mouse_up();
+
+        let (_, _, pixels) = editor.pixels();
+        for (i, p) in pixels.iter().enumerate() {
+            let (x, y) = (i % 8, i / 8);
+            let painted = y == 1 && x <= 6;
+
+            assert_eq!(p.a == 0xff, painted, "pixel at {},{}", x, y);
+        }
+    }
+
+    #[test]
+    fn test_editor_input_ruler() {
+        let mut editor = Editor::new().unwrap();
+
+        editor.command("f/resize 4 4").unwrap();
+        editor.command("set ui/rulers = on").unwrap();
+
+        // Drag a guide from the top ruler.
+        editor.event("cursor/moved 200 2").unwrap();
+        editor.mouse_down();
+        assert_eq!(editor.session().guide_drag, Some(Axis::Horizontal));
+
+        editor.cursor(1, 1);
+        editor.mouse_up();
+        assert_eq!(editor.session().guide_drag, None);
+        assert_eq!(
+            editor.session().active_view().guides,
+            vec![Guide::Horizontal(2)]
+        );
+        // Nothing was painted along the way.
+        assert!(editor.pixels().2.iter().all(|p| *p == Rgba8::TRANSPARENT));
+
+        // Dropping a guide outside of the view discards it.
+        let x = editor.session().width - 2.;
+        editor.event(&format!("cursor/moved {} 200", x)).unwrap();
+        editor.mouse_down();
+        assert_eq!(editor.session().guide_drag, Some(Axis::Vertical));
+        editor.event(&format!("cursor/moved {} 201", x)).unwrap();
+        editor.mouse_up();
+        assert_eq!(editor.session().active_view().guides.len(), 1);
+    }
+}
@@ -65,6 +65,7 @@ impl FromStr for TimedEvent {
 pub enum Event {
     MouseInput(platform::MouseButton, platform::InputState),
     MouseWheel(platform::LogicalDelta),
+    Pinch(f64),
     CursorMoved(platform::LogicalPosition),
     KeyboardInput(platform::KeyboardInput),
     ReceivedCharacter(char, platform::ModifiersState),
@@ -78,6 +79,7 @@ impl From<Event> for String {
             Event::MouseInput(_, platform::InputState::Released) => format!("mouse/input released"),
             Event::MouseInput(_, platform::InputState::Repeated) => unreachable!(),
             Event::MouseWheel(delta) => format!("mouse/wheel {} {}", delta.x, delta.y),
+            Event::Pinch(delta) => format!("pinch {}", delta),
             Event::CursorMoved(platform::LogicalPosition { x, y }) => {
                 format!("cursor/moved {} {}", x, y)
             }
@@ -120,6 +122,13 @@ impl FromStr for Event {
                     .map_err(|(e, _)| e)?;
                 Ok((Event::MouseWheel(platform::LogicalDelta { x, y }), p))
             }
+            "pinch" => {
+                let (delta, p) = rational::<f64>()
+                    .followed_by(end())
+                    .parse(p)
+                    .map_err(|(e, _)| e)?;
+                Ok((Event::Pinch(delta), p))
+            }
             "cursor/moved" => {
                 let ((x, y), p) = parser::tuple::<f64>(rational(), rational())
                     .followed_by(end())
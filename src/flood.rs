@@ -28,20 +28,17 @@ impl Grid {
             None
         }
     }
-
-    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut Rgba8> {
-        if x < self.width && y < self.height {
-            self.pixels.get_mut(x + y * self.width)
-        } else {
-            None
-        }
-    }
 }
 
 pub struct FloodFiller {
     grid: Grid,
     replacement_color: Rgba8,
     target_color: Rgba8,
+    /// Maximum difference of any color channel with the target color, for a
+    /// pixel to be filled.
+    tolerance: u8,
+    /// Pixels that were already filled.
+    filled: Vec<bool>,
     rects: Vec<(Rect<f32>, Rgba8)>,
     stack: Vec<Point2<usize>>,
 }
@@ -51,6 +48,7 @@ impl FloodFiller {
         view: &View<ViewResource>,
         starting_point: LayerCoords<f32>,
         replacement_color: Rgba8,
+        tolerance: u8,
     ) -> Option<FloodFiller> {
         let (snapshot, pixels) = view.current_snapshot(view.active_layer_id)?;
         let bounds = snapshot.extent.rect();
@@ -67,9 +65,11 @@ impl FloodFiller {
 
         let target_color = *grid.get(starting_point.x, starting_point.y)?;
         Some(FloodFiller {
+            filled: vec![false; grid.pixels.len()],
             grid,
             target_color,
             replacement_color,
+            tolerance,
             rects: Vec::new(),
             stack: vec![starting_point],
         })
@@ -87,23 +87,35 @@ impl FloodFiller {
         ));
     }
 
-    fn try_set_at(&mut self, x: usize, y: usize) -> bool {
-        match self.grid.get_mut(x, y) {
+    /// Check whether the pixel at the given position should be filled.
+    fn is_fillable(&self, x: usize, y: usize) -> bool {
+        match self.grid.get(x, y) {
             Some(c) => {
-                if *c != self.target_color {
-                    false
-                } else {
-                    *c = self.replacement_color;
-                    true
-                }
+                let d = |a: u8, b: u8| (a as i16 - b as i16).unsigned_abs() as u8;
+                let t = self.target_color;
+                let diff = d(c.r, t.r)
+                    .max(d(c.g, t.g))
+                    .max(d(c.b, t.b))
+                    .max(d(c.a, t.a));
+
+                diff <= self.tolerance && !self.filled[x + y * self.grid.width]
             }
             None => false,
         }
     }
 
+    fn try_set_at(&mut self, x: usize, y: usize) -> bool {
+        if self.is_fillable(x, y) {
+            self.filled[x + y * self.grid.width] = true;
+            true
+        } else {
+            false
+        }
+    }
+
     fn push_on_change(&mut self, x: usize, y: usize, edge: &mut bool) {
-        if let Some(c) = self.grid.get(x, y) {
-            if *c == self.target_color {
+        if self.grid.get(x, y).is_some() {
+            if self.is_fillable(x, y) {
                 if *edge {
                     // We're at an edge, we'll come back to this point in the next loop to start a
                     // new horizontal span.
@@ -132,7 +144,7 @@ impl FloodFiller {
         // not equal to self.target_color. When we see one of these transitions, we push the next
         // point onto the stack and, later, we come back and repeat the horizontal scan from that
         // point.
-        if self.target_color == self.replacement_color {
+        if self.target_color == self.replacement_color && self.tolerance == 0 {
             return None;
         }
 
@@ -155,9 +167,8 @@ impl FloodFiller {
                 self.look_above_below(x, p.y, &mut up_edge, &mut down_edge);
             }
 
-            up_edge = p.y > 0 && self.grid.get(p.x, p.y - 1) != Some(&self.target_color);
-            down_edge = p.y < self.grid.height - 1
-                && self.grid.get(p.x, p.y + 1) != Some(&self.target_color);
+            up_edge = p.y > 0 && !self.is_fillable(p.x, p.y - 1);
+            down_edge = p.y < self.grid.height - 1 && !self.is_fillable(p.x, p.y + 1);
 
             // scan left
             for x in (0..p.x).rev() {
@@ -174,6 +185,32 @@ impl FloodFiller {
 
         Some(to_shapes(self.rects))
     }
+
+    /// Fill every matching pixel of the layer, whether or not it's connected to
+    /// the starting point.
+    pub fn run_global(mut self) -> Option<Vec<Shape>> {
+        if self.target_color == self.replacement_color && self.tolerance == 0 {
+            return None;
+        }
+
+        for y in 0..self.grid.height {
+            let mut x = 0;
+
+            while x < self.grid.width {
+                let start = x;
+                while self.try_set_at(x, y) {
+                    x += 1;
+                }
+                if x > start {
+                    self.push_rect(start, y, x - start, 1, self.replacement_color);
+                } else {
+                    x += 1;
+                }
+            }
+        }
+
+        Some(to_shapes(self.rects))
+    }
 }
 
 fn to_shapes(input: Vec<(Rect<f32>, Rgba8)>) -> Vec<Shape> {
@@ -75,6 +75,34 @@ impl Rgba8 {
         Self::new(self.r, self.g, self.b, a)
     }
 
+    /// Blend the color over another color, using "source over" compositing.
+    ///
+    /// ```
+    /// use rx::gfx::color::Rgba8;
+    ///
+    /// assert_eq!(Rgba8::RED.over(Rgba8::BLUE), Rgba8::RED);
+    /// assert_eq!(Rgba8::TRANSPARENT.over(Rgba8::BLUE), Rgba8::BLUE);
+    /// assert_eq!(Rgba8::RED.alpha(0x80).over(Rgba8::TRANSPARENT), Rgba8::RED.alpha(0x80));
+    /// ```
+    pub fn over(self, other: Self) -> Self {
+        match self.a {
+            0xff => self,
+            0 => other,
+            alpha => {
+                let (sa, da) = (alpha as u32, other.a as u32 * (0xff - alpha as u32) / 0xff);
+                let out = sa + da;
+                let mix = |s: u8, d: u8| ((s as u32 * sa + d as u32 * da) / out) as u8;
+
+                Self::new(
+                    mix(self.r, other.r),
+                    mix(self.g, other.g),
+                    mix(self.b, other.b),
+                    out as u8,
+                )
+            }
+        }
+    }
+
     /// Given a byte slice, returns a slice of [`Rgba8`] values.
     pub fn align<'a, S: 'a, T: AsRef<[S]> + ?Sized>(bytes: &'a T) -> &'a [Rgba8] {
         let bytes = bytes.as_ref();
@@ -0,0 +1,128 @@
+//! A second window mirroring the active view, eg. to see a sprite at actual
+//! size on another monitor while editing it zoomed in.
+use crate::gfx::{Rect, Rgba8};
+use crate::platform::{self, LogicalSize};
+use crate::util;
+
+use std::io;
+
+/// Title of the mirror window.
+const TITLE: &str = "rx - preview";
+
+pub struct Mirror {
+    window: platform::backend::Window,
+    events: platform::backend::Events,
+    texture: gl::types::GLuint,
+    framebuffer: gl::types::GLuint,
+    /// Logical size the window was last set to.
+    size: Option<LogicalSize>,
+}
+
+impl Mirror {
+    /// Open the mirror window, sharing the main window's context. The main
+    /// window's context is current again once this returns.
+    pub fn new(main: &mut platform::backend::Window) -> io::Result<Self> {
+        let (mut window, events) = main.create_shared(TITLE, 64, 64)?;
+        let (mut texture, mut framebuffer) = (0, 0);
+
+        window.make_current();
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::GenFramebuffers(1, &mut framebuffer);
+        }
+        main.make_current();
+
+        Ok(Self {
+            window,
+            events,
+            texture,
+            framebuffer,
+            size: None,
+        })
+    }
+
+    /// Whether the user closed the mirror window.
+    pub fn is_closing(&self) -> bool {
+        self.window.is_closing()
+    }
+
+    /// Display `src`, an area of the given `w` by `h` layer pixels, at the
+    /// given zoom. The window is resized to fit.
+    pub fn frame(
+        &mut self,
+        main: &mut platform::backend::Window,
+        (w, h, pixels): (u32, u32, &[Rgba8]),
+        src: Rect<u32>,
+        zoom: u32,
+    ) {
+        // The mirror window doesn't handle any input.
+        self.events.flush().for_each(drop);
+
+        let scale = self.window.scale_factor();
+        let size = LogicalSize::new(
+            (src.width() * zoom) as f64 / scale,
+            (src.height() * zoom) as f64 / scale,
+        );
+        if self.size != Some(size) {
+            self.window.set_size(size);
+            self.size = Some(size);
+        }
+        let (fw, fh) = self.window.framebuffer_size();
+        let (dw, dh) = (src.width() * zoom, src.height() * zoom);
+
+        self.window.make_current();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as i32,
+                w as i32,
+                h as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                util::align_u8(pixels).as_ptr() as *const _,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferTexture2D(
+                gl::READ_FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.texture,
+                0,
+            );
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+            gl::Viewport(0, 0, fw as i32, fh as i32);
+            gl::ClearColor(0., 0., 0., 1.);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+
+            // Layer pixels are stored top row first, while the window's origin
+            // is in the bottom-left, so the destination is flipped.
+            gl::BlitFramebuffer(
+                src.x1 as i32,
+                src.y1 as i32,
+                src.x2 as i32,
+                src.y2 as i32,
+                0,
+                fh as i32,
+                dw as i32,
+                fh as i32 - dh as i32,
+                gl::COLOR_BUFFER_BIT,
+                gl::NEAREST,
+            );
+        }
+        self.window.present();
+        main.make_current();
+    }
+
+    /// Close the mirror window and free its resources.
+    pub fn close(mut self, main: &mut platform::backend::Window) {
+        self.window.make_current();
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer);
+            gl::DeleteTextures(1, &self.texture);
+        }
+        main.make_current();
+    }
+}
@@ -1,7 +1,12 @@
+mod mirror;
+
+pub use mirror::Mirror;
+
 use crate::cmd::Axis;
 use crate::draw;
 use crate::execution::Execution;
 use crate::font::TextBatch;
+use crate::pixels;
 use crate::platform::{self, LogicalSize};
 use crate::renderer;
 use crate::session::{self, Blending, Effect, Session};
@@ -128,6 +133,9 @@ struct Cursor2dVertex {
 #[derive(UniformInterface)]
 struct Screen2dInterface {
     framebuffer: Uniform<TextureBinding<Dim2, pixel::NormUnsigned>>,
+    /// Size of the framebuffer, in pixels. Only used by display shaders.
+    #[uniform(unbound)]
+    resolution: Uniform<[f32; 2]>,
 }
 
 pub struct Renderer {
@@ -139,6 +147,7 @@ pub struct Renderer {
     scale: f64,
     present_fb: Framebuffer<Backend, Dim2, (), ()>,
     screen_fb: Framebuffer<Backend, Dim2, pixel::SRGBA8UI, pixel::Depth32F>,
+    split_fb: Option<Framebuffer<Backend, Dim2, pixel::SRGBA8UI, pixel::Depth32F>>,
     render_st: RenderState,
     pipeline_st: PipelineState,
     blending: Blending,
@@ -149,13 +158,23 @@ pub struct Renderer {
     font: Texture<Backend, Dim2, pixel::SRGBA8UI>,
     cursors: Texture<Backend, Dim2, pixel::SRGBA8UI>,
     checker: Texture<Backend, Dim2, pixel::SRGBA8UI>,
+    /// Colors last uploaded to the checker texture.
+    checker_colors: Option<[Rgba8; 4]>,
     paste: Texture<Backend, Dim2, pixel::SRGBA8UI>,
+    /// Animation preview backdrop, if any.
+    backdrop: Option<Texture<Backend, Dim2, pixel::SRGBA8UI>>,
+    /// Layers of the palette cycling view, with their colors cycled.
+    cycle: Option<(ViewId, Vec<Texture<Backend, Dim2, pixel::SRGBA8UI>>)>,
     paste_outputs: Vec<Tess<Backend, Sprite2dVertex>>,
 
     sprite2d: Program<Backend, VertexSemantics, (), Sprite2dInterface>,
     shape2d: Program<Backend, VertexSemantics, (), Shape2dInterface>,
     cursor2d: Program<Backend, VertexSemantics, (), Cursor2dInterface>,
     screen2d: Program<Backend, VertexSemantics, (), Screen2dInterface>,
+    /// User fragment shader for the final display pass, and the path it was
+    /// loaded from. See the `display/shader` setting.
+    display_shader: Option<Program<Backend, VertexSemantics, (), Screen2dInterface>>,
+    display_shader_path: String,
 
     view_data: BTreeMap<ViewId, ViewData>,
 }
@@ -409,7 +428,7 @@ impl<'a> renderer::Renderer<'a> for Renderer {
             .map_err(Error::Texture)?;
         let paste =
             Texture::new(&mut ctx, [paste_w, paste_h], 0, self::SAMPLER).map_err(Error::Texture)?;
-        let mut checker = Texture::new(&mut ctx, [checker_w, checker_h], 0, self::SAMPLER)
+        let checker = Texture::new(&mut ctx, [checker_w, checker_h], 0, self::SAMPLER)
             .map_err(Error::Texture)?;
 
         font.upload_raw(GenMipmaps::No, &font_img)
@@ -417,9 +436,6 @@ impl<'a> renderer::Renderer<'a> for Renderer {
         cursors
             .upload_raw(GenMipmaps::No, &cursors_img)
             .map_err(Error::Texture)?;
-        checker
-            .upload_raw(GenMipmaps::No, &draw::CHECKER)
-            .map_err(Error::Texture)?;
 
         let sprite2d = ctx.program::<Sprite2dInterface>(
             include_str!("data/sprite.vert"),
@@ -465,6 +481,7 @@ impl<'a> renderer::Renderer<'a> for Renderer {
 
         let draw_ctx = draw::Context {
             ui_batch: shape2d::Batch::new(),
+            chrome_batch: shape2d::Batch::new(),
             text_batch: self::text_batch(font.size()),
             overlay_batch: self::text_batch(font.size()),
             cursor_sprite: sprite::Sprite::new(cursors_w, cursors_h),
@@ -482,16 +499,22 @@ impl<'a> renderer::Renderer<'a> for Renderer {
             blending: Blending::Alpha,
             present_fb,
             screen_fb,
+            split_fb: None,
             render_st,
             pipeline_st,
             sprite2d,
             shape2d,
             cursor2d,
             screen2d,
+            display_shader: None,
+            display_shader_path: String::new(),
             font,
             cursors,
             checker,
+            checker_colors: None,
             paste,
+            backdrop: None,
+            cycle: None,
             paste_outputs: Vec::new(),
             staging_batch: shape2d::Batch::new(),
             final_batch: shape2d::Batch::new(),
@@ -520,9 +543,55 @@ impl<'a> renderer::Renderer<'a> for Renderer {
         self.update_view_animations(session);
         self.update_view_composites(session);
 
+        // The checker colors are settings, so they can change at any time.
+        let checker_colors = draw::checker(session);
+        if self.checker_colors != Some(checker_colors) {
+            self.checker
+                .upload_raw(GenMipmaps::No, util::align_u8(&checker_colors))
+                .map_err(RendererError::Texture)?;
+            self.checker_colors = Some(checker_colors);
+        }
+
+        // The display shader is a setting, so it can change at any time.
+        let path = session.settings["display/shader"].to_str();
+        if path != self.display_shader_path {
+            let path = path.to_owned();
+
+            self.display_shader = None;
+            if !path.is_empty() {
+                match self.load_display_shader(&path) {
+                    Ok(program) => self.display_shader = Some(program),
+                    Err(e) => session.message(
+                        format!("Error loading display shader `{}`: {}", path, e),
+                        session::MessageType::Error,
+                    ),
+                }
+            }
+            self.display_shader_path = path;
+        }
+
         let [screen_w, screen_h] = self.screen_fb.size();
         let ortho: M44 = Matrix4::ortho(screen_w, screen_h, Origin::TopLeft).into();
         let identity: M44 = Matrix4::identity().into();
+        let u = session.ui_scale();
+        let ui_transform: M44 = Matrix4::from_nonuniform_scale(u, u, 1.).into();
+
+        // Keep the split pane framebuffer in sync with the split pane size.
+        let split = session
+            .split
+            .map(|s| (s, s.rect(session.width, session.height)));
+        if let Some((_, r)) = split {
+            let size = [r.width() as u32, r.height() as u32];
+
+            if self.split_fb.as_ref().map(|fb| fb.size()) != Some(size) {
+                self.split_fb = Some(
+                    Framebuffer::new(&mut self.ctx, size, 0, self::SAMPLER)
+                        .map_err(RendererError::Framebuffer)?,
+                );
+            }
+        } else {
+            self.split_fb = None;
+        }
 
         let Self {
             draw_ctx,
@@ -533,13 +602,17 @@ impl<'a> renderer::Renderer<'a> for Renderer {
             shape2d,
             cursor2d,
             screen2d,
+            display_shader,
             scale_factor,
             present_fb,
             blending,
             screen_fb,
+            split_fb,
             render_st,
             pipeline_st,
             paste,
+            backdrop,
+            cycle,
             paste_outputs,
             view_data,
             ..
@@ -557,6 +630,9 @@ impl<'a> renderer::Renderer<'a> for Renderer {
         let ui_tess = self
             .ctx
             .tessellation::<_, Shape2dVertex>(&draw_ctx.ui_batch.vertices());
+        let chrome_tess = self
+            .ctx
+            .tessellation::<_, Shape2dVertex>(&draw_ctx.chrome_batch.vertices());
         let tool_tess = self
             .ctx
             .tessellation::<_, Sprite2dVertex>(&draw_ctx.tool_batch.vertices());
@@ -572,6 +648,64 @@ impl<'a> renderer::Renderer<'a> for Renderer {
             .build()
             .unwrap();
 
+        let split_tess = if let Some((_, r)) = split {
+            let batch = sprite2d::Batch::singleton(
+                r.width() as u32,
+                r.height() as u32,
+                Rect::origin(r.width(), r.height()),
+                r,
+                draw::SPLIT_LAYER,
+                Rgba::TRANSPARENT,
+                1.,
+                Repeat::default(),
+            );
+            Some(
+                self.ctx
+                    .tessellation::<_, Sprite2dVertex>(batch.vertices().as_slice()),
+            )
+        } else {
+            None
+        };
+        let mut minimap_tess = Vec::new();
+        if let Some(r) = session.minimap_rect() {
+            let v = session.active_view();
+            let (w, h) = (v.width() as f32, v.fh as f32);
+            let lh = r.height() / v.layers.len() as f32;
+
+            for i in 0..v.layers.len() {
+                let y = r.y1 + lh * i as f32;
+                let batch = sprite2d::Batch::singleton(
+                    w as u32,
+                    h as u32,
+                    Rect::origin(w, h),
+                    Rect::new(r.x1, y, r.x2, y + lh),
+                    draw::MINIMAP_LAYER,
+                    Rgba::TRANSPARENT,
+                    1.,
+                    Repeat::default(),
+                );
+                minimap_tess.push(
+                    self.ctx
+                        .tessellation::<_, Sprite2dVertex>(batch.vertices().as_slice()),
+                );
+            }
+        }
+        let backdrop_tess = if let Some(batch) = draw::draw_preview_backdrop(session) {
+            Some(
+                self.ctx
+                    .tessellation::<_, Sprite2dVertex>(batch.vertices().as_slice()),
+            )
+        } else {
+            None
+        };
+        let preview_tess = if let Some(batch) = draw::draw_preview(session) {
+            Some(
+                self.ctx
+                    .tessellation::<_, Sprite2dVertex>(batch.vertices().as_slice()),
+            )
+        } else {
+            None
+        };
         let paste_tess = if draw_ctx.paste_batch.is_empty() {
             None
         } else {
@@ -670,15 +804,17 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                         iface.set(&uni.ortho, view_ortho.into());
                         iface.set(&uni.transform, identity);
 
-                        let render_st = if blending == &Blending::Constant {
-                            render_st.clone().set_blending(blending::Blending {
-                                equation: Equation::Additive,
-                                src: Factor::One,
-                                dst: Factor::Zero,
-                            })
-                        } else {
-                            render_st.clone()
+                        let (src, dst) = match blending {
+                            Blending::Constant => (Factor::One, Factor::Zero),
+                            Blending::Alpha => (Factor::SrcAlpha, Factor::SrcAlphaComplement),
+                            Blending::Behind => (Factor::DstAlphaComplement, Factor::One),
+                            Blending::Replace => (Factor::DstAlpha, Factor::SrcAlphaComplement),
                         };
+                        let render_st = render_st.clone().set_blending(blending::Blending {
+                            equation: Equation::Additive,
+                            src,
+                            dst,
+                        });
 
                         rdr_gate.render(&render_st, |mut tess_gate| tess_gate.render(&tess))
                     })?;
@@ -704,12 +840,53 @@ impl<'a> renderer::Renderer<'a> for Renderer {
         let screen_st = &pipeline_st
             .clone()
             .set_clear_color([bg.r, bg.g, bg.b, bg.a]);
+
+        // Render to split pane framebuffer.
+        if let (Some((split, r)), Some(fb)) = (split, split_fb.as_ref()) {
+            if let (Some(view), Some(v)) = (
+                session.views.get(split.view),
+                view_data.get_mut(&split.view),
+            ) {
+                let split_ortho: M44 =
+                    Matrix4::ortho(r.width() as u32, r.height() as u32, Origin::TopLeft).into();
+                let split_st = &pipeline_st.clone().set_clear_color([bg.r, bg.g, bg.b, 1.]);
+
+                builder.pipeline::<PipelineError, _, _, _, _>(
+                    fb,
+                    split_st,
+                    |pipeline, mut shd_gate| {
+                        for (layer_id, l) in v.layers.iter_mut().enumerate() {
+                            let layer_offset = view.layer_offset(layer_id, split.zoom);
+                            let s = view.pixel_size(split.zoom);
+                            let transform = Matrix4::from_translation(
+                                (split.offset + layer_offset).extend(*draw::VIEW_LAYER),
+                            ) * Matrix4::from_nonuniform_scale(s.x, s.y, 1.0);
+
+                            shd_gate.shade(sprite2d, |mut iface, uni, mut rdr_gate| {
+                                let bound_view = pipeline
+                                    .bind_texture(l.fb.color_slot())
+                                    .expect("binding textures never fails");
+
+                                iface.set(&uni.ortho, split_ortho);
+                                iface.set(&uni.transform, transform.into());
+                                iface.set(&uni.tex, bound_view.binding());
+
+                                rdr_gate
+                                    .render(render_st, |mut tess_gate| tess_gate.render(&l.tess))
+                            })?;
+                        }
+                        Ok(())
+                    },
+                );
+            }
+        }
+
         builder.pipeline::<PipelineError, _, _, _, _>(
             screen_fb,
             screen_st,
             |pipeline, mut shd_gate| {
-                // Draw view checkers to screen framebuffer.
-                if session.settings["checker"].is_set() {
+                // Draw view checkers and backdrops to screen framebuffer.
+                if draw::has_backdrop(session) {
                     shd_gate.shade(sprite2d, |mut iface, uni, mut rdr_gate| {
                         let bound_checker = pipeline
                             .bind_texture(checker)
@@ -729,11 +906,10 @@ impl<'a> renderer::Renderer<'a> for Renderer {
 
                         for (layer_id, l) in v.layers.iter_mut().enumerate() {
                             let layer_offset = view.layer_offset(layer_id, view.zoom);
-                            let transform =
-                                Matrix4::from_translation(
-                                    (session.offset + view.offset + layer_offset)
-                                        .extend(*draw::VIEW_LAYER),
-                                ) * Matrix4::from_nonuniform_scale(view.zoom, view.zoom, 1.0);
+                            let transform = Matrix4::from_translation(
+                                (session.offset + view.offset + layer_offset)
+                                    .extend(*draw::VIEW_LAYER),
+                            ) * view.transform();
 
                             // Render views.
                             shd_gate.shade(sprite2d, |mut iface, uni, mut rdr_gate| {
@@ -765,12 +941,72 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                     }
                 }
 
+                // Render split pane.
+                if let (Some(tess), Some(fb)) = (&split_tess, split_fb.as_mut()) {
+                    shd_gate.shade(sprite2d, |mut iface, uni, mut rdr_gate| {
+                        let bound_split = pipeline
+                            .bind_texture(fb.color_slot())
+                            .expect("binding textures never fails");
+
+                        iface.set(&uni.ortho, ortho);
+                        iface.set(&uni.transform, identity);
+                        iface.set(&uni.tex, bound_split.binding());
+
+                        rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(tess))
+                    })?;
+                }
+
+                // Render minimap.
+                if !minimap_tess.is_empty() {
+                    if let Some(v) = view_data.get_mut(&session.views.active_id) {
+                        shd_gate.shade(sprite2d, |mut iface, uni, mut rdr_gate| {
+                            iface.set(&uni.ortho, ortho);
+                            iface.set(&uni.transform, identity);
+
+                            for (l, tess) in v.layers.iter_mut().zip(minimap_tess.iter()) {
+                                let bound_layer = pipeline
+                                    .bind_texture(l.fb.color_slot())
+                                    .expect("binding textures never fails");
+
+                                iface.set(&uni.tex, bound_layer.binding());
+                                rdr_gate
+                                    .render(render_st, |mut tess_gate| tess_gate.render(tess))?;
+                            }
+                            Ok(())
+                        })?;
+                    }
+                }
+
+                // Render nine-slice preview.
+                if let Some(tess) = &preview_tess {
+                    if let Some(v) = view_data.get_mut(&session.views.active_id) {
+                        shd_gate.shade(sprite2d, |mut iface, uni, mut rdr_gate| {
+                            iface.set(&uni.ortho, ortho);
+                            iface.set(&uni.transform, identity);
+
+                            for l in v.layers.iter_mut() {
+                                let bound_layer = pipeline
+                                    .bind_texture(l.fb.color_slot())
+                                    .expect("binding textures never fails");
+
+                                iface.set(&uni.tex, bound_layer.binding());
+                                rdr_gate
+                                    .render(render_st, |mut tess_gate| tess_gate.render(tess))?;
+                            }
+                            Ok(())
+                        })?;
+                    }
+                }
+
                 // Render UI.
                 shd_gate.shade(shape2d, |mut iface, uni, mut rdr_gate| {
                     iface.set(&uni.ortho, ortho);
                     iface.set(&uni.transform, identity);
 
-                    rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&ui_tess))
+                    rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&ui_tess))?;
+
+                    iface.set(&uni.transform, ui_transform);
+                    rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&chrome_tess))
                 })?;
 
                 // Render text, tool & view animations.
@@ -798,25 +1034,51 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                         }
                     }
 
+                    // Render animation backdrops.
+                    if let (Some(tess), Some(backdrop)) = (&backdrop_tess, backdrop.as_mut()) {
+                        let bound_backdrop = pipeline
+                            .bind_texture(backdrop)
+                            .expect("binding textures never fails");
+
+                        iface.set(&uni.tex, bound_backdrop.binding());
+                        iface.set(&uni.transform, identity);
+                        rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(tess))?;
+                    }
+
                     // Render view animations.
                     if session.settings["animation"].is_set() {
+                        let strokes = session.settings["animation/strokes"].is_set();
+
                         for (id, v) in view_data.iter_mut() {
                             match (&v.anim_tess, session.views.get(*id)) {
-                                (Some(tess), Some(view)) if view.animation.len() > 1 => {
+                                (Some(tess), Some(view))
+                                    if view.animation.len() > 1
+                                        || cycle.as_ref().map(|(c, _)| c) == Some(id) =>
+                                {
                                     let composite_t = Matrix4::from_translation(
-                                        Vector2::new(0., -(v.h as f32 * view.zoom)).extend(0.),
+                                        Vector2::new(0., -(v.h as f32 * view.scale().y)).extend(0.),
                                     );
 
+                                    let staging_texture = v.staging_fb.color_slot();
+
                                     for (i, l) in v.layers.iter_mut().enumerate() {
                                         // TODO: Assert that this layer is actually registered in the
                                         // view manager.
 
+                                        // Show the cycled colors, if the view's palette
+                                        // is being cycled.
+                                        let texture = match cycle.as_mut() {
+                                            Some((c, layers)) if c == id && i < layers.len() => {
+                                                &mut layers[i]
+                                            }
+                                            _ => l.fb.color_slot(),
+                                        };
                                         let bound_layer = pipeline
-                                            .bind_texture(l.fb.color_slot())
+                                            .bind_texture(texture)
                                             .expect("binding textures never fails");
                                         let layer_offset = v.h as usize * i;
                                         let t = Matrix4::from_translation(
-                                            Vector2::new(0., layer_offset as f32 * view.zoom)
+                                            Vector2::new(0., layer_offset as f32 * view.scale().y)
                                                 .extend(0.),
                                         );
 
@@ -827,6 +1089,19 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                                             tess_gate.render(tess)
                                         })?;
 
+                                        // Render the strokes being drawn over the layer
+                                        // animation, from the view staging buffer.
+                                        if strokes && i == view.active_layer_id {
+                                            let bound_staging = pipeline
+                                                .bind_texture(staging_texture)
+                                                .expect("binding textures never fails");
+
+                                            iface.set(&uni.tex, bound_staging.binding());
+                                            rdr_gate.render(render_st, |mut tess_gate| {
+                                                tess_gate.render(tess)
+                                            })?;
+                                        }
+
                                         // Render composite animation.
                                         if view.layers.len() > 1 {
                                             iface.set(&uni.transform, composite_t.into());
@@ -846,7 +1121,7 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                             .bind_texture(font)
                             .expect("binding textures never fails");
                         iface.set(&uni.tex, bound_font.binding());
-                        iface.set(&uni.transform, identity);
+                        iface.set(&uni.transform, ui_transform);
 
                         // Render text.
                         rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&text_tess))?;
@@ -858,6 +1133,7 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                         iface.set(&uni.tex, bound_tool.binding());
 
                         // Render tool.
+                        iface.set(&uni.transform, identity);
                         rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&tool_tess))?;
                     }
                     Ok(())
@@ -865,7 +1141,10 @@ impl<'a> renderer::Renderer<'a> for Renderer {
 
                 // Render help.
                 if let Some((win_tess, text_tess)) = help_tess {
-                    shd_gate.shade(shape2d, |_iface, _uni, mut rdr_gate| {
+                    shd_gate.shade(shape2d, |mut iface, uni, mut rdr_gate| {
+                        iface.set(&uni.ortho, ortho);
+                        iface.set(&uni.transform, ui_transform);
+
                         rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&win_tess))
                     })?;
                     shd_gate.shade(sprite2d, |mut iface, uni, mut rdr_gate| {
@@ -875,7 +1154,7 @@ impl<'a> renderer::Renderer<'a> for Renderer {
 
                         iface.set(&uni.tex, bound_font.binding());
                         iface.set(&uni.ortho, ortho);
-                        iface.set(&uni.transform, identity);
+                        iface.set(&uni.transform, ui_transform);
 
                         rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&text_tess))
                     })?;
@@ -893,8 +1172,11 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                 let bound_screen = pipeline
                     .bind_texture(screen_fb.color_slot())
                     .expect("binding textures never fails");
-                shd_gate.shade(screen2d, |mut iface, uni, mut rdr_gate| {
+                let program = display_shader.as_mut().unwrap_or(screen2d);
+
+                shd_gate.shade(program, |mut iface, uni, mut rdr_gate| {
                     iface.set(&uni.framebuffer, bound_screen.binding());
+                    iface.set(&uni.resolution, [screen_w as f32, screen_h as f32]);
 
                     rdr_gate.render(render_st, |mut tess_gate| tess_gate.render(&screen_tess))
                 })?;
@@ -949,13 +1231,13 @@ impl<'a> renderer::Renderer<'a> for Renderer {
                         vr.record_view_resized(v_data.layer_pixels().collect(), extent);
                     }
                     ViewState::Dirty(_) => {
-                        vr.record_view_painted(v_data.layer_pixels().collect());
+                        vr.record_view_painted(v_data.layer_pixels().collect(), extent);
                     }
                     ViewState::LayerDirty(layer_id) => {
                         let pixels = v_data.get_layer_mut(layer_id).pixels();
                         vr.record_layer_painted(layer_id, pixels, extent);
                     }
-                    ViewState::Okay | ViewState::Damaged(_) | ViewState::LayerDamaged(_) => {}
+                    ViewState::Okay | ViewState::Damaged(_) | ViewState::LayerDamaged(_, _) => {}
                 }
             }
         }
@@ -980,6 +1262,32 @@ impl<'a> renderer::Renderer<'a> for Renderer {
 }
 
 impl Renderer {
+    /// Compile a user fragment shader for the display pass. The shader is
+    /// given the screen `framebuffer` texture, its `resolution`, and the
+    /// texture coordinates as `f_uv`.
+    fn load_display_shader(
+        &mut self,
+        path: &str,
+    ) -> io::Result<Program<Backend, VertexSemantics, (), Screen2dInterface>> {
+        let frag = std::fs::read_to_string(path)?;
+
+        self.ctx
+            .new_shader_program()
+            .from_strings(include_str!("data/screen.vert"), None, None, &frag)
+            .map(|p| p.ignore_warnings())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    /// The active layer of the active view as it's currently displayed, along
+    /// with its size.
+    pub fn active_layer_pixels(&mut self, session: &Session) -> Option<(u32, u32, Vec<Rgba8>)> {
+        let v = session.views.active()?;
+        let data = self.view_data.get_mut(&v.id)?;
+        let pixels = data.get_layer_mut(v.active_layer_id).pixels();
+
+        Some((data.w, data.h, pixels))
+    }
+
     pub fn handle_resized(&mut self, size: platform::LogicalSize) {
         let physical = size.to_physical(self.scale_factor);
 
@@ -1044,7 +1352,10 @@ impl Renderer {
                 Effect::ViewDamaged(id, None) => {
                     self.handle_view_damaged(session.view(id))?;
                 }
-                Effect::ViewLayerDamaged(id, layer) => {
+                Effect::ViewLayerDamaged(id, layer, Some(rect)) => {
+                    self.handle_view_layer_rect_damaged(session.view(id), layer, rect)?;
+                }
+                Effect::ViewLayerDamaged(id, layer, None) => {
                     self.handle_view_layer_damaged(session.view(id), layer)?;
                 }
                 Effect::ViewBlendingChanged(blending) => {
@@ -1057,6 +1368,50 @@ impl Renderer {
                     shapes.into_iter().for_each(|s| self.final_batch.add(s));
                 }
                 Effect::ViewTouched(_) => {}
+                Effect::PaletteCycled => {
+                    let cycle = session.palette_cycle.as_ref();
+
+                    self.cycle = match cycle.and_then(|c| session.views.get(c.view).map(|v| (c, v)))
+                    {
+                        Some((c, v)) => {
+                            let mut layers = Vec::new();
+
+                            for (_, layer) in v.resource.layers.iter() {
+                                let (snapshot, pixels) = layer.current_snapshot();
+                                let pixels = c.apply(&session.palette.colors, pixels);
+                                let mut texture = Texture::new(
+                                    &mut self.ctx,
+                                    [snapshot.width(), snapshot.height()],
+                                    0,
+                                    self::SAMPLER,
+                                )
+                                .map_err(RendererError::Texture)?;
+                                texture
+                                    .upload_raw(GenMipmaps::No, util::align_u8(&pixels))
+                                    .map_err(RendererError::Texture)?;
+
+                                layers.push(texture);
+                            }
+                            Some((c.view, layers))
+                        }
+                        None => None,
+                    };
+                }
+                Effect::PreviewBackdropChanged => {
+                    self.backdrop = match &session.preview_backdrop {
+                        Some(b) => {
+                            let mut texture =
+                                Texture::new(&mut self.ctx, [b.width, b.height], 0, self::SAMPLER)
+                                    .map_err(RendererError::Texture)?;
+                            texture
+                                .upload_raw(GenMipmaps::No, util::align_u8(&b.pixels))
+                                .map_err(RendererError::Texture)?;
+
+                            Some(texture)
+                        }
+                        None => None,
+                    };
+                }
             }
         }
         Ok(())
@@ -1095,6 +1450,26 @@ impl Renderer {
                             .map_err(Error::Texture)?;
                     }
                 }
+                ViewOp::ClearRect(color, rect) => {
+                    let view = self
+                        .view_data
+                        .get_mut(&v.id)
+                        .expect("views must have associated view data");
+                    let (w, h) = (rect.width() as u32, rect.height() as u32);
+                    let texels = vec![*color; (w * h) as usize];
+                    let texels = util::align_u8(&texels);
+
+                    for l in view.layers.iter_mut() {
+                        l.fb.color_slot()
+                            .upload_part_raw(
+                                GenMipmaps::No,
+                                [rect.x1 as u32, rect.y1 as u32],
+                                [w, h],
+                                texels,
+                            )
+                            .map_err(Error::Texture)?;
+                    }
+                }
                 ViewOp::Blit(src, dst) => {
                     let view = self
                         .view_data
@@ -1176,6 +1551,23 @@ impl Renderer {
                         .upload_raw(GenMipmaps::No, body)
                         .map_err(Error::Texture)?;
                 }
+                ViewOp::Shift(layer_id, src, (dx, dy), wrap) => {
+                    let (_, pixels) = v.layer(*layer_id).get_snapshot_rect(src).unwrap(); // TODO: Handle this nicely?
+                    let (w, h) = (src.width() as u32, src.height() as u32);
+                    let [paste_w, paste_h] = self.paste.size();
+
+                    if paste_w != w || paste_h != h {
+                        self.paste = Texture::new(&mut self.ctx, [w, h], 0, self::SAMPLER)
+                            .map_err(Error::Texture)?;
+                    }
+                    // Snapshot rows are top to bottom, while view coordinates point up.
+                    let pixels = pixels::shift(&pixels, w, h, (*dx, -*dy), *wrap);
+                    let body = util::align_u8(&pixels);
+
+                    self.paste
+                        .upload_raw(GenMipmaps::No, body)
+                        .map_err(Error::Texture)?;
+                }
                 ViewOp::Paste(dst) => {
                     let [paste_w, paste_h] = self.paste.size();
                     let batch = sprite2d::Batch::singleton(
@@ -1207,6 +1599,28 @@ impl Renderer {
                         .upload_part_raw(GenMipmaps::No, [*x as u32, *y as u32], [1, 1], texels)
                         .map_err(Error::Texture)?;
                 }
+                ViewOp::SetPaste(w, h, pixels) => {
+                    let [paste_w, paste_h] = self.paste.size();
+
+                    if paste_w != *w || paste_h != *h {
+                        self.paste = Texture::new(&mut self.ctx, [*w, *h], 0, self::SAMPLER)
+                            .map_err(Error::Texture)?;
+                    }
+                    self.paste
+                        .upload_raw(GenMipmaps::No, util::align_u8(pixels))
+                        .map_err(Error::Texture)?;
+                }
+                ViewOp::SetPixels(layer_id, pixels) => {
+                    let fb = &mut self
+                        .view_data
+                        .get_mut(&v.id)
+                        .expect("views must have associated view data")
+                        .get_layer_mut(*layer_id)
+                        .fb;
+                    fb.color_slot()
+                        .upload_raw(GenMipmaps::No, util::align_u8(pixels))
+                        .map_err(Error::Texture)?;
+                }
             }
         }
         Ok(())
@@ -1233,6 +1647,38 @@ impl Renderer {
         Ok(())
     }
 
+    /// Only re-upload the damaged area of a layer.
+    fn handle_view_layer_rect_damaged(
+        &mut self,
+        view: &View<ViewResource>,
+        layer_id: LayerId,
+        rect: Rect<u32>,
+    ) -> Result<(), RendererError> {
+        if rect.is_empty() {
+            return Ok(());
+        }
+        let layer = self
+            .view_data
+            .get_mut(&view.id)
+            .expect("views must have associated view data")
+            .get_layer_mut(layer_id);
+
+        let (snapshot, texels) = view
+            .layer(layer_id)
+            .get_snapshot_rect(&rect.map(|n| n as i32))
+            .expect("the rect is within the view");
+        let texels = util::align_u8(&texels);
+
+        // Texture rows are stored top to bottom.
+        layer.upload_part(
+            [rect.x1, snapshot.height() - rect.y2],
+            [rect.width(), rect.height()],
+            texels,
+        )?;
+
+        Ok(())
+    }
+
     fn handle_view_damaged(&mut self, view: &View<ViewResource>) -> Result<(), RendererError> {
         let n = self
             .view_data
@@ -1,5 +1,9 @@
+pub mod psd;
+
 use crate::gfx::color::Rgba8;
+use crate::palette::Swatches;
 
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::ffi::OsStr;
 use std::fmt;
@@ -30,6 +34,70 @@ impl Encoding {
     }
 }
 
+/// Metadata stored in `tEXt` chunks of the PNGs written by rx.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadata {
+    /// Number of frames in the image.
+    pub nframes: Option<usize>,
+    /// Animation frame delay, in milliseconds.
+    pub delay: Option<u64>,
+    /// Palette of the view the image was saved from.
+    pub palette: Option<Swatches>,
+}
+
+impl Metadata {
+    const FRAMES: &'static str = "rx:frames";
+    const DELAY: &'static str = "rx:delay";
+    const PALETTE: &'static str = "rx:palette";
+
+    /// Read the metadata from the `tEXt` chunks of an encoded PNG.
+    pub fn read(data: &[u8]) -> Self {
+        let mut meta = Self::default();
+        // Skip the PNG signature.
+        let mut data = data.get(8..).unwrap_or_default();
+
+        // Each chunk is made of a length, a type, the data and a CRC.
+        while data.len() >= 12 {
+            let len = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+            let (name, rest) = data[4..].split_at(4);
+
+            if rest.len() < len + 4 || name == b"IEND" {
+                break;
+            }
+            if name == b"tEXt" {
+                let mut fields = rest[..len].splitn(2, |b| *b == 0);
+                let keyword = fields.next().unwrap_or_default();
+                let text = String::from_utf8_lossy(fields.next().unwrap_or_default());
+
+                match keyword {
+                    k if k == Self::FRAMES.as_bytes() => meta.nframes = text.parse().ok(),
+                    k if k == Self::DELAY.as_bytes() => meta.delay = text.parse().ok(),
+                    k if k == Self::PALETTE.as_bytes() => meta.palette = text.parse().ok(),
+                    _ => {}
+                }
+            }
+            data = &rest[len + 4..];
+        }
+        meta
+    }
+
+    /// Write the metadata as `tEXt` chunks.
+    fn write<W: io::Write>(&self, writer: &mut png::Writer<W>) -> io::Result<()> {
+        let fields = [
+            (Self::FRAMES, self.nframes.map(|n| n.to_string())),
+            (Self::DELAY, self.delay.map(|d| d.to_string())),
+            (Self::PALETTE, self.palette.as_ref().map(|p| p.to_string())),
+        ];
+        for (keyword, text) in fields.iter() {
+            if let Some(text) = text {
+                let chunk = [keyword.as_bytes(), &[0], text.as_bytes()].concat();
+                writer.write_chunk(*b"tEXt", &chunk)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 pub struct Path {
     parent: PathBuf,
     name: String,
@@ -82,20 +150,22 @@ impl TryFrom<&path::Path> for Path {
     }
 }
 
-pub fn load<P: AsRef<path::Path>>(path: P) -> io::Result<(Vec<u8>, u32, u32)> {
-    let f = File::open(&path).map_err(|e| {
+pub fn load<P: AsRef<path::Path>>(path: P) -> io::Result<(Vec<u8>, u32, u32, Metadata)> {
+    let data = std::fs::read(&path).map_err(|e| {
         io::Error::new(
             e.kind(),
             format!("error opening {}: {}", path.as_ref().display(), e),
         )
     })?;
 
-    self::read(f).map_err(|e| {
+    let (buffer, width, height) = self::read(data.as_slice()).map_err(|e| {
         io::Error::new(
             e.kind(),
             format!("error loading {}: {}", path.as_ref().display(), e),
         )
-    })
+    })?;
+
+    Ok((buffer, width, height, Metadata::read(&data)))
 }
 
 pub fn read<R: io::Read>(reader: R) -> io::Result<(Vec<u8>, u32, u32)> {
@@ -128,44 +198,112 @@ pub fn save_as<P: AsRef<path::Path>>(
     h: u32,
     scale: u32,
     pixels: &[Rgba8],
+    meta: &Metadata,
 ) -> io::Result<()> {
     let f = File::create(path.as_ref())?;
     let out = &mut io::BufWriter::new(f);
 
-    self::write(out, w, h, scale, pixels)
+    self::write(out, w, h, scale, pixels, meta)
 }
 
-pub fn write<W: io::Write>(out: W, w: u32, h: u32, scale: u32, pixels: &[Rgba8]) -> io::Result<()> {
+/// Write a PNG image. If the image has at most 256 colors, an indexed
+/// image is written, with a palette.
+pub fn write<W: io::Write>(
+    out: W,
+    w: u32,
+    h: u32,
+    scale: u32,
+    pixels: &[Rgba8],
+    meta: &Metadata,
+) -> io::Result<()> {
     let width = w * scale;
     let height = h * scale;
     let mut encoder = png::Encoder::new(out, width, height);
 
-    encoder.set_color(png::ColorType::RGBA);
     encoder.set_depth(png::BitDepth::Eight);
 
-    let mut writer = encoder.write_header()?;
+    let scaled;
+    let pixels = if scale == 1 {
+        pixels
+    } else {
+        scaled = pixels::scale(pixels, w, h, scale);
+        scaled.as_slice()
+    };
 
-    if scale == 1 {
-        let pixels = util::align_u8(pixels);
+    if let Some((palette, indices)) = self::index(pixels) {
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_palette(palette.iter().flat_map(|c| vec![c.r, c.g, c.b]).collect());
+
+        let mut writer = encoder.write_header()?;
+        // Always write the alpha of each palette entry, so that the image
+        // decodes to RGBA.
+        let alphas: Vec<u8> = palette.iter().map(|c| c.a).collect();
+        writer.write_chunk(*b"tRNS", &alphas)?;
+        meta.write(&mut writer)?;
 
         return writer
-            .write_image_data(pixels)
+            .write_image_data(&indices)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e));
     }
-    let scaled = pixels::scale(pixels, w, h, scale);
-    let pixels = util::align_u8(scaled.as_slice());
+    encoder.set_color(png::ColorType::RGBA);
+
+    let mut writer = encoder.write_header()?;
+    meta.write(&mut writer)?;
 
     writer
-        .write_image_data(pixels)
+        .write_image_data(util::align_u8(pixels))
         .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
 }
 
+/// Convert the pixels to a palette and palette indices, if there are at most
+/// 256 distinct colors.
+fn index(pixels: &[Rgba8]) -> Option<(Vec<Rgba8>, Vec<u8>)> {
+    let mut palette = Vec::new();
+    let mut lookup: BTreeMap<Rgba8, u8> = BTreeMap::new();
+    let mut indices = Vec::with_capacity(pixels.len());
+
+    for c in pixels.iter() {
+        let index = match lookup.get(c) {
+            Some(index) => *index,
+            None if palette.len() < 256 => {
+                let index = palette.len() as u8;
+                palette.push(*c);
+                lookup.insert(*c, index);
+                index
+            }
+            None => return None,
+        };
+        indices.push(index);
+    }
+    Some((palette, indices))
+}
+
 #[cfg(test)]
 mod test {
-    use super::Path;
+    use super::{Metadata, Path};
+    use crate::gfx::color::Rgba8;
     use std::convert::TryFrom;
     use std::path;
 
+    #[test]
+    fn test_write_indexed() {
+        let (red, clear) = (Rgba8::new(0xff, 0, 0, 0xff), Rgba8::TRANSPARENT);
+        let pixels = vec![red, clear, clear, red];
+        let meta = Metadata {
+            nframes: Some(2),
+            delay: Some(160),
+            palette: "#ff0000\nskin: #ffffff".parse().ok(),
+        };
+        let mut buffer = Vec::new();
+
+        super::write(&mut buffer, 2, 2, 1, &pixels, &meta).unwrap();
+
+        let (data, w, h) = super::read(buffer.as_slice()).unwrap();
+        assert_eq!((w, h), (2, 2));
+        assert_eq!(Rgba8::align(&data), pixels.as_slice());
+        assert_eq!(Metadata::read(&buffer), meta);
+    }
+
     #[test]
     fn test_image_path() {
         assert!(Path::try_from(path::Path::new("/")).is_err());
@@ -0,0 +1,261 @@
+//! A basic reader for Photoshop (PSD) files.
+//!
+//! Only the merged composite image is read, which Photoshop stores alongside
+//! the layers when "maximize compatibility" is enabled (the default). 8-bit RGB
+//! and grayscale images are supported, with or without an alpha channel.
+use crate::gfx::color::Rgba8;
+
+use std::io;
+
+/// RGB color mode.
+const MODE_RGB: u16 = 3;
+/// Grayscale color mode.
+const MODE_GRAYSCALE: u16 = 1;
+
+/// Raw image data compression.
+const COMPRESSION_RAW: u16 = 0;
+/// RLE (PackBits) image data compression.
+const COMPRESSION_RLE: u16 = 1;
+
+/// Largest width or height of a version 1 psd file.
+const MAX_SIZE: u32 = 30000;
+
+/// Read the composite image of a PSD file.
+pub fn read<R: io::Read>(mut reader: R) -> io::Result<(Vec<Rgba8>, u32, u32)> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut r = Reader::new(&data);
+
+    if r.bytes(4)? != b"8BPS" {
+        return Err(invalid("not a psd file"));
+    }
+    if r.u16()? != 1 {
+        return Err(invalid("only version 1 psd files are supported"));
+    }
+    r.bytes(6)?; // Reserved.
+
+    let channels = r.u16()? as usize;
+    let height = r.u32()?;
+    let width = r.u32()?;
+    let depth = r.u16()?;
+    let mode = r.u16()?;
+
+    if depth != 8 {
+        return Err(invalid("only 8-bit psd files are supported"));
+    }
+    let ncolors = match mode {
+        MODE_RGB => 3,
+        MODE_GRAYSCALE => 1,
+        _ => return Err(invalid("only rgb and grayscale psd files are supported")),
+    };
+    if channels < ncolors {
+        return Err(invalid("image has too few channels"));
+    }
+    if width > MAX_SIZE || height > MAX_SIZE {
+        return Err(invalid("image is too big"));
+    }
+    let size = (width as usize)
+        .checked_mul(height as usize)
+        .filter(|n| n.checked_mul(channels).is_some())
+        .ok_or_else(|| invalid("image is too big"))?;
+
+    // Skip the color mode data, image resources, and layer & mask sections.
+    for _ in 0..3 {
+        let len = r.u32()? as usize;
+        r.bytes(len)?;
+    }
+
+    let planes = match r.u16()? {
+        COMPRESSION_RAW => r.bytes(size * channels)?.to_vec(),
+        COMPRESSION_RLE => {
+            let mut lengths = Vec::with_capacity(channels * height as usize);
+            for _ in 0..channels * height as usize {
+                lengths.push(r.u16()? as usize);
+            }
+
+            let mut planes = Vec::with_capacity(size * channels);
+            for len in lengths {
+                let row = unpack_bits(r.bytes(len)?, width as usize)?;
+                planes.extend_from_slice(&row);
+            }
+            planes
+        }
+        _ => return Err(invalid("unsupported psd compression")),
+    };
+
+    let plane = |c: usize, i: usize| planes[c * size + i];
+    let has_alpha = channels > ncolors;
+
+    let pixels = (0..size)
+        .map(|i| {
+            let (r, g, b) = if ncolors == 3 {
+                (plane(0, i), plane(1, i), plane(2, i))
+            } else {
+                (plane(0, i), plane(0, i), plane(0, i))
+            };
+            let a = if has_alpha { plane(ncolors, i) } else { 0xff };
+
+            unmatte(Rgba8::new(r, g, b, a))
+        })
+        .collect();
+
+    Ok((pixels, width, height))
+}
+
+/// The composite image is matted against white. Reverse that, to get the
+/// original colors of semi-transparent pixels.
+fn unmatte(c: Rgba8) -> Rgba8 {
+    match c.a {
+        0 => Rgba8::TRANSPARENT,
+        0xff => c,
+        a => {
+            let f = |n: u8| {
+                let n = (n as i32 - (0xff - a as i32)) * 0xff / a as i32;
+                n.clamp(0, 0xff) as u8
+            };
+            Rgba8::new(f(c.r), f(c.g), f(c.b), a)
+        }
+    }
+}
+
+/// Decode a row compressed with the PackBits algorithm.
+fn unpack_bits(mut input: &[u8], len: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(len);
+
+    while let Some((&n, rest)) = input.split_first() {
+        let n = n as i8;
+        input = rest;
+
+        if n >= 0 {
+            let count = n as usize + 1;
+            let literal = input
+                .get(..count)
+                .ok_or_else(|| invalid("truncated rle data"))?;
+
+            output.extend_from_slice(literal);
+            input = &input[count..];
+        } else if n != -128 {
+            let (&byte, rest) = input
+                .split_first()
+                .ok_or_else(|| invalid("truncated rle data"))?;
+
+            output.resize(output.len() + (1 - n as isize) as usize, byte);
+            input = rest;
+        }
+    }
+    if output.len() != len {
+        return Err(invalid("rle row has the wrong length"));
+    }
+    Ok(output)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A big-endian byte reader.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < n {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "unexpected end of psd file",
+            ));
+        }
+        let (bytes, rest) = self.data.split_at(n);
+        self.data = rest;
+
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(channels: u16, width: u32, height: u32, compression: u16) -> Vec<u8> {
+        let mut data = Vec::new();
+
+        data.extend_from_slice(b"8BPS");
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&[0; 6]);
+        data.extend_from_slice(&channels.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&8u16.to_be_bytes());
+        data.extend_from_slice(&MODE_RGB.to_be_bytes());
+        // Empty color mode data, image resources, and layer & mask sections.
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&compression.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_read_raw() {
+        let mut data = header(4, 2, 1, COMPRESSION_RAW);
+        data.extend_from_slice(&[0xff, 0x00]); // Red.
+        data.extend_from_slice(&[0x00, 0xff]); // Green.
+        data.extend_from_slice(&[0x00, 0xff]); // Blue.
+        data.extend_from_slice(&[0xff, 0x00]); // Alpha.
+
+        let (pixels, w, h) = read(data.as_slice()).unwrap();
+
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(
+            pixels,
+            vec![Rgba8::new(0xff, 0, 0, 0xff), Rgba8::TRANSPARENT]
+        );
+    }
+
+    #[test]
+    fn test_read_rle() {
+        let mut data = header(3, 3, 1, COMPRESSION_RLE);
+        // Row lengths, for each channel.
+        for len in &[2u16, 4, 2] {
+            data.extend_from_slice(&len.to_be_bytes());
+        }
+        data.extend_from_slice(&[0xfe, 0x10]); // Run of 3.
+        data.extend_from_slice(&[0x02, 0x01, 0x02, 0x03]); // Literal of 3.
+        data.extend_from_slice(&[0xfe, 0x20]); // Run of 3.
+
+        let (pixels, _, _) = read(data.as_slice()).unwrap();
+
+        assert_eq!(
+            pixels,
+            vec![
+                Rgba8::new(0x10, 0x01, 0x20, 0xff),
+                Rgba8::new(0x10, 0x02, 0x20, 0xff),
+                Rgba8::new(0x10, 0x03, 0x20, 0xff),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_too_big() {
+        // The pixel count of this image overflows a `u32`.
+        let data = header(3, 0x10000, 0x10000, COMPRESSION_RAW);
+        let err = read(data.as_slice()).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "image is too big");
+    }
+}
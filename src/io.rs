@@ -20,13 +20,20 @@ pub struct Archive {
     pub manifest: Manifest,
 }
 
-pub fn load_image<P: AsRef<Path>>(path: P) -> io::Result<(u32, u32, Vec<Rgba8>)> {
-    let (buffer, width, height) = image::load(path)?;
+pub fn load_image<P: AsRef<Path>>(path: P) -> io::Result<(u32, u32, Vec<Rgba8>, image::Metadata)> {
+    let (buffer, width, height, meta) = image::load(path)?;
     let pixels = Rgba8::align(&buffer);
 
     // TODO: (perf) Avoid the copy?
 
-    Ok((width, height, pixels.into()))
+    Ok((width, height, pixels.into(), meta))
+}
+
+pub fn load_psd<P: AsRef<Path>>(path: P) -> io::Result<(u32, u32, Vec<Rgba8>)> {
+    let f = File::open(&path)?;
+    let (pixels, width, height) = image::psd::read(io::BufReader::new(f))?;
+
+    Ok((width, height, pixels))
 }
 
 pub fn load_archive<P: AsRef<Path>>(path: P) -> io::Result<Archive> {
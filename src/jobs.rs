@@ -0,0 +1,296 @@
+//! Background jobs, for work that shouldn't block the UI, like encoding and
+//! writing exports to disk.
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::panic;
+use std::path::Path;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time;
+
+/// A unit of work. On success, returns a message to show the user.
+type Task = Box<dyn FnOnce() -> io::Result<String> + Send>;
+
+/// Run a shell command with a path as its last argument, eg. to optimize or deploy
+/// an export. If the command fails, its error output is returned as the error.
+pub fn run(command: &str, path: &Path) -> io::Result<()> {
+    let output = if cfg!(windows) {
+        process::Command::new("cmd")
+            .arg("/C")
+            .arg(format!("{} \"{}\"", command, path.display()))
+            .output()?
+    } else {
+        // Passing the path as a positional parameter saves us from quoting it.
+        process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", command))
+            .arg("sh")
+            .arg(path)
+            .output()?
+    };
+
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(self::failure(command, &output))
+}
+
+/// Run a shell command with the given input on its standard input, and return its
+/// standard output. If the command fails, its error output is returned as the error.
+pub fn pipe(command: &str, input: &str) -> io::Result<String> {
+    let mut child = if cfg!(windows) {
+        let mut cmd = process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+    .stdin(process::Stdio::piped())
+    .stdout(process::Stdio::piped())
+    .stderr(process::Stdio::piped())
+    .spawn()?;
+
+    // Write the input from another thread, so that a command that starts writing
+    // before it's done reading doesn't block on a full pipe.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_owned();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let output = child.wait_with_output()?;
+    // The command may exit without reading all of its input, which is fine.
+    writer.join().ok();
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    Err(self::failure(command, &output))
+}
+
+/// The error of a failed command, with its error output on a single line.
+fn failure(command: &str, output: &process::Output) -> io::Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    io::Error::other(if stderr.is_empty() {
+        format!("`{}` failed with {}", command, output.status)
+    } else {
+        format!("`{}` failed: {}", command, stderr)
+    })
+}
+
+/// The error of a job that panicked, with the panic message if there is one.
+fn panicked(payload: Box<dyn Any + Send>) -> io::Error {
+    let msg = if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_owned()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        return io::Error::other("job panicked");
+    };
+    io::Error::other(format!("job panicked: {}", msg))
+}
+
+/// The outcome of a finished job.
+#[derive(Debug)]
+pub struct Done {
+    /// Name of the job.
+    pub name: String,
+    /// Message returned by the job, or the error it failed with.
+    pub result: io::Result<String>,
+    /// How long the job took to run.
+    pub elapsed: time::Duration,
+}
+
+/// A queue of jobs, run in order on a single worker thread.
+pub struct Jobs {
+    sender: Option<mpsc::Sender<(String, Task)>>,
+    receiver: Option<mpsc::Receiver<Done>>,
+    /// Names of jobs that haven't finished yet, oldest first.
+    pending: VecDeque<String>,
+}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self {
+            sender: None,
+            receiver: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Queue a job. The worker thread is started on the first call.
+    pub fn spawn<F>(&mut self, name: String, f: F)
+    where
+        F: FnOnce() -> io::Result<String> + Send + 'static,
+    {
+        if self.sender.is_none() {
+            let (tasks, queue) = mpsc::channel::<(String, Task)>();
+            let (results, receiver) = mpsc::channel();
+
+            thread::spawn(move || {
+                for (name, task) in queue {
+                    let start = time::Instant::now();
+                    // A panicking job fails like any other, and doesn't take the
+                    // worker down with it.
+                    let result = panic::catch_unwind(panic::AssertUnwindSafe(task))
+                        .unwrap_or_else(|e| Err(self::panicked(e)));
+                    let elapsed = start.elapsed();
+
+                    if results
+                        .send(Done {
+                            name,
+                            result,
+                            elapsed,
+                        })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+            self.sender = Some(tasks);
+            self.receiver = Some(receiver);
+        }
+
+        if let Some(sender) = &self.sender {
+            match sender.send((name.clone(), Box::new(f))) {
+                Ok(()) => self.pending.push_back(name),
+                Err(mpsc::SendError((name, task))) => {
+                    // The worker is gone. Start over with a new one.
+                    self.reset();
+                    self.spawn(name, task);
+                }
+            }
+        }
+    }
+
+    /// Return the jobs that finished since the last call, without blocking.
+    pub fn poll(&mut self) -> Vec<Done> {
+        let mut done = Vec::new();
+
+        if let Some(receiver) = &self.receiver {
+            loop {
+                match receiver.try_recv() {
+                    Ok(d) => done.push(d),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        self.reset();
+                        break;
+                    }
+                }
+            }
+        }
+        self.pending.drain(..done.len().min(self.pending.len()));
+
+        done
+    }
+
+    /// Block until all queued jobs have finished, and return them.
+    pub fn wait(&mut self) -> Vec<Done> {
+        let mut done = Vec::new();
+
+        if let Some(receiver) = &self.receiver {
+            while done.len() < self.pending.len() {
+                match receiver.recv() {
+                    Ok(d) => done.push(d),
+                    Err(_) => break,
+                }
+            }
+        }
+        self.pending.clear();
+
+        done
+    }
+
+    /// Names of the jobs that haven't finished yet, oldest first.
+    pub fn pending(&self) -> impl Iterator<Item = &String> {
+        self.pending.iter()
+    }
+
+    /// Whether there are unfinished jobs.
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn reset(&mut self) {
+        self.sender = None;
+        self.receiver = None;
+        self.pending.clear();
+    }
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_jobs() {
+        let mut jobs = Jobs::new();
+
+        jobs.spawn("a".to_owned(), || Ok(String::from("done a")));
+        jobs.spawn("b".to_owned(), || {
+            Err(io::Error::new(io::ErrorKind::NotFound, "b failed"))
+        });
+        assert!(jobs.is_busy());
+
+        let done = jobs.wait();
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[0].name, "a");
+        assert_eq!(done[0].result.as_ref().unwrap(), "done a");
+        assert!(done[1].result.is_err());
+        assert!(!jobs.is_busy());
+        assert!(jobs.poll().is_empty());
+    }
+
+    #[test]
+    fn test_jobs_panic() {
+        let mut jobs = Jobs::new();
+
+        jobs.spawn("a".to_owned(), || panic!("out of tiles"));
+        jobs.spawn("b".to_owned(), || Ok(String::from("done b")));
+
+        let done = jobs.wait();
+        assert_eq!(done.len(), 2);
+        assert_eq!(
+            done[0].result.as_ref().unwrap_err().to_string(),
+            "job panicked: out of tiles"
+        );
+        assert_eq!(done[1].result.as_ref().unwrap(), "done b");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_pipe() {
+        assert_eq!(pipe("tr a-z A-Z", "rx\n").unwrap(), "RX\n");
+        assert_eq!(pipe("true", "ignored").unwrap(), "");
+        assert!(pipe("exit 1", "").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_run() {
+        let path = Path::new("out file.png");
+
+        assert!(run("test -n", path).is_ok());
+        assert_eq!(
+            run("echo >&2 bad input; false", path)
+                .unwrap_err()
+                .to_string(),
+            "`echo >&2 bad input; false` failed: bad input"
+        );
+        assert_eq!(
+            run("false", path).unwrap_err().to_string(),
+            "`false` failed with exit status: 1"
+        );
+    }
+}
@@ -22,10 +22,12 @@ pub mod session;
 
 mod alloc;
 mod autocomplete;
+mod backup;
 mod brush;
 mod cmd;
 mod color;
 mod draw;
+mod editor;
 mod event;
 mod flood;
 mod font;
@@ -33,19 +35,29 @@ mod gl;
 mod history;
 mod image;
 mod io;
+mod jobs;
+mod net;
 mod palette;
 mod parser;
+mod picker;
 mod pixels;
 mod platform;
+mod profile;
 mod renderer;
+mod software;
 mod sprite;
+mod statusline;
+mod theme;
+mod timelapse;
 mod timer;
 mod view;
 
 #[macro_use]
 pub mod util;
 
-use cmd::Value;
+pub use cmd::Value;
+pub use editor::Editor;
+
 use event::Event;
 use execution::{DigestMode, Execution, ExecutionMode};
 use platform::{WindowEvent, WindowHint};
@@ -79,6 +91,8 @@ pub struct Options<'a> {
     pub exec: ExecutionMode,
     pub glyphs: &'a [u8],
     pub debug: bool,
+    pub readonly: bool,
+    pub frames: bool,
 }
 
 impl<'a> Default for Options<'a> {
@@ -92,6 +106,8 @@ impl<'a> Default for Options<'a> {
             exec: ExecutionMode::Normal,
             glyphs: data::GLYPHS,
             debug: false,
+            readonly: false,
+            frames: false,
         }
     }
 }
@@ -140,6 +156,12 @@ pub fn init<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Resul
             .set("debug", Value::Bool(true))
             .expect("'debug' is a bool'");
     }
+    if options.readonly {
+        session
+            .settings
+            .set("readonly", Value::Bool(true))
+            .expect("'readonly' is a bool");
+    }
 
     let mut execution = match options.exec {
         ExecutionMode::Normal => Execution::normal(),
@@ -165,9 +187,14 @@ pub fn init<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Resul
 
     let wait_events = execution.is_normal() || execution.is_recording();
 
-    let mut renderer: gl::Renderer = Renderer::new(&mut win, win_size, scale_factor, assets)?;
+    let mut renderer: renderer::Backend = Renderer::new(&mut win, win_size, scale_factor, assets)?;
 
-    if let Err(e) = session.edit(paths) {
+    let loaded = if options.frames {
+        session.edit_frames(paths)
+    } else {
+        session.edit(paths).map(|_| ())
+    };
+    if let Err(e) = loaded {
         session.message(format!("Error loading path(s): {}", e), MessageType::Error);
     }
     // Make sure our session ticks once before anything is rendered.
@@ -185,22 +212,26 @@ pub fn init<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Resul
     let mut last = Instant::now();
     let mut resized = false;
     let mut hovering = false;
+    let mut mirror: Option<gl::Mirror> = None;
+    let mut title = String::new();
     let mut delta;
 
     while !win.is_closing() {
-        match session.animation_delay() {
-            Some(delay) if session.is_running() => {
-                // How much time is left until the next animation frame?
-                let remaining = delay - session.accumulator;
-                // If more than 1ms remains, let's wait.
-                if remaining.as_millis() > 1 {
-                    events.wait_timeout(remaining);
-                } else {
-                    events.poll();
-                }
-            }
-            _ if wait_events => events.wait(),
-            _ => events.poll(),
+        // How much time is left until the next animation frame or key repeat?
+        let remaining = match session.animation_wait() {
+            Some(wait) if session.is_running() => Some(wait),
+            _ => None,
+        };
+        let remaining = match (remaining, session.key_repeat_wait()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        match remaining {
+            // If more than 1ms remains, let's wait.
+            Some(remaining) if remaining.as_millis() > 1 => events.wait_timeout(remaining),
+            Some(_) => events.poll(),
+            None if wait_events => events.wait(),
+            None => events.poll(),
         }
 
         for event in events.flush() {
@@ -260,6 +291,9 @@ pub fn init<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Resul
                     });
                     win.present();
                 }
+                WindowEvent::FileDropped(paths) => {
+                    session.handle_file_dropped(&paths);
+                }
                 WindowEvent::ScaleFactorChanged(factor) => {
                     renderer.handle_scale_factor_changed(factor);
                 }
@@ -275,6 +309,9 @@ pub fn init<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Resul
                 WindowEvent::MouseWheel { delta, .. } => {
                     session_events.push(Event::MouseWheel(delta));
                 }
+                WindowEvent::Pinch { delta } => {
+                    session_events.push(Event::Pinch(delta));
+                }
                 WindowEvent::KeyboardInput(input) => match input {
                     // Intercept `<insert>` key for pasting.
                     //
@@ -324,6 +361,9 @@ pub fn init<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Resul
                 });
         });
 
+        self::update_mirror(&mut mirror, &mut win, &mut renderer, &mut session);
+        self::update_window(&mut title, &mut win, &mut session);
+
         session.cleanup();
         win.present();
 
@@ -340,3 +380,129 @@ pub fn init<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Resul
 
     Ok(())
 }
+
+/// Update the window title, and request the user's attention if the session
+/// asks for it while the window isn't focused.
+fn update_window(title: &mut String, win: &mut platform::backend::Window, session: &mut Session) {
+    let t = session.window_title();
+
+    if t != *title {
+        win.set_title(&t);
+        *title = t;
+    }
+    if std::mem::take(&mut session.attention) && !win.is_focused() {
+        win.request_attention();
+    }
+}
+
+/// Open, update or close the window mirroring the active view, following the
+/// `preview/window` command.
+fn update_mirror(
+    mirror: &mut Option<gl::Mirror>,
+    win: &mut platform::backend::Window,
+    renderer: &mut renderer::Backend,
+    session: &mut Session,
+) {
+    match (session.preview_window, mirror.take()) {
+        (Some(_), Some(m)) if m.is_closing() => {
+            m.close(win);
+            session.preview_window = None;
+        }
+        (Some(zoom), Some(mut m)) => {
+            let v = session.active_view();
+            let i = v.animation.index as u32;
+            let src = gfx::Rect::new(i * v.fw, 0, (i + 1) * v.fw, v.fh);
+
+            if let Some((w, h, pixels)) = renderer.active_layer_pixels(session) {
+                m.frame(win, (w, h, &pixels), src, zoom);
+            }
+            *mirror = Some(m);
+        }
+        (Some(_), None) => match gl::Mirror::new(win) {
+            Ok(m) => *mirror = Some(m),
+            Err(e) => {
+                session.preview_window = None;
+                session.message(format!("Error: {}", e), MessageType::Error);
+            }
+        },
+        (None, Some(m)) => m.close(win),
+        (None, None) => {}
+    }
+}
+
+/// Run rx without a window, using the software renderer. The given paths are
+/// opened, and the `source` script is run one command at a time, with a frame
+/// rendered in between, so that each command sees the effects of the previous
+/// ones. Returns once the script is done, or when the session quits.
+pub fn batch<P: AsRef<Path>>(paths: &[P], options: Options<'_>) -> std::io::Result<()> {
+    use std::io::{self, BufRead};
+
+    let script = options
+        .source
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "a script is required"))?;
+    let mut editor = Editor::new()?;
+    if options.frames {
+        editor.open_frames(paths)?;
+    } else {
+        editor.open(paths)?;
+    }
+
+    let reader = io::BufReader::new(std::fs::File::open(&script)?);
+    for (i, line) in reader.lines().enumerate() {
+        editor.command(&line?).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: {} on line {}", script.display(), e, i + 1),
+            )
+        })?;
+
+        if editor.is_closed() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Run rx as a filter in a shell pipeline, without a window. If `stdin` is set,
+/// a PNG image is read from standard input, otherwise the given paths are opened.
+/// The `;`-separated `commands` are then run in order, and if `stdout` is set, the
+/// active layer is written to standard output as a PNG.
+pub fn filter<P: AsRef<Path>>(
+    paths: &[P],
+    commands: &str,
+    stdin: bool,
+    stdout: bool,
+) -> std::io::Result<()> {
+    use std::io::{self, Write};
+
+    let mut editor = Editor::new()?;
+
+    if stdin {
+        editor.read(io::stdin().lock())?;
+    } else {
+        editor.open(paths)?;
+    }
+
+    for cmd in commands.split(';').map(|c| c.trim()) {
+        let cmd = cmd.strip_prefix(':').unwrap_or(cmd);
+
+        if cmd.is_empty() {
+            continue;
+        }
+        editor.command(cmd).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("`{}`: {}", cmd, e))
+        })?;
+
+        if editor.is_closed() {
+            return Ok(());
+        }
+    }
+
+    if stdout {
+        let mut out = io::BufWriter::new(io::stdout().lock());
+
+        editor.write(&mut out)?;
+        out.flush()?;
+    }
+    Ok(())
+}
@@ -27,6 +27,13 @@ OPTIONS
     --width <width>      Set the window width
     --height <height>    Set the window height
     --debug              Set debug mode
+    --view               Open the paths read-only, for viewing
+    --frames             Open the paths as the frames of a single view
+    --batch              Run the -u script without a window, then exit
+
+    --stdin              Read a PNG image from standard input
+    --stdout             Write the resulting PNG image to standard output
+    --filter <commands>  Run the ';'-separated <commands> without a window, then exit
 "#;
 
 fn main() {
@@ -53,12 +60,18 @@ fn execute(mut args: pico_args::Arguments) -> Result<(), Box<dyn std::error::Err
 
     let verbose = args.contains("-v");
     let debug = args.contains("--debug");
+    let readonly = args.contains("--view");
+    let frames = args.contains("--frames");
     let width = args.opt_value_from_str("--width")?;
     let height = args.opt_value_from_str("--height")?;
     let record_digests = args.contains("--record-digests");
     let record_gif = args.contains("--record-gif");
     let verify_digests = args.contains("--verify-digests");
     let headless = args.contains("--headless");
+    let batch = args.contains("--batch");
+    let stdin = args.contains("--stdin");
+    let stdout = args.contains("--stdout");
+    let filter = args.opt_value_from_str::<_, String>("--filter")?;
     let source = args.opt_value_from_str::<_, PathBuf>("-u")?;
     let replay = args.opt_value_from_str::<_, PathBuf>("--replay")?;
     let record = args.opt_value_from_str::<_, PathBuf>("--record")?;
@@ -91,7 +104,15 @@ fn execute(mut args: pico_args::Arguments) -> Result<(), Box<dyn std::error::Err
         return Err("'--record-digests' has no effect without '--record' or '--replay'".into());
     }
 
-    let log_lvl = if verbose {
+    if batch && (stdin || stdout || filter.is_some()) {
+        return Err("'--batch' can't be used with '--stdin', '--stdout' or '--filter'".into());
+    }
+
+    // Only errors are logged when writing to standard output, and they go to
+    // standard error, so that the image isn't corrupted.
+    let log_lvl = if stdout {
+        log::Level::Error
+    } else if verbose {
         log::Level::Debug
     } else {
         log::Level::Info
@@ -120,9 +141,15 @@ fn execute(mut args: pico_args::Arguments) -> Result<(), Box<dyn std::error::Err
         exec,
         glyphs,
         debug,
+        readonly,
+        frames,
     };
 
     match args.free() {
+        Ok(paths) if stdin || stdout || filter.is_some() => {
+            rx::filter(&paths, &filter.unwrap_or_default(), stdin, stdout).map_err(|e| e.into())
+        }
+        Ok(paths) if batch => rx::batch(&paths, options).map_err(|e| e.into()),
         Ok(paths) => rx::init(&paths, options).map_err(|e| e.into()),
         Err(e) => {
             Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}\n{}", e, HELP)).into())
@@ -0,0 +1,269 @@
+//! Experimental shared sessions over the network.
+//!
+//! One instance hosts the session, and others join it. Editing operations are
+//! exchanged as rx commands, one per line. The host relays every line it receives
+//! to the other peers, so that operations reach everyone in the order the host saw
+//! them. Peers only run the editing operations they receive, and drop any other
+//! command. There is no locking: when two peers paint the same pixel, the last
+//! operation to arrive wins.
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+/// Maximum length of a line received from a peer, in bytes. Operations are much
+/// shorter than this, so peers sending longer lines are dropped.
+const MAX_LINE: usize = 4096;
+
+/// Append received bytes to the incomplete line in `input`, and move the lines
+/// that are now complete to `lines`. Returns `Err` if a line is too long.
+fn split_lines(input: &mut Vec<u8>, bytes: &[u8], lines: &mut Vec<String>) -> io::Result<()> {
+    input.extend_from_slice(bytes);
+
+    while let Some(n) = input.iter().position(|b| *b == b'\n') {
+        let line = input.drain(..=n).collect::<Vec<_>>();
+        lines.push(String::from_utf8_lossy(&line[..n]).into_owned());
+    }
+    if input.len() > MAX_LINE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+    }
+    Ok(())
+}
+
+/// A connected peer.
+struct Peer {
+    stream: TcpStream,
+    /// Bytes received, up to the last incomplete line.
+    input: Vec<u8>,
+    /// Bytes waiting to be sent.
+    output: Vec<u8>,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            input: Vec::new(),
+            output: Vec::new(),
+        })
+    }
+
+    /// Read the complete lines received so far. Returns `Err` once the peer
+    /// has disconnected.
+    fn read(&mut self, lines: &mut Vec<String>) -> io::Result<()> {
+        let mut buf = [0; 4096];
+
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => split_lines(&mut self.input, &buf[..n], lines)?,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send as much of the pending output as the socket accepts.
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.output.is_empty() {
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.output.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A link to the other peers of a shared session.
+pub struct Link {
+    /// Listener for new peers, if we're hosting.
+    listener: Option<TcpListener>,
+    /// Connected peers. When joining a session, this is only the host.
+    peers: Vec<Peer>,
+}
+
+impl Link {
+    /// Host a session on the given address.
+    pub fn host<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener: Some(listener),
+            peers: Vec::new(),
+        })
+    }
+
+    /// Join the session hosted at the given address.
+    pub fn join<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let peer = Peer::new(TcpStream::connect(addr)?)?;
+
+        Ok(Self {
+            listener: None,
+            peers: vec![peer],
+        })
+    }
+
+    /// Whether we're hosting the session.
+    pub fn is_host(&self) -> bool {
+        self.listener.is_some()
+    }
+
+    /// The address we're hosting on, if we're hosting.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|l| l.local_addr().ok())
+    }
+
+    /// Number of connected peers.
+    pub fn peers(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Send an operation to all peers. The line is sent on the next poll.
+    pub fn send(&mut self, line: &str) {
+        for peer in self.peers.iter_mut() {
+            peer.output.extend_from_slice(line.as_bytes());
+            peer.output.push(b'\n');
+        }
+    }
+
+    /// Accept new peers, and return the operations received since the last poll.
+    /// Peers that disconnect are dropped. Returns `Err` if we joined a session
+    /// and the host went away.
+    pub fn poll(&mut self) -> io::Result<Vec<String>> {
+        if let Some(listener) = &self.listener {
+            while let Ok((stream, addr)) = listener.accept() {
+                info!("net: {} joined", addr);
+                self.peers.push(Peer::new(stream)?);
+            }
+        }
+        let mut received = Vec::new();
+        let mut i = 0;
+
+        while i < self.peers.len() {
+            let mut lines = Vec::new();
+
+            if self.peers[i].read(&mut lines).is_err() {
+                self.peers.remove(i);
+
+                if !self.is_host() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "host disconnected",
+                    ));
+                }
+                continue;
+            }
+            // Relay operations to the other peers.
+            if self.is_host() {
+                for (j, peer) in self.peers.iter_mut().enumerate() {
+                    if j != i {
+                        for line in &lines {
+                            peer.output.extend_from_slice(line.as_bytes());
+                            peer.output.push(b'\n');
+                        }
+                    }
+                }
+            }
+            received.extend(lines);
+            i += 1;
+        }
+
+        let host = self.is_host();
+        let before = self.peers.len();
+        self.peers.retain_mut(|p| p.flush().is_ok());
+
+        if !host && self.peers.len() < before {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "host disconnected",
+            ));
+        }
+        Ok(received)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time;
+
+    /// Poll a link until it receives something, or a second passes.
+    fn receive(link: &mut Link, others: &mut [&mut Link]) -> Vec<String> {
+        let start = time::Instant::now();
+
+        while start.elapsed() < time::Duration::from_secs(1) {
+            for other in others.iter_mut() {
+                other.poll().unwrap();
+            }
+            let lines = link.poll().unwrap();
+            if !lines.is_empty() {
+                return lines;
+            }
+            std::thread::sleep(time::Duration::from_millis(5));
+        }
+        vec![]
+    }
+
+    #[test]
+    fn test_split_lines() {
+        let mut input = Vec::new();
+        let mut lines = Vec::new();
+
+        split_lines(&mut input, b"f/add\nf/re", &mut lines).unwrap();
+        assert_eq!(lines, vec!["f/add"]);
+        assert_eq!(input, b"f/re");
+
+        split_lines(&mut input, b"move\n", &mut lines).unwrap();
+        assert_eq!(lines, vec!["f/add", "f/remove"]);
+        assert!(input.is_empty());
+
+        // A peer that never ends its line can't grow the buffer without limit.
+        let long = vec![b'x'; MAX_LINE + 1];
+        assert!(split_lines(&mut input, &long, &mut lines).is_err());
+    }
+
+    #[test]
+    fn test_link() {
+        let mut host = Link::host("127.0.0.1:0").unwrap();
+        let addr = host.local_addr().unwrap();
+
+        let mut a = Link::join(addr).unwrap();
+        let mut b = Link::join(addr).unwrap();
+
+        while host.peers() < 2 {
+            host.poll().unwrap();
+        }
+        assert!(host.is_host());
+        assert!(!a.is_host());
+
+        a.send("paint/color #ff0000 1 2");
+        assert_eq!(
+            receive(&mut host, &mut [&mut a]),
+            vec!["paint/color #ff0000 1 2"]
+        );
+        // The host relays it to the other peer, but not back to the sender.
+        assert_eq!(
+            receive(&mut b, &mut [&mut host]),
+            vec!["paint/color #ff0000 1 2"]
+        );
+        assert!(a.poll().unwrap().is_empty());
+
+        drop(host);
+
+        let start = time::Instant::now();
+        while a.poll().is_ok() {
+            assert!(start.elapsed() < time::Duration::from_secs(1));
+            std::thread::sleep(time::Duration::from_millis(5));
+        }
+    }
+}
@@ -3,8 +3,122 @@ use crate::session::SessionCoords;
 use crate::gfx::Rgba8;
 use arrayvec::ArrayVec;
 
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// A color, given directly or by its index in a palette group, eg. `skin.2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorRef {
+    Color(Rgba8),
+    Named(String, usize),
+}
+
+impl fmt::Display for ColorRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Color(c) => c.fmt(f),
+            Self::Named(group, i) => write!(f, "{}.{}", group, i),
+        }
+    }
+}
+
+/// A named group of palette colors. Each group starts a new palette column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub name: String,
+    /// Index of the group's first color. The group extends to the next group.
+    pub start: usize,
+}
+
+/// The colors and groups of a palette, eg. the palette of a view.
+///
+/// As text, each line lists colors, optionally starting with a group name
+/// followed by `:`, eg. `skin: #ffccaa #e0a080`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Swatches {
+    pub colors: Vec<Rgba8>,
+    pub groups: Vec<Group>,
+}
+
+impl fmt::Display for Swatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ungrouped = self.groups.first().map_or(self.colors.len(), |g| g.start);
+        let mut lines = vec![(None, 0..ungrouped)];
+
+        for (i, g) in self.groups.iter().enumerate() {
+            let end = self
+                .groups
+                .get(i + 1)
+                .map_or(self.colors.len(), |g| g.start);
+            lines.push((Some(&g.name), g.start..end));
+        }
+        for (i, (name, range)) in lines.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            let mut sep = "";
+            if let Some(name) = name {
+                write!(f, "{}:", name)?;
+                sep = " ";
+            }
+            for c in &self.colors[range] {
+                write!(f, "{}{}", sep, c)?;
+                sep = " ";
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Swatches {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut swatches = Self::default();
+
+        for line in input.lines() {
+            let mut tokens = line.split_whitespace().peekable();
+
+            if let Some(name) = tokens.peek().and_then(|t| t.strip_suffix(':')) {
+                swatches.groups.push(Group {
+                    name: name.to_owned(),
+                    start: swatches.colors.len(),
+                });
+                tokens.next();
+            }
+            for token in tokens {
+                swatches.colors.push(self::parse_hex(token)?);
+            }
+        }
+        Ok(swatches)
+    }
+}
+
+/// Parse a color of the form `#rrggbb` or `#rrggbbaa`.
+fn parse_hex(s: &str) -> Result<Rgba8, String> {
+    let err = || format!("invalid color `{}`", s);
+
+    match s.len() {
+        7 => Rgba8::from_str(s).map_err(|_| err()),
+        9 if s.is_char_boundary(7) => {
+            let c = Rgba8::from_str(&s[..7]).map_err(|_| err())?;
+            let a = u8::from_str_radix(&s[7..], 16).map_err(|_| err())?;
+
+            Ok(c.alpha(a))
+        }
+        _ => Err(err()),
+    }
+}
+
 pub struct Palette {
     pub colors: ArrayVec<[Rgba8; 256]>,
+    pub groups: Vec<Group>,
+    /// Recently painted colors, most recent first. Shown after the palette
+    /// colors, in their own column.
+    pub recent: Vec<Rgba8>,
+    /// Number of recent colors kept.
+    pub recent_size: usize,
     pub hover: Option<Rgba8>,
     pub cellsize: f32,
     pub height: usize,
@@ -13,9 +127,12 @@ pub struct Palette {
 }
 
 impl Palette {
-    pub fn new(cellsize: f32, height: usize) -> Self {
+    pub fn new(cellsize: f32, height: usize, recent_size: usize) -> Self {
         Self {
             colors: ArrayVec::new(),
+            groups: Vec::new(),
+            recent: Vec::new(),
+            recent_size,
             hover: None,
             cellsize,
             height,
@@ -24,12 +141,171 @@ impl Palette {
         }
     }
 
+    /// Add a color to the last group, unless the group already has it.
     pub fn add(&mut self, color: Rgba8) {
-        if !self.colors.contains(&color) {
+        let start = self.groups.last().map_or(0, |g| g.start);
+
+        if !self.colors[start..].contains(&color) {
             self.colors.push(color);
         }
     }
 
+    /// The palette colors and groups.
+    pub fn swatches(&self) -> Swatches {
+        Swatches {
+            colors: self.colors.to_vec(),
+            groups: self.groups.clone(),
+        }
+    }
+
+    /// Replace the palette colors and groups.
+    pub fn set_swatches(&mut self, swatches: Swatches) {
+        self.clear();
+        self.colors
+            .extend(swatches.colors.into_iter().take(self.colors.capacity()));
+        self.groups = swatches.groups;
+        self.hover = None;
+
+        for g in self.groups.iter_mut() {
+            g.start = g.start.min(self.colors.len());
+        }
+    }
+
+    /// Record a color as painted with, making it the most recent color.
+    pub fn add_recent(&mut self, color: Rgba8) {
+        self.recent.retain(|c| *c != color);
+        self.recent.insert(0, color);
+        self.recent.truncate(self.recent_size);
+    }
+
+    /// Start a new named group, which colors are added to from now on.
+    /// Returns `false` if a group with that name already exists.
+    pub fn group(&mut self, name: &str) -> bool {
+        if self.groups.iter().any(|g| g.name == name) {
+            return false;
+        }
+        let start = self.size();
+
+        // An empty group is simply renamed.
+        match self.groups.last_mut() {
+            Some(g) if g.start == start => g.name = name.to_owned(),
+            _ => self.groups.push(Group {
+                name: name.to_owned(),
+                start,
+            }),
+        }
+        true
+    }
+
+    /// Get the color at the given index of a group.
+    pub fn get(&self, group: &str, index: usize) -> Option<Rgba8> {
+        let i = self.groups.iter().position(|g| g.name == group)?;
+        let end = self.groups.get(i + 1).map_or(self.size(), |g| g.start);
+
+        (self.groups[i].start..end)
+            .nth(index)
+            .map(|i| self.colors[i])
+    }
+
+    /// Resolve a color reference.
+    pub fn resolve(&self, color: &ColorRef) -> Option<Rgba8> {
+        match color {
+            ColorRef::Color(c) => Some(*c),
+            ColorRef::Named(group, index) => self.get(group, *index),
+        }
+    }
+
+    /// The palette color closest to the given color, if the palette isn't empty.
+    pub fn nearest(&self, color: Rgba8) -> Option<Rgba8> {
+        let dist = |c: &Rgba8| {
+            [
+                (c.r, color.r),
+                (c.g, color.g),
+                (c.b, color.b),
+                (c.a, color.a),
+            ]
+            .iter()
+            .map(|(a, b)| (*a as i32 - *b as i32).pow(2))
+            .sum::<i32>()
+        };
+        self.colors.iter().min_by_key(|c| dist(c)).copied()
+    }
+
+    /// The color `n` steps away from the given color along its ramp, ie. the group,
+    /// or run of ungrouped colors containing it. Stops at either end of the ramp.
+    /// Returns `None` if the color isn't in the palette.
+    pub fn ramp_step(&self, color: Rgba8, n: i32) -> Option<Rgba8> {
+        let i = self.colors.iter().position(|c| *c == color)?;
+        let ramp = self.ranges().find(|r| r.contains(&i))?;
+        let j = (i as i32 + n).clamp(ramp.start as i32, ramp.end as i32 - 1);
+
+        Some(self.colors[j as usize])
+    }
+
+    /// Sort the colors of each group by total luminosity.
+    pub fn sort(&mut self) {
+        let ranges = self.ranges().collect::<Vec<_>>();
+
+        for range in ranges {
+            self.colors[range].sort_by_key(|c| c.r as u32 + c.g as u32 + c.b as u32);
+        }
+    }
+
+    /// Index range of the colors added before the first group.
+    fn ungrouped(&self) -> Range<usize> {
+        0..self.groups.first().map_or(self.size(), |g| g.start)
+    }
+
+    /// Palette colors followed by the recent colors, in display order.
+    pub fn entries(&self) -> impl Iterator<Item = &Rgba8> + '_ {
+        self.colors.iter().chain(self.recent.iter())
+    }
+
+    /// Index ranges of the non-empty color runs, ie. the ungrouped colors,
+    /// followed by each group, followed by the recent colors.
+    fn ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let ends = self
+            .groups
+            .iter()
+            .skip(1)
+            .map(|g| g.start)
+            .chain(std::iter::once(self.size()));
+        let groups = self.groups.iter().zip(ends).map(|(g, end)| g.start..end);
+
+        let recent = self.size()..self.size() + self.recent.len();
+
+        std::iter::once(self.ungrouped())
+            .filter(|r| !r.is_empty())
+            .chain(groups)
+            .chain(std::iter::once(recent))
+    }
+
+    /// Number of rows of the palette, as displayed.
+    pub fn rows(&self) -> usize {
+        self.ranges()
+            .map(|r| r.len().min(self.height))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Column and row of each entry, as displayed. Colors fill columns from
+    /// the top, and each group, as well as the recent colors, starts a new column.
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        let height = self.height.max(1);
+        let mut cells = Vec::with_capacity(self.size() + self.recent.len());
+        let mut column = 0;
+
+        for range in self.ranges() {
+            let len = range.len();
+
+            for i in 0..len {
+                cells.push((column + i / height, i % height));
+            }
+            column += len.div_ceil(height);
+        }
+        cells
+    }
+
     pub fn gradient(&mut self, colorstart: Rgba8, colorend: Rgba8, number: usize) {
         fn blend_component(start: u8, end: u8, coef: f32) -> u8 {
             (start as f32 * (1.0 - coef) + end as f32 * coef).round() as u8
@@ -49,8 +325,41 @@ impl Palette {
         }
     }
 
+    /// Parse a GIMP palette (`.gpl`) and return its colors.
+    pub fn parse_gpl(input: &str) -> Result<Vec<Rgba8>, String> {
+        let mut lines = input.lines();
+
+        if lines.next().map(str::trim) != Some("GIMP Palette") {
+            return Err(String::from("not a GIMP palette"));
+        }
+
+        let mut colors = Vec::new();
+        for line in lines.map(str::trim) {
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("Name:")
+                || line.starts_with("Columns:")
+            {
+                continue;
+            }
+            let rgb = line
+                .split_whitespace()
+                .take(3)
+                .map(|c| c.parse::<u8>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| format!("invalid color `{}`", line))?;
+
+            match rgb.as_slice() {
+                [r, g, b] => colors.push(Rgba8::new(*r, *g, *b, 0xff)),
+                _ => return Err(format!("invalid color `{}`", line)),
+            }
+        }
+        Ok(colors)
+    }
+
     pub fn clear(&mut self) {
         self.colors.clear();
+        self.groups.clear();
     }
 
     pub fn size(&self) -> usize {
@@ -58,39 +367,123 @@ impl Palette {
     }
 
     pub fn handle_cursor_moved(&mut self, p: SessionCoords) {
-        let (x, y) = (p.x, p.y);
-        let mut x = x as i32 - self.x as i32;
-        let mut y = y as i32 - self.y as i32;
+        let x = p.x as i32 - self.x as i32;
+        let y = p.y as i32 - self.y as i32;
         let cellsize = self.cellsize as i32;
-        let size = self.size() as i32;
-        let height = self.height as i32;
-        let columns = (self.size() as f32 / self.height as f32).ceil() as i32;
-
-        let width = if size > height {
-            cellsize * columns
-        } else {
-            cellsize
-        };
-        let height = i32::min(size, height) * cellsize;
 
-        if x >= width || y >= height || x < 0 || y < 0 {
+        if x < 0 || y < 0 {
             self.hover = None;
             return;
         }
+        // The Y axis points up, while rows are counted from the top.
+        let column = (x / cellsize) as usize;
+        let row = self.rows() as i32 - 1 - y / cellsize;
 
-        x /= cellsize;
-        y /= cellsize;
+        self.hover = self
+            .cells()
+            .iter()
+            .position(|c| *c == (column, row as usize) && row >= 0)
+            .and_then(|i| self.entries().nth(i).copied());
+    }
+}
 
-        let index = y + x * (height / cellsize);
+#[cfg(test)]
+mod test {
+    use super::*;
 
-        self.hover = if index < size {
-            // We index from the back because the palette is reversed
-            // before it is displayed, due to the Y axis pointing up,
-            // where as the palette is created starting at the top
-            // and going down.
-            Some(self.colors[self.size() - index as usize - 1])
-        } else {
-            None
-        };
+    #[test]
+    fn test_groups() {
+        let mut p = Palette::new(1., 2, 2);
+        let (a, b, c) = (Rgba8::RED, Rgba8::BLACK, Rgba8::WHITE);
+
+        p.add(a);
+        assert!(p.group("skin"));
+        p.add(a);
+        p.add(b);
+        p.add(c);
+        assert!(p.group("metal"));
+        assert!(!p.group("skin"));
+        p.add(b);
+
+        assert_eq!(p.size(), 5);
+        assert_eq!(p.get("skin", 2), Some(c));
+        assert_eq!(p.get("metal", 0), Some(b));
+        assert_eq!(p.get("metal", 1), None);
+        assert_eq!(p.get("bg", 0), None);
+        assert_eq!(
+            p.resolve(&ColorRef::Named(String::from("skin"), 0)),
+            Some(a)
+        );
+        assert_eq!(p.ramp_step(b, 1), Some(c));
+        assert_eq!(p.ramp_step(a, 1), Some(a));
+        assert_eq!(p.ramp_step(c, 1), Some(c));
+        assert_eq!(p.ramp_step(c, -2), Some(a));
+        assert_eq!(p.ramp_step(Rgba8::BLUE, 1), None);
+
+        // Each group starts a new column.
+        assert_eq!(p.cells(), vec![(0, 0), (1, 0), (1, 1), (2, 0), (3, 0)]);
+        assert_eq!(p.rows(), 2);
+
+        // Recent colors come last, most recent first.
+        p.add_recent(a);
+        p.add_recent(b);
+        p.add_recent(c);
+        p.add_recent(b);
+        assert_eq!(p.recent, vec![b, c]);
+        assert_eq!(p.cells()[5..], [(4, 0), (4, 1)]);
+        assert_eq!(p.entries().nth(6), Some(&c));
+    }
+
+    #[test]
+    fn test_swatches() {
+        let mut p = Palette::new(1., 8, 0);
+
+        p.add(Rgba8::RED);
+        p.group("skin");
+        p.add(Rgba8::WHITE);
+        p.add(Rgba8::TRANSPARENT);
+        p.group("bg");
+
+        let swatches = p.swatches();
+        let text = swatches.to_string();
+
+        assert_eq!(text, "#ff0000\nskin: #ffffff #00000000\nbg:");
+        assert_eq!(text.parse::<Swatches>(), Ok(swatches));
+        assert!("skin: #ff00".parse::<Swatches>().is_err());
+    }
+
+    #[test]
+    fn test_nearest() {
+        let mut p = Palette::new(1., 8, 0);
+        assert_eq!(p.nearest(Rgba8::RED), None);
+
+        p.add(Rgba8::BLACK);
+        p.add(Rgba8::RED);
+        p.add(Rgba8::WHITE);
+
+        assert_eq!(
+            p.nearest(Rgba8::new(0xee, 0x11, 0x22, 0xff)),
+            Some(Rgba8::RED)
+        );
+        assert_eq!(
+            p.nearest(Rgba8::new(0x22, 0x22, 0x22, 0xff)),
+            Some(Rgba8::BLACK)
+        );
+        assert_eq!(p.nearest(Rgba8::WHITE), Some(Rgba8::WHITE));
+    }
+
+    #[test]
+    fn test_parse_gpl() {
+        let gpl = "GIMP Palette\nName: Test\nColumns: 2\n#\n255   0   0\tRed\n  0 128 255 Blue\n";
+
+        assert_eq!(
+            Palette::parse_gpl(gpl),
+            Ok(vec![
+                Rgba8::new(0xff, 0x00, 0x00, 0xff),
+                Rgba8::new(0x00, 0x80, 0xff, 0xff)
+            ])
+        );
+        assert!(Palette::parse_gpl("255 0 0").is_err());
+        assert!(Palette::parse_gpl("GIMP Palette\n255 0").is_err());
     }
 }
@@ -3,10 +3,13 @@ use memoir::*;
 
 use directories as dirs;
 
-use crate::brush::BrushMode;
+use crate::brush::{BrushMode, Frames, Snap};
+use crate::cmd::Scope;
 use crate::gfx::Rgba8;
+use crate::palette::ColorRef;
 use crate::platform;
 use crate::session::{Direction, Mode, VisualState};
+use crate::view::Playback;
 
 use std::ffi::OsString;
 use std::str::FromStr;
@@ -163,22 +166,64 @@ pub fn color() -> Parser<Rgba8> {
     )
 }
 
+/// A color, or a palette group color, eg. `skin.2`.
+pub fn color_ref() -> Parser<ColorRef> {
+    color()
+        .map(ColorRef::Color)
+        .or(identifier()
+            .skip(symbol('.'))
+            .then(natural::<usize>())
+            .map(|(group, i)| ColorRef::Named(group, i)))
+        .label("<color>")
+}
+
 impl Parse for BrushMode {
     fn parser() -> Parser<Self> {
         Parser::new(
             |input| {
                 let (id, p) = identifier().parse(input)?;
                 match id.as_str() {
-                    "erase" => Ok((BrushMode::Erase, p)),
-                    "multi" => Ok((BrushMode::Multi, p)),
+                    "erase" => optional(whitespace())
+                        .then(optional(string("bg")))
+                        .parse(p)
+                        .map(|((_, bg), p)| (BrushMode::Erase(bg.is_some()), p)),
+                    "multi" => optional(whitespace())
+                        .then(optional(choice(vec![
+                            string("all").value(Frames::All),
+                            string("every")
+                                .skip(whitespace())
+                                .then(natural::<u32>())
+                                .map(|(_, n)| Frames::Every(n)),
+                            natural::<u32>()
+                                .skip(whitespace())
+                                .then(natural::<u32>())
+                                .map(|(a, b)| Frames::Range(a, b)),
+                        ])))
+                        .parse(p)
+                        .map(|((_, frames), p)| {
+                            (BrushMode::Multi(frames.unwrap_or(Frames::Rest)), p)
+                        }),
+                    "mirror" => Ok((BrushMode::Mirror, p)),
                     "perfect" => Ok((BrushMode::Perfect, p)),
-                    "xsym" => Ok((BrushMode::XSym, p)),
-                    "ysym" => Ok((BrushMode::YSym, p)),
+                    "xsym" => optional(whitespace())
+                        .then(optional(natural()))
+                        .parse(p)
+                        .map(|((_, x), p)| (BrushMode::XSym(x), p)),
+                    "ysym" => optional(whitespace())
+                        .then(optional(natural()))
+                        .parse(p)
+                        .map(|((_, y), p)| (BrushMode::YSym(y), p)),
                     "xray" => Ok((BrushMode::XRay, p)),
+                    "behind" => Ok((BrushMode::Behind, p)),
+                    "replace" => Ok((BrushMode::Replace, p)),
+                    "isosym" => Ok((BrushMode::IsoSym, p)),
+                    "octsym" => Ok((BrushMode::OctSym, p)),
+                    "connect" => Ok((BrushMode::Connect, p)),
                     "line" => optional(whitespace())
-                        .then(optional(natural()))
+                        .then(optional(snap()))
                         .parse(p)
                         .map(|((_, snap), p)| (BrushMode::Line(snap), p)),
+                    "circle" => Ok((BrushMode::Circle, p)),
                     mode => Err((
                         memoir::result::Error::new(format!("unknown brush mode '{}'", mode)),
                         input,
@@ -190,6 +235,26 @@ impl Parse for BrushMode {
     }
 }
 
+impl Parse for Playback {
+    fn parser() -> Parser<Self> {
+        Parser::new(
+            |input| {
+                let (id, p) = identifier().parse(input)?;
+                match id.as_str() {
+                    "forward" => Ok((Playback::Forward, p)),
+                    "reverse" => Ok((Playback::Reverse, p)),
+                    "pingpong" => Ok((Playback::PingPong, p)),
+                    mode => Err((
+                        memoir::result::Error::new(format!("unknown playback mode: {}", mode)),
+                        input,
+                    )),
+                }
+            },
+            "<playback>",
+        )
+    }
+}
+
 impl Parse for Mode {
     fn parser() -> Parser<Self> {
         Parser::new(
@@ -219,6 +284,27 @@ pub fn paths() -> Parser<Vec<String>> {
     any::<_, Vec<String>>(path().skip(optional(whitespace()))).label("<path>..")
 }
 
+pub fn snap() -> Parser<Snap> {
+    string("iso")
+        .value(Snap::Iso)
+        .or(string("vp").value(Snap::Vanishing))
+        .or(natural::<u32>().map(Snap::Degrees))
+        .label("<degrees>|iso|vp")
+}
+
+/// An optional `--frame` or `--view` flag, defaulting to the whole view.
+pub fn scope() -> Parser<Scope> {
+    optional(
+        whitespace().then(
+            string("--frame")
+                .value(Scope::Frame)
+                .or(string("--view").value(Scope::View))
+                .label("--frame/--view"),
+        ),
+    )
+    .map(|s| s.map_or(Scope::View, |(_, s)| s))
+}
+
 pub fn setting() -> Parser<String> {
     identifier().label("<setting>")
 }
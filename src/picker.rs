@@ -0,0 +1,225 @@
+//! An HSV color picker, for choosing colors that aren't in the palette.
+//!
+//! The picker is made of a saturation/value square, with saturation growing
+//! to the right and value growing upwards, and a hue slider next to it.
+use crate::gfx::{Point2, Rect, Rgba8};
+
+/// Color picker state.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Picker {
+    /// Hue, in degrees, in the range `0..360`.
+    pub hue: f32,
+    /// Saturation, in the range `0..=1`.
+    pub saturation: f32,
+    /// Value, in the range `0..=1`.
+    pub value: f32,
+}
+
+/// Part of the picker under a point.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Area {
+    /// The saturation/value square, at the given saturation and value.
+    Square(f32, f32),
+    /// The hue slider, at the given hue.
+    Slider(f32),
+}
+
+impl Picker {
+    /// Number of saturation and value steps, ie. cells on each side of the square.
+    pub const STEPS: usize = 16;
+    /// Number of hue steps, ie. cells of the hue slider.
+    pub const HUE_STEPS: usize = 32;
+    /// Size of the square cells, in pixels.
+    pub const CELL_SIZE: f32 = 12.;
+    /// Width of the hue slider, in pixels.
+    pub const SLIDER_WIDTH: f32 = 16.;
+    /// Space between the square, the slider and the color swatch, in pixels.
+    pub const GAP: f32 = 8.;
+
+    /// A picker starting from the given color.
+    pub fn new(color: Rgba8) -> Self {
+        let (hue, saturation, value) = self::rgb_to_hsv(color);
+
+        Self {
+            hue,
+            saturation,
+            value,
+        }
+    }
+
+    /// The picked color.
+    pub fn color(&self) -> Rgba8 {
+        self::hsv_to_rgb(self.hue, self.saturation, self.value)
+    }
+
+    /// Move along the square, by the given number of saturation and value steps.
+    pub fn step(&mut self, ds: i32, dv: i32) {
+        let step = 1. / (Self::STEPS - 1) as f32;
+
+        self.saturation = (self.saturation + ds as f32 * step).clamp(0., 1.);
+        self.value = (self.value + dv as f32 * step).clamp(0., 1.);
+    }
+
+    /// Move along the hue slider, by the given number of steps. Hue wraps around.
+    pub fn step_hue(&mut self, dh: i32) {
+        let step = 360. / Self::HUE_STEPS as f32;
+
+        self.hue = (self.hue + dh as f32 * step).rem_euclid(360.);
+    }
+
+    /// Pick the color at the given area.
+    pub fn pick(&mut self, area: Area) {
+        match area {
+            Area::Square(s, v) => {
+                self.saturation = s;
+                self.value = v;
+            }
+            Area::Slider(h) => {
+                self.hue = h;
+            }
+        }
+    }
+
+    /// Area of the whole picker, centered in a workspace of the given size.
+    pub fn rect(width: f32, height: f32) -> Rect<f32> {
+        let side = Self::STEPS as f32 * Self::CELL_SIZE;
+        let (w, h) = (
+            side + Self::GAP + Self::SLIDER_WIDTH,
+            side + Self::GAP + Self::CELL_SIZE,
+        );
+        let (x, y) = (
+            (width / 2. - w / 2.).floor(),
+            (height / 2. - h / 2.).floor(),
+        );
+
+        Rect::new(x, y, x + w, y + h)
+    }
+
+    /// Saturation/value square, given the picker area.
+    pub fn square(r: Rect<f32>) -> Rect<f32> {
+        let side = Self::STEPS as f32 * Self::CELL_SIZE;
+        Rect::new(r.x1, r.y2 - side, r.x1 + side, r.y2)
+    }
+
+    /// Hue slider, given the picker area.
+    pub fn slider(r: Rect<f32>) -> Rect<f32> {
+        let s = Self::square(r);
+        Rect::new(r.x2 - Self::SLIDER_WIDTH, s.y1, r.x2, s.y2)
+    }
+
+    /// Swatch showing the picked color, given the picker area.
+    pub fn swatch(r: Rect<f32>) -> Rect<f32> {
+        Rect::new(r.x1, r.y1, r.x2, r.y1 + Self::CELL_SIZE)
+    }
+
+    /// The picker area under the given point, if any.
+    pub fn area(r: Rect<f32>, p: Point2<f32>) -> Option<Area> {
+        let (square, slider) = (Self::square(r), Self::slider(r));
+
+        if square.contains(p) {
+            let max = (Self::STEPS - 1) as f32;
+            let i = ((p.x - square.x1) / Self::CELL_SIZE).floor().min(max);
+            let j = ((p.y - square.y1) / Self::CELL_SIZE).floor().min(max);
+
+            Some(Area::Square(i / max, j / max))
+        } else if slider.contains(p) {
+            let n = Self::HUE_STEPS as f32;
+            let k = ((p.y - slider.y1) / slider.height() * n)
+                .floor()
+                .min(n - 1.);
+
+            Some(Area::Slider(k * 360. / n))
+        } else {
+            None
+        }
+    }
+}
+
+/// Convert an RGB color to hue, saturation and value.
+pub fn rgb_to_hsv(c: Rgba8) -> (f32, f32, f32) {
+    let (r, g, b) = (c.r as f32 / 255., c.g as f32 / 255., c.b as f32 / 255.);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0. {
+        0.
+    } else if max == r {
+        60. * ((g - b) / delta).rem_euclid(6.)
+    } else if max == g {
+        60. * ((b - r) / delta + 2.)
+    } else {
+        60. * ((r - g) / delta + 4.)
+    };
+    let saturation = if max == 0. { 0. } else { delta / max };
+
+    (hue, saturation, max)
+}
+
+/// Convert a hue, saturation and value to an opaque RGB color.
+pub fn hsv_to_rgb(h: f32, s: f32, v: f32) -> Rgba8 {
+    let c = v * s;
+    let h = h.rem_euclid(360.) / 60.;
+    let x = c * (1. - (h.rem_euclid(2.) - 1.).abs());
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    let m = v - c;
+    let u8 = |n: f32| ((n + m) * 255.).round() as u8;
+
+    Rgba8::new(u8(r), u8(g), u8(b), 0xff)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hsv() {
+        for c in &[
+            Rgba8::RED,
+            Rgba8::WHITE,
+            Rgba8::BLACK,
+            Rgba8::new(0x29, 0x36, 0x6f, 0xff),
+            Rgba8::new(0xbb, 0xff, 0xee, 0xff),
+        ] {
+            assert_eq!(Picker::new(*c).color(), *c);
+        }
+        assert_eq!(hsv_to_rgb(120., 1., 1.), Rgba8::new(0, 0xff, 0, 0xff));
+    }
+
+    #[test]
+    fn test_picker() {
+        let mut p = Picker::new(Rgba8::RED);
+
+        p.step(-(Picker::STEPS as i32), 0);
+        assert_eq!(p.color(), Rgba8::WHITE);
+        p.step(0, -1);
+        p.step_hue(-1);
+        assert_eq!(p.hue, 360. - 360. / Picker::HUE_STEPS as f32);
+
+        let r = Picker::rect(1280., 720.);
+        let square = Picker::square(r);
+        let slider = Picker::slider(r);
+
+        assert_eq!(
+            Picker::area(r, Point2::new(square.x2 - 1., square.y1)),
+            Some(Area::Square(1., 0.))
+        );
+        assert_eq!(
+            Picker::area(r, Point2::new(slider.x1, slider.y1)),
+            Some(Area::Slider(0.))
+        );
+        assert_eq!(Picker::area(r, Point2::new(r.x1, r.y1)), None);
+
+        p.pick(Area::Square(1., 1.));
+        p.pick(Area::Slider(0.));
+        assert_eq!(p.color(), Rgba8::RED);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::gfx::rect::Rect;
+
 /// A view into a pixel buffer.
 pub struct Pixels<'a, T> {
     width: usize,
@@ -75,3 +77,480 @@ pub fn scale<T: Default + Clone + Copy>(
     }
     output_buf
 }
+
+/// Width and height of a sheet of `cols` by `rows` frames, with `padding` around
+/// the sheet and `spacing` between frames. Returns `None` if the sheet is too big
+/// for its pixels to be addressed.
+pub fn sheet_size(
+    fw: u32,
+    fh: u32,
+    (cols, rows): (u32, u32),
+    padding: u32,
+    spacing: u32,
+) -> Option<(u32, u32)> {
+    let side = |n: u32, size: u32| {
+        padding
+            .checked_mul(2)?
+            .checked_add(n.checked_mul(size)?)?
+            .checked_add(n.saturating_sub(1).checked_mul(spacing)?)
+    };
+    let (width, height) = (side(cols, fw)?, side(rows, fh)?);
+
+    (width as usize).checked_mul(height as usize)?;
+
+    Some((width, height))
+}
+
+/// Arrange a horizontal strip of frames into a sheet of `cols` by `rows` frames,
+/// with `padding` around the sheet and `spacing` between frames. Returns the sheet
+/// pixels along with its width and height.
+pub fn arrange<T: Default + Clone + Copy>(
+    image: &[T],
+    fw: u32,
+    fh: u32,
+    nframes: usize,
+    (cols, rows): (u32, u32),
+    padding: u32,
+    spacing: u32,
+) -> (Vec<T>, u32, u32) {
+    assert_eq!(image.len(), (fw * fh) as usize * nframes);
+    assert!(cols as u64 * rows as u64 >= nframes as u64);
+
+    let input = Pixels::new(image, fw as usize * nframes, fh as usize);
+
+    let (width, height) =
+        sheet_size(fw, fh, (cols, rows), padding, spacing).expect("the sheet isn't too big");
+
+    let mut output = vec![T::default(); width as usize * height as usize];
+
+    for n in 0..nframes as u32 {
+        let (col, row) = (n % cols, n / cols);
+        let ox = padding + col * (fw + spacing);
+        let oy = padding + row * (fh + spacing);
+
+        for y in 0..fh {
+            for x in 0..fw {
+                let i = (oy + y) as usize * width as usize + (ox + x) as usize;
+                output[i] = *input.get((n * fw + x) as usize, y as usize).unwrap();
+            }
+        }
+    }
+    (output, width, height)
+}
+
+/// Scale an image by two with the Scale2x algorithm, which smooths diagonal edges
+/// without adding colors. Pixels past the edges of the image are taken to be the
+/// nearest edge pixel.
+pub fn scale2x<T: PartialEq + Copy>(image: &[T], width: u32, height: u32) -> Vec<T> {
+    assert_eq!(image.len(), (width * height) as usize);
+
+    let (width, height) = (width as usize, height as usize);
+    let at = |x: usize, y: usize, dx: isize, dy: isize| {
+        let x = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+        let y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+        image[y * width + x]
+    };
+    let mut output = Vec::with_capacity(image.len() * 4);
+
+    for y in 0..height {
+        let mut rows = [Vec::with_capacity(width * 2), Vec::with_capacity(width * 2)];
+
+        for x in 0..width {
+            let e = at(x, y, 0, 0);
+            let (b, d, f, h) = (
+                at(x, y, 0, -1),
+                at(x, y, -1, 0),
+                at(x, y, 1, 0),
+                at(x, y, 0, 1),
+            );
+            let pick = |cond: bool, c: T| if cond { c } else { e };
+
+            if b != h && d != f {
+                rows[0].extend([pick(d == b, d), pick(b == f, f)]);
+                rows[1].extend([pick(d == h, d), pick(h == f, f)]);
+            } else {
+                rows[0].extend([e, e]);
+                rows[1].extend([e, e]);
+            }
+        }
+        output.extend(rows.concat());
+    }
+    output
+}
+
+/// Scale an image by three with the Scale3x algorithm. See `scale2x`.
+pub fn scale3x<T: PartialEq + Copy>(image: &[T], width: u32, height: u32) -> Vec<T> {
+    assert_eq!(image.len(), (width * height) as usize);
+
+    let (width, height) = (width as usize, height as usize);
+    let at = |x: usize, y: usize, dx: isize, dy: isize| {
+        let x = (x as isize + dx).clamp(0, width as isize - 1) as usize;
+        let y = (y as isize + dy).clamp(0, height as isize - 1) as usize;
+        image[y * width + x]
+    };
+    let mut output = Vec::with_capacity(image.len() * 9);
+
+    for y in 0..height {
+        let mut rows = [
+            Vec::with_capacity(width * 3),
+            Vec::with_capacity(width * 3),
+            Vec::with_capacity(width * 3),
+        ];
+
+        for x in 0..width {
+            let (a, b, c) = (at(x, y, -1, -1), at(x, y, 0, -1), at(x, y, 1, -1));
+            let (d, e, f) = (at(x, y, -1, 0), at(x, y, 0, 0), at(x, y, 1, 0));
+            let (g, h, i) = (at(x, y, -1, 1), at(x, y, 0, 1), at(x, y, 1, 1));
+            let pick = |cond: bool, c: T| if cond { c } else { e };
+
+            if b != h && d != f {
+                rows[0].extend([
+                    pick(d == b, d),
+                    pick((d == b && e != c) || (b == f && e != a), b),
+                    pick(b == f, f),
+                ]);
+                rows[1].extend([
+                    pick((d == b && e != g) || (d == h && e != a), d),
+                    e,
+                    pick((b == f && e != i) || (h == f && e != c), f),
+                ]);
+                rows[2].extend([
+                    pick(d == h, d),
+                    pick((d == h && e != i) || (h == f && e != g), h),
+                    pick(h == f, f),
+                ]);
+            } else {
+                for row in rows.iter_mut() {
+                    row.extend([e, e, e]);
+                }
+            }
+        }
+        output.extend(rows.concat());
+    }
+    output
+}
+
+/// Extend an image to the given size, with default pixels added to the right
+/// and bottom. The size must be at least the image size.
+pub fn pad<T: Default + Clone + Copy>(
+    image: &[T],
+    width: u32,
+    height: u32,
+    (w, h): (u32, u32),
+) -> Vec<T> {
+    assert_eq!(image.len(), (width * height) as usize);
+    assert!(w >= width && h >= height);
+
+    let mut output = vec![T::default(); (w * h) as usize];
+
+    for (y, row) in image.chunks(width as usize).enumerate() {
+        let i = y * w as usize;
+        output[i..i + width as usize].copy_from_slice(row);
+    }
+    output
+}
+
+/// Shift an image by `dx` pixels to the right and `dy` rows down. With `wrap`,
+/// pixels shifted out come back in on the opposite edge, otherwise default
+/// pixels are shifted in.
+pub fn shift<T: Default + Clone + Copy>(
+    image: &[T],
+    width: u32,
+    height: u32,
+    (dx, dy): (i32, i32),
+    wrap: bool,
+) -> Vec<T> {
+    assert_eq!(image.len(), (width * height) as usize);
+
+    let (w, h) = (width as i32, height as i32);
+    let mut output = vec![T::default(); image.len()];
+
+    for (i, pixel) in image.iter().enumerate() {
+        let (x, y) = (i as i32 % w + dx, i as i32 / w + dy);
+        let (x, y) = if wrap {
+            (x.rem_euclid(w), y.rem_euclid(h))
+        } else if x >= 0 && x < w && y >= 0 && y < h {
+            (x, y)
+        } else {
+            continue;
+        };
+        output[(y * w + x) as usize] = *pixel;
+    }
+    output
+}
+
+/// Flip an image, reversing its columns if `x` is set, and its rows if `y` is set.
+pub fn flip<T: Clone + Copy>(image: &[T], width: u32, height: u32, (x, y): (bool, bool)) -> Vec<T> {
+    assert_eq!(image.len(), (width * height) as usize);
+
+    let mut output = image.to_vec();
+
+    if x {
+        output
+            .chunks_exact_mut(width as usize)
+            .for_each(|row| row.reverse());
+    }
+    if y {
+        output = output
+            .chunks_exact(width as usize)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+    }
+    output
+}
+
+/// Rotate an image clockwise by the given number of quarter turns. Returns the
+/// rotated image along with its width and height, which are swapped after an
+/// odd number of turns.
+pub fn rotate<T: Default + Clone + Copy>(
+    image: &[T],
+    width: u32,
+    height: u32,
+    turns: u32,
+) -> (Vec<T>, u32, u32) {
+    assert_eq!(image.len(), (width * height) as usize);
+
+    match turns % 4 {
+        0 => (image.to_vec(), width, height),
+        2 => (flip(image, width, height, (true, true)), width, height),
+        turns => {
+            let input = Pixels::new(image, width as usize, height as usize);
+            let (w, h) = (height as usize, width as usize);
+            let mut output = vec![T::default(); w * h];
+
+            for (i, pixel) in output.iter_mut().enumerate() {
+                let (x, y) = (i % w, i / w);
+                let (sx, sy) = if turns == 1 {
+                    (y, height as usize - 1 - x)
+                } else {
+                    (width as usize - 1 - y, x)
+                };
+                *pixel = *input.get(sx, sy).unwrap();
+            }
+            (output, w as u32, h as u32)
+        }
+    }
+}
+
+/// Crop an image to the given area, in image coordinates. The area must be
+/// within the image.
+pub fn crop<T: Clone + Copy>(image: &[T], width: u32, height: u32, area: Rect<u32>) -> Vec<T> {
+    assert_eq!(image.len(), (width * height) as usize);
+    assert!(area.x2 <= width && area.y2 <= height);
+
+    image
+        .chunks_exact(width as usize)
+        .skip(area.y1 as usize)
+        .take(area.height() as usize)
+        .flat_map(|row| &row[area.x1 as usize..area.x2 as usize])
+        .copied()
+        .collect()
+}
+
+/// Bounds of the pixels for which `f` returns `true`, in image coordinates.
+/// Returns `None` if there are no such pixels.
+pub fn bounds<T>(image: &[T], width: u32, f: impl Fn(&T) -> bool) -> Option<Rect<u32>> {
+    let mut bounds: Option<Rect<u32>> = None;
+
+    for (i, _) in image.iter().enumerate().filter(|(_, p)| f(p)) {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+
+        bounds = Some(match bounds {
+            Some(r) => Rect::new(r.x1.min(x), r.y1.min(y), r.x2.max(x + 1), r.y2.max(y + 1)),
+            None => Rect::new(x, y, x + 1, y + 1),
+        });
+    }
+    bounds
+}
+
+/// Bounds of the pixels that differ between two images of the same size, in
+/// image coordinates, ie. with the first row at `y = 0`. Returns `None` if the
+/// images are identical.
+pub fn changed<T: PartialEq>(a: &[T], b: &[T], width: u32) -> Option<Rect<u32>> {
+    assert_eq!(a.len(), b.len());
+
+    let mut bounds: Option<Rect<u32>> = None;
+
+    for (i, _) in a
+        .iter()
+        .zip(b.iter())
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+    {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+
+        bounds = Some(match bounds {
+            Some(r) => Rect::new(r.x1.min(x), r.y1.min(y), r.x2.max(x + 1), r.y2.max(y + 1)),
+            None => Rect::new(x, y, x + 1, y + 1),
+        });
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_arrange() {
+        // Two 1x1 frames, laid out vertically with padding and spacing.
+        let (sheet, w, h) = arrange(&[1, 2], 1, 1, 2, (1, 2), 1, 1);
+
+        assert_eq!((w, h), (3, 5));
+        #[rustfmt::skip]
+        assert_eq!(sheet, vec![
+            0, 0, 0,
+            0, 1, 0,
+            0, 0, 0,
+            0, 2, 0,
+            0, 0, 0,
+        ]);
+
+        // No padding or spacing preserves the strip.
+        let (sheet, w, h) = arrange(&[1, 2, 3, 4], 2, 1, 2, (2, 1), 0, 0);
+
+        assert_eq!((w, h), (4, 1));
+        assert_eq!(sheet, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_sheet_size() {
+        assert_eq!(sheet_size(2, 1, (2, 3), 1, 1), Some((7, 7)));
+        assert_eq!(sheet_size(u32::MAX / 2, 1, (3, 1), 0, 0), None);
+        assert_eq!(sheet_size(1, 1, (1, 1), u32::MAX / 2 + 1, 0), None);
+    }
+
+    #[test]
+    fn test_scale2x_scale3x() {
+        // A diagonal line is smoothed, instead of becoming a staircase.
+        #[rustfmt::skip]
+        let image = [
+            0, 0, 0, 0,
+            0, 1, 0, 0,
+            0, 0, 1, 0,
+            0, 0, 0, 0,
+        ];
+        #[rustfmt::skip]
+        assert_eq!(scale2x(&image, 4, 4), vec![
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 1, 1, 0, 0, 0, 0,
+            0, 0, 1, 1, 1, 0, 0, 0,
+            0, 0, 0, 1, 1, 1, 0, 0,
+            0, 0, 0, 0, 1, 1, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+        let scaled = scale3x(&image, 4, 4);
+        let middle: Vec<_> = scaled
+            .chunks(12)
+            .skip(3)
+            .take(6)
+            .flatten()
+            .copied()
+            .collect();
+        #[rustfmt::skip]
+        assert_eq!(middle, vec![
+            0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 0, 0,
+        ]);
+
+        // Flat areas are scaled like with nearest-neighbor.
+        assert_eq!(scale2x(&[1, 2], 2, 1), scale(&[1, 2], 2, 1, 2));
+        assert_eq!(scale3x(&[1, 2], 2, 1), scale(&[1, 2], 2, 1, 3));
+    }
+
+    #[test]
+    fn test_pad() {
+        #[rustfmt::skip]
+        assert_eq!(pad(&[1, 2, 3, 4], 2, 2, (3, 3)), vec![
+            1, 2, 0,
+            3, 4, 0,
+            0, 0, 0,
+        ]);
+    }
+
+    #[test]
+    fn test_shift() {
+        let image = [1, 2, 3, 4, 5, 6];
+
+        #[rustfmt::skip]
+        assert_eq!(shift(&image, 3, 2, (1, 0), true), vec![
+            3, 1, 2,
+            6, 4, 5,
+        ]);
+        #[rustfmt::skip]
+        assert_eq!(shift(&image, 3, 2, (-1, 1), false), vec![
+            0, 0, 0,
+            2, 3, 0,
+        ]);
+        assert_eq!(shift(&image, 3, 2, (3, -2), true), image.to_vec());
+    }
+
+    #[test]
+    fn test_flip_rotate() {
+        #[rustfmt::skip]
+        let image = [
+            1, 2, 3,
+            4, 5, 6,
+        ];
+
+        assert_eq!(flip(&image, 3, 2, (true, false)), vec![3, 2, 1, 6, 5, 4]);
+        assert_eq!(flip(&image, 3, 2, (false, true)), vec![4, 5, 6, 1, 2, 3]);
+
+        #[rustfmt::skip]
+        assert_eq!(rotate(&image, 3, 2, 1), (vec![
+            4, 1,
+            5, 2,
+            6, 3,
+        ], 2, 3));
+        #[rustfmt::skip]
+        assert_eq!(rotate(&image, 3, 2, 3), (vec![
+            3, 6,
+            2, 5,
+            1, 4,
+        ], 2, 3));
+        assert_eq!(rotate(&image, 3, 2, 2), (vec![6, 5, 4, 3, 2, 1], 3, 2));
+        assert_eq!(rotate(&image, 3, 2, 4), (image.to_vec(), 3, 2));
+    }
+
+    #[test]
+    fn test_crop_bounds() {
+        #[rustfmt::skip]
+        let image = [
+            0, 0, 0, 0,
+            0, 1, 0, 0,
+            0, 0, 2, 0,
+        ];
+
+        assert_eq!(bounds(&image, 4, |p| *p != 0), Some(Rect::new(1, 1, 3, 3)));
+        assert_eq!(bounds(&image, 4, |p| *p > 2), None);
+        assert_eq!(crop(&image, 4, 3, Rect::new(1, 1, 3, 3)), vec![1, 0, 0, 2]);
+        assert_eq!(crop(&image, 4, 3, Rect::new(0, 0, 4, 3)), image.to_vec());
+    }
+
+    #[test]
+    fn test_changed() {
+        #[rustfmt::skip]
+        let a = [
+            0, 0, 0,
+            0, 0, 0,
+            0, 0, 0,
+        ];
+        #[rustfmt::skip]
+        let b = [
+            0, 0, 0,
+            0, 1, 0,
+            0, 0, 1,
+        ];
+
+        assert_eq!(changed(&a, &a, 3), None);
+        assert_eq!(changed(&a, &b, 3), Some(Rect::new(1, 1, 3, 3)));
+    }
+}
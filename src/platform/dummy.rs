@@ -69,6 +69,24 @@ impl Window {
     pub fn clipboard(&self) -> Option<String> {
         None
     }
+
+    pub fn set_title(&mut self, _title: &str) {}
+
+    pub fn request_attention(&mut self) {}
+
+    pub fn create_shared(&self, _title: &str, _w: u32, _h: u32) -> io::Result<(Window, Events)> {
+        unreachable!()
+    }
+
+    pub fn make_current(&mut self) {}
+
+    pub fn set_size(&mut self, _size: LogicalSize) {
+        unreachable!()
+    }
+
+    pub fn framebuffer_size(&self) -> (u32, u32) {
+        unreachable!()
+    }
 }
 
 pub fn init(
@@ -137,6 +137,49 @@ impl Window {
     pub fn clipboard(&self) -> Option<String> {
         self.handle.get_clipboard_string()
     }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.handle.set_title(title);
+    }
+
+    /// Flash the taskbar entry or bounce the dock icon, until the window is focused.
+    pub fn request_attention(&mut self) {
+        self.handle.request_attention();
+    }
+
+    /// Create a window that shares this window's graphics resources, eg. textures.
+    pub fn create_shared(&self, title: &str, w: u32, h: u32) -> io::Result<(Window, Events)> {
+        let (window, events) = self
+            .handle
+            .create_shared(w, h, title, glfw::WindowMode::Windowed)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "glfw: error creating window"))?;
+        let glfw = window.glfw.clone();
+
+        Ok((
+            Window {
+                handle: window,
+                context: self.context,
+            },
+            Events {
+                handle: events,
+                glfw,
+            },
+        ))
+    }
+
+    pub fn make_current(&mut self) {
+        self.handle.make_current();
+    }
+
+    pub fn set_size(&mut self, size: LogicalSize) {
+        self.handle.set_size(size.width as i32, size.height as i32);
+    }
+
+    /// Size of the window's framebuffer, in physical pixels.
+    pub fn framebuffer_size(&self) -> (u32, u32) {
+        let (w, h) = self.handle.get_framebuffer_size();
+        (w as u32, h as u32)
+    }
 }
 
 impl From<WindowHint> for glfw::WindowHint {
@@ -207,6 +250,7 @@ impl From<glfw::WindowEvent> for WindowEvent {
                 modifiers: modifiers.into(),
             }),
             Glfw::Focus(b) => WindowEvent::Focused(b),
+            Glfw::FileDrop(paths) => WindowEvent::FileDropped(paths),
             Glfw::ContentScale(x, y) => {
                 if (x - y).abs() > 0.1 {
                     warn!("glfw: content scale isn't uniform: {} x {}", x, y);
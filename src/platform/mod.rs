@@ -2,6 +2,7 @@
 use std::io;
 
 use std::fmt;
+use std::path::PathBuf;
 
 #[cfg(not(feature = "glfw"))]
 #[path = "dummy.rs"]
@@ -86,12 +87,19 @@ pub enum WindowEvent {
     /// The mouse wheel has been used.
     MouseWheel { delta: LogicalDelta },
 
+    /// A touchpad pinch gesture, as a change in magnification.
+    /// Positive values zoom in.
+    Pinch { delta: f64 },
+
     /// The OS or application has requested that the window be redrawn.
     RedrawRequested,
 
     /// There are no more inputs to process, the application can do work.
     Ready,
 
+    /// Files were dropped onto the window.
+    FileDropped(Vec<PathBuf>),
+
     /// The content scale factor of the window has changed.  For example,
     /// the window was moved to a higher DPI screen.
     ScaleFactorChanged(f64),
@@ -118,6 +126,7 @@ impl WindowEvent {
                 | Self::CursorEntered
                 | Self::CursorLeft
                 | Self::MouseInput { .. }
+                | Self::FileDropped(_)
                 | Self::ScaleFactorChanged(_)
         )
     }
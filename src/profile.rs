@@ -0,0 +1,325 @@
+//! Export profiles and raw tile formats, for fantasy consoles and retro hardware.
+use crate::gfx::Rgba8;
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// The PICO-8 palette.
+const PICO8: [u32; 16] = [
+    0x000000, 0x1d2b53, 0x7e2553, 0x008751, 0xab5236, 0x5f574f, 0xc2c3c7, 0xfff1e8, 0xff004d,
+    0xffa300, 0xffec27, 0x00e436, 0x29adff, 0x83769c, 0xff77a8, 0xffccaa,
+];
+
+/// The TIC-80 default palette, SWEETIE-16.
+const TIC80: [u32; 16] = [
+    0x1a1c2c, 0x5d275d, 0xb13e53, 0xef7d57, 0xffcd75, 0xa7f070, 0x38b764, 0x257179, 0x29366f,
+    0x3b5dc9, 0x41a6f6, 0x73eff7, 0xf4f4f4, 0x94b0c2, 0x566c86, 0x333c57,
+];
+
+/// The Game Boy shades, from lightest to darkest.
+const GAMEBOY: [u32; 4] = [0x9bbc0f, 0x8bac0f, 0x306230, 0x0f380f];
+
+/// Size of the tiles of raw tile formats, in pixels.
+const TILE_SIZE: u32 = 8;
+
+/// An export profile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Profile {
+    /// PICO-8, with its 16 color palette and 128x128 sprite sheet.
+    Pico8,
+    /// TIC-80, with the SWEETIE-16 palette and 128x128 sprite banks.
+    Tic80,
+    /// Game Boy, with 4 shades and 2 bits per pixel tile data.
+    GameBoy,
+}
+
+impl Profile {
+    /// The console palette. The first color is used for transparent pixels.
+    pub fn palette(self) -> Vec<Rgba8> {
+        let colors: &[u32] = match self {
+            Self::Pico8 => &PICO8,
+            Self::Tic80 => &TIC80,
+            Self::GameBoy => &GAMEBOY,
+        };
+        colors
+            .iter()
+            .map(|c| {
+                let [_, r, g, b] = c.to_be_bytes();
+                Rgba8::new(r, g, b, 0xff)
+            })
+            .collect()
+    }
+
+    /// Size of the console sprite sheet, if it has a fixed size.
+    pub fn sheet(self) -> Option<(u32, u32)> {
+        match self {
+            Self::Pico8 | Self::Tic80 => Some((128, 128)),
+            Self::GameBoy => None,
+        }
+    }
+
+    /// Palette index of the closest console color. Transparent pixels map to the first color.
+    pub fn index(self, palette: &[Rgba8], color: Rgba8) -> usize {
+        if color.a == 0 {
+            return 0;
+        }
+        let dist = |c: &Rgba8| {
+            [(c.r, color.r), (c.g, color.g), (c.b, color.b)]
+                .iter()
+                .map(|(a, b)| (*a as i32 - *b as i32).pow(2))
+                .sum::<i32>()
+        };
+        (0..palette.len())
+            .min_by_key(|i| dist(&palette[*i]))
+            .unwrap_or_default()
+    }
+
+    /// Replace each pixel with the closest console color. Transparent pixels are kept.
+    pub fn quantize(self, pixels: &[Rgba8]) -> Vec<Rgba8> {
+        let palette = self.palette();
+
+        pixels
+            .iter()
+            .map(|c| {
+                if c.a == 0 {
+                    *c
+                } else {
+                    palette[self.index(&palette, *c)]
+                }
+            })
+            .collect()
+    }
+
+    /// Palette index of the closest console color for each pixel.
+    pub fn indices(self, pixels: &[Rgba8]) -> Vec<u8> {
+        let palette = self.palette();
+
+        pixels
+            .iter()
+            .map(|c| self.index(&palette, *c) as u8)
+            .collect()
+    }
+}
+
+/// A raw tile data format, made of 8x8 tiles in reading order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TileFormat {
+    /// Game Boy, 2 bits per pixel, with the two bit planes interleaved by row.
+    GameBoy,
+    /// NES CHR, 2 bits per pixel, with the first bit plane followed by the second.
+    Nes,
+    /// SNES, 4 bits per pixel, with planes 0 and 1 interleaved by row,
+    /// followed by planes 2 and 3.
+    Snes,
+    /// Genesis, 4 bits per pixel, linear, with the left pixel in the high nibble.
+    Genesis,
+}
+
+impl TileFormat {
+    /// Number of bits per pixel.
+    pub fn bits(self) -> u32 {
+        match self {
+            Self::GameBoy | Self::Nes => 2,
+            Self::Snes | Self::Genesis => 4,
+        }
+    }
+
+    /// Export profile used for the colors when none is given, if any. Game Boy
+    /// tiles are always made of its four shades.
+    pub fn profile(self) -> Option<Profile> {
+        match self {
+            Self::GameBoy => Some(Profile::GameBoy),
+            Self::Nes | Self::Snes | Self::Genesis => None,
+        }
+    }
+
+    /// Tile format implied by a file extension, if any.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "2bpp" => Some(Self::GameBoy),
+            "chr" => Some(Self::Nes),
+            "4bpp" => Some(Self::Snes),
+            _ => None,
+        }
+    }
+
+    /// Encode a `w` by `h` image of palette indices as tile data.
+    pub fn encode(self, w: u32, h: u32, indices: &[u8]) -> io::Result<Vec<u8>> {
+        if !w.is_multiple_of(TILE_SIZE) || !h.is_multiple_of(TILE_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("image size must be a multiple of {}", TILE_SIZE),
+            ));
+        }
+        if let Some(i) = indices.iter().find(|i| **i >> self.bits() != 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "palette index {} doesn't fit in {} bits per pixel",
+                    i,
+                    self.bits()
+                ),
+            ));
+        }
+        let mut out = Vec::with_capacity((w * h * self.bits() / 8) as usize);
+
+        for ty in (0..h).step_by(TILE_SIZE as usize) {
+            for tx in (0..w).step_by(TILE_SIZE as usize) {
+                let tile = (ty..ty + TILE_SIZE)
+                    .map(|y| {
+                        let row = (y * w + tx) as usize;
+                        &indices[row..row + TILE_SIZE as usize]
+                    })
+                    .collect::<Vec<_>>();
+
+                self.encode_tile(&tile, &mut out);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Encode a single tile, given its rows.
+    fn encode_tile(self, tile: &[&[u8]], out: &mut Vec<u8>) {
+        // Bit plane `n` of a row, with the leftmost pixel in the high bit.
+        let plane = |row: &[u8], n: u32| row.iter().fold(0u8, |byte, i| byte << 1 | (i >> n & 1));
+
+        match self {
+            Self::GameBoy => {
+                for row in tile {
+                    out.extend_from_slice(&[plane(row, 0), plane(row, 1)]);
+                }
+            }
+            Self::Nes => {
+                out.extend(tile.iter().map(|row| plane(row, 0)));
+                out.extend(tile.iter().map(|row| plane(row, 1)));
+            }
+            Self::Snes => {
+                for planes in &[(0, 1), (2, 3)] {
+                    for row in tile {
+                        out.extend_from_slice(&[plane(row, planes.0), plane(row, planes.1)]);
+                    }
+                }
+            }
+            Self::Genesis => {
+                for row in tile {
+                    out.extend(row.chunks(2).map(|px| px[0] << 4 | px[1]));
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for TileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::GameBoy => write!(f, "gb"),
+            Self::Nes => write!(f, "nes"),
+            Self::Snes => write!(f, "snes"),
+            Self::Genesis => write!(f, "genesis"),
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pico8 => write!(f, "pico8"),
+            Self::Tic80 => write!(f, "tic80"),
+            Self::GameBoy => write!(f, "gb"),
+        }
+    }
+}
+
+impl FromStr for TileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gb" => Ok(Self::GameBoy),
+            "nes" => Ok(Self::Nes),
+            "snes" => Ok(Self::Snes),
+            "genesis" => Ok(Self::Genesis),
+            _ => Err(format!("unknown tile format `{}`", s)),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pico8" => Ok(Self::Pico8),
+            "tic80" => Ok(Self::Tic80),
+            "gb" => Ok(Self::GameBoy),
+            _ => Err(format!("unknown export profile `{}`", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quantize() {
+        let p = Profile::Pico8;
+        let pixels = [
+            Rgba8::new(0xfe, 0x01, 0x50, 0xff),
+            Rgba8::TRANSPARENT,
+            Rgba8::WHITE,
+        ];
+
+        assert_eq!(p.palette().len(), 16);
+        assert_eq!(
+            p.quantize(&pixels),
+            vec![
+                Rgba8::new(0xff, 0x00, 0x4d, 0xff),
+                Rgba8::TRANSPARENT,
+                Rgba8::new(0xff, 0xf1, 0xe8, 0xff),
+            ]
+        );
+        assert_eq!("gb".parse::<Profile>(), Ok(Profile::GameBoy));
+        assert!("nes".parse::<Profile>().is_err());
+    }
+
+    #[test]
+    fn test_tiles() {
+        let mut indices = vec![0u8; 8 * 8];
+
+        // Leftmost pixels of the first row, and rightmost pixel of the last row.
+        indices[0] = 3;
+        indices[1] = 1;
+        indices[63] = 2;
+
+        let gb = TileFormat::GameBoy.encode(8, 8, &indices).unwrap();
+        assert_eq!(gb.len(), 16);
+        assert_eq!(&gb[..2], &[0b1100_0000, 0b1000_0000]);
+        assert_eq!(&gb[14..], &[0b0000_0000, 0b0000_0001]);
+
+        let nes = TileFormat::Nes.encode(8, 8, &indices).unwrap();
+        assert_eq!(nes.len(), 16);
+        assert_eq!((nes[0], nes[8]), (0b1100_0000, 0b1000_0000));
+        assert_eq!((nes[7], nes[15]), (0b0000_0000, 0b0000_0001));
+
+        indices[2] = 0xf;
+
+        let snes = TileFormat::Snes.encode(8, 8, &indices).unwrap();
+        assert_eq!(snes.len(), 32);
+        assert_eq!(&snes[..2], &[0b1110_0000, 0b1010_0000]);
+        assert_eq!(&snes[16..18], &[0b0010_0000, 0b0010_0000]);
+
+        let genesis = TileFormat::Genesis.encode(8, 8, &indices).unwrap();
+        assert_eq!(genesis.len(), 32);
+        assert_eq!(&genesis[..2], &[0x31, 0xf0]);
+        assert_eq!(genesis[31], 0x02);
+
+        assert!(TileFormat::Nes.encode(8, 8, &indices).is_err());
+        assert!(TileFormat::Snes.encode(8, 4, &indices[..32]).is_err());
+        assert_eq!(
+            Profile::GameBoy.indices(&Profile::GameBoy.palette()),
+            vec![0, 1, 2, 3]
+        );
+    }
+}
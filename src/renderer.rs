@@ -5,6 +5,10 @@ use crate::session::{self, Effect, Session};
 
 use std::time;
 
+/// The renderer backend. Alternative backends implement [`Renderer`] and are
+/// selected here, behind their own feature flag.
+pub type Backend = crate::gl::Renderer;
+
 pub trait Renderer<'a>: std::marker::Sized {
     type Error;
 
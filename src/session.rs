@@ -1,23 +1,33 @@
 #![allow(clippy::needless_collect)]
 ///! Session
 use crate::autocomplete::FileCompleter;
+use crate::backup;
 use crate::brush::*;
-use crate::cmd::{self, Command, CommandLine, KeyMapping, Op, Value};
+use crate::cmd::{self, Axis, Command, CommandLine, KeyMapping, Op, Scope, Value};
 use crate::color;
 use crate::data;
 use crate::event::{Event, TimedEvent};
 use crate::execution::{DigestMode, DigestState, Execution};
 use crate::flood::FloodFiller;
 use crate::hashmap;
+use crate::image;
+use crate::jobs::{self, Jobs};
+use crate::net;
 use crate::palette::*;
+use crate::picker::Picker;
+use crate::pixels;
 use crate::platform::{self, InputState, Key, KeyboardInput, LogicalSize, ModifiersState};
+use crate::profile::Profile;
+use crate::statusline;
+use crate::theme::{self, Theme};
+use crate::timelapse::Timelapse;
 use crate::util;
 use crate::view::layer::{LayerCoords, LayerId};
 use crate::view::path;
-use crate::view::resource::ViewResource;
+use crate::view::resource::{self, EditId, ViewResource};
 use crate::view::{
-    self, FileStatus, FileStorage, View, ViewCoords, ViewExtent, ViewId, ViewManager, ViewOp,
-    ViewState,
+    self, ExportFormat, ExportOptions, FileStatus, FileStorage, Guide, SheetLayout, View,
+    ViewCoords, ViewExtent, ViewId, ViewManager, ViewOp, ViewState,
 };
 
 use crate::gfx::math::*;
@@ -33,7 +43,7 @@ use nonempty::NonEmpty;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io;
 use std::io::Write;
 use std::ops::{Add, Deref, Sub};
@@ -46,20 +56,75 @@ SETTINGS
 
 debug             on/off             Debug mode
 checker           on/off             Alpha checker toggle
+checker/size      0..64              Checker square size, or `0` for the default
+checker/dark      #000000..#ffffff   Checker dark square color
+checker/light     #000000..#ffffff   Checker light square color
 scale             1.0..4.0           UI scale
+ui/scale          1..4               Scale of the status line, palette & text, independent of views
+pixel-ratio       <x>:<y>            Displayed pixel aspect ratio, eg. 2:1
+p/recent          0..64              Number of recently painted colors shown, or `0` to hide them
+palette/lock      on/off             Snap colors to the nearest palette color
 animation         on/off             View animation toggle
 animation/delay   1..1000            View animation delay (ms)
+animation/strokes on/off             Show brush strokes on the animation while they're drawn
 background        #000000..#ffffff   Set background appearance to <color>
+backdrop          #000000..#ffffff   Solid color behind views, mixed over the checker by alpha
+matte             on/off             Preview views over the matte color, instead of the checker
+matte/color       #000000..#ffffff   Matte color, eg. the in-game background
 grid              on/off             Grid display
 grid/color        #000000..#ffffff   Grid color
 grid/spacing      <x> <y>            Grid spacing
+grid/major/color  #000000..#ffffff   Grid major line color
+grid/major/spacing <x> <y>           Grid major line spacing (0 to disable)
+grid/iso          on/off             Draw the grid as 2:1 isometric diamonds, <x> wide and <y> high
+guide/color       #000000..#ffffff   Guide color
+snap              on/off             Snap selections to guides & grid
+tileset           0..256             Tileset mode tile size, or `0` to disable
+tileset/color     #000000..#ffffff   Tile boundary color
+ui/minimap        on/off             Minimap display
+ui/rulers         on/off             Rulers to drag guides from
+readonly          on/off             Disable editing, for viewing images
+display/shader    <path>             Fragment shader applied to the display, or "" to disable
+ui/statusline     <format>           Status line format, eg. "%file %pixel%=%zoom", or "" for the default
+window/title      <format>           Window title format, eg. "%name%modified", or "" for the default
+export/scale      0..64              Export scale, or `0` to use the view zoom
+brush/grid        0..256             Snap brush positions to a grid of this size, or `0` to disable
+undo/scope        view/session       Undo the active view's edits, or the latest edits of any view
+on-export         <command>          Command run on each exported path, or "" to disable
+max-colors        0..256             Warn when a frame has more colors than this, or `0` to disable
+backup            on/off             Keep previous versions of files when saving, in `.rx-backups`
+backup/count      1..64              Number of previous versions kept per file
+init/reload       on/off             Source the init script again when it changes on disk
+input/touchpad    on/off             Scroll to pan, pinch or <ctrl>-scroll to zoom
+input/pan-sensitivity  0.1..10.0     Touchpad pan speed
+input/zoom-sensitivity 0.1..10.0     Touchpad zoom speed
+input/invert      on/off             Invert touchpad pan & zoom directions
+input/repeat-delay 0..2000           Delay before held keys repeat (ms), or `0` to use the OS key repeat
+input/repeat-rate 1..120             Number of key repeats per second, with `input/repeat-delay`
 "#;
 
+/// Background jobs that take at least this long request the user's attention
+/// when they finish.
+const LONG_JOB: time::Duration = time::Duration::from_secs(3);
+
 #[derive(Copy, Clone, Debug)]
 enum InternalCommand {
     StopRecording,
 }
 
+/// A held key whose command is repeated by the session instead of the OS, as set
+/// by `input/repeat-delay` and `input/repeat-rate`. This keeps repeats consistent
+/// across platforms.
+#[derive(Debug, Clone)]
+struct KeyRepeat {
+    /// The key held down.
+    key: platform::Key,
+    /// Command to repeat.
+    command: Command,
+    /// Time left until the command is run again.
+    wait: time::Duration,
+}
+
 /// Session coordinates.
 /// Encompasses anything within the window, such as the cursor position.
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -202,6 +267,222 @@ impl Deref for Selection {
     }
 }
 
+/// A nine-slice preview of the current frame of the active view. The frame
+/// corners are drawn as-is, and its edges and center are stretched to fill
+/// the target size.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct NineSlice {
+    /// Target width, in pixels.
+    pub width: u32,
+    /// Target height, in pixels.
+    pub height: u32,
+    /// Size of the border slices, in pixels. Defaults to a third of the frame.
+    pub margins: Option<u32>,
+}
+
+impl NineSlice {
+    /// The border slice size, for the given frame size.
+    pub fn margins(&self, fw: u32, fh: u32) -> u32 {
+        let max = fw.min(fh) / 2;
+        self.margins.unwrap_or(fw.min(fh) / 3).min(max)
+    }
+}
+
+impl fmt::Display for NineSlice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "9slice {}x{}", self.width, self.height)?;
+        if let Some(m) = self.margins {
+            write!(f, " --margins {}", m)?;
+        }
+        Ok(())
+    }
+}
+
+/// An image drawn behind the animation preview, tiled at the view scale.
+/// Solid colors are a single pixel.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Backdrop {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Rgba8>,
+}
+
+impl Backdrop {
+    /// A solid color backdrop.
+    pub fn color(color: Rgba8) -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            pixels: vec![color],
+        }
+    }
+}
+
+/// Palette cycling, as in the VGA era: the colors of a range of palette entries are
+/// rotated over time, and the animation preview of the view shows its pixels with
+/// the rotated colors. Pixels are matched to palette entries by color.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PaletteCycle {
+    /// The view being previewed.
+    pub view: ViewId,
+    /// First palette index of the range.
+    pub from: usize,
+    /// Last palette index of the range, inclusive.
+    pub to: usize,
+    /// Number of animation frames between steps.
+    pub frames: usize,
+    /// Number of steps the range has been rotated by.
+    pub step: usize,
+    /// Animation frames since the last step.
+    ticks: usize,
+}
+
+impl PaletteCycle {
+    /// The color to show in place of `color`, given the palette. Colors are
+    /// left as they are if the palette has shrunk since the range was chosen.
+    pub fn map(&self, palette: &[Rgba8], color: Rgba8) -> Rgba8 {
+        let n = self.to - self.from + 1;
+
+        if self.to >= palette.len() {
+            return color;
+        }
+        match palette.iter().position(|c| *c == color) {
+            Some(i) if i >= self.from && i <= self.to => {
+                palette[self.from + (i - self.from + n - self.step % n) % n]
+            }
+            _ => color,
+        }
+    }
+
+    /// Map each pixel to its color in the current step.
+    pub fn apply(&self, palette: &[Rgba8], pixels: &[Rgba8]) -> Vec<Rgba8> {
+        pixels.iter().map(|c| self.map(palette, *c)).collect()
+    }
+}
+
+/// A secondary workspace pane, displaying a single view with its own
+/// offset and zoom. The split pane always occupies the right side of
+/// the workspace.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Split {
+    /// The view displayed in the split pane.
+    pub view: ViewId,
+    /// The view offset, relative to the split pane origin.
+    pub offset: Vector2<f32>,
+    /// The view zoom, in the split pane.
+    pub zoom: f32,
+    /// The fraction of the workspace width occupied by the split pane.
+    pub ratio: f32,
+}
+
+impl Split {
+    /// Minimum split pane ratio.
+    pub const MIN_RATIO: f32 = 0.1;
+    /// Maximum split pane ratio.
+    pub const MAX_RATIO: f32 = 0.9;
+    /// Amount the split pane ratio is changed by when resizing.
+    pub const RATIO_STEP: f32 = 0.05;
+
+    /// Create a new split pane for the given view.
+    pub fn new(view: ViewId, zoom: f32) -> Self {
+        Self {
+            view,
+            offset: Vector2::zero(),
+            zoom,
+            ratio: 0.5,
+        }
+    }
+
+    /// Return the split pane area, given the workspace size.
+    pub fn rect(&self, width: f32, height: f32) -> Rect<f32> {
+        Rect::new((width * (1. - self.ratio)).floor(), 0., width, height)
+    }
+}
+
+/// A workspace position, recorded in the jump list.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Jump {
+    /// The active view.
+    pub view: ViewId,
+    /// The session offset.
+    pub offset: Vector2<f32>,
+    /// The active view zoom.
+    pub zoom: f32,
+}
+
+/// Positions jumped from, for navigating back and forth, like vim's jump list.
+#[derive(Default, Debug)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    /// Current position in the list, when navigating it. Otherwise, equal to
+    /// the list length.
+    index: usize,
+    /// Whether the last position was recorded when zooming.
+    zooming: bool,
+}
+
+impl JumpList {
+    /// Maximum number of positions kept.
+    pub const MAX_LEN: usize = 100;
+
+    /// Record a position, discarding positions ahead of the current one.
+    pub fn push(&mut self, jump: Jump) {
+        self.zooming = false;
+        self.jumps.truncate(self.index + 1);
+
+        if self.jumps.last() != Some(&jump) {
+            self.jumps.push(jump);
+        }
+        if self.jumps.len() > Self::MAX_LEN {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+
+    /// Record a position before zooming. Consecutive zooms record only the
+    /// position the first one started from.
+    pub fn push_zoom(&mut self, jump: Jump) {
+        if !self.zooming {
+            self.push(jump);
+            self.zooming = true;
+        }
+    }
+
+    /// Stop coalescing zooms, eg. because the workspace was panned.
+    pub fn moved(&mut self) {
+        self.zooming = false;
+    }
+
+    /// Go back to the previous position, given the current one.
+    pub fn back(&mut self, current: Jump) -> Option<Jump> {
+        self.zooming = false;
+
+        if self.index >= self.jumps.len() {
+            // Remember where we jumped back from, to be able to go forward again.
+            if self.jumps.last() != Some(&current) {
+                self.jumps.push(current);
+            }
+            self.index = self.jumps.len() - 1;
+        }
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        self.jumps.get(self.index).copied()
+    }
+
+    /// Go forward to the next position.
+    pub fn forward(&mut self) -> Option<Jump> {
+        self.zooming = false;
+
+        if self.index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.index += 1;
+        self.jumps.get(self.index).copied()
+    }
+}
+
 /// Session effects. Eg. view creation/destruction.
 /// Anything the renderer might want to know.
 #[derive(Clone, Debug)]
@@ -223,19 +504,43 @@ pub enum Effect {
     /// When a view requires re-drawing.
     ViewDamaged(ViewId, Option<ViewExtent>),
     /// When a view layer requires re-drawing.
-    ViewLayerDamaged(ViewId, LayerId),
+    ViewLayerDamaged(ViewId, LayerId, Option<Rect<u32>>),
     /// When the active view is non-permanently painted on.
     ViewPaintDraft(Vec<Shape>),
     /// When the active view is painted on.
     ViewPaintFinal(Vec<Shape>),
     /// The blend mode used for painting has changed.
     ViewBlendingChanged(Blending),
+    /// When the animation preview backdrop has changed.
+    PreviewBackdropChanged,
+    /// When palette cycling has started, stopped or stepped.
+    PaletteCycled,
 }
 
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Blending {
     Constant,
     Alpha,
+    /// Paint behind the existing pixels, ie. only where they're transparent.
+    Behind,
+    /// Paint over the existing pixels, keeping their alpha.
+    Replace,
+}
+
+impl Blending {
+    /// Blend a source color onto a destination pixel.
+    pub fn blend(self, src: Rgba8, dst: Rgba8) -> Rgba8 {
+        match self {
+            Self::Alpha => src.over(dst),
+            Self::Constant => src,
+            Self::Behind => dst.over(src),
+            Self::Replace if dst.a == 0 => dst,
+            Self::Replace => Rgba8 {
+                a: dst.a,
+                ..src.over(dst)
+            },
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -282,6 +587,16 @@ impl Default for Tool {
     }
 }
 
+/// Flood fill tool options.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub struct FloodOptions {
+    /// Replace the color everywhere, instead of only the connected region.
+    pub global: bool,
+    /// Maximum difference of any color channel with the clicked color, for
+    /// a pixel to be filled. Useful with anti-aliased images.
+    pub tolerance: u8,
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum PanState {
     Panning,
@@ -412,6 +727,9 @@ impl MessageType {
 /// A session error.
 type Error = String;
 
+/// An export, run on the worker thread. Returns the message to show once done.
+type ExportJob = Box<dyn FnOnce() -> io::Result<String> + Send>;
+
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum Input {
     Key(Key),
@@ -533,17 +851,65 @@ pub struct Settings {
 
 impl Settings {
     const DEPRECATED: &'static [&'static str] = &["frame_delay", "input/delay", "vsync"];
+    /// Inclusive bounds of numeric settings.
+    const BOUNDS: &'static [(&'static str, f64, f64)] = &[
+        ("checker/size", 0., 64.),
+        ("scale", 1., 4.),
+        ("ui/scale", 1., 4.),
+        ("p/recent", 0., 64.),
+        ("animation/delay", 1., 1000.),
+        ("tileset", 0., 256.),
+        ("export/scale", 0., 64.),
+        ("brush/grid", 0., 256.),
+        ("backup/count", 1., 64.),
+        ("max-colors", 0., 256.),
+        ("input/pan-sensitivity", 0.1, 10.),
+        ("input/zoom-sensitivity", 0.1, 10.),
+        ("input/repeat-delay", 0., 2000.),
+        ("input/repeat-rate", 1., 120.),
+    ];
 
     /// Lookup a setting.
     pub fn get(&self, setting: &str) -> Option<&Value> {
         self.map.get(setting)
     }
 
+    /// Iterate over the settings that aren't deprecated, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> + '_ {
+        let mut settings = self
+            .map
+            .iter()
+            .filter(|(k, _)| !Self::DEPRECATED.contains(&k.as_str()))
+            .map(|(k, v)| (k.as_str(), v))
+            .collect::<Vec<_>>();
+        settings.sort_by_key(|(k, _)| *k);
+        settings.into_iter()
+    }
+
     /// Set an existing setting to a new value. Returns `Err` if there is a type
-    /// mismatch or the setting isn't found. Otherwise, returns `Ok` with the
-    /// old value.
+    /// mismatch, the value is out of bounds or the setting isn't found. Otherwise,
+    /// returns `Ok` with the old value. Integers are accepted for float settings.
     pub fn set(&mut self, k: &str, v: Value) -> Result<Value, Error> {
         if let Some(current) = self.get(k) {
+            let v = match (current, v) {
+                (Value::F64(_), Value::U32(n)) => Value::F64(n as f64),
+                (_, v) => v,
+            };
+            let n = match v {
+                Value::U32(n) => Some(n as f64),
+                Value::F64(n) => Some(n),
+                _ => None,
+            };
+            if let (Some(n), Some((_, min, max))) =
+                (n, Self::BOUNDS.iter().find(|(name, _, _)| *name == k))
+            {
+                if n < *min || n > *max {
+                    return Err(format!(
+                        "invalid value `{}` for `{}`, expected a value between {} and {}",
+                        v, k, min, max
+                    ));
+                }
+            }
             if std::mem::discriminant(&v) == std::mem::discriminant(current) {
                 return Ok(self.map.insert(k.to_string(), v).unwrap());
             }
@@ -566,25 +932,64 @@ impl Default for Settings {
             map: hashmap! {
                 "debug" => Value::Bool(false),
                 "checker" => Value::Bool(false),
+                "checker/size" => Value::U32(0),
+                "checker/dark" => Value::Rgba8(color::DARK_GREY),
+                "checker/light" => Value::Rgba8(color::CHECKER_GREY),
                 "background" => Value::Rgba8(color::TRANSPARENT),
+                "backdrop" => Value::Rgba8(color::TRANSPARENT),
+                "matte" => Value::Bool(false),
+                "matte/color" => Value::Rgba8(color::BLACK),
                 "input/mouse" => Value::Bool(true),
+                "input/touchpad" => Value::Bool(false),
+                "input/pan-sensitivity" => Value::F64(1.0),
+                "input/zoom-sensitivity" => Value::F64(1.0),
+                "input/invert" => Value::Bool(false),
+                "input/repeat-delay" => Value::U32(0),
+                "input/repeat-rate" => Value::U32(30),
                 "scale" => Value::F64(1.0),
+                "pixel-ratio" => Value::U32Tuple(1, 1),
                 "animation" => Value::Bool(true),
                 "animation/delay" => Value::U32(160),
+                "animation/strokes" => Value::Bool(false),
+                "ui/scale" => Value::U32(1),
                 "ui/palette" => Value::Bool(true),
                 "ui/status" => Value::Bool(true),
                 "ui/cursor" => Value::Bool(true),
                 "ui/message" => Value::Bool(true),
                 "ui/switcher" => Value::Bool(true),
                 "ui/view-info" => Value::Bool(true),
+                "ui/minimap" => Value::Bool(false),
+                "ui/rulers" => Value::Bool(false),
+                "ui/statusline" => Value::Str(String::new()),
+                "window/title" => Value::Str(String::new()),
+                "display/shader" => Value::Str(String::new()),
 
                 "grid" => Value::Bool(false),
                 "grid/color" => Value::Rgba8(color::BLUE),
                 "grid/spacing" => Value::U32Tuple(8, 8),
+                "grid/major/color" => Value::Rgba8(color::WHITE),
+                "grid/major/spacing" => Value::U32Tuple(0, 0),
+                "grid/iso" => Value::Bool(false),
+                "guide/color" => Value::Rgba8(color::LIGHT_GREEN),
+                "snap" => Value::Bool(false),
+                "tileset" => Value::U32(0),
+                "tileset/color" => Value::Rgba8(color::RED),
+
+                "export/scale" => Value::U32(0),
+                "brush/grid" => Value::U32(0),
+                "undo/scope" => Value::Ident(String::from("view")),
+                "on-export" => Value::Str(String::new()),
+                "max-colors" => Value::U32(0),
+                "backup" => Value::Bool(false),
+                "backup/count" => Value::U32(3),
+                "init/reload" => Value::Bool(true),
 
                 "p/height" => Value::U32(Session::PALETTE_HEIGHT),
+                "p/recent" => Value::U32(Session::PALETTE_RECENT),
+                "palette/lock" => Value::Bool(false),
 
                 "debug/crosshair" => Value::Bool(false),
+                "readonly" => Value::Bool(false),
 
                 // Deprecated.
                 "frame_delay" => Value::F64(0.0),
@@ -655,11 +1060,52 @@ pub struct Session {
     ignore_received_characters: bool,
     /// The set of keys currently pressed.
     keys_pressed: HashSet<platform::Key>,
+    /// Key held down whose command is being repeated, if any.
+    key_repeat: Option<KeyRepeat>,
     /// The list of all active key bindings.
     pub key_bindings: KeyBindings,
 
     /// Current pixel selection.
     pub selection: Option<Selection>,
+    /// The split pane, if the workspace is split.
+    pub split: Option<Split>,
+    /// Pairs of linked views. Brush strokes on one view of a pair are also
+    /// applied to the other.
+    pub links: Vec<(ViewId, ViewId)>,
+    /// Positions jumped from, across views.
+    pub jump_list: JumpList,
+    /// Nine-slice preview of the active view, if shown.
+    pub preview: Option<NineSlice>,
+    /// Zoom of the window mirroring the active view, if open.
+    pub preview_window: Option<u32>,
+    /// Whether the window should request the user's attention, eg. because
+    /// a long export finished.
+    pub attention: bool,
+    /// Backdrop of the animation preview, if any.
+    pub preview_backdrop: Option<Backdrop>,
+    /// Palette cycling preview, if any.
+    pub palette_cycle: Option<PaletteCycle>,
+    /// Color picker, if open.
+    pub picker: Option<Picker>,
+    /// Orientation of the guide being dragged from a ruler, if any.
+    pub guide_drag: Option<Axis>,
+    /// Color to highlight the pixels of in the active view. Set with `:p/highlight`.
+    pub highlight: Option<Rgba8>,
+    /// Flood fill tool options.
+    pub flood: FloodOptions,
+    /// Timelapse being recorded, if any.
+    pub timelapse: Option<Timelapse>,
+    /// Color count of each frame of a view, as of the given edit.
+    /// Kept up to date while `max-colors` is set.
+    frame_colors: Option<(ViewId, EditId, Vec<usize>)>,
+    /// Pixels matching the search color of each view, as of the given edit and layer.
+    search_matches: HashMap<ViewId, (EditId, LayerId, Rgba8, Vec<LayerCoords<u32>>)>,
+    /// Pixels of the active view matching the highlighted color, as of the given edit and layer.
+    highlight_matches: Option<(ViewId, EditId, LayerId, Rgba8, Vec<LayerCoords<u32>>)>,
+    /// Exports running in the background.
+    pub jobs: Jobs,
+    /// UI colors.
+    pub theme: Theme,
 
     /// The session's current settings.
     pub settings: Settings,
@@ -671,7 +1117,7 @@ pub struct Session {
     /// Effects produced by the session. Cleared at the beginning of every
     /// update.
     pub effects: Vec<Effect>,
-    /// Animation frame time accumulator.
+    /// Palette cycle time accumulator.
     pub accumulator: time::Duration,
 
     /// The current state of the command line.
@@ -691,6 +1137,27 @@ pub struct Session {
 
     /// Input state of the mouse.
     mouse_state: InputState,
+    /// Unsnapped zoom of a view, accumulated from fractional wheel steps.
+    smooth_zoom: Option<(ViewId, f32)>,
+    /// Frame size of the paste buffer, if it holds a frame copied with `f/copy`.
+    copied_frame: Option<(u32, u32)>,
+    /// Copy of the paste buffer, since the buffer itself is kept by the renderer.
+    clipboard: Option<(u32, u32, Vec<Rgba8>)>,
+    /// View whose own palette is shown, if any.
+    palette_view: Option<ViewId>,
+    /// The session palette, while a view palette is shown.
+    session_palette: Swatches,
+    /// The init script and its modification time, if it's watched for changes.
+    init_script: Option<(PathBuf, time::SystemTime)>,
+    /// When the init script was last checked for changes.
+    init_checked: time::Instant,
+    /// Link to the other peers, when the session is shared over the network.
+    net: Option<net::Link>,
+    /// Whether the commands being run were received from a peer.
+    net_remote: bool,
+    /// Views whose undo group ends on the next update. Groups are ended one
+    /// update late, once the renderer has recorded the last edits.
+    undo_group_ends: Vec<ViewId>,
 
     /// Internal command bus. Used to send internal messages asynchronously.
     /// We do this when we want the renderer to have a chance to run before
@@ -713,6 +1180,8 @@ impl Session {
     const PALETTE_CELL_SIZE: f32 = 24.;
     /// Default palette height in cells.
     const PALETTE_HEIGHT: u32 = 16;
+    /// Default number of recent colors shown in the palette.
+    const PALETTE_RECENT: u32 = 8;
     /// Distance to pan when using keyboard.
     const PAN_PIXELS: i32 = 32;
     /// Minimum brush size.
@@ -721,6 +1190,18 @@ impl Session {
     const MAX_FRAME_SIZE: u32 = 4096;
     /// Maximum zoom amount as a multiplier.
     const MAX_ZOOM: f32 = 128.0;
+    /// Zoom factor, as a power of two, of a whole fractional wheel step.
+    const SMOOTH_ZOOM_RATE: f32 = 0.5;
+    /// Pixels panned per scroll step, with `input/touchpad` on.
+    const SCROLL_PAN_PIXELS: f32 = 16.;
+    /// Distance within which selections snap to guides and grid lines, in pixels.
+    const SNAP_DISTANCE: f32 = 4.;
+    /// Maximum width or height of the minimap, in pixels.
+    const MINIMAP_SIZE: f32 = 128.;
+    /// Thickness of the rulers, in pixels.
+    const RULER_SIZE: f32 = 12.;
+    /// Maximum size of the nine-slice preview, in screen pixels.
+    const PREVIEW_SIZE: f32 = 256.;
     /// Zoom levels used when zooming in/out.
     const ZOOM_LEVELS: &'static [f32] = &[
         1.,
@@ -741,6 +1222,8 @@ impl Session {
 
     /// Name of rx initialization script.
     const INIT: &'static str = "init.rx";
+    /// How often the init script is checked for changes.
+    const INIT_POLL_INTERVAL: time::Duration = time::Duration::from_secs(1);
 
     /// Create a new un-initialized session.
     pub fn new<P: AsRef<Path>>(
@@ -766,6 +1249,7 @@ impl Session {
             tool: Tool::default(),
             prev_tool: Option::default(),
             mouse_state: InputState::Released,
+            smooth_zoom: None,
             hover_color: Option::default(),
             hover_view: Option::default(),
             fg: color::WHITE,
@@ -776,17 +1260,49 @@ impl Session {
             views: ViewManager::new(),
             effects: Vec::new(),
             accumulator: time::Duration::from_secs(0),
-            palette: Palette::new(Self::PALETTE_CELL_SIZE, Self::PALETTE_HEIGHT as usize),
+            palette: Palette::new(
+                Self::PALETTE_CELL_SIZE,
+                Self::PALETTE_HEIGHT as usize,
+                Self::PALETTE_RECENT as usize,
+            ),
             key_bindings: KeyBindings::default(),
             keys_pressed: HashSet::new(),
+            key_repeat: None,
             ignore_received_characters: false,
             cmdline: CommandLine::new(cwd, history_path, path::SUPPORTED_READ_FORMATS),
             mode: Mode::Normal,
             prev_mode: Option::default(),
             selection: Option::default(),
+            split: Option::default(),
+            links: Vec::new(),
+            jump_list: JumpList::default(),
+            preview: Option::default(),
+            preview_window: Option::default(),
+            attention: false,
+            preview_backdrop: Option::default(),
+            palette_cycle: Option::default(),
+            picker: Option::default(),
+            guide_drag: Option::default(),
+            highlight: Option::default(),
+            flood: FloodOptions::default(),
+            timelapse: Option::default(),
+            frame_colors: Option::default(),
+            search_matches: HashMap::new(),
+            highlight_matches: None,
+            jobs: Jobs::default(),
+            theme: Theme::default(),
             message: Message::default(),
             avg_time: time::Duration::from_secs(0),
             frame_number: 0,
+            copied_frame: None,
+            clipboard: None,
+            palette_view: None,
+            session_palette: Swatches::default(),
+            init_script: None,
+            init_checked: time::Instant::now(),
+            net: None,
+            net_remote: false,
+            undo_group_ends: Vec::new(),
             queue: Vec::new(),
         }
     }
@@ -800,13 +1316,15 @@ impl Session {
             // The special source '-' is used to skip initialization.
             if init.as_os_str() != "-" {
                 self.source_path(&init)?;
+                self.watch_init(&init);
             }
         } else {
             let dir = self.proj_dirs.config_dir().to_owned();
             let cfg = dir.join(Self::INIT);
 
             if cfg.exists() {
-                self.source_path(cfg)?;
+                self.source_path(&cfg)?;
+                self.watch_init(&cfg);
             }
         }
 
@@ -834,6 +1352,13 @@ impl Session {
         self.edit_view(id);
     }
 
+    /// Open a view of the given pixels, which aren't backed by a file.
+    pub fn open_pixels(&mut self, w: u32, h: u32, pixels: Vec<Rgba8>) {
+        let id = self.add_view(FileStatus::NoFile, w, h, vec![pixels]);
+        self.organize_views();
+        self.edit_view(id);
+    }
+
     pub fn with_blank(mut self, fs: FileStatus, w: u32, h: u32) -> Self {
         self.blank(fs, w, h);
 
@@ -867,22 +1392,47 @@ impl Session {
         self.settings_changed.clear();
         self.avg_time = avg_time;
 
+        for id in self.undo_group_ends.drain(..) {
+            if let Some(v) = self.views.get_mut(id) {
+                v.resource.end_group();
+            }
+        }
+
         if let Tool::Brush = self.tool {
             self.brush.update();
         }
 
-        if let Some(delay) = self.animation_delay() {
-            self.accumulator += delta;
-            if self.accumulator >= delay {
-                for v in self.views.iter_mut() {
-                    v.animation.step();
+        if self.animations_playing() {
+            let delay = self.animation_delay_setting();
+
+            // Views step at their own pace, if they have their own frame delay.
+            for v in self.views.iter_mut() {
+                v.animation.advance(delta, delay);
+            }
+            if let Some(cycle) = &mut self.palette_cycle {
+                self.accumulator += delta;
+
+                if self.accumulator >= delay {
+                    cycle.ticks += 1;
+
+                    if cycle.ticks >= cycle.frames {
+                        cycle.ticks = 0;
+                        cycle.step += 1;
+                        self.effects.push(Effect::PaletteCycled);
+                    }
+                    self.accumulator = time::Duration::from_secs(0);
                 }
-                self.accumulator = time::Duration::from_secs(0);
             }
         }
+        self.repeat_key(delta);
+
         if self.ignore_received_characters {
             self.ignore_received_characters = false;
         }
+        let done = self.jobs.poll();
+        self.jobs_done(done);
+        self.reload_init();
+        self.net_poll();
 
         // TODO: This whole block needs refactoring..
         if let Execution::Replaying {
@@ -971,19 +1521,23 @@ impl Session {
 
         if let Tool::Brush = self.tool {
             let brush = &self.brush;
-            let output = brush.output(
-                Stroke::NONE,
-                Fill::Solid(brush.color.into()),
-                1.0,
-                Align::BottomLeft,
-            );
+            let output = if brush.jitter.is_set() && !brush.is_erasing() {
+                brush.jittered(&self.palette.colors)
+            } else {
+                brush.output(
+                    Stroke::NONE,
+                    Fill::Solid(brush.color.into()),
+                    1.0,
+                    Align::BottomLeft,
+                )
+            };
             if !output.is_empty() {
                 match brush.state {
                     // If we're erasing, we can't use the staging framebuffer, since we
                     // need to be replacing pixels on the real buffer.
-                    _ if brush.is_set(BrushMode::Erase) => {
+                    _ if brush.is_erasing() => {
                         self.effects.extend_from_slice(&[
-                            Effect::ViewBlendingChanged(Blending::Constant),
+                            Effect::ViewBlendingChanged(self.brush_blending()),
                             Effect::ViewPaintFinal(output),
                         ]);
                     }
@@ -992,9 +1546,11 @@ impl Session {
                         self.effects.push(Effect::ViewPaintDraft(output));
                     }
                     // Once we're done drawing, we can render into the real buffer.
+                    // Modes that depend on the pixels being painted on are only
+                    // blended then, since the output covers the whole stroke.
                     BrushState::DrawEnded(_) => {
                         self.effects.extend_from_slice(&[
-                            Effect::ViewBlendingChanged(Blending::Alpha),
+                            Effect::ViewBlendingChanged(self.brush_blending()),
                             Effect::ViewPaintFinal(output),
                         ]);
                     }
@@ -1008,6 +1564,8 @@ impl Session {
         if self.views.is_empty() {
             self.quit(ExitReason::Normal);
         } else {
+            self.switch_palette();
+
             for v in self.views.iter_mut() {
                 if !v.ops.is_empty() {
                     self.effects
@@ -1018,8 +1576,9 @@ impl Session {
                     ViewState::Damaged(extent) => {
                         self.effects.push(Effect::ViewDamaged(v.id, extent));
                     }
-                    ViewState::LayerDamaged(layer) => {
-                        self.effects.push(Effect::ViewLayerDamaged(v.id, layer));
+                    ViewState::LayerDamaged(layer, dirty) => {
+                        self.effects
+                            .push(Effect::ViewLayerDamaged(v.id, layer, dirty));
                     }
                     ViewState::Okay => {}
                 }
@@ -1052,13 +1611,125 @@ impl Session {
 
     /// Cleanup to be run at the end of the frame.
     pub fn cleanup(&mut self) {
+        // Snapshots have been recorded by now, capture the timelapse frame.
+        if let Some(timelapse) = &mut self.timelapse {
+            if let Some(v) = self.views.get(timelapse.view) {
+                timelapse.capture(v);
+            }
+        }
+        if self.settings["max-colors"].to_u64() > 0 && !self.views.is_empty() {
+            let v = self.active_view();
+            let key = (v.id, v.resource.current_edit());
+
+            if self.frame_colors.as_ref().map(|(id, e, _)| (*id, *e)) != Some(key) {
+                self.frame_colors = Some((key.0, key.1, v.frame_colors()));
+            }
+        } else {
+            self.frame_colors = None;
+        }
+        for v in self.views.iter() {
+            if let Some(color) = v.search {
+                let key = (v.resource.current_edit(), v.active_layer_id, color);
+                let cached = self
+                    .search_matches
+                    .get(&v.id)
+                    .map(|(e, l, c, _)| (*e, *l, *c));
+
+                if cached != Some(key) {
+                    let matches = v.resource.find_color(v.active_layer_id, color);
+                    self.search_matches
+                        .insert(v.id, (key.0, key.1, key.2, matches));
+                }
+            }
+        }
+        let views = &self.views;
+        self.search_matches
+            .retain(|id, _| views.get(*id).is_some_and(|v| v.search.is_some()));
+
+        match self.palette.hover.or(self.highlight) {
+            Some(color) if !self.views.is_empty() => {
+                let v = self.active_view();
+                let key = (v.id, v.resource.current_edit(), v.active_layer_id, color);
+                let cached = self
+                    .highlight_matches
+                    .as_ref()
+                    .map(|(id, e, l, c, _)| (*id, *e, *l, *c));
+
+                if cached != Some(key) {
+                    let matches = v.resource.find_color(v.active_layer_id, color);
+                    self.highlight_matches = Some((key.0, key.1, key.2, key.3, matches));
+                }
+            }
+            _ => {
+                self.highlight_matches = None;
+            }
+        }
+
         for v in self.views.iter_mut() {
             v.okay();
         }
     }
 
+    /// Pixels of the given view's active layer matching its search color.
+    /// Updated at the end of every frame in which the search or the pixels changed.
+    pub fn search_matches(&self, id: ViewId) -> &[LayerCoords<u32>] {
+        match self.search_matches.get(&id) {
+            Some((_, _, _, matches)) => matches,
+            None => &[],
+        }
+    }
+
+    /// Pixels of the active view's active layer matching the hovered palette color,
+    /// or else the highlighted color. Updated like the search matches.
+    pub fn highlight_matches(&self) -> &[LayerCoords<u32>] {
+        match &self.highlight_matches {
+            Some((id, _, _, _, matches)) if *id == self.views.active_id => matches,
+            _ => &[],
+        }
+    }
+
+    /// Frames of the active view with more colors than `max-colors` allows,
+    /// with their color count.
+    pub fn frames_over_budget(&self) -> Vec<(usize, usize)> {
+        let max = self.settings["max-colors"].to_u64() as usize;
+
+        match &self.frame_colors {
+            Some((id, _, counts)) if max > 0 && *id == self.views.active_id => counts
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| **n > max)
+                .map(|(i, n)| (i, *n))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Color budget warning for the active view, eg. `2 frame(s) over 16 colors`.
+    pub fn budget_status(&self) -> Option<String> {
+        let over = self.frames_over_budget();
+
+        if over.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "{} frame(s) over {} colors",
+                over.len(),
+                self.settings["max-colors"].to_u64()
+            ))
+        }
+    }
+
+    /// Block until all background jobs have finished, and report their results.
+    pub fn wait_jobs(&mut self) {
+        let done = self.jobs.wait();
+        self.jobs_done(done);
+    }
+
     /// Quit the session.
     pub fn quit(&mut self, r: ExitReason) {
+        // Don't leave exports half-written.
+        self.wait_jobs();
+
         if self.cmdline.history.save().is_err() {
             error!(
                 "Error: couldn't save command history to {}",
@@ -1076,25 +1747,85 @@ impl Session {
     /// Snap the given session coordinates to the pixel grid.
     /// This only has an effect at zoom levels greater than `1.0`.
     #[allow(dead_code)]
-    pub fn snap(&self, p: SessionCoords, offx: f32, offy: f32, zoom: f32) -> SessionCoords {
+    pub fn snap(
+        &self,
+        p: SessionCoords,
+        offx: f32,
+        offy: f32,
+        zoom: Vector2<f32>,
+    ) -> SessionCoords {
         SessionCoords::new(
-            p.x - ((p.x - offx - self.offset.x) % zoom),
-            p.y - ((p.y - offy - self.offset.y) % zoom),
+            p.x - ((p.x - offx - self.offset.x) % zoom.x),
+            p.y - ((p.y - offy - self.offset.y) % zoom.y),
         )
         .floor()
     }
 
-    /// Get the current animation delay. Returns `None` if animations aren't playing,
-    /// or if none of the views have more than one frame.
-    pub fn animation_delay(&self) -> Option<time::Duration> {
-        let animations = self.views.iter().any(|v| v.animation.len() > 1);
+    /// Get the animation delay setting, whether or not animations are playing.
+    fn animation_delay_setting(&self) -> time::Duration {
+        time::Duration::from_millis(self.settings["animation/delay"].to_u64())
+    }
 
-        if self.settings["animation"].is_set() && animations {
-            let delay = self.settings["animation/delay"].to_u64();
-            Some(time::Duration::from_millis(delay))
-        } else {
-            None
+    /// Get the frame delay of the given view: its own, or else the setting.
+    fn view_animation_delay(&self, id: ViewId) -> time::Duration {
+        self.view(id)
+            .animation
+            .delay
+            .unwrap_or_else(|| self.animation_delay_setting())
+    }
+
+    /// Whether animations are playing, and any of the views have more than one
+    /// frame, or the palette is cycling.
+    fn animations_playing(&self) -> bool {
+        let animations =
+            self.views.iter().any(|v| v.animation.len() > 1) || self.palette_cycle.is_some();
+
+        self.settings["animation"].is_set() && animations
+    }
+
+    /// Get the time left until the next animation frame, of any view, or the next
+    /// palette cycle step. Returns `None` if animations aren't playing.
+    pub fn animation_wait(&self) -> Option<time::Duration> {
+        if !self.animations_playing() {
+            return None;
         }
+        let delay = self.animation_delay_setting();
+        let frames = self
+            .views
+            .iter()
+            .filter(|v| v.animation.len() > 1)
+            .map(|v| v.animation.remaining(delay));
+        let cycle = self
+            .palette_cycle
+            .as_ref()
+            .map(|_| delay.checked_sub(self.accumulator).unwrap_or_default());
+
+        frames.chain(cycle).min()
+    }
+
+    /// Time left until a held key repeats its command, if any.
+    pub fn key_repeat_wait(&self) -> Option<time::Duration> {
+        self.key_repeat.as_ref().map(|r| r.wait)
+    }
+
+    /// Run the command of the held key, if it's time to repeat it. Commands are
+    /// repeated at most once per update.
+    fn repeat_key(&mut self, delta: time::Duration) {
+        let rate = self.settings["input/repeat-rate"].to_u64().max(1) as u32;
+        let interval = time::Duration::from_secs(1) / rate;
+
+        let command = match &mut self.key_repeat {
+            Some(r) if delta >= r.wait => {
+                r.wait = interval;
+                r.command.clone()
+            }
+            Some(r) => {
+                r.wait -= delta;
+                return;
+            }
+            None => return,
+        };
+        self.command(command);
     }
 
     /// Check whether the session is running.
@@ -1130,6 +1861,7 @@ impl Session {
             _ => {
                 self.offset.x += x;
                 self.offset.y += y;
+                self.jump_list.moved();
             }
         }
         self.cursor_dirty();
@@ -1145,7 +1877,7 @@ impl Session {
         let cursor = self.cursor;
         let palette_hover = self.palette.hover.is_some();
 
-        self.palette.handle_cursor_moved(cursor);
+        self.palette.handle_cursor_moved(self.ui_coords(cursor));
         self.hover_view = None;
 
         let gained_palette_focus = !palette_hover && self.palette.hover.is_some();
@@ -1168,11 +1900,14 @@ impl Session {
             _ => {}
         }
 
-        for v in self.views.iter_mut() {
-            let p = cursor - self.offset;
-            if let Some(l) = v.contains(p) {
-                self.hover_view = Some((v.id, l));
-                break;
+        // Views can't be hovered through the split pane or the minimap.
+        if !self.is_split_hovered() && !self.is_minimap_hovered() {
+            for v in self.views.iter_mut() {
+                let p = cursor - self.offset;
+                if let Some(l) = v.contains(p) {
+                    self.hover_view = Some((v.id, l));
+                    break;
+                }
             }
         }
 
@@ -1197,6 +1932,35 @@ impl Session {
                 self.palette.height = new.to_u64() as usize;
                 self.center_palette();
             }
+            "p/recent" => {
+                let p = &mut self.palette;
+
+                p.recent_size = new.to_u64() as usize;
+                p.recent.truncate(p.recent_size);
+                self.center_palette();
+            }
+            "animation/delay" if !self.views.is_empty() => {
+                // The active view follows the setting, even if it had its own delay.
+                self.active_view_mut().animation.delay = None;
+            }
+            "palette/lock" if new.is_set() => {
+                self.fg = self.locked_color(self.fg);
+                self.bg = self.locked_color(self.bg);
+            }
+            "brush/grid" => {
+                self.brush.grid = new.to_u64() as usize;
+            }
+            "undo/scope" if !matches!(new.to_str(), "view" | "session") => {
+                self.settings.set(name, old.clone()).ok();
+                self.message(
+                    "Error: undo scope must be `view` or `session`",
+                    MessageType::Error,
+                );
+            }
+            "ui/scale" => {
+                self.center_palette();
+                self.cursor_dirty();
+            }
             "scale" => {
                 // TODO: We need to recompute the cursor position here
                 // from the window coordinates. Currently, cursor position
@@ -1204,6 +1968,25 @@ impl Session {
                 // to change.
                 self.rescale(old.to_f64(), new.to_f64());
             }
+            "pixel-ratio" => {
+                if let Value::U32Tuple(x, y) = *new {
+                    if x == 0 || y == 0 {
+                        self.settings.set(name, old.clone()).ok();
+                        self.message(
+                            "Error: pixel ratio must be greater than zero",
+                            MessageType::Error,
+                        );
+                        return;
+                    }
+                    let ratio = Session::pixel_ratio(x, y);
+
+                    for v in self.views.iter_mut() {
+                        v.pixel_ratio = ratio;
+                    }
+                    self.organize_views();
+                    self.center_active_view();
+                }
+            }
             _ => {}
         }
     }
@@ -1345,7 +2128,9 @@ impl Session {
         if self.views.active_id == id {
             return;
         }
+        self.record_jump();
         self.views.activate(id);
+        self.brush.last = None;
         self.effects.push(Effect::ViewActivated(id));
     }
 
@@ -1376,73 +2161,397 @@ impl Session {
         )
     }
 
-    /// Convert session coordinates to view coordinates of the given view.
-    pub fn view_coords(&self, v: ViewId, p: SessionCoords) -> ViewCoords<f32> {
-        let v = self.view(v);
-        let SessionCoords(mut p) = p;
+    /// Scale of the UI chrome, ie. the status line, palette and text.
+    pub fn ui_scale(&self) -> f32 {
+        self.settings["ui/scale"].to_u64() as f32
+    }
 
-        p = p - self.offset - v.offset;
-        p = p / v.zoom;
+    /// Size of the session in UI coordinates, which are scaled by `ui/scale`.
+    pub fn ui_size(&self) -> (f32, f32) {
+        let u = self.ui_scale();
+        (self.width / u, self.height / u)
+    }
 
-        if v.flip_x {
-            p.x = v.width() as f32 - p.x;
-        }
-        if v.flip_y {
-            p.y = v.height() as f32 - p.y;
+    /// Convert session coordinates to UI coordinates.
+    pub fn ui_coords(&self, p: SessionCoords) -> SessionCoords {
+        let u = self.ui_scale();
+        SessionCoords::new(p.x / u, p.y / u)
+    }
+
+    /// The area occupied by the minimap of the active view, if the minimap is enabled.
+    /// The minimap is placed in the top-right corner of the workspace.
+    pub fn minimap_rect(&self) -> Option<Rect<f32>> {
+        if !self.settings["ui/minimap"].is_set() {
+            return None;
         }
+        let v = self.active_view();
+        let (w, h) = (
+            v.width() as f32 * v.pixel_ratio.x,
+            v.height() as f32 * v.pixel_ratio.y,
+        );
+        let scale = (Self::MINIMAP_SIZE / w).min(Self::MINIMAP_SIZE / h).min(1.);
+        let (w, h) = ((w * scale).floor(), (h * scale).floor());
+        let (x, y) = (
+            self.width - Self::VIEW_MARGIN - w,
+            self.height - Self::VIEW_MARGIN - h,
+        );
 
-        ViewCoords::new(p.x.floor(), p.y.floor())
+        Some(Rect::new(x, y, x + w, y + h))
     }
 
-    /// Convert view coordinates to session coordinates.
-    pub fn session_coords(&self, v: ViewId, p: ViewCoords<f32>) -> SessionCoords {
-        let v = self.view(v);
+    /// The horizontal ruler along the top of the workspace, and the vertical ruler
+    /// along its right side, clear of the palette, if rulers are shown. Dragging
+    /// from a ruler onto the active view adds a guide of the same orientation.
+    pub fn ruler_rects(&self) -> Option<(Rect<f32>, Rect<f32>)> {
+        if !self.settings["ui/rulers"].is_set() {
+            return None;
+        }
+        let (w, h, s) = (self.width, self.height, Self::RULER_SIZE);
 
-        let p = Point2::new(p.x * v.zoom, p.y * v.zoom);
-        let p = p + self.offset + v.offset;
+        Some((Rect::new(0., h - s, w, h), Rect::new(w - s, 0., w, h - s)))
+    }
 
-        if v.flip_x {
-            unimplemented!();
-        }
-        if v.flip_y {
-            unimplemented!();
-        }
+    /// The area of the nine-slice preview, in the bottom-right corner of the
+    /// workspace. The preview is scaled up by a whole factor to fit.
+    pub fn preview_rect(&self) -> Option<Rect<f32>> {
+        let p = self.preview?;
+        let (w, h) = (p.width as f32, p.height as f32);
+        let scale = (Self::PREVIEW_SIZE / w.max(h)).floor().max(1.);
+        let (w, h) = (w * scale, h * scale);
+        let (x, y) = (self.width - Self::VIEW_MARGIN - w, Self::VIEW_MARGIN * 2.);
 
-        SessionCoords::new(p.x, p.y).floor()
+        Some(Rect::new(x, y, x + w, y + h))
     }
 
-    /// Convert session coordinates to view coordinates of the active view.
-    pub fn active_view_coords(&self, p: SessionCoords) -> ViewCoords<f32> {
-        self.view_coords(self.views.active_id, p)
+    /// The region of the active view visible in the workspace, in view coordinates.
+    pub fn visible_rect(&self) -> Rect<f32> {
+        self.view_visible_rect(self.active_view())
     }
 
-    pub fn layer_coords(&self, v: ViewId, l: LayerId, p: SessionCoords) -> LayerCoords<f32> {
-        let v = self.view(v);
-        let SessionCoords(p) = p;
+    /// The region of the given view visible in the workspace, in view coordinates.
+    pub fn view_visible_rect(&self, v: &View<ViewResource>) -> Rect<f32> {
+        let offset = self.offset + v.offset;
+        let (w, h) = (v.width() as f32, v.height() as f32);
 
-        let p = p - self.offset - v.offset - v.layer_offset(l, v.zoom);
-        let mut p = p / v.zoom;
+        let s = v.scale();
 
-        if v.flip_x {
-            p.x = v.width() as f32 - p.x;
-        }
-        if v.flip_y {
-            p.y = v.height() as f32 - p.y;
-        }
+        let x1 = (-offset.x / s.x).clamp(0., w);
+        let y1 = (-offset.y / s.y).clamp(0., h);
+        let x2 = ((self.width - offset.x) / s.x).clamp(x1, w);
+        let y2 = ((self.height - offset.y) / s.y).clamp(y1, h);
 
-        LayerCoords::new(p.x.floor(), p.y.floor())
+        Rect::new(x1, y1, x2, y2)
     }
 
-    /// Convert session coordinates to layer coordinates of the active layer.
-    pub fn active_layer_coords(&self, p: SessionCoords) -> LayerCoords<f32> {
-        let v = self.active_view();
-        self.layer_coords(v.id, v.active_layer_id, p)
+    /// The tile size, if tileset mode is on. Otherwise, shows an error.
+    fn tile_size(&mut self) -> Option<u32> {
+        match self.settings["tileset"].to_u64() as u32 {
+            0 => {
+                self.message(
+                    "Error: tileset mode is off, `:set tileset = <size>` to turn it on",
+                    MessageType::Error,
+                );
+                None
+            }
+            size => Some(size),
+        }
     }
 
-    /// Check whether a point is inside the selection, if any.
-    pub fn is_selected(&self, p: LayerCoords<i32>) -> bool {
-        if let Some(s) = self.selection {
-            s.abs().bounds().contains(*p)
+    /// In tileset mode, the selection covering the tiles of the active view from
+    /// the tile containing `from`, to the one containing `to`. The selection starts
+    /// from the far edge of the first tile, so that it keeps track of it.
+    fn tile_selection(&self, from: Point2<i32>, to: Point2<i32>) -> Option<Selection> {
+        let size = self.settings["tileset"].to_u64() as u32;
+        let v = self.active_view();
+        let a = v.tile_at(size, from).and_then(|i| v.tile_rect(size, i))?;
+        let b = v.tile_at(size, to).and_then(|i| v.tile_rect(size, i))?;
+
+        let (x1, x2) = if b.x1 >= a.x1 {
+            (a.x1, b.x2)
+        } else {
+            (a.x2, b.x1)
+        };
+        let (y1, y2) = if b.y1 >= a.y1 {
+            (a.y1, b.y2)
+        } else {
+            (a.y2, b.y1)
+        };
+
+        Some(Selection::new(x1, y1, x2, y2))
+    }
+
+    /// Snap a brush position to the nearest guide or grid line, when drawing shapes.
+    fn snap_brush(&self, p: LayerCoords<i32>) -> LayerCoords<i32> {
+        if !self.brush.is_shape() {
+            return p;
+        }
+        let (x, y) = self.snap_to_guides(p.x, p.y);
+
+        LayerCoords::new(x, y)
+    }
+
+    /// Snap the given pixel edge of the active layer to the nearest guide or grid line,
+    /// if snapping is enabled and a line is close enough.
+    fn snap_to_guides(&self, x: i32, y: i32) -> (i32, i32) {
+        if !self.settings["snap"].is_set() {
+            return (x, y);
+        }
+        let v = self.active_view();
+        let distance = (Self::SNAP_DISTANCE / v.zoom).ceil() as i32;
+        let (gx, gy): (u32, u32) = if self.settings["grid"].is_set() {
+            self.settings["grid/spacing"].clone().into()
+        } else {
+            (0, 0)
+        };
+
+        let snap = |n: i32, spacing: u32, guides: Vec<i32>| {
+            let grid = if spacing > 0 {
+                let s = spacing as f32;
+                Some(((n as f32 / s).round() * s) as i32)
+            } else {
+                None
+            };
+            guides
+                .into_iter()
+                .chain(grid)
+                .filter(|l| (l - n).abs() <= distance)
+                .min_by_key(|l| (l - n).abs())
+                .unwrap_or(n)
+        };
+        let vertical = v
+            .guides
+            .iter()
+            .filter_map(|g| match g {
+                Guide::Vertical(x) => Some(*x),
+                Guide::Horizontal(_) => None,
+            })
+            .collect();
+        let horizontal = v
+            .guides
+            .iter()
+            .filter_map(|g| match g {
+                Guide::Horizontal(y) => Some(*y),
+                Guide::Vertical(_) => None,
+            })
+            .collect();
+
+        (snap(x, gx, vertical), snap(y, gy, horizontal))
+    }
+
+    /// Convert session coordinates to view coordinates of the given view.
+    pub fn view_coords(&self, v: ViewId, p: SessionCoords) -> ViewCoords<f32> {
+        let v = self.view(v);
+        let SessionCoords(mut p) = p;
+
+        let s = v.scale();
+
+        p = p - self.offset - v.offset;
+        p = Point2::new(p.x / s.x, p.y / s.y);
+
+        if v.flip_x {
+            p.x = v.width() as f32 - p.x;
+        }
+        if v.flip_y {
+            p.y = v.height() as f32 - p.y;
+        }
+
+        ViewCoords::new(p.x.floor(), p.y.floor())
+    }
+
+    /// The window title, following the `window/title` format. By default, this is
+    /// the active view's file name, with a `*` if it's modified.
+    pub fn window_title(&self) -> String {
+        if self.views.is_empty() {
+            return String::from("rx");
+        }
+        match self.settings["window/title"].to_str() {
+            "" => match self.status_item("name") {
+                Some(name) if !name.is_empty() => format!(
+                    "{}{} - rx",
+                    name,
+                    self.status_item("modified").unwrap_or_default()
+                ),
+                _ => String::from("rx"),
+            },
+            fmt => statusline::expand(fmt, |item| self.status_item(item)),
+        }
+    }
+
+    /// Value of a status line item, eg. `file` or `zoom`, for the active view.
+    pub fn status_item(&self, item: &str) -> Option<String> {
+        let v = self.active_view();
+        let cursor = self.view_coords(v.id, self.cursor);
+
+        let value = match item {
+            "file" => v.status(),
+            "name" => match v.file_storage() {
+                Some(FileStorage::Single(path)) => path
+                    .file_name()
+                    .map_or(String::new(), |n| n.to_string_lossy().into_owned()),
+                Some(storage) => storage.to_string(),
+                None => String::new(),
+            },
+            "modified" => match v.file_status {
+                FileStatus::Modified(_) | FileStatus::New(_) => String::from("*"),
+                _ => String::new(),
+            },
+            "mode" => self.mode.to_string(),
+            "zoom" => format!("{}%", (v.zoom * 100.) as u32),
+            "cursor" => format!("{},{}", cursor.x, cursor.y),
+            "x" => cursor.x.to_string(),
+            "y" => cursor.y.to_string(),
+            "color" => self
+                .hover_color
+                .map_or(String::from("-"), |c| Rgb8::from(c).to_string()),
+            "pixel" => format!(
+                "{},{} {}",
+                cursor.x,
+                cursor.y,
+                self.hover_color
+                    .map_or(String::new(), |c| Rgb8::from(c).to_string())
+            ),
+            "frame" => {
+                let p = ViewCoords::<i32>::from(cursor);
+                if v.bounds().contains(*p) {
+                    v.extent().to_frame(cursor.into()).to_string()
+                } else {
+                    String::from("-")
+                }
+            }
+            "brush" => self
+                .brush
+                .modes()
+                .map(|m| m.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            "fg" => Rgb8::from(self.fg).to_string(),
+            "bg" => Rgb8::from(self.bg).to_string(),
+            "line" => self.line_status().unwrap_or_default(),
+            "budget" => self.budget_status().unwrap_or_default(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Value of a variable that can be used in command paths, eg. `f` in
+    /// `:export %d/out/%f@4x.png`. These are the file name without its extension
+    /// (`f`) and the directory (`d`) of the active view, the frame under the cursor
+    /// (`F`), and today's date (`date`).
+    pub fn path_variable(&self, name: &str) -> Option<String> {
+        match name {
+            "date" => return Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+            "f" | "d" | "F" if !self.views.is_empty() => {}
+            _ => return None,
+        }
+        let v = self.active_view();
+        let path = match v.file_storage() {
+            Some(FileStorage::Single(path)) => Some(path.as_path()),
+            Some(FileStorage::Range(paths)) => Some(paths.first().as_path()),
+            None => None,
+        };
+
+        let value = match name {
+            "f" => path
+                .and_then(Path::file_stem)
+                .map_or(String::new(), |s| s.to_string_lossy().into_owned()),
+            "d" => match path.and_then(Path::parent) {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.display().to_string(),
+                _ => String::from("."),
+            },
+            "F" => {
+                let cursor = self.view_coords(v.id, self.cursor);
+                let p = ViewCoords::<i32>::from(cursor);
+
+                if v.bounds().contains(*p) {
+                    v.extent().to_frame(cursor.into()).to_string()
+                } else {
+                    String::from("0")
+                }
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Length and angle of the line being drawn in line mode, eg. `12.0px 45deg`.
+    /// Angles are counter-clockwise from the x-axis.
+    pub fn line_status(&self) -> Option<String> {
+        let (start, end) = self.brush.current_line()?;
+        let (dx, dy) = ((end.x - start.x) as f32, (end.y - start.y) as f32);
+        let angle = dy.atan2(dx).to_degrees().round().rem_euclid(360.);
+
+        Some(format!("{:.1}px {}deg", dx.hypot(dy), angle))
+    }
+
+    /// Convert view coordinates to session coordinates.
+    pub fn session_coords(&self, v: ViewId, p: ViewCoords<f32>) -> SessionCoords {
+        let v = self.view(v);
+
+        let s = v.scale();
+
+        let p = Point2::new(p.x * s.x, p.y * s.y);
+        let p = p + self.offset + v.offset;
+
+        if v.flip_x {
+            unimplemented!();
+        }
+        if v.flip_y {
+            unimplemented!();
+        }
+
+        SessionCoords::new(p.x, p.y).floor()
+    }
+
+    /// Convert session coordinates to view coordinates of the active view.
+    pub fn active_view_coords(&self, p: SessionCoords) -> ViewCoords<f32> {
+        self.view_coords(self.views.active_id, p)
+    }
+
+    pub fn layer_coords(&self, v: ViewId, l: LayerId, p: SessionCoords) -> LayerCoords<f32> {
+        let v = self.view(v);
+        let SessionCoords(p) = p;
+
+        let s = v.scale();
+
+        let p = p - self.offset - v.offset - v.layer_offset(l, v.zoom);
+        let mut p = Point2::new(p.x / s.x, p.y / s.y);
+
+        if v.flip_x {
+            p.x = v.width() as f32 - p.x;
+        }
+        if v.flip_y {
+            p.y = v.height() as f32 - p.y;
+        }
+
+        LayerCoords::new(p.x.floor(), p.y.floor())
+    }
+
+    /// Convert session coordinates to layer coordinates of the active layer.
+    pub fn active_layer_coords(&self, p: SessionCoords) -> LayerCoords<f32> {
+        let v = self.active_view();
+        self.layer_coords(v.id, v.active_layer_id, p)
+    }
+
+    /// Offset to apply to the cursor to get the pixel the brush is painted at.
+    /// Even-sized brushes are centered on the pixel corner nearest to the cursor,
+    /// rather than on the pixel under it, so that they grow evenly on all sides.
+    pub fn brush_offset(&self) -> Vector2<f32> {
+        if self.brush.size % 2 == 1 {
+            return Vector2::new(0., 0.);
+        }
+        let v = self.active_view();
+        let s = v.scale() * 0.5;
+
+        Vector2::new(
+            if v.flip_x { -s.x } else { s.x },
+            if v.flip_y { -s.y } else { s.y },
+        )
+    }
+
+    /// Check whether a point is inside the selection, if any.
+    pub fn is_selected(&self, p: LayerCoords<i32>) -> bool {
+        if let Some(s) = self.selection {
+            s.abs().bounds().contains(*p)
         } else {
             false
         }
@@ -1565,11 +2674,11 @@ impl Session {
 
         // Use the first frame as a reference for what size the rest of
         // the frames should be.
-        if let Some((fw, fh, _)) = frames.peek() {
+        if let Some((fw, fh, _, _)) = frames.peek() {
             let (fw, fh) = (*fw, *fh);
 
-            if frames.clone().all(|(w, h, _)| w == fw && h == fh) {
-                let frames: Vec<_> = frames.map(|(_, _, pixels)| pixels).collect();
+            if frames.clone().all(|(w, h, _, _)| w == fw && h == fh) {
+                let frames: Vec<_> = frames.map(|(_, _, pixels, _)| pixels).collect();
                 self.add_view(FileStatus::Saved(FileStorage::Range(paths)), fw, fh, frames);
             } else {
                 return Err(io::Error::new(
@@ -1594,10 +2703,12 @@ impl Session {
     /// Save the given view to disk with the current file name. Returns
     /// an error if the view has no file name.
     pub fn save_view(&mut self, id: ViewId) -> io::Result<(FileStorage, usize)> {
-        let view = self.view_mut(id);
+        let delay = self.view_animation_delay(id);
+        self.store_view_palette();
 
-        if let Some(f) = view.file_storage().cloned() {
-            view.save_as(&f).map(|w| (f, w))
+        if let Some(f) = self.view(id).file_storage().cloned() {
+            self.backup_files(&f)?;
+            self.view_mut(id).save_as(&f, delay).map(|w| (f, w))
         } else {
             Err(io::Error::new(io::ErrorKind::Other, "no file name given"))
         }
@@ -1605,14 +2716,256 @@ impl Session {
 
     /// Private ///////////////////////////////////////////////////////////////////
 
-    /// Export a layer in a specific format.
+    /// Back up the files of the given storage before they're overwritten,
+    /// if backups are enabled.
+    fn backup_files(&self, storage: &FileStorage) -> io::Result<()> {
+        if !self.settings["backup"].is_set() {
+            return Ok(());
+        }
+        let keep = self.settings["backup/count"].to_u64() as usize;
+
+        match storage {
+            FileStorage::Single(path) => {
+                backup::rotate(path, keep)?;
+            }
+            FileStorage::Range(paths) => {
+                for path in paths.iter() {
+                    backup::rotate(path, keep)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// List the backups of the active view's file, or restore one of them and
+    /// reload the view. Returns the message to display.
+    fn backups(&mut self, n: Option<usize>) -> io::Result<String> {
+        let id = self.views.active_id;
+        let path = match self.view(id).file_storage() {
+            Some(FileStorage::Single(path)) => path.clone(),
+            Some(FileStorage::Range(_)) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "backups of frame ranges can't be listed",
+                ));
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no file name given",
+                ))
+            }
+        };
+
+        if let Some(n) = n {
+            if let FileStatus::Modified(_) = self.view(id).file_status {
+                return Err(io::Error::other("no write since last change"));
+            }
+            let keep = self.settings["backup/count"].to_u64() as usize;
+            backup::restore(&path, n, keep)?;
+
+            self.quit_view(id);
+            self.edit(&[&path])?;
+
+            return Ok(format!("Backup {} of \"{}\" restored", n, path.display()));
+        }
+
+        let backups = backup::list(&path);
+        if backups.is_empty() {
+            return Ok(format!("No backups of \"{}\"", path.display()));
+        }
+        let now = time::SystemTime::now();
+        let ages = backups
+            .iter()
+            .map(|(n, modified)| {
+                let secs = now.duration_since(*modified).unwrap_or_default().as_secs();
+                let age = match secs {
+                    0..=59 => format!("{}s", secs),
+                    60..=3599 => format!("{}m", secs / 60),
+                    3600..=86399 => format!("{}h", secs / 3600),
+                    _ => format!("{}d", secs / 86400),
+                };
+                format!("{} ({} ago)", n, age)
+            })
+            .collect::<Vec<_>>();
+
+        Ok(format!(
+            "Backups of \"{}\": {}",
+            path.display(),
+            ages.join(", ")
+        ))
+    }
+
+    /// Write all modified views to disk, or export them into a directory.
+    /// Returns the summary message to display.
+    fn export_all(&mut self, dir: Option<&Path>, scale: Option<u32>) -> io::Result<String> {
+        if scale == Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "export scale must be at least 1",
+            ));
+        }
+        let ids = self
+            .views
+            .iter()
+            .filter(|v| matches!(v.file_status, FileStatus::Modified(_) | FileStatus::New(_)))
+            .map(|v| v.id)
+            .collect::<Vec<_>>();
+
+        if ids.is_empty() {
+            return Ok(String::from("No modified views"));
+        }
+        let dir = match dir {
+            Some(dir) => dir,
+            None if scale.is_some() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "`--scale` requires `--dir`",
+                ));
+            }
+            None => {
+                let errors = ids
+                    .iter()
+                    .filter_map(|id| self.save_view(*id).err())
+                    .collect::<Vec<_>>();
+
+                return match errors.first() {
+                    None => Ok(format!("{} view(s) written", ids.len())),
+                    Some(e) => Err(io::Error::other(format!(
+                        "{} of {} view(s) written, {}",
+                        ids.len() - errors.len(),
+                        ids.len(),
+                        e
+                    ))),
+                };
+            }
+        };
+        fs::create_dir_all(dir)?;
+
+        let names = ids
+            .iter()
+            .map(|id| match self.view(*id).file_storage() {
+                Some(FileStorage::Single(path)) => path.file_name().map(PathBuf::from),
+                Some(FileStorage::Range(paths)) => paths
+                    .first()
+                    .parent()
+                    .and_then(|p| p.file_name())
+                    .map(|name| Path::new(name).with_extension("png")),
+                None => None,
+            })
+            .collect::<Vec<_>>();
+        // Views with the same file name, from different directories, would overwrite
+        // each other's export. None of them are exported.
+        let mut counts = HashMap::new();
+        for name in names.iter().flatten() {
+            *counts.entry(name).or_insert(0) += 1;
+        }
+
+        let mut jobs = Vec::new();
+        let mut errors = Vec::new();
+
+        for (id, name) in ids.iter().copied().zip(names.iter()) {
+            let view = self.view(id);
+            let scale = scale.unwrap_or(match self.settings["export/scale"].to_u64() {
+                0 => view.zoom as u32,
+                n => n as u32,
+            });
+            let job = match name {
+                _ if scale == 0 => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "export scale must be at least 1",
+                )),
+                None => Err(io::Error::other("no file name given")),
+                Some(name) if counts[name] > 1 => Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!(
+                        "more than one view would be exported as `{}`",
+                        name.display()
+                    ),
+                )),
+                Some(_) if view.layers.len() > 1 => {
+                    Err(io::Error::other("only single layer views can be exported"))
+                }
+                Some(name) => {
+                    let path = dir.join(name);
+                    let scale = if ExportFormat::from_extension(
+                        path.extension()
+                            .and_then(|e| e.to_str())
+                            .unwrap_or_default(),
+                    )
+                    .is_some()
+                    {
+                        1
+                    } else {
+                        scale
+                    };
+                    self.export_job(
+                        id,
+                        view.active_layer_id,
+                        &path,
+                        scale,
+                        &ExportOptions::default(),
+                    )
+                }
+            };
+            match job {
+                Ok(job) => jobs.push(job),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        let total = ids.len();
+        let display = dir.display().to_string();
+        let summary = format!("Exporting {} view(s) to `{}`...", total, display);
+
+        self.jobs.spawn(display.clone(), move || {
+            for job in jobs {
+                if let Err(e) = job() {
+                    errors.push(e);
+                }
+            }
+            match errors.first() {
+                None => Ok(format!("{} view(s) exported to `{}`", total, display)),
+                Some(e) => Err(io::Error::other(format!(
+                    "{} of {} view(s) exported, {}",
+                    total - errors.len(),
+                    total,
+                    e
+                ))),
+            }
+        });
+        Ok(summary)
+    }
+
+    /// Export a layer in a specific format. With an export profile, colors are
+    /// quantized to the profile's palette. The export format is given by the file
+    /// extension, unless it's set in the options. Once written, the exported path
+    /// is passed to the `--pipe` command, or else the `on-export` hook.
     fn export_layer_as(
         &mut self,
         id: ViewId,
         layer_id: LayerId,
         path: &Path,
         scale: u32,
+        opts: &ExportOptions,
     ) -> io::Result<()> {
+        let job = self.export_job(id, layer_id, path, scale, opts)?;
+        self.jobs.spawn(path.display().to_string(), job);
+
+        Ok(())
+    }
+
+    /// Prepare the export of a layer, to be run as a background job. See
+    /// `export_layer_as`.
+    fn export_job(
+        &self,
+        id: ViewId,
+        layer_id: LayerId,
+        path: &Path,
+        scale: u32,
+        opts: &ExportOptions,
+    ) -> io::Result<ExportJob> {
+        let (layout, profile) = (&opts.layout, opts.profile);
         let ext = path.extension().ok_or_else(|| {
             io::Error::new(io::ErrorKind::Other, "file path requires an extension")
         })?;
@@ -1620,16 +2973,188 @@ impl Session {
             io::Error::new(io::ErrorKind::Other, "file extension is not valid unicode")
         })?;
 
-        let written = match ext {
-            "gif" => {
-                let palette = self.colors();
-                let view = self.view(id);
-                let delay = time::Duration::from_millis(self.settings["animation/delay"].to_u64());
+        let format = opts.format.or_else(|| ExportFormat::from_extension(ext));
+
+        if (ext != "png" || format.is_some()) && *layout != SheetLayout::default() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sheet layout options are only supported for `png` exports",
+            ));
+        }
+
+        let (extent, pixels) = self
+            .view(id)
+            .resource
+            .export_snapshot(layer_id)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        let (extent, pixels) = match opts.frames {
+            Some((a, b)) if a > b || b >= extent.nframes => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("frame range must be within 0..{}", extent.nframes - 1),
+                ));
+            }
+            Some((a, b)) => {
+                let (fw, fh) = (extent.fw as usize, extent.fh as usize);
+                let frames = util::split_frames(pixels, fw, fh, extent.nframes)
+                    .into_iter()
+                    .skip(a)
+                    .take(b - a + 1)
+                    .collect();
+
+                (
+                    ViewExtent::new(extent.fw, extent.fh, b - a + 1),
+                    util::stitch_frames(frames, fw, fh, Rgba8::TRANSPARENT),
+                )
+            }
+            None => (extent, pixels),
+        };
+        let (extent, pixels) = match (opts.selection, self.selection) {
+            (false, _) => (extent, pixels),
+            (true, _) if opts.frames.is_some() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "`--selection` can't be combined with a frame range",
+                ));
+            }
+            (true, None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "there is no selection to export",
+                ));
+            }
+            (true, Some(s)) => {
+                let (w, h) = (extent.width() as i32, extent.height() as i32);
+                let bounds = Rect::origin(w, h);
+                let area = s.abs().bounds();
+
+                if !area.intersects(bounds) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "selection is outside the view",
+                    ));
+                }
+                let area = area.intersection(bounds);
+                // Selections point up, while pixel rows go from the top.
+                let pixels = pixels
+                    .chunks_exact(w as usize)
+                    .skip((h - area.y2) as usize)
+                    .take(area.height() as usize)
+                    .flat_map(|row| &row[area.x1 as usize..area.x2 as usize])
+                    .copied()
+                    .collect();
+
+                (
+                    ViewExtent::new(area.width() as u32, area.height() as u32, 1),
+                    pixels,
+                )
+            }
+        };
+        // Frames are filtered on their own, so that their edges don't blend into each
+        // other. Formats that can't be scaled are left as they are, and rejected below.
+        let (extent, pixels, scale) = match opts.filter {
+            Some(filter) if format.is_none() || format == Some(ExportFormat::SpriteFrames) => {
+                let (fw, fh) = (extent.fw as usize, extent.fh as usize);
+                let frames = util::split_frames(pixels, fw, fh, extent.nframes)
+                    .into_iter()
+                    .map(|frame| filter.scale(&frame, extent.fw, extent.fh, scale))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("`{}` can't scale by {}", filter, scale),
+                        )
+                    })?;
+                let (fw, fh) = (fw * scale as usize, fh * scale as usize);
+
+                (
+                    ViewExtent::new(fw as u32, fh as u32, extent.nframes),
+                    util::stitch_frames(frames, fw, fh, Rgba8::TRANSPARENT),
+                    1,
+                )
+            }
+            _ => (extent, pixels, scale),
+        };
+        let pixels = match profile {
+            Some(p) => p.quantize(&pixels),
+            None => pixels,
+        };
+        let path = path.to_owned();
+
+        // Encoding and writing happens on the worker thread, on a copy of the pixels,
+        // so that editing can go on in the meantime.
+        let job: Box<dyn FnOnce(&Path) -> io::Result<usize> + Send> = match (format, ext) {
+            (Some(f), _) if scale != 1 && f != ExportFormat::SpriteFrames => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{}` exports can't be scaled", ext),
+                ));
+            }
+            (Some(ExportFormat::Tiles(format)), _) => {
+                let indices = match profile.or_else(|| format.profile()) {
+                    Some(p) => p.indices(&pixels),
+                    None => self.palette_indices(&pixels, format.bits())?,
+                };
+                Box::new(move |path| resource::save_tiles(path, extent, &indices, format))
+            }
+            (Some(ExportFormat::Array { indexed: true }), _) => {
+                let (indices, palette) = match profile {
+                    Some(p) => (p.indices(&pixels), p.palette()),
+                    None => (
+                        self.palette_indices(&pixels, u8::BITS)?,
+                        self.palette.colors.to_vec(),
+                    ),
+                };
+                Box::new(move |path| resource::save_array(path, extent, &indices, Some(&palette)))
+            }
+            (Some(ExportFormat::Array { indexed: false }), _) => {
+                let bytes: Vec<u8> = pixels
+                    .iter()
+                    .flat_map(|c| vec![c.r, c.g, c.b, c.a])
+                    .collect();
+
+                Box::new(move |path| resource::save_array(path, extent, &bytes, None))
+            }
+            (Some(ExportFormat::SpriteFrames), _) => {
+                let delay = self.view_animation_delay(id);
+                let playback = self.view(id).animation.playback;
 
-                view.save_gif(layer_id, &path, delay, &palette, scale)?
+                Box::new(move |path| {
+                    resource::save_sprite_frames(path, extent, &pixels, scale, delay, playback)
+                })
+            }
+            (Some(ExportFormat::Xpm), _) => {
+                Box::new(move |path| resource::save_xpm(path, extent, &pixels))
+            }
+            (None, "gif") => {
+                let palette = match profile {
+                    Some(p) => p.palette(),
+                    None => self.colors().to_vec(),
+                };
+                let delay = self.view_animation_delay(id);
+
+                Box::new(move |path| {
+                    resource::save_gif(path, extent, &pixels, delay, &palette, scale)
+                })
+            }
+            (None, "svg") => Box::new(move |path| resource::save_svg(path, extent, &pixels, scale)),
+            (None, "png") => {
+                let meta = if extent.nframes > 1 && *layout == SheetLayout::default() {
+                    image::Metadata {
+                        nframes: Some(extent.nframes),
+                        delay: Some(self.view_animation_delay(id).as_millis() as u64),
+                        ..image::Metadata::default()
+                    }
+                } else {
+                    image::Metadata::default()
+                };
+                let layout = *layout;
+                let sheet = profile.and_then(Profile::sheet);
+
+                Box::new(move |path| {
+                    resource::save_png(path, extent, &pixels, scale, &layout, &meta, sheet)
+                })
             }
-            "svg" => self.view(id).save_svg(layer_id, &path, scale)?,
-            "png" => self.view(id).save_png(layer_id, &path, scale)?,
             _ => {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidInput,
@@ -1637,10 +3162,98 @@ impl Session {
                 ));
             }
         };
-        self.message(
-            format!("\"{}\" {} pixels written", path.display(), written),
-            MessageType::Info,
-        );
+        let hook = match &opts.pipe {
+            Some(cmd) => cmd.clone(),
+            None => self.settings["on-export"].to_string(),
+        };
+        Ok(Box::new(move || {
+            let written = job(&path)?;
+
+            if !hook.is_empty() {
+                jobs::run(&hook, &path)?;
+            }
+            Ok(format!("\"{}\" {} pixels written", path.display(), written))
+        }))
+    }
+
+    /// Index of the closest palette color of each pixel, for exporting indexed data
+    /// with the given number of bits per pixel. Transparent pixels map to the first
+    /// index. Fails if the palette has more colors than can be indexed.
+    fn palette_indices(&self, pixels: &[Rgba8], bits: u32) -> io::Result<Vec<u8>> {
+        let colors = &self.palette.colors;
+        let max = 1usize << bits;
+
+        if colors.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the palette is empty",
+            ));
+        }
+        if colors.len() > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "the palette has {} colors, but only {} can be indexed with {} bits per pixel",
+                    colors.len(),
+                    max,
+                    bits
+                ),
+            ));
+        }
+        Ok(pixels
+            .iter()
+            .map(|c| match self.palette.nearest(*c) {
+                _ if c.a == 0 => 0,
+                Some(n) => colors.iter().position(|p| *p == n).unwrap_or_default() as u8,
+                None => 0,
+            })
+            .collect())
+    }
+
+    /// Report the results of finished background jobs.
+    fn jobs_done(&mut self, done: Vec<jobs::Done>) {
+        for d in done {
+            if d.elapsed >= LONG_JOB {
+                self.attention = true;
+            }
+            match d.result {
+                Ok(msg) => self.message(msg, MessageType::Info),
+                Err(e) => self.message(
+                    format!("Error: exporting \"{}\": {}", d.name, e),
+                    MessageType::Error,
+                ),
+            }
+        }
+    }
+
+    /// Load a UI theme. Themes are looked up by name in the `themes` directory
+    /// of the config directory, else `name` is used as a path.
+    fn load_theme(&mut self, name: &str) -> io::Result<()> {
+        let theme = if name == "default" {
+            Theme::default()
+        } else {
+            let path = self
+                .proj_dirs
+                .config_dir()
+                .join("themes")
+                .join(name)
+                .with_extension(theme::EXTENSION);
+
+            if path.is_file() {
+                Theme::load(path)?
+            } else {
+                Theme::load(name)?
+            }
+        };
+
+        if let Some(c) = theme.grid {
+            self.command(Command::Set("grid/color".to_owned(), Value::Rgba8(c)));
+        }
+        if let Some(c) = theme.grid_major {
+            self.command(Command::Set("grid/major/color".to_owned(), Value::Rgba8(c)));
+        }
+        self.theme = theme;
+
         Ok(())
     }
 
@@ -1664,14 +3277,25 @@ impl Session {
 
         match path.format {
             view::Format::Png => {
-                let (width, height, pixels) = crate::io::load_image(&*path)?;
+                let (width, height, pixels, meta) = crate::io::load_image(&*path)?;
 
-                self.add_view(
+                // Restore the animation frames and delay, if the image was saved by rx.
+                let nframes = match meta.nframes {
+                    Some(n) if n > 0 && width % n as u32 == 0 => n,
+                    _ => 1,
+                };
+                let fw = width / nframes as u32;
+                let frames = util::split_frames(pixels, fw as usize, height as usize, nframes);
+
+                let id = self.add_view(
                     FileStatus::Saved(FileStorage::Single((*path).into())),
-                    width,
+                    fw,
                     height,
-                    vec![pixels],
+                    frames,
                 );
+                self.view_mut(id).palette = meta.palette;
+
+                self.view_mut(id).animation.delay = meta.delay.map(time::Duration::from_millis);
                 self.message(
                     format!("\"{}\" {} pixels read", path.display(), width * height),
                     MessageType::Info,
@@ -1700,6 +3324,21 @@ impl Session {
                     self.view_mut(view_id).add_layer(Some(pixels));
                 }
             }
+            view::Format::Psd => {
+                let (width, height, pixels) = crate::io::load_psd(&*path)?;
+
+                // PSD files can't be written, so the view is saved as a PNG.
+                self.add_view(
+                    FileStatus::New(FileStorage::Single(path.with_extension("png"))),
+                    width,
+                    height,
+                    vec![pixels],
+                );
+                self.message(
+                    format!("\"{}\" {} pixels read", path.display(), width * height),
+                    MessageType::Info,
+                );
+            }
             view::Format::Gif => {
                 return Err(io::Error::new(
                     io::ErrorKind::Other,
@@ -1734,6 +3373,11 @@ impl Session {
         let resource = ViewResource::new(pixels, ViewExtent::new(fw, fh, nframes));
         let id = self.views.add(file_status, fw, fh, nframes, resource);
 
+        if let Value::U32Tuple(x, y) = self.settings["pixel-ratio"] {
+            if let Some(v) = self.views.get_mut(id) {
+                v.pixel_ratio = Session::pixel_ratio(x, y);
+            }
+        }
         self.effects.push(Effect::ViewAdded(id));
 
         id
@@ -1745,6 +3389,11 @@ impl Session {
 
         self.views.remove(id);
         self.effects.push(Effect::ViewRemoved(id));
+
+        if self.split.map(|s| s.view) == Some(id) {
+            self.split = None;
+        }
+        self.links.retain(|(a, b)| *a != id && *b != id);
     }
 
     /// Quit the view.
@@ -1798,16 +3447,16 @@ impl Session {
         first.offset.y = 0.;
 
         // TODO: We need a way to distinguish view content size with real (rendered) size.
-        let mut offset = first.height() as f32 * first.zoom + Self::VIEW_MARGIN;
+        let mut offset = first.height() as f32 * first.scale().y + Self::VIEW_MARGIN;
 
         for v in self.views.iter_mut().skip(1) {
             if v.layers.len() > 1 {
                 // Account for layer composite.
-                offset += v.fh as f32 * v.zoom;
+                offset += v.fh as f32 * v.scale().y;
             }
             v.offset.y = offset;
 
-            offset += v.height() as f32 * v.zoom + Self::VIEW_MARGIN;
+            offset += v.height() as f32 * v.scale().y + Self::VIEW_MARGIN;
         }
         self.cursor_dirty();
     }
@@ -1835,6 +3484,9 @@ impl Session {
 
                 v.yank(s);
 
+                let pixels = v.resource.layer(v.active_layer_id).get_snapshot_rect(&s);
+                self.clipboard = pixels.map(|(_, p)| (s.width() as u32, s.height() as u32, p));
+                self.copied_frame = None;
                 self.selection = Some(Selection::from(s));
                 self.switch_mode(Mode::Visual(VisualState::Pasting));
 
@@ -1844,14 +3496,66 @@ impl Session {
         None
     }
 
+    /// Load an image into the paste buffer, and start pasting it into the active view.
+    /// The image is centered on the cursor, or placed with its top-left corner at the
+    /// given position, counting rows from the top like `paint/color`.
+    fn import(&mut self, path: &str, pos: Option<(i32, i32)>) -> io::Result<(u32, u32)> {
+        let (data, w, h, _) = image::load(path)?;
+        let pixels = Rgba8::align(&data).to_vec();
+        let (iw, ih) = (w as i32, h as i32);
+
+        let (x, y) = match pos {
+            Some((x, y)) => (x, self.active_view().fh as i32 - y - ih),
+            None => {
+                let c = self.active_layer_coords(self.cursor);
+                (c.x as i32 - iw / 2, c.y as i32 - ih / 2)
+            }
+        };
+        self.active_view_mut().load_paste(w, h, pixels.clone());
+
+        self.clipboard = Some((w, h, pixels));
+        self.copied_frame = None;
+        self.selection = Some(Selection::new(x, y, x + iw, y + ih));
+        self.switch_mode(Mode::Visual(VisualState::Pasting));
+
+        Ok((w, h))
+    }
+
     fn undo(&mut self, id: ViewId) {
         self.restore_view_snapshot(id, Direction::Backward);
     }
 
+    /// The view to undo or redo in. With the `session` undo scope, this is the view
+    /// with the latest edit to undo, or the earliest edit to redo, so that edits are
+    /// undone across views in the order they were made.
+    fn undo_view(&self, dir: Direction) -> ViewId {
+        if self.settings["undo/scope"].to_str() != "session" {
+            return self.views.active_id;
+        }
+        let times = self.views.iter().filter_map(|v| {
+            let time = match dir {
+                Direction::Backward => v.resource.undo_time(),
+                Direction::Forward => v.resource.redo_time(),
+            };
+            time.map(|t| (t, v.id))
+        });
+        let view = match dir {
+            Direction::Backward => times.max_by_key(|(t, _)| *t),
+            Direction::Forward => times.min_by_key(|(t, _)| *t),
+        };
+        view.map_or(self.views.active_id, |(_, id)| id)
+    }
+
     fn redo(&mut self, id: ViewId) {
         self.restore_view_snapshot(id, Direction::Forward);
     }
 
+    fn revert_view(&mut self, id: ViewId, edit: EditId) {
+        self.view_mut(id).revert(edit);
+        self.organize_views();
+        self.cursor_dirty();
+    }
+
     fn restore_view_snapshot(&mut self, id: ViewId, dir: Direction) {
         self.view_mut(id).restore_snapshot(dir);
         self.organize_views();
@@ -1909,6 +3613,11 @@ impl Session {
                     self.handle_mouse_wheel(delta);
                 }
             }
+            Event::Pinch(delta) => {
+                if self.settings["input/mouse"].is_set() {
+                    self.handle_pinch(delta);
+                }
+            }
             Event::CursorMoved(position) => {
                 if self.settings["input/mouse"].is_set() {
                     let coords = self.window_to_session_coords(position);
@@ -1939,17 +3648,89 @@ impl Session {
         self.effects.push(Effect::SessionScaled(new));
     }
 
+    /// Displayed pixel size for an `x:y` pixel ratio, reduced so that `4:2`
+    /// looks the same as `2:1`.
+    fn pixel_ratio(x: u32, y: u32) -> Vector2<f32> {
+        let (mut a, mut b) = (x.max(1), y.max(1));
+
+        while b != 0 {
+            let r = a % b;
+            a = b;
+            b = r;
+        }
+        Vector2::new((x.max(1) / a) as f32, (y.max(1) / a) as f32)
+    }
+
     pub fn handle_resized(&mut self, size: platform::LogicalSize) {
         self.resize(size, self.settings["scale"].to_f64());
         self.effects.push(Effect::SessionResized(size));
     }
 
+    /// Open files dropped onto the window. Images are opened as views, and
+    /// GIMP palettes replace the current palette.
+    pub fn handle_file_dropped(&mut self, paths: &[PathBuf]) {
+        let (palettes, images): (Vec<_>, Vec<_>) = paths
+            .iter()
+            .partition(|p| p.extension().map(|e| e == "gpl") == Some(true));
+
+        for path in palettes {
+            let colors = std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|s| Palette::parse_gpl(&s));
+
+            match colors {
+                Ok(colors) => {
+                    self.palette.clear();
+                    for color in colors {
+                        self.palette.add(color);
+                    }
+                    self.center_palette();
+                    self.message(
+                        format!(
+                            "Palette read from {} ({} colors)",
+                            path.display(),
+                            self.palette.size()
+                        ),
+                        MessageType::Info,
+                    );
+                }
+                Err(e) => self.message(
+                    format!("Error: `{}`: {}", path.display(), e),
+                    MessageType::Error,
+                ),
+            }
+        }
+
+        if !images.is_empty() {
+            if let Err(e) = self.edit(&images) {
+                self.message(format!("Error loading path(s): {}", e), MessageType::Error);
+            }
+        }
+    }
+
     fn handle_mouse_input(&mut self, button: platform::MouseButton, state: platform::InputState) {
         if button != platform::MouseButton::Left {
             return;
         }
         self.mouse_state = state;
 
+        // Click on the color picker. Clicking outside of it closes it.
+        let (w, h) = self.ui_size();
+        let cursor = self.ui_coords(self.cursor);
+
+        if let Some(picker) = &mut self.picker {
+            if state == InputState::Pressed {
+                let r = Picker::rect(w, h);
+
+                match Picker::area(r, *cursor) {
+                    Some(area) => picker.pick(area),
+                    None if !r.contains(*cursor) => self.picker = None,
+                    None => {}
+                }
+            }
+            return;
+        }
+
         // Pan tool.
         match &mut self.tool {
             Tool::Pan(ref mut p) => match (&p, state) {
@@ -1978,6 +3759,29 @@ impl Session {
                     return;
                 }
 
+                // Click on minimap.
+                if self.is_minimap_hovered() {
+                    self.center_minimap_cursor();
+                    return;
+                }
+
+                // Click on split pane.
+                if self.is_split_hovered() {
+                    self.split_focus();
+                    return;
+                }
+
+                // Drag a guide from a ruler.
+                if let Some((horizontal, vertical)) = self.ruler_rects() {
+                    if horizontal.contains(*self.cursor) {
+                        self.guide_drag = Some(Axis::Horizontal);
+                        return;
+                    } else if vertical.contains(*self.cursor) {
+                        self.guide_drag = Some(Axis::Vertical);
+                        return;
+                    }
+                }
+
                 // Click on a view.
                 if let Some((id, layer_id)) = self.hover_view {
                     // Clicking on a view is one way to get out of command mode.
@@ -1994,16 +3798,34 @@ impl Session {
                         let p = self.active_layer_coords(self.cursor);
 
                         let extent = v.extent();
+                        let (sx, sy) = self.snap_to_guides(p.x as i32, p.y as i32);
+                        let tile = self.tile_selection(p.map(|n| n as i32), p.map(|n| n as i32));
 
                         match self.mode {
+                            Mode::Normal
+                                if self.settings["readonly"].is_set()
+                                    && matches!(self.tool, Tool::Brush | Tool::FloodFill) =>
+                            {
+                                self.readonly_error();
+                            }
                             Mode::Normal => match self.tool {
                                 Tool::Brush => {
-                                    let color = if self.brush.is_set(BrushMode::Erase) {
+                                    let color = if self.brush.is_set(BrushMode::Erase(true)) {
+                                        self.bg
+                                    } else if self.brush.is_erasing() {
                                         Rgba8::TRANSPARENT
                                     } else {
                                         self.fg
                                     };
-                                    self.brush.start_drawing(p.into(), color, extent);
+                                    let p =
+                                        self.active_layer_coords(self.cursor + self.brush_offset());
+                                    let p = self.snap_brush(p.into());
+
+                                    if self.brush.is_set(BrushMode::Connect) {
+                                        self.brush.start_line(p, color, extent);
+                                    } else {
+                                        self.brush.start_drawing(p, color, extent);
+                                    }
                                 }
                                 Tool::Sampler => {
                                     self.sample_color();
@@ -2011,10 +3833,20 @@ impl Session {
                                 Tool::Pan(_) => {}
                                 Tool::FloodFill => {
                                     let start_time = time::Instant::now();
-                                    let filler = FloodFiller::new(self.active_view(), p, self.fg);
-                                    if let Some(shapes) = filler.and_then(|f| f.run()) {
+                                    let FloodOptions { global, tolerance } = self.flood;
+                                    let filler =
+                                        FloodFiller::new(self.active_view(), p, self.fg, tolerance);
+                                    let shapes = filler.and_then(|f| {
+                                        if global {
+                                            f.run_global()
+                                        } else {
+                                            f.run()
+                                        }
+                                    });
+                                    if let Some(shapes) = shapes {
                                         self.effects.push(Effect::ViewPaintFinal(shapes));
                                         self.active_view_mut().touch_layer();
+                                        self.add_recent_color(self.fg);
                                     }
                                     debug!("flood fill in: {:?}", start_time.elapsed());
                                 }
@@ -2024,7 +3856,7 @@ impl Session {
                             }
                             Mode::Visual(VisualState::Selecting { ref mut dragging }) => {
                                 let p = p.map(|n| n as i32);
-                                let unit = Selection::new(p.x, p.y, p.x + 1, p.y + 1);
+                                let unit = tile.unwrap_or(Selection::new(sx, sy, sx + 1, sy + 1));
 
                                 if let Some(s) = &mut self.selection {
                                     if s.abs().bounds().contains(p) {
@@ -2058,6 +3890,14 @@ impl Session {
                     }
                 }
             }
+            // Drop the dragged guide, if it's over the active view.
+            InputState::Released if self.guide_drag.is_some() => {
+                let axis = self.guide_drag.take().expect("a guide is being dragged");
+
+                if matches!(self.hover_view, Some((id, _)) if self.is_active(id)) {
+                    self.command(Command::GuideAdd(axis, None));
+                }
+            }
             InputState::Released => match self.mode {
                 Mode::Visual(VisualState::Selecting { ref mut dragging }) => {
                     *dragging = false;
@@ -2066,7 +3906,12 @@ impl Session {
                     if let Tool::Brush = self.tool {
                         match self.brush.state {
                             BrushState::Drawing { .. } | BrushState::DrawStarted { .. } => {
+                                if !self.brush.is_erasing() {
+                                    self.add_recent_color(self.brush.color);
+                                }
                                 self.brush.stop_drawing();
+                                self.share_stroke();
+                                self.link_stroke();
                                 self.active_view_mut().touch_layer();
                             }
                             _ => {}
@@ -2080,16 +3925,73 @@ impl Session {
     }
 
     fn handle_mouse_wheel(&mut self, delta: platform::LogicalDelta) {
+        // On touchpads, two-finger scrolling pans, and zooming is done by pinching,
+        // which some platforms report as scrolling with `<ctrl>` held.
+        if self.settings["input/touchpad"].is_set() && !self.is_split_hovered() {
+            let invert = if self.settings["input/invert"].is_set() {
+                -1.
+            } else {
+                1.
+            };
+
+            if self.keys_pressed.contains(&platform::Key::Control) {
+                let steps = delta.y as f32
+                    * invert
+                    * self.settings["input/zoom-sensitivity"].to_f64() as f32;
+                self.zoom_smooth(steps, self.cursor);
+            } else {
+                let speed = Self::SCROLL_PAN_PIXELS
+                    * invert
+                    * self.settings["input/pan-sensitivity"].to_f64() as f32;
+                self.pan(
+                    (-delta.x as f32 * speed).round(),
+                    (-delta.y as f32 * speed).round(),
+                );
+            }
+            return;
+        }
+
+        if self.is_split_hovered() {
+            if delta.y > 0. {
+                self.split_zoom(Direction::Forward, self.cursor);
+            } else if delta.y < 0. {
+                self.split_zoom(Direction::Backward, self.cursor);
+            }
+            return;
+        }
+
         if delta.y > 0. {
             if let Some((v, _)) = self.hover_view {
                 self.activate(v);
             }
+        }
+        // Mouse wheels scroll in whole steps, which move between zoom levels,
+        // while touchpads send fractional steps, which zoom gradually.
+        if delta.y.fract() != 0. {
+            self.zoom_smooth(delta.y as f32, self.cursor);
+        } else if delta.y > 0. {
             self.zoom_in(self.cursor);
         } else if delta.y < 0. {
             self.zoom_out(self.cursor);
         }
     }
 
+    fn handle_pinch(&mut self, delta: f64) {
+        if self.is_split_hovered() {
+            return;
+        }
+        let delta = if self.settings["input/invert"].is_set() {
+            -delta
+        } else {
+            delta
+        };
+        let sensitivity = self.settings["input/zoom-sensitivity"].to_f64();
+        // Convert the change in magnification to fractional zoom steps.
+        let steps = ((1. + delta * sensitivity).max(0.01).log2()) as f32 / Self::SMOOTH_ZOOM_RATE;
+
+        self.zoom_smooth(steps, self.cursor);
+    }
+
     fn handle_cursor_moved(&mut self, cursor: SessionCoords) {
         if self.cursor == cursor {
             return;
@@ -2099,6 +4001,8 @@ impl Session {
         let p = self.active_layer_coords(cursor);
         let prev_p = self.active_layer_coords(prev_cursor);
         let (vw, vh) = self.active_view().layer_size();
+        let brush_p = self.active_layer_coords(cursor + self.brush_offset());
+        let brush_prev_p = self.active_layer_coords(prev_cursor + self.brush_offset());
 
         self.cursor = cursor;
         self.cursor_dirty();
@@ -2107,17 +4011,18 @@ impl Session {
             Tool::Pan(PanState::Panning) => {
                 self.pan(cursor.x - prev_cursor.x, cursor.y - prev_cursor.y);
             }
+            _ if self.guide_drag.is_some() => {}
             Tool::Sampler if self.mouse_state == InputState::Pressed => {
                 self.sample_color();
             }
             _ => {
                 match self.mode {
                     Mode::Normal => match self.tool {
-                        Tool::Brush if p != prev_p => match self.brush.state {
+                        Tool::Brush if brush_p != brush_prev_p => match self.brush.state {
                             BrushState::DrawStarted { .. } | BrushState::Drawing { .. } => {
-                                let mut p: LayerCoords<i32> = p.into();
+                                let mut p = self.snap_brush(brush_p.into());
                                 let brush = &mut self.brush;
-                                if brush.is_set(BrushMode::Multi) {
+                                if brush.is_multi() {
                                     p.clamp(Rect::new(
                                         (brush.size / 2) as i32,
                                         (brush.size / 2) as i32,
@@ -2133,8 +4038,18 @@ impl Session {
                     },
                     Mode::Visual(VisualState::Selecting { dragging: false }) => {
                         if self.mouse_state == InputState::Pressed {
-                            if let Some(ref mut s) = self.selection {
-                                *s = Selection::new(s.x1, s.y1, p.x as i32 + 1, p.y as i32 + 1);
+                            let (x, y) = self.snap_to_guides(p.x as i32 + 1, p.y as i32 + 1);
+
+                            if let Some(s) = self.selection {
+                                // The pixel the selection was started from.
+                                let anchor = Point2::new(
+                                    if s.x1 > s.x2 { s.x1 - 1 } else { s.x1 },
+                                    if s.y1 > s.y2 { s.y1 - 1 } else { s.y1 },
+                                );
+                                self.selection = Some(
+                                    self.tile_selection(anchor, p.map(|n| n as i32))
+                                        .unwrap_or_else(|| Selection::new(s.x1, s.y1, x, y)),
+                                );
                             }
                         }
                     }
@@ -2197,6 +4112,11 @@ impl Session {
         let state = if repeat { InputState::Pressed } else { state };
 
         if let Some(key) = key {
+            if state == InputState::Released
+                && self.key_repeat.as_ref().is_some_and(|r| r.key == key)
+            {
+                self.key_repeat = None;
+            }
             // While the mouse is down, don't accept keyboard input.
             if self.mouse_state == InputState::Pressed {
                 return;
@@ -2210,6 +4130,14 @@ impl Session {
                 }
             }
 
+            // The color picker takes all keyboard input while open.
+            if self.picker.is_some() {
+                if state == InputState::Pressed {
+                    self.handle_picker_key(key, modifiers);
+                }
+                return;
+            }
+
             match self.mode {
                 Mode::Visual(VisualState::Selecting { .. }) => {
                     if key == platform::Key::Escape && state == InputState::Pressed {
@@ -2274,6 +4202,25 @@ impl Session {
                 .key_bindings
                 .find(Input::Key(key), modifiers, state, self.mode)
             {
+                let delay = self.settings["input/repeat-delay"].to_u64();
+
+                // With a repeat delay, repeating commands are repeated by the session,
+                // and key repeats from the OS are ignored.
+                if delay > 0
+                    && state == InputState::Pressed
+                    && kb.command.repeats()
+                    && !kb.is_toggle
+                {
+                    if !repeat {
+                        self.key_repeat = Some(KeyRepeat {
+                            key,
+                            command: kb.command.clone(),
+                            wait: time::Duration::from_millis(delay),
+                        });
+                        self.command(kb.command);
+                    }
+                    return;
+                }
                 // For toggle-like key bindings, we don't want to run the command
                 // on key repeats. For regular key bindings, we run the command
                 // depending on if it's supposed to repeat.
@@ -2293,6 +4240,289 @@ impl Session {
         }
     }
 
+    /// Report that an edit was attempted in read-only mode.
+    fn readonly_error(&mut self) {
+        self.message(
+            "Error: read-only mode, `:set readonly = off` to edit",
+            MessageType::Error,
+        );
+    }
+
+    ///////////////////////////////////////////////////////////////////////////
+    // Shared sessions
+    ///////////////////////////////////////////////////////////////////////////
+
+    /// Send an editing command to the other peers, if the session is shared.
+    /// Colors are resolved first, so that all peers paint the same ones. Commands
+    /// apply to each peer's active view.
+    fn share(&mut self, cmd: &Command) {
+        if self.net.is_none() || self.net_remote {
+            return;
+        }
+        let cmd = match *cmd {
+            Command::PaintForeground(x, y) => Command::PaintColor(self.fg, x, y),
+            Command::PaintBackground(x, y) => Command::PaintColor(self.bg, x, y),
+            Command::PaintPalette(i, x, y) => match self.palette.colors.get(i) {
+                Some(c) => Command::PaintColor(*c, x, y),
+                None => return,
+            },
+            _ if cmd.is_shared() => cmd.clone(),
+            _ => return,
+        };
+        if let Some(link) = &mut self.net {
+            link.send(&String::from(cmd));
+        }
+    }
+
+    /// Send the brush stroke that was just finished to the other peers, as the
+    /// pixels it covers. Brush pixels have their origin at the bottom-left, while
+    /// `paint/color` counts rows from the top.
+    fn share_stroke(&mut self) {
+        let v = self.views.active().expect("there is an active view");
+        let (w, h) = (v.width() as i32, v.fh as i32);
+
+        if let Some(link) = &mut self.net {
+            for p in self.brush.pixels() {
+                if p.x >= 0 && p.x < w && p.y >= 0 && p.y < h {
+                    let color = self.brush.color_at(p, &self.palette.colors);
+                    link.send(&String::from(Command::PaintColor(color, p.x, h - 1 - p.y)));
+                }
+            }
+        }
+    }
+
+    /// Apply the brush stroke that was just finished to the views linked to the
+    /// active view, at the same coordinates. Like with sharing, rows are flipped
+    /// for `paint_color`.
+    fn link_stroke(&mut self) {
+        let linked = self.linked_views(self.views.active_id);
+
+        if linked.is_empty() {
+            return;
+        }
+        let pixels = self.brush.pixels();
+        let blending = self.brush_blending();
+
+        for id in linked {
+            if let Some(v) = self.views.get_mut(id) {
+                let (w, h) = (v.width() as i32, v.fh as i32);
+                let layer = match v.current_snapshot(v.active_layer_id) {
+                    Some((_, layer)) => layer.to_vec(),
+                    None => continue,
+                };
+
+                // Pixels are blended like the stroke was on the active view, since
+                // they're set directly on the linked ones.
+                for p in pixels.iter() {
+                    if p.x >= 0 && p.x < w && p.y >= 0 && p.y < h {
+                        let y = h - 1 - p.y;
+                        let src = self.brush.color_at(*p, &self.palette.colors);
+                        let dst = layer[(y * w + p.x) as usize];
+
+                        v.paint_color(blending.blend(src, dst), p.x, y);
+                    }
+                }
+                v.touch_layer();
+            }
+        }
+    }
+
+    /// How the brush stroke is blended onto the layer it's painted on.
+    fn brush_blending(&self) -> Blending {
+        if self.brush.is_erasing() {
+            Blending::Constant
+        } else if self.brush.is_set(BrushMode::Behind) {
+            Blending::Behind
+        } else if self.brush.is_set(BrushMode::Replace) {
+            Blending::Replace
+        } else {
+            Blending::Alpha
+        }
+    }
+
+    /// Transform the pixels of the frame under the cursor, or of every frame of the
+    /// active view, on all layers. See `View::transform_frames`.
+    fn transform_frames(
+        &mut self,
+        scope: Scope,
+        transform: impl Fn(&[Rgba8], u32, u32) -> (Vec<Rgba8>, u32, u32),
+    ) {
+        let frame = match scope {
+            Scope::View => None,
+            Scope::Frame => {
+                let v = self.active_view();
+                let cursor = self.active_view_coords(self.cursor);
+
+                if v.bounds().contains(*ViewCoords::<i32>::from(cursor)) {
+                    Some(v.extent().to_frame(cursor.into()))
+                } else {
+                    self.message("Error: cursor must be over a frame", MessageType::Error);
+                    return;
+                }
+            }
+        };
+        self.active_view_mut().transform_frames(frame, transform);
+
+        self.check_selection();
+        self.organize_views();
+    }
+
+    /// Pipe the pixels of the selection, or of the whole active layer, through a shell
+    /// command, and paint the pixels it outputs. Pixels are exchanged one per line, as
+    /// `<x> <y> <color>`, with colors of the form `#rrggbbaa`, and with rows counted
+    /// from the top like `paint/color`. The alpha is optional in the output. Returns
+    /// the number of pixels painted.
+    fn filter_pixels(&mut self, command: &str) -> io::Result<usize> {
+        let v = self.active_view();
+        let bounds = v.layer_bounds();
+        let area = match self.selection {
+            Some(s) if s.abs().bounds().intersects(bounds) => s.abs().bounds().intersection(bounds),
+            Some(_) => return Ok(0),
+            None => bounds,
+        };
+        let (_, pixels) = v
+            .resource
+            .layer(v.active_layer_id)
+            .get_snapshot_rect(&area)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+
+        // Snapshot rows are top-down, starting from the top of the area, which is
+        // in view coordinates.
+        let top = bounds.y2 - area.y2;
+        let mut input = String::with_capacity(pixels.len() * 16);
+
+        for (i, c) in pixels.iter().enumerate() {
+            let x = area.x1 + (i % area.width() as usize) as i32;
+            let y = top + (i / area.width() as usize) as i32;
+
+            input.push_str(&format!(
+                "{} {} #{:02x}{:02x}{:02x}{:02x}\n",
+                x, y, c.r, c.g, c.b, c.a
+            ));
+        }
+        let output = jobs::pipe(command, &input)?;
+
+        let mut painted = Vec::new();
+        for (n, line) in output.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let pixel = Self::parse_pixel(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid pixel on line {} of output: `{}`", n + 1, line),
+                )
+            })?;
+            painted.push(pixel);
+        }
+        // Pixels are painted with `paint/color`, so that they're shared like any
+        // other painting.
+        painted.retain(|(x, y, _)| bounds.contains(Point2::new(*x, *y)));
+
+        for (x, y, color) in painted.iter().copied() {
+            self.command(Command::PaintColor(color, x, y));
+        }
+        self.active_view_mut().touch_layer();
+
+        Ok(painted.len())
+    }
+
+    /// Parse a `<x> <y> <color>` line of `paint/filter` output.
+    fn parse_pixel(line: &str) -> Option<(i32, i32, Rgba8)> {
+        let mut parts = line.split_whitespace();
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        let hex = parts.next()?.strip_prefix('#')?;
+
+        if parts.next().is_some() || !(hex.len() == 6 || hex.len() == 8) {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        let a = if hex.len() == 8 { byte(6)? } else { 0xff };
+
+        Some((x, y, Rgba8::new(byte(0)?, byte(2)?, byte(4)?, a)))
+    }
+
+    /// Views linked to the given view.
+    pub fn linked_views(&self, id: ViewId) -> Vec<ViewId> {
+        self.links
+            .iter()
+            .filter_map(|(a, b)| {
+                if *a == id {
+                    Some(*b)
+                } else if *b == id {
+                    Some(*a)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Find the view showing the given path. Also matches paths by their last
+    /// components, eg. `hero.png` matches `sprites/hero.png`.
+    fn find_view(&self, path: &str) -> Option<ViewId> {
+        self.views
+            .iter()
+            .find(|v| match v.file_storage() {
+                Some(FileStorage::Single(p)) => p.ends_with(path),
+                Some(FileStorage::Range(paths)) => paths.iter().any(|p| p.ends_with(path)),
+                None => false,
+            })
+            .map(|v| v.id)
+    }
+
+    /// Run the commands received from the other peers, if the session is shared.
+    fn net_poll(&mut self) {
+        let link = match &mut self.net {
+            None => return,
+            Some(link) => link,
+        };
+        let peers = link.peers();
+        let lines = match link.poll() {
+            Ok(lines) => lines,
+            Err(e) => {
+                self.net = None;
+                self.message(
+                    format!("Error: the shared session ended: {}", e),
+                    MessageType::Error,
+                );
+                return;
+            }
+        };
+
+        // Let the host know when peers join or leave.
+        if let Some(link) = &self.net {
+            if link.is_host() && link.peers() != peers {
+                let n = link.peers();
+                self.message(
+                    format!("{} peer{} connected", n, if n == 1 { "" } else { "s" }),
+                    MessageType::Info,
+                );
+            }
+        }
+
+        self.net_receive(lines);
+    }
+
+    /// Run the commands received from the other peers. Only the editing commands
+    /// that peers share are accepted, since anyone can connect to a session: the
+    /// rest are dropped.
+    fn net_receive(&mut self, lines: Vec<String>) {
+        self.net_remote = true;
+        for line in lines {
+            match self.cmdline.parse(&format!(":{}", line)) {
+                Ok(cmd) if cmd.is_shared() => self.command(cmd),
+                Ok(_) => self.message(
+                    format!("Error: ignoring `{}` from a peer", line),
+                    MessageType::Error,
+                ),
+                Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+            }
+        }
+        self.net_remote = false;
+    }
+
     ///////////////////////////////////////////////////////////////////////////
     /// Sourcing
     ///////////////////////////////////////////////////////////////////////////
@@ -2314,6 +4544,51 @@ impl Session {
             })
     }
 
+    /// Watch the init script, so that it's sourced again when it's modified.
+    fn watch_init(&mut self, path: &Path) {
+        let path = if path.exists() {
+            path.to_owned()
+        } else {
+            self.proj_dirs.config_dir().join(path)
+        };
+        if let Ok(mtime) = std::fs::metadata(&path).and_then(|m| m.modified()) {
+            self.init_script = Some((path, mtime));
+        }
+    }
+
+    /// Source the init script again if it was modified since it was last sourced.
+    /// Key bindings, settings and aliases it defines are applied over the current ones.
+    fn reload_init(&mut self) {
+        if self.init_checked.elapsed() < Self::INIT_POLL_INTERVAL {
+            return;
+        }
+        self.init_checked = time::Instant::now();
+
+        if !self.settings["init/reload"].is_set() {
+            return;
+        }
+        let (path, mtime) = match &self.init_script {
+            Some((path, mtime)) => (path.clone(), *mtime),
+            None => return,
+        };
+
+        match std::fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(modified) if modified != mtime => {
+                self.init_script = Some((path.clone(), modified));
+                self.source_init(&path);
+            }
+            _ => {}
+        }
+    }
+
+    /// Source the init script, and report the outcome.
+    fn source_init(&mut self, path: &Path) {
+        match self.source_path(path) {
+            Ok(()) => self.message(format!("Sourced {}", path.display()), MessageType::Info),
+            Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+        }
+    }
+
     /// Source a directory which contains a `.rxrc` script. Returns an
     /// error if the script wasn't found or couldn't be sourced.
     fn source_dir<P: AsRef<Path>>(&mut self, dir: P) -> io::Result<()> {
@@ -2325,34 +4600,79 @@ impl Session {
         for (i, line) in r.lines().enumerate() {
             let line = line?;
 
-            if line.starts_with(cmd::COMMENT) {
-                continue;
-            }
-            match self.cmdline.parse(&format!(":{}", line)) {
-                Err(e) => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!("{} on line {}", e, i + 1),
-                    ))
-                }
-                Ok(cmd) => self.command(cmd),
+            if let Err(e) = self.source_line(&line) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} on line {}", e, i + 1),
+                ));
             }
         }
         Ok(())
     }
 
+    /// Source a single line of an rx script. Comment lines are skipped.
+    pub fn source_line(&mut self, line: &str) -> Result<(), String> {
+        if line.starts_with(cmd::COMMENT) {
+            return Ok(());
+        }
+        let cmd = self
+            .cmdline
+            .parse(&format!(":{}", line))
+            .map_err(|e| e.to_string())?;
+        self.command(cmd);
+
+        Ok(())
+    }
+
     ///////////////////////////////////////////////////////////////////////////
     /// Centering
     ///////////////////////////////////////////////////////////////////////////
 
+    /// Keep the shown view palette up to date, so that it's saved with the view.
+    fn store_view_palette(&mut self) {
+        if let Some(id) = self.palette_view {
+            let swatches = self.palette.swatches();
+
+            if let Some(v) = self.views.get_mut(id) {
+                v.palette = Some(swatches);
+            }
+        }
+    }
+
+    /// Show the palette of the active view if it has its own, or else the
+    /// session palette.
+    fn switch_palette(&mut self) {
+        self.store_view_palette();
+
+        let owner = self
+            .views
+            .active()
+            .filter(|v| v.palette.is_some())
+            .map(|v| v.id);
+
+        if owner == self.palette_view {
+            return;
+        }
+        if self.palette_view.is_none() {
+            self.session_palette = self.palette.swatches();
+        }
+        let swatches = match owner {
+            Some(id) => self.view(id).palette.clone().unwrap_or_default(),
+            None => std::mem::take(&mut self.session_palette),
+        };
+        self.palette.set_swatches(swatches);
+        self.palette_view = owner;
+        self.center_palette();
+    }
+
     /// Center the palette in the workspace.
     fn center_palette(&mut self) {
-        let h = self.settings["p/height"].to_u64() as usize;
-        let n = usize::min(self.palette.size(), h) as f32;
+        let n = self.palette.rows() as f32;
+        let (_, height) = self.ui_size();
         let p = &mut self.palette;
 
         p.x = 0.;
-        p.y = self.height / 2. - n * p.cellsize / 2.;
+        p.y = height / 2. - n * p.cellsize / 2.;
     }
 
     /// Vertically the active view in the workspace.
@@ -2360,7 +4680,7 @@ impl Session {
         if let Some(v) = self.views.active() {
             self.offset.y =
                 // TODO: This should center based on the total view height, not the frame height.
-                (self.height / 2. - v.fh as f32 / 2. * v.zoom - v.offset.y).floor();
+                (self.height / 2. - v.fh as f32 / 2. * v.scale().y - v.offset.y).floor();
             self.cursor_dirty();
         }
     }
@@ -2368,7 +4688,8 @@ impl Session {
     /// Horizontally center the active view in the workspace.
     fn center_active_view_h(&mut self) {
         if let Some(v) = self.views.active() {
-            self.offset.x = (self.width / 2. - v.width() as f32 * v.zoom / 2. - v.offset.x).floor();
+            self.offset.x =
+                (self.width / 2. - v.width() as f32 * v.scale().x / 2. - v.offset.x).floor();
             self.cursor_dirty();
         }
     }
@@ -2379,14 +4700,40 @@ impl Session {
         self.center_active_view_h();
     }
 
+    /// Check whether the cursor is over the minimap.
+    fn is_minimap_hovered(&self) -> bool {
+        match self.minimap_rect() {
+            Some(r) => r.contains(*self.cursor),
+            None => false,
+        }
+    }
+
+    /// Center the active view on the point under the cursor in the minimap.
+    fn center_minimap_cursor(&mut self) {
+        if let Some(r) = self.minimap_rect() {
+            let v = self.active_view();
+            let (sx, sy) = (r.width() / v.width() as f32, r.height() / v.height() as f32);
+            let s = v.scale();
+            let p = Vector2::new(
+                (self.cursor.x - r.x1) / sx * s.x,
+                (self.cursor.y - r.y1) / sy * s.y,
+            );
+
+            self.offset =
+                (Vector2::new(self.width / 2., self.height / 2.) - v.offset - p).map(f32::floor);
+            self.cursor_dirty();
+        }
+    }
+
     /// Center the given frame of the active view in the workspace.
     fn center_active_view_frame(&mut self, frame: usize) {
         self.center_active_view_v();
 
         if let Some(v) = self.views.active() {
-            let offset = (frame as u32 * v.fw) as f32 * v.zoom;
+            let zx = v.scale().x;
+            let offset = (frame as u32 * v.fw) as f32 * zx;
 
-            self.offset.x = self.width / 2. - offset - v.offset.x - v.fw as f32 / 2. * v.zoom;
+            self.offset.x = self.width / 2. - offset - v.offset.x - v.fw as f32 / 2. * zx;
             self.offset.x = self.offset.x.floor();
 
             self.cursor_dirty();
@@ -2449,34 +4796,259 @@ impl Session {
         }
     }
 
-    /// Set the active view zoom. Takes a center to zoom to.
-    fn zoom(&mut self, z: f32, center: SessionCoords) {
-        let px = center.x - self.offset.x;
-        let py = center.y - self.offset.y;
-
-        let zprev = self.active_view().zoom;
-        let zdiff = z / zprev;
+    /// Zoom the active view gradually, by a fractional number of steps.
+    /// The zoom is snapped to integer scales, so that pixels stay sharp.
+    fn zoom_smooth(&mut self, steps: f32, center: SessionCoords) {
+        let v = self.active_view();
+        let base = match self.smooth_zoom {
+            Some((id, z)) if id == v.id && z.round() == v.zoom => z,
+            _ => v.zoom,
+        };
+        let z = (base * (steps * Self::SMOOTH_ZOOM_RATE).exp2()).clamp(1., Self::MAX_ZOOM);
+        let (id, zprev) = (v.id, v.zoom);
+
+        self.smooth_zoom = Some((id, z));
+
+        if z.round() != zprev {
+            self.zoom(z.round(), center);
+        }
+    }
+
+    /// Zoom the active view so that it fits in the workspace.
+    fn zoom_fit(&mut self) {
+        self.record_jump();
+
+        let v = self.active_view();
+        let (w, h) = (
+            v.width() as f32 * v.pixel_ratio.x,
+            v.fh as f32 * v.pixel_ratio.y,
+        );
+        let (ww, wh) = (
+            self.width - Self::VIEW_MARGIN * 2.,
+            self.height - Self::VIEW_MARGIN * 2.,
+        );
+        let z = (ww / w).min(wh / h).floor().clamp(1., Self::MAX_ZOOM);
+
+        self.active_view_mut().zoom = z;
+        self.organize_views();
+        self.center_active_view();
+    }
+
+    /// Set the active view zoom. Takes a center to zoom to, which stays
+    /// over the same view pixel.
+    fn zoom(&mut self, z: f32, center: SessionCoords) {
+        let jump = self.jump_position();
+        self.jump_list.push_zoom(jump);
+
+        let px = center.x - self.offset.x;
+        let py = center.y - self.offset.y;
+
+        let v = self.active_view();
+        let zdiff = z / v.zoom;
+        let (vx, vy) = (v.offset.x, v.offset.y);
+
+        let nx = (px * zdiff).floor();
+        let ny = (py * zdiff).floor();
+
+        self.active_view_mut().zoom = z;
+        // Views below the active one move when its size changes, and so can
+        // the active view.
+        self.organize_views();
+
+        let v = self.active_view();
+        let dx = v.offset.x - (vx * zdiff);
+        let dy = v.offset.y - (vy * zdiff);
+
+        self.offset = Vector2::new(center.x - nx - dx, center.y - ny - dy).map(f32::floor);
+        self.cursor_dirty();
+    }
+
+    /// The current workspace position, for the jump list.
+    fn jump_position(&self) -> Jump {
+        Jump {
+            view: self.views.active_id,
+            offset: self.offset,
+            zoom: self.views.active().map_or(1., |v| v.zoom),
+        }
+    }
+
+    /// Record the current position in the jump list.
+    fn record_jump(&mut self) {
+        if self.views.active().is_some() {
+            let jump = self.jump_position();
+            self.jump_list.push(jump);
+        }
+    }
+
+    /// Go to a recorded position. Returns `false` if its view no longer exists.
+    fn jump_to(&mut self, j: Jump) -> bool {
+        if self.views.get(j.view).is_none() {
+            return false;
+        }
+        if self.views.active_id != j.view {
+            self.views.activate(j.view);
+            self.effects.push(Effect::ViewActivated(j.view));
+        }
+        self.active_view_mut().zoom = j.zoom;
+        self.organize_views();
+        self.offset = j.offset;
+        self.cursor_dirty();
+
+        true
+    }
+
+    /// Move the cursor to the next or previous pixel matching the active
+    /// view's search color, in reading order from the pixel under the cursor,
+    /// and center the view on it.
+    fn search_step(&mut self, forward: bool) {
+        let v = self.active_view();
+        let color = match v.search {
+            Some(c) => c,
+            None => {
+                self.message("Error: no active search", MessageType::Error);
+                return;
+            }
+        };
+        let (layer, h) = (v.active_layer_id, v.fh as i64);
+        let matches = v.resource.find_color(layer, color);
+
+        if matches.is_empty() {
+            self.message(format!("Pattern not found: {}", color), MessageType::Error);
+            return;
+        }
+        // Reading order: top to bottom, left to right.
+        let key = |x: i64, y: i64| (h - 1 - y, x);
+
+        let cursor = self.active_layer_coords(self.cursor);
+        let here = key(cursor.x as i64, cursor.y as i64);
+        let i = if forward {
+            matches
+                .iter()
+                .position(|p| key(p.x as i64, p.y as i64) > here)
+                .unwrap_or(0)
+        } else {
+            matches
+                .iter()
+                .rposition(|p| key(p.x as i64, p.y as i64) < here)
+                .unwrap_or(matches.len() - 1)
+        };
+        let p = matches[i];
+
+        self.record_jump();
+
+        let v = self.active_view();
+        // Center of the matching pixel, relative to the workspace offset.
+        let s = v.scale();
+        let center = v.offset
+            + Vector2::new((p.x as f32 + 0.5) * s.x, (p.y as f32 + 0.5) * s.y)
+            + v.layer_offset(layer, v.zoom);
+
+        self.offset = (Vector2::new(self.width / 2., self.height / 2.) - center).map(f32::floor);
+        let c = self.offset + center;
+        self.cursor = SessionCoords::new(c.x, c.y);
+        self.cursor_dirty();
+        self.message(
+            format!("Match {} of {} at {},{}", i + 1, matches.len(), p.x, p.y),
+            MessageType::Info,
+        );
+    }
+
+    ///////////////////////////////////////////////////////////////////////////
+    /// Split functions
+    ///////////////////////////////////////////////////////////////////////////
+
+    /// Check whether the cursor is over the split pane.
+    fn is_split_hovered(&self) -> bool {
+        match self.split {
+            Some(s) => s.rect(self.width, self.height).contains(*self.cursor),
+            None => false,
+        }
+    }
+
+    /// Split the workspace, displaying the active view in the split pane.
+    fn split(&mut self) {
+        let v = self.active_view();
+        let mut split = Split::new(v.id, v.zoom);
+
+        if let Some(s) = self.split {
+            split.ratio = s.ratio;
+        }
+        self.split = Some(split);
+        self.center_split();
+    }
+
+    /// Swap the active view with the view displayed in the split pane.
+    fn split_focus(&mut self) {
+        if let Some(split) = self.split {
+            let (id, zoom) = {
+                let v = self.active_view();
+                (v.id, v.zoom)
+            };
+            self.split = Some(Split {
+                view: id,
+                zoom,
+                ..split
+            });
+            self.center_split();
+
+            if split.view != id {
+                self.views.activate(split.view);
+                self.active_view_mut().zoom = split.zoom;
+                self.organize_views();
+                self.center_active_view();
+            }
+        }
+    }
+
+    /// Center the split pane view inside the split pane.
+    fn center_split(&mut self) {
+        if let Some(ref mut split) = self.split {
+            if let Some(v) = self.views.get(split.view) {
+                let r = split.rect(self.width, self.height);
 
-        let nx = (px * zdiff).floor();
-        let ny = (py * zdiff).floor();
+                let s = v.pixel_size(split.zoom);
 
-        let mut offset = Vector2::new(center.x - nx, center.y - ny);
-
-        let v = self.active_view_mut();
+                split.offset = Vector2::new(
+                    (r.width() / 2. - v.width() as f32 * s.x / 2.).floor(),
+                    (r.height() / 2. - v.fh as f32 * s.y / 2.).floor(),
+                );
+            }
+        }
+        self.cursor_dirty();
+    }
 
-        let vx = v.offset.x;
-        let vy = v.offset.y;
+    /// Zoom the split pane view in or out. Takes a center to zoom to.
+    fn split_zoom(&mut self, dir: Direction, center: SessionCoords) {
+        if let Some(ref mut split) = self.split {
+            let lvls = Self::ZOOM_LEVELS;
+            let i = lvls.iter().position(|z| split.zoom <= *z).unwrap_or(0);
 
-        v.zoom = z;
+            let z = match dir {
+                Direction::Forward => lvls.get(i + 1),
+                Direction::Backward => i.checked_sub(1).and_then(|i| lvls.get(i)),
+            };
 
-        let dx = v.offset.x - (vx * zdiff);
-        let dy = v.offset.y - (vy * zdiff);
+            if let Some(z) = z {
+                let r = split.rect(self.width, self.height);
+                let p = Vector2::new(center.x - r.x1, center.y - r.y1);
 
-        offset.x -= dx;
-        offset.y -= dy;
+                split.offset = (p - (p - split.offset) * (z / split.zoom)).map(f32::floor);
+                split.zoom = *z;
+            }
+        }
+    }
 
-        self.offset = offset.map(f32::floor);
-        self.organize_views();
+    /// Resize the split pane.
+    fn split_resize(&mut self, op: Op) {
+        if let Some(ref mut split) = self.split {
+            let ratio = match op {
+                Op::Incr => split.ratio + Split::RATIO_STEP,
+                Op::Decr => split.ratio - Split::RATIO_STEP,
+                Op::Set(r) => r,
+            };
+            split.ratio = ratio.clamp(Split::MIN_RATIO, Split::MAX_RATIO);
+        }
+        self.center_split();
     }
 
     ///////////////////////////////////////////////////////////////////////////
@@ -2487,6 +5059,14 @@ impl Session {
     fn command(&mut self, cmd: Command) {
         debug!("command: {:?}", cmd);
 
+        if cmd.is_edit() && self.settings["readonly"].is_set() {
+            self.readonly_error();
+            return;
+        }
+        let cmd = cmd.map_paths(|p| statusline::expand(p, |name| self.path_variable(name)));
+
+        self.share(&cmd);
+
         match cmd {
             Command::Mode(m) => {
                 self.toggle_mode(m);
@@ -2513,6 +5093,18 @@ impl Session {
             Command::BrushToggle(mode) => {
                 self.brush.toggle(mode);
             }
+            Command::BrushSnap(snap) => {
+                self.brush.set_snap(snap);
+            }
+            Command::BrushAxis => {
+                let v = self.active_view();
+                let p = self.active_layer_coords(self.cursor);
+
+                if v.layer_bounds().contains(p.map(|n| n as i32)) {
+                    let x = p.x as u32 % v.fw;
+                    self.brush.set_axis(x, p.y as u32);
+                }
+            }
             Command::Brush => {
                 self.unimplemented();
             }
@@ -2535,6 +5127,67 @@ impl Session {
                     b.size = Self::MIN_BRUSH_SIZE;
                 }
             }
+            Command::BrushSpacing(n) => {
+                self.brush.spacing = n.max(1);
+            }
+            Command::BrushVanishingPoint => {
+                let p = self.active_layer_coords(self.cursor);
+                self.brush.add_vanishing_point(p.map(|n| n.floor() as i32));
+            }
+            Command::BrushVanishingClear => {
+                self.brush.vanishing.clear();
+            }
+            Command::BrushJitterColor(n) => {
+                self.brush.jitter.color = n;
+            }
+            Command::BrushJitterAlpha(n) => {
+                self.brush.jitter.alpha = n;
+            }
+            Command::ColorPicker => {
+                self.picker = Some(Picker::new(self.fg));
+            }
+            Command::ColorBudget => {
+                let max = self.settings["max-colors"].to_u64();
+                let over = self.frames_over_budget();
+
+                if max == 0 {
+                    self.message("Error: `max-colors` isn't set", MessageType::Error);
+                } else if over.is_empty() {
+                    self.message(format!("No frames over {} colors", max), MessageType::Info);
+                } else {
+                    let frames = over
+                        .iter()
+                        .map(|(i, n)| format!("{} ({})", i, n))
+                        .collect::<Vec<_>>();
+                    self.message(
+                        format!("Frames over {} colors: {}", max, frames.join(", ")),
+                        MessageType::Warning,
+                    );
+                }
+            }
+            Command::ColorRamp(dir) => match self.palette.ramp_step(self.fg, i32::from(dir)) {
+                Some(color) => {
+                    self.fg = self.locked_color(color);
+                }
+                None => self.message(
+                    format!("Error: {} isn't in the palette", self.fg),
+                    MessageType::Error,
+                ),
+            },
+            Command::ColorRecent(i) => match self.palette.recent.get(i) {
+                Some(color) => self.pick_color(*color),
+                None => self.message(
+                    format!("Error: there is no recent color #{}", i),
+                    MessageType::Error,
+                ),
+            },
+            Command::BrushColor(c) => match self.palette.resolve(&c) {
+                Some(color) => self.pick_color(color),
+                None => self.message(
+                    format!("Error: palette color `{}` not found", c),
+                    MessageType::Error,
+                ),
+            },
             Command::FrameResize(fw, fh) => {
                 if fw == 0 || fh == 0 {
                     self.message(
@@ -2585,7 +5238,9 @@ impl Session {
             Command::ForceQuitAll => self.quit(ExitReason::Normal),
             Command::Echo(ref v) => {
                 let result = match v {
-                    Value::Str(s) => Ok(Value::Str(s.clone())),
+                    Value::Str(s) => Ok(Value::Str(statusline::expand(s, |item| {
+                        self.status_item(item)
+                    }))),
                     Value::Ident(s) => match s.as_str() {
                         "config/dir" => Ok(Value::Str(format!(
                             "{}",
@@ -2617,17 +5272,88 @@ impl Session {
             Command::PaletteClear => {
                 self.palette.clear();
             }
+            Command::PaletteView(true) => {
+                let swatches = self.palette.swatches();
+                let v = self.active_view_mut();
+
+                if v.palette.is_none() {
+                    v.palette = Some(swatches);
+                }
+            }
+            Command::PaletteView(false) => {
+                let id = self.views.active_id;
+
+                if self.palette_view == Some(id) {
+                    let swatches = std::mem::take(&mut self.session_palette);
+
+                    self.palette.set_swatches(swatches);
+                    self.palette_view = None;
+                    self.center_palette();
+                }
+                self.active_view_mut().palette = None;
+            }
+            Command::PaletteGroup(name) => {
+                if !self.palette.group(&name) {
+                    self.message(
+                        format!("Error: palette group `{}` already exists", name),
+                        MessageType::Error,
+                    );
+                }
+            }
             Command::PaletteGradient(colorstart, colorend, steps) => {
                 self.palette.gradient(colorstart, colorend, steps);
                 self.center_palette();
             }
+            Command::PaletteCycle(None) => {
+                if self.palette_cycle.take().is_some() {
+                    self.effects.push(Effect::PaletteCycled);
+                }
+            }
+            Command::PaletteCycle(Some((from, to, frames))) => {
+                let len = self.palette.colors.len();
+
+                if from >= to || to >= len {
+                    self.message(
+                        format!(
+                            "Error: cycling range must be two palette indices within 0..{}, in order",
+                            len.max(1) - 1
+                        ),
+                        MessageType::Error,
+                    );
+                } else {
+                    self.palette_cycle = Some(PaletteCycle {
+                        view: self.views.active_id,
+                        from,
+                        to,
+                        frames: frames.max(1),
+                        step: 0,
+                        ticks: 0,
+                    });
+                    self.effects.push(Effect::PaletteCycled);
+                }
+            }
+            Command::PaletteHighlight(None) => {
+                self.highlight = None;
+            }
+            Command::PaletteHighlight(Some(i)) => match self.palette.colors.get(i) {
+                Some(color) => {
+                    let v = self.active_view();
+                    let n = v.resource.find_color(v.active_layer_id, *color).len();
+
+                    self.highlight = Some(*color);
+                    self.message(format!("{} pixel(s) using {}", n, color), MessageType::Info);
+                }
+                None => {
+                    self.message(
+                        format!("Error: no palette color at index {}", i),
+                        MessageType::Error,
+                    );
+                }
+            },
             Command::PaletteSort => {
                 // Sort by total luminosity. This is pretty lame, but it's
                 // something to work with.
-                self.palette.colors.sort_by(|a, b| {
-                    (a.r as u32 + a.g as u32 + a.b as u32)
-                        .cmp(&(b.r as u32 + b.g as u32 + b.b as u32))
-                });
+                self.palette.sort();
             }
             Command::PaletteSample => {
                 {
@@ -2666,11 +5392,14 @@ impl Session {
                     self.message(format!("Error: `{}`: {}", path, err), MessageType::Error);
                 }
             },
+            Command::ZoomFit => {
+                self.zoom_fit();
+            }
             Command::Zoom(op) => {
                 let center = if let Some(s) = self.selection {
                     let v = self.active_view();
                     let coords = s.bounds().center().map(|n| n as f32)
-                        + v.layer_offset(v.active_layer_id, 1.);
+                        + Vector2::new(0., (v.fh * v.active_layer_id as u32) as f32);
                     self.session_coords(v.id, coords.into())
                 } else if self.hover_view.is_some() {
                     self.cursor
@@ -2742,8 +5471,132 @@ impl Session {
                 }
             }
             Command::ViewCenter => {
+                self.record_jump();
                 self.center_active_view();
             }
+            Command::JumpBack => loop {
+                match self.jump_list.back(self.jump_position()) {
+                    Some(j) if self.jump_to(j) => break,
+                    // The view was removed, skip it.
+                    Some(_) => continue,
+                    None => {
+                        self.message("Already at oldest position", MessageType::Hint);
+                        break;
+                    }
+                }
+            },
+            Command::JumpForward => loop {
+                match self.jump_list.forward() {
+                    Some(j) if self.jump_to(j) => break,
+                    Some(_) => continue,
+                    None => {
+                        self.message("Already at newest position", MessageType::Hint);
+                        break;
+                    }
+                }
+            },
+            Command::Search(None) => {
+                self.active_view_mut().search = None;
+            }
+            Command::Search(Some(color)) => {
+                let v = self.active_view_mut();
+                let n = v.resource.find_color(v.active_layer_id, color).len();
+
+                if n == 0 {
+                    self.message(format!("Pattern not found: {}", color), MessageType::Error);
+                } else {
+                    v.search = Some(color);
+                    self.message(format!("{} match(es) for {}", n, color), MessageType::Info);
+                }
+            }
+            Command::SearchNext => {
+                self.search_step(true);
+            }
+            Command::SearchPrev => {
+                self.search_step(false);
+            }
+            Command::Grid(0, _) => {
+                self.message(
+                    "Error: grid spacing must be greater than zero",
+                    MessageType::Error,
+                );
+            }
+            Command::Grid(n, color) => {
+                self.settings.set("grid", Value::Bool(true)).ok();
+                self.settings
+                    .set("grid/spacing", Value::U32Tuple(n, n))
+                    .ok();
+
+                if let Some(color) = color {
+                    self.settings.set("grid/color", Value::Rgba8(color)).ok();
+                }
+            }
+            Command::GuideAdd(axis, pos) => {
+                let p = self.active_layer_coords(self.cursor);
+                let guide = match axis {
+                    Axis::Horizontal => Guide::Horizontal(pos.unwrap_or(p.y as i32)),
+                    Axis::Vertical => Guide::Vertical(pos.unwrap_or(p.x as i32)),
+                };
+                let v = self.active_view_mut();
+
+                if !v.guides.contains(&guide) {
+                    v.guides.push(guide);
+                }
+            }
+            Command::GuideClear => {
+                self.active_view_mut().guides.clear();
+            }
+            Command::Split => {
+                self.split();
+            }
+            Command::SplitClose => {
+                self.split = None;
+                self.cursor_dirty();
+            }
+            Command::SplitFocus => {
+                if self.split.is_some() {
+                    self.split_focus();
+                } else {
+                    self.message("Error: workspace is not split", MessageType::Error);
+                }
+            }
+            Command::SplitResize(op) => {
+                if self.split.is_some() {
+                    self.split_resize(op);
+                } else {
+                    self.message("Error: workspace is not split", MessageType::Error);
+                }
+            }
+            Command::AnimationFps(0) => {
+                self.message(
+                    "Error: frame rate must be greater than zero",
+                    MessageType::Error,
+                );
+            }
+            Command::AnimationFps(n) => {
+                let delay = (1000 / n).max(1);
+                self.settings.set("animation/delay", Value::U32(delay)).ok();
+                self.active_view_mut().animation.delay = None;
+            }
+            Command::AnimationPlayback(m) => {
+                self.active_view_mut().animation.playback = m;
+            }
+            Command::AnimationRange(Some((a, b))) => {
+                let v = self.active_view_mut();
+                let l = v.animation.len();
+
+                if a <= b && b < l {
+                    v.animation.range = Some((a, b));
+                } else {
+                    self.message(
+                        format!("Error: frame range must be within 0..{}", l - 1),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::AnimationRange(None) => {
+                self.active_view_mut().animation.range = None;
+            }
             Command::FrameAdd => {
                 self.active_view_mut().extend();
             }
@@ -2759,9 +5612,157 @@ impl Session {
                     );
                 }
             }
-            Command::FrameRemove => {
-                self.active_view_mut().shrink();
-                self.check_selection();
+            Command::FrameInsert(n) => {
+                let v = self.active_view_mut();
+                let l = v.animation.len();
+                if n <= l {
+                    v.insert_frame(n);
+                } else {
+                    self.message(
+                        format!("Error: insert index must be in the range {}..{}", 0, l),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::FrameDup(n) => {
+                let v = self.active_view_mut();
+                let l = v.animation.len();
+                if n < l {
+                    v.duplicate_frame(n);
+                } else {
+                    self.message(
+                        format!("Error: frame index must be in the range {}..{}", 0, l - 1),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::FrameRemove(None) => {
+                let v = self.active_view_mut();
+                let l = v.animation.len();
+                match v.remove_frame(l - 1) {
+                    Ok(()) => self.check_selection(),
+                    Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+                }
+            }
+            Command::FrameRemove(Some(n)) => {
+                let v = self.active_view_mut();
+                let l = v.animation.len();
+                if n < l {
+                    match v.remove_frame(n) {
+                        Ok(()) => self.check_selection(),
+                        Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+                    }
+                } else {
+                    self.message(
+                        format!("Error: frame index must be in the range {}..{}", 0, l - 1),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::FrameMove(from, to) => {
+                let v = self.active_view_mut();
+                let l = v.animation.len();
+                if from < l && to < l {
+                    v.move_frame(from, to);
+                } else {
+                    self.message(
+                        format!("Error: frame index must be in the range {}..{}", 0, l - 1),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::FrameCopy(view, n) => {
+                let id = self.views.iter().nth(view).map(|v| v.id);
+
+                if let Some(v) = id.map(|id| self.view_mut(id)) {
+                    let l = v.animation.len();
+                    if n < l {
+                        let (fw, fh) = (v.fw, v.fh);
+                        let x = (n as u32 * fw) as i32;
+
+                        let area = Rect::new(x, 0, x + fw as i32, fh as i32);
+                        let snapshot = v
+                            .resource
+                            .layer(v.active_layer_id)
+                            .get_snapshot_rect(&area)
+                            .map(|(_, p)| p);
+
+                        v.yank(area);
+                        self.copied_frame = Some((fw, fh));
+                        self.clipboard = snapshot.map(|p| (fw, fh, p));
+                    } else {
+                        self.message(
+                            format!("Error: frame index must be in the range {}..{}", 0, l - 1),
+                            MessageType::Error,
+                        );
+                    }
+                } else {
+                    self.message(
+                        format!(
+                            "Error: view index must be in the range {}..{}",
+                            0,
+                            self.views.iter().count() - 1
+                        ),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::FramePaste(n) => {
+                let frame = match n {
+                    Some(n) => Some(n),
+                    None => {
+                        let v = self.active_view();
+                        let cursor = self.active_view_coords(self.cursor);
+
+                        if v.bounds().contains(*ViewCoords::<i32>::from(cursor)) {
+                            Some(v.extent().to_frame(cursor.into()))
+                        } else {
+                            None
+                        }
+                    }
+                };
+                let v = self.active_view();
+                let (fw, fh, l) = (v.fw, v.fh, v.animation.len());
+
+                match (self.copied_frame, frame) {
+                    (None, _) => {
+                        self.message("Error: no frame was copied", MessageType::Error);
+                    }
+                    (Some(size), _) if size != (fw, fh) => {
+                        self.message(
+                            format!("Error: copied frame size must be {}x{}", fw, fh),
+                            MessageType::Error,
+                        );
+                    }
+                    (Some(_), Some(n)) if n < l => {
+                        let x = (n as u32 * fw) as i32;
+                        let area = Rect::new(x, 0, x + fw as i32, fh as i32);
+
+                        // The copied frame replaces the frame, including where it's
+                        // transparent, so the frame is cleared before pasting.
+                        self.effects.extend_from_slice(&[
+                            Effect::ViewBlendingChanged(Blending::Constant),
+                            Effect::ViewPaintFinal(vec![Shape::Rectangle(
+                                area.map(|n| n as f32),
+                                ZDepth::default(),
+                                Rotation::ZERO,
+                                Stroke::NONE,
+                                Fill::Solid(Rgba8::TRANSPARENT.into()),
+                            )]),
+                        ]);
+                        self.lock_paste();
+                        self.active_view_mut().paste(area);
+                    }
+                    (Some(_), Some(_)) => {
+                        self.message(
+                            format!("Error: frame index must be in the range {}..{}", 0, l - 1),
+                            MessageType::Error,
+                        );
+                    }
+                    (Some(_), None) => {
+                        self.message("Error: cursor is not over a frame", MessageType::Error);
+                    }
+                }
             }
             Command::LayerAdd => {
                 self.active_view_mut().add_layer(None);
@@ -2803,12 +5804,27 @@ impl Session {
                         self.message(format!("Error: {}", e), MessageType::Error);
                     }
                     Ok(ref old) => {
-                        if old != v {
-                            self.setting_changed(k, old, v);
+                        let new = self.settings[k].clone();
+
+                        if *old != new {
+                            self.setting_changed(k, old, &new);
                         }
                     }
                 }
             }
+            Command::Settings(None) => {
+                for (k, v) in self.settings.iter() {
+                    info!("{} = {}", k, v);
+                }
+                self.command(Command::Mode(Mode::Help));
+            }
+            Command::Settings(Some(ref k)) => match self.settings.get(k) {
+                Some(v) => self.message(format!("{} = {}", k, v), MessageType::Echo),
+                None => self.message(
+                    format!("Error: no such setting `{}`", k),
+                    MessageType::Error,
+                ),
+            },
             #[allow(mutable_borrow_reservation_conflict)]
             Command::Toggle(ref k) => match self.settings.get(k) {
                 Some(Value::Bool(b)) => self.command(Command::Set(k.clone(), Value::Bool(!b))),
@@ -2837,20 +5853,130 @@ impl Session {
                     Err(e) => self.message(format!("Error: {}: {:?}", e, path), MessageType::Error),
                 }
             }
-            Command::Source(Some(ref path)) => {
-                if let Err(ref e) = self.source_path(path) {
+            Command::Theme(None) => {
+                self.message(format!("theme: {}", self.theme.name), MessageType::Echo);
+            }
+            Command::Theme(Some(name)) => match self.load_theme(&name) {
+                Ok(()) => {}
+                Err(e) => self.message(
+                    format!("Error loading theme `{}`: {}", name, e),
+                    MessageType::Error,
+                ),
+            },
+            Command::Source(Some(ref path)) => {
+                if let Err(ref e) = self.source_path(path) {
+                    self.message(
+                        format!("Error sourcing `{}`: {}", path, e),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::NetHost(ref addr) => match net::Link::host(addr.as_str()) {
+                Ok(link) => {
+                    let addr = link
+                        .local_addr()
+                        .map_or_else(|| addr.clone(), |a| a.to_string());
+
+                    self.net = Some(link);
+                    self.message(
+                        format!("Hosting a shared session on {}", addr),
+                        MessageType::Info,
+                    );
+                }
+                Err(e) => self.message(
+                    format!("Error: can't host on {}: {}", addr, e),
+                    MessageType::Error,
+                ),
+            },
+            Command::NetJoin(ref addr) => match net::Link::join(addr.as_str()) {
+                Ok(link) => {
+                    self.net = Some(link);
+                    self.message(
+                        format!("Joined the shared session at {}", addr),
+                        MessageType::Info,
+                    );
+                }
+                Err(e) => self.message(
+                    format!("Error: can't join {}: {}", addr, e),
+                    MessageType::Error,
+                ),
+            },
+            Command::NetLeave => {
+                if self.net.take().is_some() {
+                    self.message("Left the shared session", MessageType::Info);
+                } else {
+                    self.message("Error: the session isn't shared", MessageType::Error);
+                }
+            }
+            Command::RecordStart(path, n) => {
+                if self.timelapse.is_some() {
+                    self.message("Error: already recording", MessageType::Error);
+                } else {
+                    match Timelapse::new(&path, self.views.active_id, n) {
+                        Ok(t) => {
+                            self.timelapse = Some(t);
+                            self.message(
+                                format!("Recording timelapse to {}", path),
+                                MessageType::Info,
+                            );
+                        }
+                        Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+                    }
+                }
+            }
+            Command::CheckpointSave(name) => {
+                self.active_view_mut().resource.checkpoint(&name);
+                self.message(format!("Checkpoint `{}` saved", name), MessageType::Info);
+            }
+            Command::CheckpointRevert(name) => {
+                let checkpoint = self.active_view().resource.checkpoints.get(&name);
+
+                if let Some(edit) = checkpoint.map(|c| c.edit) {
+                    self.revert_view(self.views.active_id, edit);
+                } else {
+                    self.message(
+                        format!("Error: no checkpoint named `{}`", name),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::CheckpointDiff(None) => {
+                self.active_view_mut().diff = None;
+            }
+            Command::CheckpointDiff(Some(name)) => {
+                let v = self.active_view_mut();
+
+                if v.resource.checkpoints.contains_key(&name) {
+                    v.diff = Some(name);
+                } else {
                     self.message(
-                        format!("Error sourcing `{}`: {}", path, e),
+                        format!("Error: no checkpoint named `{}`", name),
                         MessageType::Error,
                     );
                 }
             }
-            Command::Source(None) => {
-                self.message(
+            Command::RecordStop => {
+                if let Some(t) = self.timelapse.take() {
+                    let path = t.path.clone();
+
+                    match t.finish() {
+                        Ok(n) => self.message(
+                            format!("Timelapse written to {} ({} frames)", path.display(), n),
+                            MessageType::Info,
+                        ),
+                        Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+                    }
+                } else {
+                    self.message("Error: not recording", MessageType::Error);
+                }
+            }
+            Command::Source(None) => match self.init_script.clone() {
+                Some((path, _)) => self.source_init(&path),
+                None => self.message(
                     format!("Error: source command requires a path"),
                     MessageType::Error,
-                );
-            }
+                ),
+            },
             Command::Edit(ref paths) => {
                 if paths.is_empty() {
                     self.unimplemented();
@@ -2870,6 +5996,16 @@ impl Session {
                     }
                 }
             }
+            Command::Import(ref path, pos) => match self.import(path, pos) {
+                Ok((w, h)) => self.message(
+                    format!("\"{}\" {}x{} imported", path, w, h),
+                    MessageType::Info,
+                ),
+                Err(e) => self.message(
+                    format!("Error importing {}: {}", path, e),
+                    MessageType::Error,
+                ),
+            },
             Command::EditFrames(ref paths) => {
                 if !paths.is_empty() {
                     if let Err(e) = self.edit_frames(paths) {
@@ -2880,24 +6016,47 @@ impl Session {
                     }
                 }
             }
-            Command::Export(scale, path) => {
+            Command::Export(path, opts) => {
                 let view = self.active_view();
                 let active_layer_id = view.active_layer_id;
                 let nlayers = view.layers.len();
                 let id = view.id;
-                let scale = scale.unwrap_or(view.zoom as u32);
+                let format = opts.format.or_else(|| {
+                    Path::new(&path)
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .and_then(ExportFormat::from_extension)
+                });
+                // Console assets and source data are exported at their actual size,
+                // and filtered exports are scaled by the filter's factor by default.
+                let scale = opts
+                    .scale
+                    .or_else(|| opts.filter.map(|f| f.factor()))
+                    .unwrap_or(match self.settings["export/scale"].to_u64() {
+                        _ if opts.profile.is_some() || format.is_some() => 1,
+                        0 => view.zoom as u32,
+                        n => n as u32,
+                    });
 
                 if nlayers > 1 {
                     self.message(
                         format!("Error: the `export` command only works with a single layer"),
                         MessageType::Error,
                     );
+                } else if scale == 0 {
+                    self.message("Error: export scale must be at least 1", MessageType::Error);
                 } else if let Err(e) =
-                    self.export_layer_as(id, active_layer_id, Path::new(&path), scale)
+                    self.export_layer_as(id, active_layer_id, Path::new(&path), scale, &opts)
                 {
                     self.message(format!("Error: {}", e), MessageType::Error);
                 }
             }
+            Command::ExportAll(dir, scale) => {
+                match self.export_all(dir.as_ref().map(Path::new), scale) {
+                    Ok(msg) => self.message(msg, MessageType::Info),
+                    Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+                }
+            }
             Command::Write(None) => match self.save_view(self.views.active_id) {
                 Ok((storage, written)) => self.message(
                     format!("\"{}\" {} pixels written", storage, written),
@@ -2906,7 +6065,13 @@ impl Session {
                 Err(err) => self.message(format!("Error: {}", err), MessageType::Error),
             },
             Command::Write(Some(ref path)) => {
-                match self.active_view_mut().save_as(&Path::new(path).into()) {
+                let delay = self.view_animation_delay(self.views.active_id);
+                let storage = Path::new(path).into();
+
+                match self
+                    .backup_files(&storage)
+                    .and_then(|()| self.active_view_mut().save_as(&storage, delay))
+                {
                     Ok(written) => self.message(
                         format!("\"{}\" {} pixels written", path, written),
                         MessageType::Info,
@@ -2928,10 +6093,13 @@ impl Session {
                 let paths = NonEmpty::from_slice(paths.as_slice())
                     .expect("views always have at least one frame");
 
-                let view = self.active_view_mut();
+                let delay = self.view_animation_delay(self.views.active_id);
                 let fs = FileStorage::Range(paths);
 
-                match view.save_as(&fs) {
+                match self
+                    .backup_files(&fs)
+                    .and_then(|()| self.active_view_mut().save_as(&fs, delay))
+                {
                     Ok(written) => self.message(
                         format!("{} {} pixels written", fs, written),
                         MessageType::Info,
@@ -2939,6 +6107,10 @@ impl Session {
                     Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
                 }
             }
+            Command::Backups(n) => match self.backups(n) {
+                Ok(msg) => self.message(msg, MessageType::Info),
+                Err(err) => self.message(format!("Error: {}", err), MessageType::Error),
+            },
             Command::WriteQuit => {
                 if self.save_view(self.views.active_id).is_ok() {
                     self.quit_view(self.views.active_id);
@@ -2977,14 +6149,218 @@ impl Session {
                 self.key_bindings = KeyBindings::default();
             }
             Command::Undo => {
-                self.undo(self.views.active_id);
+                self.undo(self.undo_view(Direction::Backward));
             }
             Command::Redo => {
-                self.redo(self.views.active_id);
+                self.redo(self.undo_view(Direction::Forward));
+            }
+            Command::UndoBegin => {
+                self.active_view_mut().resource.begin_group();
+            }
+            Command::UndoEnd => {
+                self.undo_group_ends.push(self.views.active_id);
+            }
+            Command::UndoGroup(cmds) => {
+                let id = self.views.active_id;
+
+                self.active_view_mut().resource.begin_group();
+                for cmd in cmds {
+                    self.command(cmd);
+                }
+                self.undo_group_ends.push(id);
+            }
+            Command::UndoOlder => {
+                let edit = self.active_view().resource.current_edit();
+
+                if let Some(edit) = edit.checked_sub(1) {
+                    self.revert_view(self.views.active_id, edit);
+                }
+            }
+            Command::UndoNewer => {
+                let edit = self.active_view().resource.current_edit() + 1;
+
+                if edit < self.active_view().resource.history.len() {
+                    self.revert_view(self.views.active_id, edit);
+                }
+            }
+            Command::UndoGoto(edit) => {
+                if edit < self.active_view().resource.history.len() {
+                    self.revert_view(self.views.active_id, edit);
+                } else {
+                    self.message(
+                        format!("Error: edit #{} doesn't exist", edit),
+                        MessageType::Error,
+                    );
+                }
+            }
+            Command::ViewLink(path) => match self.find_view(&path) {
+                Some(id) if id == self.views.active_id => {
+                    self.message(
+                        "Error: a view can't be linked to itself",
+                        MessageType::Error,
+                    );
+                }
+                Some(id) => {
+                    let active = self.views.active_id;
+
+                    if !self.linked_views(active).contains(&id) {
+                        self.links.push((active, id));
+                    }
+                }
+                None => {
+                    self.message(
+                        format!("Error: no view is showing `{}`", path),
+                        MessageType::Error,
+                    );
+                }
+            },
+            Command::ViewUnlink => {
+                let active = self.views.active_id;
+                self.links.retain(|(a, b)| *a != active && *b != active);
+            }
+            Command::ViewStats => {
+                /// Number of colors to list with their pixel counts.
+                const COLORS_SHOWN: usize = 8;
+
+                let v = self.active_view();
+                let stats = v.resource.stats();
+                let mut colors = stats
+                    .colors
+                    .iter()
+                    .take(COLORS_SHOWN)
+                    .map(|(c, n)| format!("{} ({})", c, n))
+                    .collect::<Vec<_>>();
+
+                if stats.colors.len() > COLORS_SHOWN {
+                    colors.push(format!("+{} more", stats.colors.len() - COLORS_SHOWN));
+                }
+                for (c, n) in stats.colors.iter() {
+                    info!("stats: {} {}", c, n);
+                }
+
+                let mut msg = format!(
+                    "{}x{}, {} frame(s), {} color(s), {:.1}% transparent, \
+                     {} snapshot(s) using {}KB",
+                    v.fw,
+                    v.fh,
+                    v.animation.len(),
+                    stats.colors.len(),
+                    stats.transparent as f64 * 100. / stats.pixels.max(1) as f64,
+                    stats.snapshots,
+                    stats.memory.div_ceil(1024),
+                );
+                if !colors.is_empty() {
+                    msg = format!("{}: {}", msg, colors.join(", "));
+                }
+                self.message(msg, MessageType::Info);
+            }
+            Command::Preview(None) => {
+                self.preview = None;
+            }
+            Command::Preview(Some(p)) => {
+                if p.width == 0 || p.height == 0 {
+                    self.message(
+                        "Error: preview size must be greater than zero",
+                        MessageType::Error,
+                    );
+                } else {
+                    self.preview = Some(p);
+                }
+            }
+            Command::PreviewWindow(0) => {
+                self.preview_window = None;
+            }
+            Command::PreviewWindow(zoom) => {
+                self.preview_window = Some(zoom);
+            }
+            Command::PreviewBackdrop(None) => {
+                self.preview_backdrop = None;
+                self.effects.push(Effect::PreviewBackdropChanged);
+            }
+            Command::PreviewBackdrop(Some(Value::Rgba8(color))) => {
+                self.preview_backdrop = Some(Backdrop::color(color));
+                self.effects.push(Effect::PreviewBackdropChanged);
+            }
+            Command::PreviewBackdrop(Some(Value::Str(path))) => {
+                match crate::io::load_image(&path) {
+                    Ok((width, height, pixels, _)) => {
+                        self.preview_backdrop = Some(Backdrop {
+                            width,
+                            height,
+                            pixels,
+                        });
+                        self.effects.push(Effect::PreviewBackdropChanged);
+                    }
+                    Err(e) => self.message(format!("Error: `{}`: {}", path, e), MessageType::Error),
+                }
+            }
+            Command::PreviewBackdrop(Some(_)) => {
+                self.message(
+                    "Error: backdrop must be a color or a path",
+                    MessageType::Error,
+                );
+            }
+            Command::TileCopy(src, dst) => {
+                if let Some(size) = self.tile_size() {
+                    if !self.active_view_mut().copy_tile(size, src, dst) {
+                        self.message("Error: tile doesn't exist", MessageType::Error);
+                    }
+                }
+            }
+            Command::TileSwap(a, b) => {
+                if let Some(size) = self.tile_size() {
+                    if !self.active_view_mut().swap_tiles(size, a, b) {
+                        self.message("Error: tile doesn't exist", MessageType::Error);
+                    }
+                }
+            }
+            Command::TileDuplicates => {
+                if let Some(size) = self.tile_size() {
+                    let dupes = self.active_view().duplicate_tiles(size);
+
+                    if dupes.is_empty() {
+                        self.message("No duplicate tiles", MessageType::Info);
+                    } else {
+                        let groups = dupes
+                            .iter()
+                            .map(|g| {
+                                g.iter()
+                                    .map(|i| format!("#{}", i))
+                                    .collect::<Vec<_>>()
+                                    .join(" = ")
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", ");
+                        self.message(format!("Duplicate tiles: {}", groups), MessageType::Info);
+                    }
+                }
+            }
+            Command::UndoTree => {
+                let resource = &self.active_view().resource;
+                let leaves = resource
+                    .history_leaves()
+                    .iter()
+                    .map(|e| format!("#{}", e))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                self.message(
+                    format!(
+                        "Edit #{} of {}, branches: {}",
+                        resource.current_edit(),
+                        resource.history.len() - 1,
+                        leaves
+                    ),
+                    MessageType::Info,
+                );
             }
             Command::Tool(t) => {
                 self.tool(t);
             }
+            Command::Flood(opts) => {
+                self.flood = opts;
+                self.tool(Tool::FloodFill);
+            }
             Command::ToolPrev => {
                 self.prev_tool();
             }
@@ -3085,6 +6461,7 @@ impl Session {
             }
             Command::SelectionPaste => {
                 if let (Mode::Visual(VisualState::Pasting), Some(s)) = (self.mode, self.selection) {
+                    self.lock_paste();
                     self.active_view_mut().paste(s.abs().bounds());
                 } else {
                     // TODO: Enter paste mode?
@@ -3093,6 +6470,93 @@ impl Session {
             Command::SelectionYank => {
                 self.yank_selection();
             }
+            Command::SelectionShift(x, y, wrap) => {
+                if let (Mode::Visual(VisualState::Selecting { .. }), Some(s)) =
+                    (self.mode, self.selection)
+                {
+                    let v = self.active_view_mut();
+                    let s = s.abs().bounds();
+
+                    if s.intersects(v.layer_bounds()) {
+                        let s = s.intersection(v.layer_bounds());
+
+                        // Like flipping, this works by copying the shifted image into
+                        // the paste buffer, and pasting.
+                        v.shift(s, (x, y), wrap);
+                        v.paste(s);
+
+                        // Snapshot rows are top to bottom, while view coordinates point up.
+                        let (w, h) = (s.width() as u32, s.height() as u32);
+                        let snapshot = v.resource.layer(v.active_layer_id).get_snapshot_rect(&s);
+
+                        self.clipboard =
+                            snapshot.map(|(_, p)| (w, h, pixels::shift(&p, w, h, (x, -y), wrap)));
+                        self.copied_frame = None;
+                        self.selection = Some(Selection::from(s));
+                        self.switch_mode(Mode::Visual(VisualState::Pasting));
+                    }
+                    self.command(Command::SelectionErase);
+                    self.command(Command::Mode(Mode::Visual(VisualState::Selecting {
+                        dragging: false,
+                    })));
+                }
+            }
+            Command::Flip(axis, scope) => {
+                let flip = match axis {
+                    Axis::Horizontal => (true, false),
+                    Axis::Vertical => (false, true),
+                };
+                self.transform_frames(scope, |pixels, w, h| {
+                    (pixels::flip(pixels, w, h, flip), w, h)
+                });
+            }
+            Command::Rotate(angle, scope) => {
+                let v = self.active_view();
+
+                // The frames of a view all have the same size, so a single frame can only
+                // be turned sideways if it's square.
+                if scope == Scope::Frame && angle != 180 && v.fw != v.fh && v.animation.len() > 1 {
+                    self.message(
+                        "Error: only square frames can be rotated by 90 degrees on their own",
+                        MessageType::Error,
+                    );
+                    return;
+                }
+                self.transform_frames(scope, |pixels, w, h| {
+                    pixels::rotate(pixels, w, h, angle / 90)
+                });
+            }
+            Command::CropAuto(padding) => {
+                let v = self.active_view();
+                let (fw, fh) = (v.fw, v.fh);
+
+                // Frames all have the same size, so they are cropped to the bounds
+                // of the content of every frame combined.
+                let bounds = match v.content_bounds() {
+                    Some(b) => b,
+                    None => {
+                        self.message("Error: view has no content to crop to", MessageType::Error);
+                        return;
+                    }
+                };
+                let area = Rect::new(
+                    bounds.x1.saturating_sub(padding),
+                    bounds.y1.saturating_sub(padding),
+                    bounds.x2.saturating_add(padding).min(fw),
+                    bounds.y2.saturating_add(padding).min(fh),
+                );
+                self.transform_frames(Scope::View, move |pixels, w, h| {
+                    (
+                        pixels::crop(pixels, w, h, area),
+                        area.width(),
+                        area.height(),
+                    )
+                });
+                self.message(
+                    format!("Cropped frames to {}x{}", area.width(), area.height()),
+                    MessageType::Info,
+                );
+            }
             Command::SelectionFlip(dir) => {
                 if let (Mode::Visual(VisualState::Selecting { .. }), Some(s)) =
                     (self.mode, self.selection)
@@ -3103,11 +6567,21 @@ impl Session {
                     if s.intersects(v.layer_bounds()) {
                         let s = s.intersection(v.layer_bounds());
 
+                        let (w, h) = (s.width() as u32, s.height() as u32);
+                        let axes = match dir {
+                            Axis::Horizontal => (true, false),
+                            Axis::Vertical => (false, true),
+                        };
                         // The flip operation works by copying the flipped image into
                         // the paste buffer, and pasting.
                         v.flip(s, dir);
                         v.paste(s);
 
+                        let snapshot = v.resource.layer(v.active_layer_id).get_snapshot_rect(&s);
+
+                        self.clipboard =
+                            snapshot.map(|(_, p)| (w, h, pixels::flip(&p, w, h, axes)));
+                        self.copied_frame = None;
                         self.selection = Some(Selection::from(s));
                         self.switch_mode(Mode::Visual(VisualState::Pasting));
                     }
@@ -3128,13 +6602,14 @@ impl Session {
             }
             Command::SelectionFill(color) => {
                 if let Some(s) = self.selection {
+                    let color = self.locked_color(color.unwrap_or(self.fg));
                     self.effects
                         .push(Effect::ViewPaintFinal(vec![Shape::Rectangle(
                             s.abs().bounds().map(|n| n as f32),
                             ZDepth::default(),
                             Rotation::ZERO,
                             Stroke::NONE,
-                            Fill::Solid(color.unwrap_or(self.fg).into()),
+                            Fill::Solid(color.into()),
                         )]));
                     self.active_view_mut().touch_layer();
                 }
@@ -3155,9 +6630,15 @@ impl Session {
                 }
             }
             Command::PaintColor(rgba, x, y) => {
+                let rgba = self.locked_color(rgba);
                 self.active_view_mut().paint_color(rgba, x, y);
             }
+            Command::PaintFilter(ref cmd) => match self.filter_pixels(cmd) {
+                Ok(n) => self.message(format!("{} pixel(s) painted", n), MessageType::Info),
+                Err(e) => self.message(format!("Error: {}", e), MessageType::Error),
+            },
             Command::PaintLine(rgba, x1, y1, x2, y2) => {
+                let rgba = self.locked_color(rgba);
                 let mut stroke = vec![];
                 Brush::line(Point2::new(x1, y1), Point2::new(x2, y2), &mut stroke);
                 for pt in stroke {
@@ -3240,6 +6721,8 @@ impl Session {
         if color.a == 0x0 {
             return;
         }
+        let color = self.locked_color(color);
+
         if color != self.fg {
             self.bg = self.fg;
             self.fg = color;
@@ -3247,6 +6730,71 @@ impl Session {
         // TODO: Switch to brush.
     }
 
+    /// The color to use in place of the given one. With `palette/lock` set, this is
+    /// the nearest palette color. Transparent colors are left alone, since they erase.
+    fn locked_color(&self, color: Rgba8) -> Rgba8 {
+        if color.a == 0x0 || !self.settings["palette/lock"].is_set() {
+            return color;
+        }
+        self.palette.nearest(color).unwrap_or(color)
+    }
+
+    /// With the palette locked, snap the paste buffer to the palette before pasting.
+    /// The paste buffer is kept by the renderer, so it's reloaded from the clipboard,
+    /// which holds the same pixels.
+    fn lock_paste(&mut self) {
+        if !self.settings["palette/lock"].is_set() {
+            return;
+        }
+        if let Some((w, h, pixels)) = &self.clipboard {
+            let (w, h) = (*w, *h);
+            let pixels = pixels.iter().map(|c| self.locked_color(*c)).collect();
+
+            self.active_view_mut().load_paste(w, h, pixels);
+        }
+    }
+
+    /// Handle a key press while the color picker is open.
+    fn handle_picker_key(&mut self, key: platform::Key, modifiers: platform::ModifiersState) {
+        let picker = match &mut self.picker {
+            Some(picker) => picker,
+            None => return,
+        };
+
+        match key {
+            platform::Key::Up if modifiers.shift => picker.step_hue(1),
+            platform::Key::Down if modifiers.shift => picker.step_hue(-1),
+            platform::Key::Up => picker.step(0, 1),
+            platform::Key::Down => picker.step(0, -1),
+            platform::Key::Left => picker.step(-1, 0),
+            platform::Key::Right => picker.step(1, 0),
+            platform::Key::Return => {
+                let color = picker.color();
+
+                if modifiers.shift {
+                    self.palette.add(color);
+                    self.center_palette();
+                }
+                self.pick_color(color);
+                self.picker = None;
+            }
+            platform::Key::Escape => {
+                self.picker = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Record a color as painted with, in the palette's recent colors.
+    fn add_recent_color(&mut self, color: Rgba8) {
+        let count = self.palette.recent.len();
+
+        self.palette.add_recent(color);
+        if self.palette.recent.len() != count {
+            self.center_palette();
+        }
+    }
+
     fn sample_color(&mut self) {
         if let Some(color) = self.hover_color {
             self.pick_color(color);
@@ -3258,6 +6806,200 @@ impl Session {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_init_reload() {
+        let tmp = tempfile::tempdir().unwrap();
+        let init = tmp.path().join("init.rx");
+        let proj_dirs = dirs::ProjectDirs::from("io", "cloudhead", "rx").unwrap();
+        let base_dirs = dirs::BaseDirs::new().unwrap();
+
+        std::fs::write(&init, "set checker = on\n").unwrap();
+
+        let mut session = Session::new(64, 64, tmp.path().to_owned(), proj_dirs, base_dirs)
+            .init(Some(init.clone()))
+            .unwrap();
+        assert!(session.settings["checker"].is_set());
+
+        let f = File::create(&init).unwrap();
+        (&f).write_all(b"set checker = off\n").unwrap();
+        f.set_modified(time::SystemTime::now() + time::Duration::from_secs(60))
+            .unwrap();
+
+        // Changes aren't picked up until the next poll.
+        session.reload_init();
+        assert!(session.settings["checker"].is_set());
+
+        session.init_checked -= Session::INIT_POLL_INTERVAL;
+        session.reload_init();
+        assert!(!session.settings["checker"].is_set());
+    }
+
+    #[test]
+    fn test_net_receive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let proj_dirs = dirs::ProjectDirs::from("io", "cloudhead", "rx").unwrap();
+        let base_dirs = dirs::BaseDirs::new().unwrap();
+        let mut session = Session::new(64, 64, tmp.path().to_owned(), proj_dirs, base_dirs)
+            .with_blank(FileStatus::NoFile, 8, 8)
+            .init(Some(PathBuf::from("-")))
+            .unwrap();
+
+        session.net_receive(vec![String::from("f/add")]);
+        assert_eq!(session.active_view().animation.len(), 2);
+
+        // Anything that isn't an editing operation is dropped.
+        let cwd = session.cwd.clone();
+        session.net_receive(vec![
+            String::from("set checker = on"),
+            String::from("cd /"),
+            String::from("paint/filter \"true\""),
+            String::from("q!"),
+        ]);
+        assert!(!session.settings["checker"].is_set());
+        assert_eq!(session.cwd, cwd);
+        assert!(!matches!(session.state, State::Closing(_)));
+        assert_eq!(session.views.iter().count(), 1);
+        assert_eq!(
+            session.message.to_string(),
+            "Error: ignoring `q!` from a peer"
+        );
+    }
+
+    #[test]
+    fn test_path_variable() {
+        let tmp = tempfile::tempdir().unwrap();
+        let proj_dirs = dirs::ProjectDirs::from("io", "cloudhead", "rx").unwrap();
+        let base_dirs = dirs::BaseDirs::new().unwrap();
+        let mut session = Session::new(64, 64, tmp.path().to_owned(), proj_dirs, base_dirs);
+
+        // Without views, only variables that don't need one are expanded.
+        assert_eq!(session.path_variable("f"), None);
+        assert_eq!(session.path_variable("x"), None);
+        assert!(session.path_variable("date").is_some());
+
+        session.blank(FileStatus::NoFile, 8, 8);
+        assert_eq!(session.path_variable("f").as_deref(), Some(""));
+        assert_eq!(session.path_variable("d").as_deref(), Some("."));
+        assert_eq!(session.path_variable("x"), None);
+    }
+
+    #[test]
+    fn test_minimap_rect() {
+        let tmp = tempfile::tempdir().unwrap();
+        let proj_dirs = dirs::ProjectDirs::from("io", "cloudhead", "rx").unwrap();
+        let base_dirs = dirs::BaseDirs::new().unwrap();
+        let mut session = Session::new(640, 480, tmp.path().to_owned(), proj_dirs, base_dirs)
+            .with_blank(FileStatus::NoFile, 16, 16)
+            .init(Some(PathBuf::from("-")))
+            .unwrap();
+
+        session.source_line("set ui/minimap = on").unwrap();
+        session.active_view_mut().pixel_ratio = Vector2::new(1., 2.);
+
+        // The minimap has the shape of the view as it's displayed.
+        let r = session.minimap_rect().unwrap();
+        assert_eq!((r.width(), r.height()), (16., 32.));
+    }
+
+    #[test]
+    fn test_palette_cycle() {
+        let (r, g, b, w) = (Rgba8::RED, Rgba8::GREEN, Rgba8::BLUE, Rgba8::WHITE);
+        let palette = [r, g, b, w];
+        let mut cycle = PaletteCycle {
+            view: ViewId::default(),
+            from: 0,
+            to: 2,
+            frames: 1,
+            step: 0,
+            ticks: 0,
+        };
+        assert_eq!(cycle.apply(&palette, &[r, g, b, w]), vec![r, g, b, w]);
+
+        cycle.step = 1;
+        assert_eq!(cycle.apply(&palette, &[r, g, b, w]), vec![b, r, g, w]);
+
+        cycle.step = 4;
+        assert_eq!(cycle.apply(&palette, &[r, g, b, w]), vec![b, r, g, w]);
+
+        // The palette shrunk below the range.
+        assert_eq!(cycle.apply(&palette[..2], &[r, g, b, w]), vec![r, g, b, w]);
+    }
+
+    #[test]
+    fn test_settings_set() {
+        let mut settings = Settings::default();
+
+        assert_eq!(settings.set("scale", Value::U32(2)), Ok(Value::F64(1.)));
+        assert_eq!(
+            settings.set("scale", Value::U32(8)),
+            Err(String::from(
+                "invalid value `8` for `scale`, expected a value between 1 and 4"
+            ))
+        );
+        assert_eq!(
+            settings.set("ui/scale", Value::U32(5)),
+            Err(String::from(
+                "invalid value `5` for `ui/scale`, expected a value between 1 and 4"
+            ))
+        );
+        assert_eq!(
+            settings.set("grid", Value::U32(3)),
+            Err(String::from(
+                "invalid value `3` for `grid`, expected on / off"
+            ))
+        );
+        assert_eq!(
+            settings.set("nope", Value::Bool(true)),
+            Err(String::from("no such setting `nope`"))
+        );
+        assert_eq!(settings["scale"], Value::F64(2.));
+    }
+
+    #[test]
+    fn test_parse_pixel() {
+        assert_eq!(
+            Session::parse_pixel("1 2 #ff0000"),
+            Some((1, 2, Rgba8::RED))
+        );
+        assert_eq!(
+            Session::parse_pixel("-1 0 #0000ff80"),
+            Some((-1, 0, Rgba8::BLUE.alpha(0x80)))
+        );
+        assert_eq!(Session::parse_pixel("0 0 red"), None);
+        assert_eq!(Session::parse_pixel("0 0 #ff00"), None);
+        assert_eq!(Session::parse_pixel("0 0 #ff0000 extra"), None);
+        assert_eq!(Session::parse_pixel("0 #ff0000"), None);
+    }
+
+    #[test]
+    fn test_jump_list() {
+        let jump = |x: f32, zoom: f32| Jump {
+            view: ViewId::default(),
+            offset: Vector2::new(x, 0.),
+            zoom,
+        };
+        let mut jumps = JumpList::default();
+
+        assert_eq!(jumps.back(jump(0., 1.)), None);
+
+        jumps.push(jump(1., 1.));
+        jumps.push_zoom(jump(2., 1.));
+        jumps.push_zoom(jump(2., 2.));
+        jumps.push_zoom(jump(2., 4.));
+
+        assert_eq!(jumps.back(jump(3., 8.)), Some(jump(2., 1.)));
+        assert_eq!(jumps.back(jump(2., 1.)), Some(jump(1., 1.)));
+        assert_eq!(jumps.forward(), Some(jump(2., 1.)));
+        assert_eq!(jumps.forward(), Some(jump(3., 8.)));
+        assert_eq!(jumps.forward(), None);
+
+        // Jumping from the middle of the list discards the positions ahead.
+        jumps.back(jump(3., 8.));
+        jumps.push(jump(5., 1.));
+        assert_eq!(jumps.forward(), None);
+        assert_eq!(jumps.back(jump(6., 1.)), Some(jump(5., 1.)));
+    }
+
     #[test]
     fn test_key_bindings() {
         let mut kbs = KeyBindings::default();
@@ -3301,6 +7043,70 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_key_repeat() {
+        let tmp = tempfile::tempdir().unwrap();
+        let proj_dirs = dirs::ProjectDirs::from("io", "cloudhead", "rx").unwrap();
+        let base_dirs = dirs::BaseDirs::new().unwrap();
+        let mut session = Session::new(64, 64, tmp.path().to_owned(), proj_dirs, base_dirs)
+            .with_blank(FileStatus::NoFile, 8, 8)
+            .init(Some(PathBuf::from("-")))
+            .unwrap();
+        let mut exec = Execution::Normal;
+
+        let key = |state| platform::KeyboardInput {
+            key: Some(platform::Key::A),
+            modifiers: ModifiersState::default(),
+            state,
+        };
+        let tick = |session: &mut Session, ms| {
+            session.update(
+                &mut vec![],
+                &mut Execution::Normal,
+                time::Duration::from_millis(ms),
+                time::Duration::default(),
+            );
+        };
+        session.key_bindings.add(KeyBinding {
+            modes: vec![Mode::Normal],
+            input: Input::Key(platform::Key::A),
+            command: Command::BrushSize(Op::Incr),
+            is_toggle: false,
+            display: None,
+            modifiers: ModifiersState::default(),
+            state: InputState::Pressed,
+        });
+        session
+            .settings
+            .set("input/repeat-delay", Value::U32(200))
+            .unwrap();
+        session
+            .settings
+            .set("input/repeat-rate", Value::U32(20))
+            .unwrap();
+
+        session.handle_keyboard_input(key(InputState::Pressed), &mut exec);
+        assert_eq!(session.brush.size, 2);
+
+        // Key repeats from the OS are ignored.
+        session.handle_keyboard_input(key(InputState::Repeated), &mut exec);
+        assert_eq!(session.brush.size, 2);
+
+        tick(&mut session, 150);
+        assert_eq!(session.brush.size, 2);
+        tick(&mut session, 50);
+        assert_eq!(session.brush.size, 4);
+        tick(&mut session, 40);
+        assert_eq!(session.brush.size, 4);
+        tick(&mut session, 10);
+        assert_eq!(session.brush.size, 6);
+
+        session.handle_keyboard_input(key(InputState::Released), &mut exec);
+        assert_eq!(session.key_repeat_wait(), None);
+        tick(&mut session, 100);
+        assert_eq!(session.brush.size, 6);
+    }
+
     #[test]
     fn test_key_bindings_modifier() {
         let kb = KeyBinding {
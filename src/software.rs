@@ -0,0 +1,474 @@
+//! A software renderer, for running rx without a window or GPU.
+//!
+//! Only view pixels are rendered: the UI isn't drawn, since there is nothing to
+//! present it on. Layer buffers are kept in the same row order as the GPU
+//! textures, top row first, so that recorded snapshots are identical.
+use crate::cmd::Axis;
+use crate::gfx::shape2d::{Shape, Vertex};
+use crate::gfx::{Rect, Rgba8};
+use crate::pixels;
+use crate::session::{self, Blending, Effect, Session};
+use crate::view::layer::LayerId;
+use crate::view::resource::ViewResource;
+use crate::view::{View, ViewId, ViewOp, ViewState};
+
+use std::collections::BTreeMap;
+
+/// The pixels of a view's layers.
+struct ViewData {
+    w: u32,
+    h: u32,
+    layers: Vec<Vec<Rgba8>>,
+}
+
+impl ViewData {
+    fn new(w: u32, h: u32, pixels: Option<&[Rgba8]>) -> Self {
+        let mut data = Self {
+            w,
+            h,
+            layers: Vec::new(),
+        };
+        data.add_layer(pixels);
+        data
+    }
+
+    fn add_layer(&mut self, pixels: Option<&[Rgba8]>) {
+        let size = (self.w * self.h) as usize;
+
+        self.layers.push(match pixels {
+            Some(pixels) if pixels.len() == size => pixels.to_vec(),
+            _ => vec![Rgba8::TRANSPARENT; size],
+        });
+    }
+
+    /// Write a rectangle of texels to a layer, top row first, at the given
+    /// row and column. This mirrors partial texture uploads on the GPU.
+    fn upload_part(
+        &mut self,
+        layer: LayerId,
+        [x, y]: [u32; 2],
+        [w, h]: [u32; 2],
+        texels: &[Rgba8],
+    ) {
+        let (vw, vh) = (self.w, self.h);
+        let pixels = &mut self.layers[layer];
+
+        let cols = w.min(vw.saturating_sub(x)) as usize;
+
+        for row in 0..h.min(vh.saturating_sub(y)) {
+            let src = (row * w) as usize;
+            let dst = ((y + row) * vw + x) as usize;
+
+            pixels[dst..dst + cols].copy_from_slice(&texels[src..src + cols]);
+        }
+    }
+
+    fn layer_pixels(&self) -> impl Iterator<Item = (LayerId, Vec<Rgba8>)> + '_ {
+        self.layers.iter().cloned().enumerate()
+    }
+}
+
+/// Renders views on the CPU.
+pub struct Renderer {
+    blending: Blending,
+    paste: (u32, u32, Vec<Rgba8>),
+    view_data: BTreeMap<ViewId, ViewData>,
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            blending: Blending::Alpha,
+            paste: (0, 0, Vec::new()),
+            view_data: BTreeMap::new(),
+        }
+    }
+
+    /// Width, height and pixels of a view layer, top row first.
+    pub fn pixels(&self, view: ViewId, layer: LayerId) -> Option<(u32, u32, &[Rgba8])> {
+        let data = self.view_data.get(&view)?;
+        let pixels = data.layers.get(layer)?;
+
+        Some((data.w, data.h, pixels.as_slice()))
+    }
+
+    pub fn frame(&mut self, session: &mut Session, effects: Vec<Effect>) {
+        if session.state != session::State::Running {
+            return;
+        }
+        let mut shapes = Vec::new();
+
+        for eff in effects {
+            match eff {
+                Effect::ViewAdded(id) => {
+                    if let Some((s, pixels)) =
+                        session.views.get_snapshot_safe(id, LayerId::default())
+                    {
+                        self.view_data
+                            .insert(id, ViewData::new(s.width(), s.height(), Some(pixels)));
+                    }
+                }
+                Effect::ViewRemoved(id) => {
+                    self.view_data.remove(&id);
+                }
+                Effect::ViewOps(id, ops) => {
+                    // Like on the GPU, finished strokes are painted before pasting.
+                    self.paint_final(session, shapes.drain(..));
+                    self.handle_view_ops(session.view(id), &ops);
+                }
+                Effect::ViewDamaged(id, Some(extent)) => {
+                    self.resize_view(session.view(id), extent.width(), extent.height());
+                }
+                Effect::ViewDamaged(id, None) => {
+                    let v = session.view(id);
+                    for layer in 0..self.view_data(v).layers.len() {
+                        self.restore_layer(v, layer);
+                    }
+                }
+                Effect::ViewLayerDamaged(id, layer, _) => {
+                    self.restore_layer(session.view(id), layer);
+                }
+                Effect::ViewBlendingChanged(blending) => {
+                    self.blending = blending;
+                }
+                Effect::ViewPaintFinal(s) => {
+                    shapes.extend(s);
+                }
+                // Draft strokes are only ever displayed, never recorded.
+                Effect::ViewPaintDraft(_)
+                | Effect::ViewActivated(_)
+                | Effect::ViewTouched(_)
+                | Effect::SessionResized(_)
+                | Effect::SessionScaled(_)
+                | Effect::PreviewBackdropChanged
+                | Effect::PaletteCycled => {}
+            }
+        }
+
+        self.paint_final(session, shapes.drain(..));
+
+        let v = session
+            .views
+            .active()
+            .expect("there must always be an active view");
+        let id = v.id;
+
+        // If active view is dirty, record a snapshot of it.
+        if v.is_dirty() {
+            let state = v.state;
+            let is_resized = v.is_resized();
+            let extent = v.extent();
+
+            if let (Some(vr), Some(v_data)) = (session.views.get_mut(id), self.view_data.get(&id)) {
+                match state {
+                    ViewState::Dirty(_) if is_resized => {
+                        vr.record_view_resized(v_data.layer_pixels().collect(), extent);
+                    }
+                    ViewState::Dirty(_) => {
+                        vr.record_view_painted(v_data.layer_pixels().collect(), extent);
+                    }
+                    ViewState::LayerDirty(layer_id) => {
+                        let pixels = v_data.layers[layer_id].clone();
+                        vr.record_layer_painted(layer_id, pixels, extent);
+                    }
+                    ViewState::Okay | ViewState::Damaged(_) | ViewState::LayerDamaged(_, _) => {}
+                }
+            }
+        }
+    }
+
+    /// Paint finished brush strokes onto the active layer of the active view.
+    fn paint_final(&mut self, session: &Session, shapes: impl Iterator<Item = Shape>) {
+        let v = session
+            .views
+            .active()
+            .expect("there must always be an active view");
+        let (id, layer) = (v.id, v.active_layer_id);
+        let blending = self.blending;
+
+        if let Some(data) = self.view_data.get_mut(&id) {
+            let (w, h) = (data.w, data.h);
+
+            for shape in shapes {
+                self::paint(&mut data.layers[layer], w, h, &shape, blending);
+            }
+        }
+    }
+
+    fn view_data(&mut self, v: &View<ViewResource>) -> &mut ViewData {
+        self.view_data
+            .get_mut(&v.id)
+            .expect("views must have associated view data")
+    }
+
+    fn restore_layer(&mut self, v: &View<ViewResource>, layer: LayerId) {
+        if let Some((_, pixels)) = v.current_snapshot(layer) {
+            let data = self.view_data(v);
+            data.layers[layer] = pixels.to_vec();
+        }
+    }
+
+    fn resize_view(&mut self, v: &View<ViewResource>, vw: u32, vh: u32) {
+        let (ew, eh) = (v.resource.extent.width(), v.resource.extent.height());
+
+        // Ensure not to transfer more data than can fit in the view buffer.
+        let tw = u32::min(ew, vw);
+        let th = u32::min(eh, vh);
+
+        let mut data = ViewData::new(vw, vh, None);
+        for _ in v.resource.layers().skip(1) {
+            data.add_layer(None);
+        }
+
+        let trect = Rect::origin(tw as i32, th as i32);
+        for (layer_id, layer) in v.resource.layers() {
+            if let Some((_, texels)) = layer.get_snapshot_rect(&trect) {
+                data.upload_part(*layer_id, [0, vh - th], [tw, th], &texels);
+            }
+        }
+        self.view_data.insert(v.id, data);
+    }
+
+    fn handle_view_ops(&mut self, v: &View<ViewResource>, ops: &[ViewOp]) {
+        for op in ops {
+            match op {
+                ViewOp::Resize(w, h) => {
+                    self.resize_view(v, *w, *h);
+                }
+                ViewOp::AddLayer(layer_id, _) => {
+                    if let Some((_, pixels)) = v.current_snapshot(*layer_id) {
+                        self.view_data(v).add_layer(Some(pixels));
+                    }
+                }
+                ViewOp::RemoveLayer(_) => {}
+                ViewOp::Clear(color) => {
+                    for l in self.view_data(v).layers.iter_mut() {
+                        l.iter_mut().for_each(|p| *p = *color);
+                    }
+                }
+                ViewOp::ClearRect(color, rect) => {
+                    let (w, h) = (rect.width() as u32, rect.height() as u32);
+                    let texels = vec![*color; (w * h) as usize];
+                    let data = self.view_data(v);
+
+                    for l in 0..data.layers.len() {
+                        data.upload_part(l, [rect.x1 as u32, rect.y1 as u32], [w, h], &texels);
+                    }
+                }
+                ViewOp::Blit(src, dst) => {
+                    let nlayers = self.view_data(v).layers.len();
+
+                    for l in 0..nlayers {
+                        if let Some((_, texels)) =
+                            v.layer(l).get_snapshot_rect(&src.map(|n| n as i32))
+                        {
+                            self.view_data(v).upload_part(
+                                l,
+                                [dst.x1 as u32, dst.y1 as u32],
+                                [src.width() as u32, src.height() as u32],
+                                &texels,
+                            );
+                        }
+                    }
+                }
+                ViewOp::Yank(layer_id, src) => {
+                    if let Some((_, pixels)) = v.layer(*layer_id).get_snapshot_rect(src) {
+                        self.paste = (src.width() as u32, src.height() as u32, pixels);
+                    }
+                }
+                ViewOp::Flip(layer_id, src, dir) => {
+                    if let Some((_, mut pixels)) = v.layer(*layer_id).get_snapshot_rect(src) {
+                        let w = src.width() as usize;
+
+                        match dir {
+                            Axis::Vertical => {
+                                let len = pixels.len();
+                                let (front, back) = pixels.split_at_mut(len / 2);
+                                for (front_row, back_row) in
+                                    front.chunks_exact_mut(w).zip(back.rchunks_exact_mut(w))
+                                {
+                                    front_row.swap_with_slice(back_row);
+                                }
+                            }
+                            Axis::Horizontal => {
+                                pixels.chunks_exact_mut(w).for_each(|row| row.reverse());
+                            }
+                        }
+                        self.paste = (src.width() as u32, src.height() as u32, pixels);
+                    }
+                }
+                ViewOp::Shift(layer_id, src, (dx, dy), wrap) => {
+                    if let Some((_, pixels)) = v.layer(*layer_id).get_snapshot_rect(src) {
+                        let (w, h) = (src.width() as u32, src.height() as u32);
+
+                        // Snapshot rows are top to bottom, while view coordinates point up.
+                        let pixels = pixels::shift(&pixels, w, h, (*dx, -*dy), *wrap);
+                        self.paste = (w, h, pixels);
+                    }
+                }
+                ViewOp::Paste(dst) => {
+                    let layer = v.active_layer_id;
+                    let blending = self.blending;
+                    let (pw, ph, paste) = &self.paste;
+                    let data = self
+                        .view_data
+                        .get_mut(&v.id)
+                        .expect("views must have associated view data");
+                    let (w, h) = (data.w as i32, data.h as i32);
+                    let pixels = &mut data.layers[layer];
+
+                    // The paste buffer is stored top row first, like the layer.
+                    for (row, y) in (dst.y1..dst.y2).rev().enumerate().take(*ph as usize) {
+                        for (col, x) in (dst.x1..dst.x2).enumerate().take(*pw as usize) {
+                            if x < 0 || y < 0 || x >= w || y >= h {
+                                continue;
+                            }
+                            let src = paste[row * *pw as usize + col];
+                            let dst = &mut pixels[((h - y - 1) * w + x) as usize];
+
+                            *dst = blending.blend(src, *dst);
+                        }
+                    }
+                }
+                ViewOp::SetPixel(layer_id, rgba, x, y) => {
+                    self.view_data(v).upload_part(
+                        *layer_id,
+                        [*x as u32, *y as u32],
+                        [1, 1],
+                        &[*rgba],
+                    );
+                }
+                ViewOp::SetPaste(w, h, pixels) => {
+                    self.paste = (*w, *h, pixels.clone());
+                }
+                ViewOp::SetPixels(layer_id, pixels) => {
+                    let data = self.view_data(v);
+                    let (w, h) = (data.w, data.h);
+
+                    data.upload_part(*layer_id, [0, 0], [w, h], pixels);
+                }
+            }
+        }
+    }
+}
+
+/// Rasterize a shape into a layer buffer. Shape coordinates have their origin at
+/// the bottom-left of the layer, while the buffer is stored top row first.
+/// Pixels are covered if their center is inside one of the shape's triangles.
+fn paint(pixels: &mut [Rgba8], w: u32, h: u32, shape: &Shape, blending: Blending) {
+    for tri in shape.triangulate().chunks_exact(3) {
+        self::rasterize(tri, w, h, |x, y, color| {
+            let dst = &mut pixels[((h - y - 1) * w + x) as usize];
+            *dst = blending.blend(color, *dst);
+        });
+    }
+}
+
+fn rasterize<F: FnMut(u32, u32, Rgba8)>(tri: &[Vertex], w: u32, h: u32, mut f: F) {
+    let mut p = [tri[0].position, tri[1].position, tri[2].position];
+    let edge = |a: (f32, f32), b: (f32, f32), c: (f32, f32)| {
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+    };
+    let area = edge((p[0].x, p[0].y), (p[1].x, p[1].y), (p[2].x, p[2].y));
+
+    if area == 0. {
+        return;
+    }
+    // Use a consistent winding, so that the edge tests share a sign.
+    if area < 0. {
+        p.swap(1, 2);
+    }
+    let color = tri[0].color;
+
+    let x1 = p
+        .iter()
+        .map(|v| v.x)
+        .fold(f32::MAX, f32::min)
+        .floor()
+        .max(0.) as u32;
+    let y1 = p
+        .iter()
+        .map(|v| v.y)
+        .fold(f32::MAX, f32::min)
+        .floor()
+        .max(0.) as u32;
+    let x2 = (p.iter().map(|v| v.x).fold(f32::MIN, f32::max).ceil() as u32).min(w);
+    let y2 = (p.iter().map(|v| v.y).fold(f32::MIN, f32::max).ceil() as u32).min(h);
+
+    for y in y1..y2 {
+        for x in x1..x2 {
+            let c = (x as f32 + 0.5, y as f32 + 0.5);
+            let inside = (0..3).all(|i| {
+                let (a, b) = (p[i], p[(i + 1) % 3]);
+                let e = edge((a.x, a.y), (b.x, b.y), c);
+
+                // On a shared edge, only one of the two triangles covers the pixel.
+                e > 0. || (e == 0. && (b.y < a.y || (b.y == a.y && b.x > a.x)))
+            });
+            if inside {
+                f(x, y, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gfx::shape2d::{Fill, Rotation, Stroke};
+    use crate::gfx::ZDepth;
+
+    #[test]
+    fn test_paint_rect() {
+        let (w, h) = (4, 3);
+        let red = Rgba8::RED;
+        let mut pixels = vec![Rgba8::TRANSPARENT; w * h];
+        let rect = Shape::Rectangle(
+            Rect::new(1., 0., 3., 2.),
+            ZDepth::default(),
+            Rotation::ZERO,
+            Stroke::NONE,
+            Fill::Solid(red.into()),
+        );
+        self::paint(&mut pixels, w as u32, h as u32, &rect, Blending::Alpha);
+
+        let t = Rgba8::TRANSPARENT;
+        #[rustfmt::skip]
+        assert_eq!(pixels, vec![
+            t, t,   t,   t,
+            t, red, red, t,
+            t, red, red, t,
+        ]);
+
+        // Pixels on the edge shared by both triangles are only blended once.
+        let mut pixels = vec![Rgba8::TRANSPARENT; w * h];
+        let half = red.alpha(0x80);
+        let rect = Shape::Rectangle(
+            Rect::new(0., 0., 4., 3.),
+            ZDepth::default(),
+            Rotation::ZERO,
+            Stroke::NONE,
+            Fill::Solid(half.into()),
+        );
+        self::paint(&mut pixels, w as u32, h as u32, &rect, Blending::Alpha);
+
+        assert!(pixels.iter().all(|p| *p == half));
+    }
+
+    #[test]
+    fn test_blend_behind_replace() {
+        let (t, red, blue) = (Rgba8::TRANSPARENT, Rgba8::RED, Rgba8::BLUE);
+
+        assert_eq!(Blending::Behind.blend(red, t), red);
+        assert_eq!(Blending::Behind.blend(red, blue), blue);
+        assert_eq!(Blending::Replace.blend(red, t), t);
+        assert_eq!(Blending::Replace.blend(red, blue), red);
+        assert_eq!(Blending::Replace.blend(red, blue.alpha(0x80)).a, 0x80);
+    }
+}
@@ -0,0 +1,96 @@
+//! Status line formatting.
+//!
+//! The status line can be customized with a format string, in which `%<item>`
+//! is replaced by the value of the item, eg. `%file` or `%zoom`. Everything
+//! after `%=` is aligned to the right. The same items can be used in `:echo`
+//! strings, eg. `:echo "%x,%y %color"`. Items can be wrapped in braces to set
+//! them apart from the text that follows, eg. `%{zoom}x`.
+
+/// Expand the `%<item>` items of a format string, using `lookup` to get the
+/// value of each item. `%%` is a literal `%`, and unknown items are kept as-is.
+pub fn expand<F>(fmt: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(fmt.len());
+    let mut rest = fmt;
+
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        if let Some(r) = rest.strip_prefix('%') {
+            out.push('%');
+            rest = r;
+            continue;
+        }
+        let (item, r) = match rest
+            .strip_prefix('{')
+            .and_then(|r| r.find('}').map(|i| (&r[..i], &r[i + 1..])))
+        {
+            Some((item, r)) => (item, r),
+            None => {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphabetic())
+                    .unwrap_or(rest.len());
+                rest.split_at(len)
+            }
+        };
+
+        match lookup(item) {
+            Some(value) if !item.is_empty() => out.push_str(&value),
+            _ => {
+                out.push('%');
+                out.push_str(&rest[..rest.len() - r.len()]);
+            }
+        }
+        rest = r;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Expand a status line format string into its left and right-aligned parts.
+pub fn render<F>(fmt: &str, lookup: F) -> (String, String)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match fmt.find("%=") {
+        Some(i) => (
+            self::expand(&fmt[..i], &lookup),
+            self::expand(&fmt[i + 2..], &lookup),
+        ),
+        None => (self::expand(fmt, lookup), String::new()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let lookup = |item: &str| match item {
+            "file" => Some(String::from("hero.png")),
+            "zoom" => Some(String::from("400%")),
+            _ => None,
+        };
+
+        assert_eq!(
+            render("%file [%unknown] 100%%%=%zoom", lookup),
+            (
+                String::from("hero.png [%unknown] 100%"),
+                String::from("400%")
+            )
+        );
+        assert_eq!(
+            render("%zoom", lookup),
+            (String::from("400%"), String::new())
+        );
+        assert_eq!(expand("50% ", lookup), "50% ");
+        assert_eq!(
+            expand("%{zoom}x %{unknown} %{file", lookup),
+            "400%x %{unknown} %{file"
+        );
+    }
+}
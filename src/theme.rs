@@ -0,0 +1,145 @@
+//! UI color themes.
+use crate::color;
+use crate::gfx::Rgba8;
+use crate::parser;
+
+use memoir::*;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Extension of theme files.
+pub const EXTENSION: &str = "theme";
+
+/// Colors used to draw the UI.
+///
+/// Theme files have one `<key> <color>` pair per line, where the keys are
+/// `text`, `text/dim`, `cmdline`, `cursor`, `selection`, `border`,
+/// `border/inactive`, `palette`, `grid` and `grid/major`. Missing keys keep
+/// their default color, and lines starting with `--` are comments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Name of the theme.
+    pub name: String,
+    /// Status line text.
+    pub text: Rgba8,
+    /// Secondary text, eg. view and split pane info.
+    pub text_dim: Rgba8,
+    /// Command line text.
+    pub cmdline: Rgba8,
+    /// Command line cursor.
+    pub cursor: Rgba8,
+    /// Selection outline and fill.
+    pub selection: Rgba8,
+    /// Border of the active view.
+    pub border: Rgba8,
+    /// Border of inactive views.
+    pub border_inactive: Rgba8,
+    /// Outline of the hovered palette color.
+    pub palette: Rgba8,
+    /// The grid color. Applied to the `grid/color` setting.
+    pub grid: Option<Rgba8>,
+    /// The grid major line color. Applied to the `grid/major/color` setting.
+    pub grid_major: Option<Rgba8>,
+}
+
+impl Theme {
+    /// Read a theme file. The theme is named after the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let name = path
+            .file_stem()
+            .map_or(String::new(), |s| s.to_string_lossy().into_owned());
+
+        Self::parse(name, &fs::read_to_string(path)?)
+    }
+
+    /// Parse a theme from the contents of a theme file.
+    pub fn parse(name: String, input: &str) -> io::Result<Self> {
+        let mut theme = Self {
+            name,
+            ..Self::default()
+        };
+        let line = parser::identifier()
+            .skip(whitespace())
+            .then(parser::color())
+            .skip(optional(whitespace()))
+            .followed_by(end());
+
+        for (i, l) in input.lines().enumerate() {
+            let l = l.trim();
+
+            if l.is_empty() || l.starts_with("--") {
+                continue;
+            }
+            let error = |e: String| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} on line {}", e, i + 1),
+                )
+            };
+            let ((key, color), _) = line.parse(l).map_err(|(e, _)| error(e.to_string()))?;
+
+            match key.as_str() {
+                "text" => theme.text = color,
+                "text/dim" => theme.text_dim = color,
+                "cmdline" => theme.cmdline = color,
+                "cursor" => theme.cursor = color,
+                "selection" => theme.selection = color,
+                "border" => theme.border = color,
+                "border/inactive" => theme.border_inactive = color,
+                "palette" => theme.palette = color,
+                "grid" => theme.grid = Some(color),
+                "grid/major" => theme.grid_major = Some(color),
+                _ => return Err(error(format!("unknown theme key `{}`", key))),
+            }
+        }
+        Ok(theme)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            text: color::WHITE,
+            text_dim: color::GREY,
+            cmdline: color::WHITE,
+            cursor: color::RED,
+            selection: color::RED,
+            border: color::WHITE,
+            border_inactive: Rgba8::new(0x80, 0x80, 0x80, 0xff),
+            palette: color::WHITE,
+            grid: None,
+            grid_major: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_theme_parse() {
+        let theme = Theme::parse(
+            String::from("dusk"),
+            "-- A darker theme.\n\
+             text       #eeeeee\n\
+             \n\
+             selection  #00ff00\n\
+             grid       #333333\n",
+        )
+        .unwrap();
+
+        assert_eq!(theme.name, "dusk");
+        assert_eq!(theme.text, Rgba8::new(0xee, 0xee, 0xee, 0xff));
+        assert_eq!(theme.selection, Rgba8::new(0x00, 0xff, 0x00, 0xff));
+        assert_eq!(theme.grid, Some(Rgba8::new(0x33, 0x33, 0x33, 0xff)));
+        assert_eq!(theme.cmdline, Theme::default().cmdline);
+
+        assert!(Theme::parse(String::new(), "colour #ffffff").is_err());
+        assert!(Theme::parse(String::new(), "text white").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Timelapse recording of view edits.
+use crate::gfx::color::Rgba8;
+use crate::image;
+use crate::util;
+use crate::view::resource::{EditId, ViewResource};
+use crate::view::{View, ViewId};
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time;
+
+/// Timelapse output format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    /// An animated GIF.
+    Gif,
+    /// A directory of numbered PNG frames.
+    Frames,
+}
+
+/// Records the state of a view after edits, and assembles the
+/// recorded frames into a timelapse.
+pub struct Timelapse {
+    /// Output path.
+    pub path: PathBuf,
+    /// View being recorded.
+    pub view: ViewId,
+
+    format: Format,
+    /// Number of edits between captures.
+    every: usize,
+    /// Number of edits since the last capture.
+    edits: usize,
+    /// Last edit seen.
+    last: Option<EditId>,
+    /// Captured frames, with their width and height.
+    frames: Vec<(u32, u32, Vec<Rgba8>)>,
+}
+
+impl Timelapse {
+    /// Delay between timelapse frames.
+    const FRAME_DELAY: time::Duration = time::Duration::from_millis(100);
+    /// GIF encoding speed, from 1 to 30. Higher is faster, with lower quality.
+    const GIF_ENCODING_SPEED: i32 = 10;
+
+    pub fn new<P: AsRef<Path>>(path: P, view: ViewId, every: usize) -> io::Result<Self> {
+        let path = path.as_ref();
+        let format = match path.extension().and_then(|e| e.to_str()) {
+            Some("gif") => Format::Gif,
+            None => Format::Frames,
+            Some(ext) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "`{}` is not a supported timelapse format, use `.gif` or a directory",
+                        ext
+                    ),
+                ))
+            }
+        };
+
+        Ok(Self {
+            path: path.to_owned(),
+            view,
+            format,
+            every: every.max(1),
+            edits: 0,
+            last: None,
+            frames: Vec::new(),
+        })
+    }
+
+    /// Number of frames captured so far.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Capture the view if it was edited enough since the last capture.
+    /// The initial state of the view is always captured.
+    pub fn capture(&mut self, view: &View<ViewResource>) {
+        let edit = view.current_edit();
+
+        match self.last {
+            Some(last) if last == edit => return,
+            Some(_) => {
+                self.edits += 1;
+                self.last = Some(edit);
+
+                if self.edits < self.every {
+                    return;
+                }
+            }
+            None => {
+                self.last = Some(edit);
+            }
+        }
+        self.edits = 0;
+        self.frames.push(self::composite(view));
+    }
+
+    /// Write the timelapse to disk. Returns the number of frames written.
+    pub fn finish(self) -> io::Result<usize> {
+        match self.format {
+            Format::Gif => self.write_gif()?,
+            Format::Frames => self.write_frames()?,
+        }
+        Ok(self.frames.len())
+    }
+
+    fn write_frames(&self) -> io::Result<()> {
+        std::fs::create_dir_all(&self.path)?;
+
+        for (i, (w, h, pixels)) in self.frames.iter().enumerate() {
+            let path = self.path.join(format!("{:03}.png", i));
+            image::save_as(path, *w, *h, 1, pixels, &image::Metadata::default())?;
+        }
+        Ok(())
+    }
+
+    fn write_gif(&self) -> io::Result<()> {
+        // Since the view may have been resized, use a canvas that fits all frames.
+        let width = self.frames.iter().map(|(w, _, _)| *w).max().unwrap_or(1);
+        let height = self.frames.iter().map(|(_, h, _)| *h).max().unwrap_or(1);
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(w), Ok(h)) => (w, h),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "a {}x{} timelapse is too big for a gif, the maximum is {}x{}",
+                        width,
+                        height,
+                        u16::MAX,
+                        u16::MAX
+                    ),
+                ))
+            }
+        };
+
+        let f = File::create(&self.path)?;
+        let mut encoder = gif::Encoder::new(io::BufWriter::new(f), width, height, &[])?;
+        {
+            use gif::SetParameter;
+            encoder.set(gif::Repeat::Infinite)?;
+        }
+
+        for (i, (w, h, pixels)) in self.frames.iter().enumerate() {
+            let mut canvas = vec![Rgba8::TRANSPARENT; width as usize * height as usize];
+            for y in 0..*h as usize {
+                let row = &pixels[y * *w as usize..(y + 1) * *w as usize];
+                let offset = y * width as usize;
+                canvas[offset..offset + row.len()].copy_from_slice(row);
+            }
+            let mut data = util::align_u8(&canvas).to_vec();
+            let mut frame =
+                gif::Frame::from_rgba_speed(width, height, &mut data, Self::GIF_ENCODING_SPEED);
+
+            // Let the last frame linger for a second...
+            frame.delay = if i + 1 == self.frames.len() {
+                100
+            } else {
+                (Self::FRAME_DELAY.as_millis() / 10) as u16
+            };
+            frame.dispose = gif::DisposalMethod::Background;
+
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+}
+
+/// Composite the visible layers of a view, from bottom to top.
+fn composite(view: &View<ViewResource>) -> (u32, u32, Vec<Rgba8>) {
+    let (w, h) = (view.resource.extent.width(), view.resource.extent.height());
+    let mut output = vec![Rgba8::TRANSPARENT; (w * h) as usize];
+
+    for (_, layer) in view.resource.layers() {
+        let (_, pixels) = layer.current_snapshot();
+
+        for (dst, src) in output.iter_mut().zip(pixels.iter()) {
+            *dst = src.over(*dst);
+        }
+    }
+    (w, h, output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_timelapse_too_big() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("timelapse.gif");
+        let mut timelapse = Timelapse::new(&path, ViewId::default(), 1).unwrap();
+
+        timelapse
+            .frames
+            .push((70000, 1, vec![Rgba8::TRANSPARENT; 70000]));
+
+        let err = timelapse.finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
@@ -39,6 +39,24 @@ pub fn stitch_frames<T: Clone>(mut frames: Vec<Vec<T>>, fw: usize, fh: usize, va
     buffer
 }
 
+/// Split a horizontal strip of frames into separate frames. The inverse of
+/// `stitch_frames`.
+pub fn split_frames<T: Clone>(pixels: Vec<T>, fw: usize, fh: usize, nframes: usize) -> Vec<Vec<T>> {
+    if nframes <= 1 {
+        return vec![pixels];
+    }
+    let width = fw * nframes;
+    let mut frames = vec![Vec::with_capacity(fw * fh); nframes];
+
+    for y in 0..fh {
+        for (i, frame) in frames.iter_mut().enumerate() {
+            let offset = i * fw + y * width;
+            frame.extend_from_slice(&pixels[offset..offset + fw]);
+        }
+    }
+    frames
+}
+
 pub fn align_u8<T>(data: &[T]) -> &[u8] {
     let (head, body, tail) = unsafe { data.align_to::<u8>() };
 
@@ -6,6 +6,10 @@ pub use path::{Format, Path};
 pub use resource::{Edit, EditId, Snapshot, ViewResource};
 
 use crate::cmd::Axis;
+use crate::image;
+use crate::palette::Swatches;
+use crate::pixels;
+use crate::profile::{Profile, TileFormat};
 use crate::session::{Direction, Session, SessionCoords};
 use crate::util;
 use crate::view::layer::{FrameRange, Layer, LayerCoords, LayerId};
@@ -19,10 +23,11 @@ use nonempty::NonEmpty;
 use microserde::{Deserialize, Serialize};
 
 use std::collections::btree_map;
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt;
 use std::io;
 use std::ops::Deref;
+use std::time;
 
 /// View identifier.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug, Default)]
@@ -128,6 +133,137 @@ impl ViewExtent {
     }
 }
 
+/// Layout of the frames in an exported sprite sheet.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SheetLayout {
+    /// Number of columns and rows. If `None`, frames are laid out in a single row.
+    pub grid: Option<(u32, u32)>,
+    /// Padding around the sheet, in pixels.
+    pub padding: u32,
+    /// Spacing between frames, in pixels.
+    pub spacing: u32,
+}
+
+impl SheetLayout {
+    /// Number of columns and rows of the sheet, given a number of frames.
+    pub fn grid(&self, nframes: usize) -> (u32, u32) {
+        self.grid.unwrap_or((nframes as u32, 1))
+    }
+}
+
+/// Options of the `export` command.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Export scale. If `None`, the `export/scale` setting is used.
+    pub scale: Option<u32>,
+    /// Filter to scale with, instead of nearest-neighbor.
+    pub filter: Option<ScaleFilter>,
+    /// Sprite sheet layout.
+    pub layout: SheetLayout,
+    /// Console profile to quantize colors to.
+    pub profile: Option<Profile>,
+    /// Export format. If `None`, it's told from the file extension.
+    pub format: Option<ExportFormat>,
+    /// Command to run on the exported path, instead of the `on-export` hook.
+    pub pipe: Option<String>,
+    /// Inclusive range of frames to export. If `None`, all frames are exported.
+    pub frames: Option<(usize, usize)>,
+    /// Export only the selected area.
+    pub selection: bool,
+}
+
+/// Format of an exported layer, for formats other than images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Raw tile data.
+    Tiles(TileFormat),
+    /// A C or Rust source array of RGBA bytes, or of palette indices if `indexed`.
+    Array { indexed: bool },
+    /// An XPM image.
+    Xpm,
+    /// A Godot `SpriteFrames` resource, along with a PNG of the frames.
+    /// All frames go in one `default` animation, since views have no frame tags.
+    SpriteFrames,
+}
+
+impl ExportFormat {
+    /// Export format implied by a file extension, if any.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext {
+            "h" | "c" | "rs" => Some(Self::Array { indexed: false }),
+            "xpm" => Some(Self::Xpm),
+            "tres" => Some(Self::SpriteFrames),
+            _ => TileFormat::from_extension(ext).map(Self::Tiles),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tiles(t) => write!(f, "{}", t),
+            Self::Array { indexed: false } => write!(f, "c-array"),
+            Self::Array { indexed: true } => write!(f, "c-indexed"),
+            Self::Xpm => write!(f, "xpm"),
+            Self::SpriteFrames => write!(f, "godot"),
+        }
+    }
+}
+
+/// Pixel-art scaling filter, used to smooth exported pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleFilter {
+    /// The Scale2x algorithm.
+    Scale2x,
+    /// The Scale3x algorithm.
+    Scale3x,
+}
+
+impl ScaleFilter {
+    /// Factor the filter scales by in a single pass.
+    pub fn factor(&self) -> u32 {
+        match self {
+            Self::Scale2x => 2,
+            Self::Scale3x => 3,
+        }
+    }
+
+    /// Scale an image by the given factor, applying the filter as many times as
+    /// needed. Returns `None` if the factor isn't a power of the filter's factor.
+    pub fn scale(
+        &self,
+        image: &[Rgba8],
+        width: u32,
+        height: u32,
+        factor: u32,
+    ) -> Option<Vec<Rgba8>> {
+        let (mut image, mut w, mut h, mut n) = (image.to_vec(), width, height, factor);
+
+        while n > 1 {
+            if n % self.factor() != 0 {
+                return None;
+            }
+            image = match self {
+                Self::Scale2x => pixels::scale2x(&image, w, h),
+                Self::Scale3x => pixels::scale3x(&image, w, h),
+            };
+            w *= self.factor();
+            h *= self.factor();
+            n /= self.factor();
+        }
+        Some(image)
+    }
+}
+
+impl fmt::Display for ScaleFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scale2x => write!(f, "scale2x"),
+            Self::Scale3x => write!(f, "scale3x"),
+        }
+    }
+}
+
 /// Current state of the view.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ViewState {
@@ -142,8 +278,9 @@ pub enum ViewState {
     /// A layer has been touched.
     LayerDirty(LayerId),
     /// A layer is damaged, it needs to be redrawn from a snapshot.
-    /// This happens when undo/redo is used.
-    LayerDamaged(LayerId),
+    /// This happens when undo/redo is used. If the parameter is `Some`,
+    /// only the given area of the layer needs to be redrawn.
+    LayerDamaged(LayerId, Option<Rect<u32>>),
 }
 
 /// A view operation to be carried out by the renderer.
@@ -153,22 +290,40 @@ pub enum ViewOp {
     Blit(Rect<f32>, Rect<f32>),
     /// Clear to a color.
     Clear(Rgba8),
+    /// Clear an area to a color.
+    ClearRect(Rgba8, Rect<f32>),
     /// Yank the given area into the paste buffer.
     Yank(LayerId, Rect<i32>),
     /// Flips a given area horizontally or vertically.
     Flip(LayerId, Rect<i32>, Axis),
+    /// Shifts the pixels of a given area, optionally wrapping them around its edges.
+    Shift(LayerId, Rect<i32>, (i32, i32), bool),
     /// Blit the paste buffer into the given area.
     Paste(Rect<i32>),
     /// Resize the view.
     Resize(u32, u32),
     /// Paint a single pixel.
     SetPixel(LayerId, Rgba8, i32, i32),
+    /// Replace all the pixels of a layer, top row first.
+    SetPixels(LayerId, Vec<Rgba8>),
+    /// Fill the paste buffer with the given pixels, top row first.
+    SetPaste(u32, u32, Vec<Rgba8>),
     /// Add a layer.
     AddLayer(LayerId, FrameRange),
     /// Remove a layer.
     RemoveLayer(LayerId),
 }
 
+/// A guide line, used for alignment. Positions are in layer coordinates,
+/// and refer to the edge between two pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Guide {
+    /// A horizontal guide at the given `y` coordinate.
+    Horizontal(i32),
+    /// A vertical guide at the given `x` coordinate.
+    Vertical(i32),
+}
+
 /// A view on a sprite or image.
 #[derive(Debug)]
 pub struct View<R> {
@@ -182,6 +337,9 @@ pub struct View<R> {
     pub id: ViewId,
     /// Zoom level.
     pub zoom: f32,
+    /// Displayed pixel width and height, relative to the zoom. Pixels are
+    /// square unless the `pixel-ratio` setting is changed.
+    pub pixel_ratio: Vector2<f32>,
     /// List of operations to carry out on the view.  Cleared every frame.
     pub ops: Vec<ViewOp>,
     /// Whether the view is flipped in the X axis.
@@ -198,6 +356,15 @@ pub struct View<R> {
     pub layers: NonEmpty<Layer>,
     /// Currently active layer.
     pub active_layer_id: LayerId,
+    /// Guide lines.
+    pub guides: Vec<Guide>,
+    /// Checkpoint to show the differences with, if any.
+    pub diff: Option<String>,
+    /// Color to highlight the pixels of, if any. Set with `:search`.
+    pub search: Option<Rgba8>,
+    /// The view's own palette, shown instead of the session palette while the
+    /// view is active. Saved with the view.
+    pub palette: Option<Swatches>,
     /// View resource.
     pub resource: R,
 
@@ -205,24 +372,124 @@ pub struct View<R> {
     saved_snapshot: Option<EditId>,
 }
 
+/// Animation playback mode.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Playback {
+    /// Loop from the first to the last frame.
+    #[default]
+    Forward,
+    /// Loop from the last to the first frame.
+    Reverse,
+    /// Alternate between playing forward and in reverse.
+    PingPong,
+}
+
+impl fmt::Display for Playback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Forward => "forward".fmt(f),
+            Self::Reverse => "reverse".fmt(f),
+            Self::PingPong => "pingpong".fmt(f),
+        }
+    }
+}
+
 /// View animation.
 #[derive(Debug)]
 pub struct Animation<T> {
     pub index: usize,
     pub frames: Vec<T>,
+    /// Playback mode.
+    pub playback: Playback,
+    /// Frame range to play back, inclusive. Plays all frames if `None`.
+    pub range: Option<(usize, usize)>,
+    /// Delay between frames, if it isn't the `animation/delay` setting.
+    /// Loaded from images saved by rx.
+    pub delay: Option<time::Duration>,
+
+    /// Whether a ping-pong playback is currently going backwards.
+    backwards: bool,
+    /// Time since the last step.
+    elapsed: time::Duration,
 }
 
 impl<T> Animation<T> {
     pub fn new(frames: Vec<T>) -> Self {
-        Self { index: 0, frames }
+        Self {
+            index: 0,
+            frames,
+            playback: Playback::default(),
+            range: None,
+            delay: None,
+            backwards: false,
+            elapsed: time::Duration::from_secs(0),
+        }
     }
 
     pub fn len(&self) -> usize {
         self.frames.len()
     }
 
+    /// The first and last frame played back, inclusive.
+    pub fn bounds(&self) -> (usize, usize) {
+        let last = self.len().saturating_sub(1);
+
+        match self.range {
+            Some((start, end)) => (start.min(last), end.min(last)),
+            None => (0, last),
+        }
+    }
+
+    /// Advance playback by the given time, stepping once the frame delay has
+    /// passed. The delay is used unless the animation has its own.
+    pub fn advance(&mut self, delta: time::Duration, delay: time::Duration) {
+        self.elapsed += delta;
+
+        if self.elapsed >= self.delay.unwrap_or(delay) {
+            self.step();
+            self.elapsed = time::Duration::from_secs(0);
+        }
+    }
+
+    /// Time left until the next step, given the default frame delay.
+    pub fn remaining(&self, delay: time::Duration) -> time::Duration {
+        self.delay
+            .unwrap_or(delay)
+            .checked_sub(self.elapsed)
+            .unwrap_or_default()
+    }
+
     pub fn step(&mut self) {
-        self.index = (self.index + 1) % self.len();
+        let (start, end) = self.bounds();
+
+        if self.index < start || self.index > end {
+            self.index = start;
+            self.backwards = false;
+            return;
+        }
+
+        self.index = match self.playback {
+            Playback::Forward if self.index == end => start,
+            Playback::Forward => self.index + 1,
+            Playback::Reverse if self.index == start => end,
+            Playback::Reverse => self.index - 1,
+            Playback::PingPong => {
+                if start == end {
+                    start
+                } else {
+                    if self.index == end {
+                        self.backwards = true;
+                    } else if self.index == start {
+                        self.backwards = false;
+                    }
+                    if self.backwards {
+                        self.index - 1
+                    } else {
+                        self.index + 1
+                    }
+                }
+            }
+        };
     }
 
     pub fn val(&self) -> &T {
@@ -264,6 +531,7 @@ impl<R> View<R> {
             fh,
             offset: Vector2::zero(),
             zoom: 1.,
+            pixel_ratio: Vector2::new(1., 1.),
             ops: Vec::new(),
             flip_x: false,
             flip_y: false,
@@ -272,6 +540,10 @@ impl<R> View<R> {
             state: ViewState::Okay,
             layers: NonEmpty::new(Layer::default()),
             active_layer_id: Default::default(),
+            guides: Vec::new(),
+            diff: None,
+            search: None,
+            palette: None,
             saved_snapshot,
             resource,
         }
@@ -346,6 +618,130 @@ impl<R> View<R> {
         ));
     }
 
+    /// Insert a blank frame at the given index, shifting the following
+    /// frames to the right.
+    pub fn insert_frame(&mut self, index: usize) {
+        let n = self.animation.len();
+
+        self.extend();
+        self.shift_frames(index, n, 1);
+        self.ops.push(ViewOp::ClearRect(
+            Rgba8::TRANSPARENT,
+            self.frame_rect(index),
+        ));
+    }
+
+    /// Duplicate the frame at the given index. The copy is inserted right
+    /// after the original.
+    pub fn duplicate_frame(&mut self, index: usize) {
+        let n = self.animation.len();
+
+        self.extend();
+        self.shift_frames(index + 1, n, 1);
+        self.ops.push(ViewOp::Blit(
+            self.frame_rect(index),
+            self.frame_rect(index + 1),
+        ));
+    }
+
+    /// Remove the frame at the given index, shifting the following frames
+    /// to the left. Views always keep at least one frame.
+    pub fn remove_frame(&mut self, index: usize) -> Result<(), &'static str> {
+        let n = self.animation.len();
+
+        if n <= 1 {
+            return Err("can't remove the last frame");
+        }
+        self.shrink();
+        self.shift_frames(index + 1, n, -1);
+
+        Ok(())
+    }
+
+    /// Move the frame at index `from` to index `to`.
+    pub fn move_frame(&mut self, from: usize, to: usize) {
+        if from == to {
+            return;
+        }
+        if from < to {
+            self.shift_frames(from + 1, to + 1, -1);
+        } else {
+            self.shift_frames(to, from, 1);
+        }
+        self.ops
+            .push(ViewOp::Blit(self.frame_rect(from), self.frame_rect(to)));
+        self.touch();
+    }
+
+    /// Number of tile columns and rows, for tiles of the given size. Only whole
+    /// tiles are counted, starting from the top-left corner of the view.
+    pub fn tiles(&self, size: u32) -> (u32, u32) {
+        if size == 0 {
+            return (0, 0);
+        }
+        (self.width() / size, self.fh / size)
+    }
+
+    /// Rect of the tile at the given index, in layer coordinates. Tiles are
+    /// numbered row by row, from the top-left.
+    pub fn tile_rect(&self, size: u32, index: usize) -> Option<Rect<i32>> {
+        let (cols, rows) = self.tiles(size);
+
+        if index >= (cols * rows) as usize {
+            return None;
+        }
+        let (tx, ty) = (index as u32 % cols, index as u32 / cols);
+        let (x, y) = ((tx * size) as i32, (self.fh - ty * size) as i32);
+
+        Some(Rect::new(x, y - size as i32, x + size as i32, y))
+    }
+
+    /// Index of the tile containing the given layer pixel, if any.
+    pub fn tile_at(&self, size: u32, p: Point2<i32>) -> Option<usize> {
+        let (cols, rows) = self.tiles(size);
+        let row = self.fh as i32 - 1 - p.y;
+
+        if size == 0 || p.x < 0 || row < 0 {
+            return None;
+        }
+        let (tx, ty) = (p.x as u32 / size, row as u32 / size);
+
+        if tx < cols && ty < rows {
+            Some((ty * cols + tx) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Copy the tile at index `src` over the tile at index `dst`.
+    pub fn copy_tile(&mut self, size: u32, src: usize, dst: usize) -> bool {
+        match (self.tile_rect(size, src), self.tile_rect(size, dst)) {
+            (Some(s), Some(d)) => {
+                self.ops
+                    .push(ViewOp::Blit(s.map(|n| n as f32), d.map(|n| n as f32)));
+                self.touch();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Swap the tiles at the given indices. Since blits read from the last
+    /// snapshot, both tiles can be copied over each other.
+    pub fn swap_tiles(&mut self, size: u32, a: usize, b: usize) -> bool {
+        match (self.tile_rect(size, a), self.tile_rect(size, b)) {
+            (Some(ra), Some(rb)) => {
+                let (ra, rb) = (ra.map(|n| n as f32), rb.map(|n| n as f32));
+
+                self.ops.push(ViewOp::Blit(ra, rb));
+                self.ops.push(ViewOp::Blit(rb, ra));
+                self.touch();
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Resize view frames to the given size.
     pub fn resize_frames(&mut self, fw: u32, fh: u32) {
         self.reset(ViewExtent::new(fw, fh, self.animation.len()));
@@ -421,6 +817,8 @@ impl<R> View<R> {
         self.touch();
     }
 
+    /// Paint a single pixel of the active layer. Unlike view coordinates,
+    /// rows are counted from the top of the layer.
     pub fn paint_color(&mut self, color: Rgba8, x: i32, y: i32) {
         self.ops
             .push(ViewOp::SetPixel(self.active_layer_id, color, x, y));
@@ -434,6 +832,16 @@ impl<R> View<R> {
         self.ops.push(ViewOp::Flip(self.active_layer_id, area, dir));
     }
 
+    pub fn shift(&mut self, area: Rect<i32>, offset: (i32, i32), wrap: bool) {
+        self.ops
+            .push(ViewOp::Shift(self.active_layer_id, area, offset, wrap));
+    }
+
+    /// Load pixels into the paste buffer, eg. from an imported image.
+    pub fn load_paste(&mut self, w: u32, h: u32, pixels: Vec<Rgba8>) {
+        self.ops.push(ViewOp::SetPaste(w, h, pixels));
+    }
+
     pub fn paste(&mut self, area: Rect<i32>) {
         self.ops.push(ViewOp::Paste(area));
         self.touch_layer();
@@ -455,8 +863,8 @@ impl<R> View<R> {
     }
 
     /// Restore a view layer to a given snapshot.
-    pub fn restore_layer(&mut self, eid: EditId, layer: LayerId) {
-        self.layer_damaged(layer);
+    pub fn restore_layer(&mut self, eid: EditId, layer: LayerId, dirty: Option<Rect<u32>>) {
+        self.layer_damaged(layer, dirty);
         self.refresh_file_status(eid);
     }
 
@@ -495,29 +903,49 @@ impl<R> View<R> {
         self.state = ViewState::Okay;
     }
 
+    /// The displayed size of a view pixel, at the given zoom.
+    pub fn pixel_size(&self, zoom: f32) -> Vector2<f32> {
+        self.pixel_ratio * zoom
+    }
+
+    /// The displayed size of a view pixel, at the view zoom.
+    pub fn scale(&self) -> Vector2<f32> {
+        self.pixel_size(self.zoom)
+    }
+
+    /// Transform from view pixels to displayed pixels, not including the offset.
+    pub fn transform(&self) -> Matrix4<f32> {
+        let s = self.scale();
+        Matrix4::from_nonuniform_scale(s.x, s.y, 1.)
+    }
+
     /// Return the view area, including the offset.
     pub fn rect(&self) -> Rect<f32> {
+        let s = self.scale();
+
         Rect::new(
             self.offset.x,
             self.offset.y,
-            self.offset.x + self.width() as f32 * self.zoom,
-            self.offset.y + self.height() as f32 * self.zoom,
+            self.offset.x + self.width() as f32 * s.x,
+            self.offset.y + self.height() as f32 * s.y,
         )
     }
 
     /// Return the area of the given layer, including the view offset.
     pub fn layer_rect(&self, index: usize) -> Rect<f32> {
+        let s = self.scale();
+
         Rect::new(
             self.offset.x,
-            self.offset.y + (self.fh * index as u32) as f32 * self.zoom,
-            self.offset.x + self.width() as f32 * self.zoom,
-            self.offset.y + (self.fh * (index + 1) as u32) as f32 * self.zoom,
+            self.offset.y + (self.fh * index as u32) as f32 * s.y,
+            self.offset.x + self.width() as f32 * s.x,
+            self.offset.y + (self.fh * (index + 1) as u32) as f32 * s.y,
         )
     }
 
     /// The offset of the layer at the given index.
     pub fn layer_offset(&self, index: usize, zoom: f32) -> Vector2<f32> {
-        Vector2::new(0., self.fh as f32 * index as f32 * zoom)
+        Vector2::new(0., self.fh as f32 * index as f32 * self.pixel_size(zoom).y)
     }
 
     /// Check whether the session coordinates are contained within the view.
@@ -541,7 +969,7 @@ impl<R> View<R> {
     pub fn active_layer_center(&self) -> ViewCoords<f32> {
         ViewCoords::new(
             self.width() as f32 / 2.,
-            self.layer_offset(self.active_layer_id, 1.).y + self.fh as f32 / 2.,
+            (self.fh * self.active_layer_id as u32) as f32 + self.fh as f32 / 2.,
         )
     }
 
@@ -575,15 +1003,15 @@ impl<R> View<R> {
 
     /// Layer should be considered damaged and needs to be restored from snapshot.
     /// Used when undoing or redoing changes.
-    pub fn layer_damaged(&mut self, layer: LayerId) {
-        self.state = ViewState::LayerDamaged(layer);
+    pub fn layer_damaged(&mut self, layer: LayerId, dirty: Option<Rect<u32>>) {
+        self.state = ViewState::LayerDamaged(layer, dirty);
     }
 
     /// Check whether the view is damaged.
     pub fn is_damaged(&self) -> bool {
         matches!(
             self.state,
-            ViewState::Damaged(_) | ViewState::LayerDamaged(_)
+            ViewState::Damaged(_) | ViewState::LayerDamaged(_, _)
         )
     }
 
@@ -634,6 +1062,22 @@ impl<R> View<R> {
         self.ops.push(ViewOp::Resize(self.width(), self.fh));
     }
 
+    /// Rect of the frame at the given index, in view coordinates.
+    fn frame_rect(&self, index: usize) -> Rect<f32> {
+        let (fw, fh) = (self.fw as f32, self.fh as f32);
+        Rect::new(fw * index as f32, 0., fw * (index + 1) as f32, fh)
+    }
+
+    /// Shift the frames in the range `from..to` by `offset` frames. Since blits read
+    /// from the last snapshot, overlapping ranges are copied correctly.
+    fn shift_frames(&mut self, from: usize, to: usize, offset: isize) {
+        for i in from..to {
+            let dst = (i as isize + offset) as usize;
+            self.ops
+                .push(ViewOp::Blit(self.frame_rect(i), self.frame_rect(dst)));
+        }
+    }
+
     /// Check whether the given snapshot has been saved to disk.
     fn is_snapshot_saved(&self, id: EditId) -> bool {
         self.saved_snapshot == Some(id)
@@ -656,11 +1100,169 @@ impl<R> View<R> {
         for i in 0..extent.nframes {
             frames.push(origin + Vector2::new(i as f32 * self.fw as f32, 0.));
         }
-        self.animation = Animation::new(frames);
+        // Playback settings belong to the image, and survive resizing. Ranges
+        // are clamped to the new frame count when played back.
+        let Animation {
+            playback,
+            range,
+            delay,
+            ..
+        } = self.animation;
+
+        self.animation = Animation {
+            playback,
+            range,
+            delay,
+            ..Animation::new(frames)
+        };
     }
 }
 
 impl View<ViewResource> {
+    /// Find groups of identical tiles, across all layers. Each group lists the tile
+    /// indices in order, and fully transparent tiles are ignored.
+    pub fn duplicate_tiles(&self, size: u32) -> Vec<Vec<usize>> {
+        let (cols, rows) = self.tiles(size);
+        let mut groups: BTreeMap<Vec<Rgba8>, Vec<usize>> = BTreeMap::new();
+
+        for i in 0..(cols * rows) as usize {
+            let rect = match self.tile_rect(size, i) {
+                Some(r) => r,
+                None => continue,
+            };
+            let mut pixels = Vec::new();
+
+            for layer in self.resource.layers.values() {
+                if let Some((_, p)) = layer.get_snapshot_rect(&rect) {
+                    pixels.extend(p);
+                }
+            }
+            if pixels.iter().all(|p| p.a == 0) {
+                continue;
+            }
+            groups.entry(pixels).or_default().push(i);
+        }
+        let mut dupes = groups
+            .into_values()
+            .filter(|g| g.len() > 1)
+            .collect::<Vec<_>>();
+        dupes.sort();
+
+        dupes
+    }
+
+    /// Number of distinct colors in each frame, across all layers. Transparent
+    /// pixels aren't counted.
+    pub fn frame_colors(&self) -> Vec<usize> {
+        let extent = self.extent();
+
+        (0..extent.nframes)
+            .map(|i| {
+                let rect = extent.frame(i).map(|n| n as i32);
+                let mut colors = BTreeSet::new();
+
+                for layer in self.resource.layers.values() {
+                    if let Some((_, pixels)) = layer.get_snapshot_rect(&rect) {
+                        colors.extend(pixels.into_iter().filter(|p| p.a != 0));
+                    }
+                }
+                colors.len()
+            })
+            .collect()
+    }
+
+    /// Bounds of the non-transparent pixels of the view, across all frames and layers,
+    /// in frame coordinates with the first row at `y = 0`. Returns `None` if the view
+    /// has no visible pixels.
+    pub fn content_bounds(&self) -> Option<Rect<u32>> {
+        let fw = self.fw as usize;
+        let mut bounds: Option<Rect<u32>> = None;
+
+        for layer in self.resource.layers.values() {
+            let (_, pixels) = layer.current_snapshot();
+
+            for i in 0..self.animation.len() {
+                let x = i * fw;
+                let frame = pixels
+                    .chunks_exact(self.width() as usize)
+                    .flat_map(|row| &row[x..x + fw])
+                    .copied()
+                    .collect::<Vec<_>>();
+
+                if let Some(r) = pixels::bounds(&frame, self.fw, |p| p.a != 0) {
+                    bounds = Some(match bounds {
+                        Some(b) => Rect::new(
+                            b.x1.min(r.x1),
+                            b.y1.min(r.y1),
+                            b.x2.max(r.x2),
+                            b.y2.max(r.y2),
+                        ),
+                        None => r,
+                    });
+                }
+            }
+        }
+        bounds
+    }
+
+    /// Replace the pixels of a frame, or of every frame if `frame` is `None`, on all
+    /// layers. The transform is given the frame pixels with its width and height, and
+    /// returns the new pixels along with their size. When the size changes, the frames
+    /// are resized, so every frame must be transformed.
+    pub fn transform_frames(
+        &mut self,
+        frame: Option<usize>,
+        transform: impl Fn(&[Rgba8], u32, u32) -> (Vec<Rgba8>, u32, u32),
+    ) {
+        let (fw, fh, nframes) = (self.fw, self.fh, self.animation.len());
+        let frames = frame.map_or(0..nframes, |i| i..i + 1);
+        let mut layers = Vec::new();
+        let mut size = (fw, fh);
+
+        for (id, layer) in self.resource.layers.iter() {
+            let (_, pixels) = layer.current_snapshot();
+            let mut output = Vec::new();
+
+            for i in 0..nframes {
+                let x = i * fw as usize;
+                let input = pixels
+                    .chunks_exact(self.width() as usize)
+                    .flat_map(|row| &row[x..x + fw as usize])
+                    .copied()
+                    .collect::<Vec<_>>();
+
+                if frames.contains(&i) {
+                    let (pixels, w, h) = transform(&input, fw, fh);
+
+                    size = (w, h);
+                    output.push(pixels);
+                } else {
+                    output.push(input);
+                }
+            }
+            let (w, h) = size;
+            let width = w as usize * nframes;
+            let mut strip = vec![Rgba8::TRANSPARENT; width * h as usize];
+
+            for (i, frame) in output.iter().enumerate() {
+                for (y, row) in frame.chunks_exact(w as usize).enumerate() {
+                    let start = y * width + i * w as usize;
+                    strip[start..start + w as usize].copy_from_slice(row);
+                }
+            }
+            layers.push((*id, strip));
+        }
+
+        if size == (fw, fh) {
+            self.touch();
+        } else {
+            self.resize_frames(size.0, size.1);
+        }
+        for (id, pixels) in layers {
+            self.ops.push(ViewOp::SetPixels(id, pixels));
+        }
+    }
+
     /// Add a new layer with optional pixels.
     pub fn add_layer(&mut self, pixels: Option<Vec<Rgba8>>) -> LayerId {
         let id = self.push_layer();
@@ -689,7 +1291,41 @@ impl View<ViewResource> {
     }
 
     /// Restore a view snapshot (undo/redo an edit).
+    /// Undo or redo. Edits that were grouped together are restored as one.
     pub fn restore_snapshot(&mut self, dir: Direction) {
+        let mut steps = 0;
+
+        loop {
+            let cursor = self.resource.current_edit();
+            self.restore_step(dir);
+
+            if self.resource.current_edit() == cursor {
+                break;
+            }
+            steps += 1;
+
+            let grouped = match dir {
+                Direction::Backward => self.resource.history_grouped(cursor),
+                Direction::Forward => self
+                    .resource
+                    .history_redo()
+                    .is_some_and(|e| self.resource.history_grouped(e)),
+            };
+            if !grouped {
+                break;
+            }
+        }
+        // Since more than one edit may have been restored, redraw the
+        // whole view from its snapshots.
+        if steps > 1 {
+            let (edit, extent) = (self.resource.current_edit(), self.resource.extent);
+            self.restore_extent(edit, extent);
+        }
+    }
+
+    /// Undo or redo a single edit.
+    fn restore_step(&mut self, dir: Direction) {
+        let cursor = self.resource.current_edit();
         let result = if dir == Direction::Backward {
             self.resource.history_prev()
         } else {
@@ -698,7 +1334,14 @@ impl View<ViewResource> {
 
         match result {
             Some((eid, Edit::LayerPainted(layer))) => {
-                self.restore_layer(eid, layer);
+                // The edit that was undone or redone.
+                let edit = match dir {
+                    Direction::Backward => cursor,
+                    Direction::Forward => eid,
+                };
+                let dirty = self.resource.history_dirty(edit, layer);
+
+                self.restore_layer(eid, layer, dirty);
             }
             Some((eid, Edit::LayerAdded(layer))) => {
                 match dir {
@@ -729,7 +1372,38 @@ impl View<ViewResource> {
         }
     }
 
-    pub fn save_as(&mut self, storage: &FileStorage) -> io::Result<usize> {
+    /// Revert the view to the given edit, by walking the history tree back to
+    /// the closest common ancestor, and forward again along the edit's branch.
+    pub fn revert(&mut self, edit: EditId) {
+        let ancestors = self.resource.history_ancestors(edit);
+        if ancestors.is_empty() {
+            return;
+        }
+
+        while !ancestors.contains(&self.resource.current_edit()) {
+            let cursor = self.resource.current_edit();
+            self.restore_step(Direction::Backward);
+
+            if self.resource.current_edit() == cursor {
+                return;
+            }
+        }
+        let cursor = self.resource.current_edit();
+        let common = ancestors.iter().position(|id| *id == cursor).unwrap_or(0);
+
+        for id in ancestors[..common].iter().rev() {
+            self.resource.history_select(*id);
+            self.restore_step(Direction::Forward);
+        }
+        // Since more than one edit may have been restored, redraw the
+        // whole view from its snapshots.
+        let extent = self.resource.extent;
+        self.restore_extent(edit, extent);
+    }
+
+    /// Save the view with the given storage. The animation `delay` is stored
+    /// in the file metadata when saving an animation to a single image.
+    pub fn save_as(&mut self, storage: &FileStorage, delay: time::Duration) -> io::Result<usize> {
         let active_layer_id = self.active_layer_id;
         let ext = self.extent();
         let nlayers = self.layers.len();
@@ -748,14 +1422,32 @@ impl View<ViewResource> {
 
                     (edit_id, written)
                 } else {
-                    let edit_id = self.save_layer_rect_as(active_layer_id, ext.rect(), path)?;
+                    let meta = if ext.nframes > 1 {
+                        image::Metadata {
+                            nframes: Some(ext.nframes),
+                            delay: Some(delay.as_millis() as u64),
+                            palette: self.palette.clone(),
+                        }
+                    } else {
+                        image::Metadata {
+                            palette: self.palette.clone(),
+                            ..image::Metadata::default()
+                        }
+                    };
+                    let edit_id =
+                        self.save_layer_rect_as(active_layer_id, ext.rect(), path, &meta)?;
 
                     (edit_id, (ext.width() * ext.height()) as usize)
                 }
             }
             FileStorage::Range(paths) if nlayers == 1 => {
                 for (i, path) in paths.iter().enumerate() {
-                    self.save_layer_rect_as(active_layer_id, ext.frame(i), path)?;
+                    self.save_layer_rect_as(
+                        active_layer_id,
+                        ext.frame(i),
+                        path,
+                        &image::Metadata::default(),
+                    )?;
                 }
 
                 let edit_id = self.resource.current_edit();
@@ -792,6 +1484,7 @@ impl View<ViewResource> {
         layer_id: LayerId,
         rect: Rect<u32>,
         path: &std::path::Path,
+        meta: &image::Metadata,
     ) -> io::Result<EditId> {
         // Only allow overwriting of files if it's the file of the view being saved.
         if path.exists() && self.file_storage().map_or(true, |f| !f.contains(path)) {
@@ -800,7 +1493,7 @@ impl View<ViewResource> {
                 format!("\"{}\" already exists", path.display()),
             ));
         }
-        let (e_id, _) = self.save_layer(layer_id, rect, &path)?;
+        let (e_id, _) = self.save_layer(layer_id, rect, &path, meta)?;
 
         Ok(e_id)
     }
@@ -1094,3 +1787,104 @@ impl ViewManager<ViewResource> {
             .get_snapshot_rect(rect)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_animation_playback() {
+        let mut anim = Animation::new(vec![(); 5]);
+        let indices = |anim: &mut Animation<()>, n: usize| -> Vec<usize> {
+            (0..n)
+                .map(|_| {
+                    anim.step();
+                    anim.index
+                })
+                .collect()
+        };
+
+        assert_eq!(indices(&mut anim, 6), vec![1, 2, 3, 4, 0, 1]);
+
+        anim.playback = Playback::Reverse;
+        assert_eq!(indices(&mut anim, 3), vec![0, 4, 3]);
+
+        anim.playback = Playback::PingPong;
+        anim.range = Some((1, 3));
+        assert_eq!(indices(&mut anim, 6), vec![2, 1, 2, 3, 2, 1]);
+
+        anim.range = Some((4, 4));
+        assert_eq!(indices(&mut anim, 2), vec![4, 4]);
+    }
+
+    #[test]
+    fn test_view_reset() {
+        let mut view = View::new(ViewId::default(), FileStatus::NoFile, 4, 4, 3, ());
+        let delay = Some(time::Duration::from_millis(80));
+
+        view.animation.playback = Playback::PingPong;
+        view.animation.range = Some((1, 2));
+        view.animation.delay = delay;
+
+        view.resize_frames(8, 8);
+        view.slice(1);
+        view.restore_extent(0, ViewExtent::new(4, 4, 2));
+
+        assert_eq!(view.animation.len(), 2);
+        assert_eq!(view.animation.playback, Playback::PingPong);
+        assert_eq!(view.animation.range, Some((1, 2)));
+        assert_eq!(view.animation.bounds(), (1, 1));
+        assert_eq!(view.animation.delay, delay);
+    }
+
+    #[test]
+    fn test_active_layer_center() {
+        let mut view = View::new(ViewId::default(), FileStatus::NoFile, 4, 6, 2, ());
+
+        view.pixel_ratio = Vector2::new(1., 2.);
+        view.active_layer_id = 1;
+
+        // The center is in view pixels, whatever the pixel ratio.
+        assert_eq!(view.active_layer_center(), ViewCoords::new(4., 9.));
+    }
+
+    #[test]
+    fn test_remove_frame() {
+        let mut view = View::new(ViewId::default(), FileStatus::NoFile, 4, 4, 2, ());
+
+        assert_eq!(view.remove_frame(0), Ok(()));
+        assert_eq!(view.animation.len(), 1);
+        assert_eq!(view.remove_frame(0), Err("can't remove the last frame"));
+        assert_eq!(view.animation.len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_tiles() {
+        let mut pixels = vec![Rgba8::TRANSPARENT; 6 * 2];
+        pixels[0] = Rgba8::RED;
+        pixels[4] = Rgba8::RED;
+
+        let resource = ViewResource::new(pixels, ViewExtent::new(6, 2, 1));
+        let view = View::new(ViewId::default(), FileStatus::NoFile, 6, 2, 1, resource);
+
+        // Empty tiles aren't duplicates of each other.
+        assert_eq!(view.duplicate_tiles(2), vec![vec![0, 2]]);
+        assert!(view.duplicate_tiles(0).is_empty());
+    }
+
+    #[test]
+    fn test_animation_delay() {
+        let ms = time::Duration::from_millis;
+        let mut anim = Animation::new(vec![(); 3]);
+
+        anim.advance(ms(100), ms(160));
+        assert_eq!((anim.index, anim.remaining(ms(160))), (0, ms(60)));
+        anim.advance(ms(60), ms(160));
+        assert_eq!(anim.index, 1);
+
+        // The animation's own delay wins over the default.
+        anim.delay = Some(ms(40));
+        anim.advance(ms(40), ms(160));
+        assert_eq!((anim.index, anim.remaining(ms(160))), (2, ms(40)));
+    }
+}
@@ -6,13 +6,14 @@ use std::path;
 /// Rx archive format extension.
 pub const ARCHIVE_FORMAT: &str = "rxz";
 /// Supported image formats for reading.
-pub const SUPPORTED_READ_FORMATS: &[&str] = &["png", self::ARCHIVE_FORMAT];
+pub const SUPPORTED_READ_FORMATS: &[&str] = &["png", "psd", self::ARCHIVE_FORMAT];
 
 #[derive(Debug, Copy, Clone)]
 pub enum Format {
     Archive,
     Png,
     Gif,
+    Psd,
 }
 
 #[derive(Debug, Clone)]
@@ -48,6 +49,7 @@ impl<'a> TryFrom<&'a path::Path> for Path<'a, path::Path> {
         let format = match ext {
             "gif" => Format::Gif,
             "png" => Format::Png,
+            "psd" => Format::Psd,
             ARCHIVE_FORMAT => Format::Archive,
             _ => {
                 return Err(io::Error::new(
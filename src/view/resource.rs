@@ -2,9 +2,10 @@ use crate::gfx::color::{Rgb8, Rgba8};
 use crate::gfx::rect::Rect;
 use crate::image;
 use crate::pixels;
+use crate::profile::TileFormat;
 use crate::util;
 use crate::view::layer::{LayerCoords, LayerId};
-use crate::view::ViewExtent;
+use crate::view::{Playback, SheetLayout, ViewExtent};
 
 use nonempty::NonEmpty;
 
@@ -12,19 +13,44 @@ use gif::{self, SetParameter};
 
 use microserde::json;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::fs::File;
 use std::io;
 use std::path::Path;
+use std::rc::Rc;
 use std::time;
 
+/// Statistics on the pixels and history of a view, across all layers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Distinct colors of the non-transparent pixels, with their pixel counts,
+    /// most used first.
+    pub colors: Vec<(Rgba8, usize)>,
+    /// Total number of pixels.
+    pub pixels: usize,
+    /// Number of fully transparent pixels.
+    pub transparent: usize,
+    /// Number of snapshots kept for the view history.
+    pub snapshots: usize,
+    /// Memory used by snapshots and pixel caches, in bytes.
+    pub memory: usize,
+}
+
 #[derive(Debug)]
 pub struct ViewResource {
     pub layers: BTreeMap<LayerId, LayerResource>,
-    pub history: NonEmpty<Edit>,
-    pub cursor: usize,
+    /// Edit history, as a tree. Edits are stored in the order they were made,
+    /// and are never discarded.
+    pub history: NonEmpty<HistoryNode>,
+    pub cursor: EditId,
     pub extent: ViewExtent,
+    /// Named checkpoints in the view history.
+    pub checkpoints: BTreeMap<String, Checkpoint>,
+    /// Nesting depth of the open undo groups.
+    group: usize,
+    /// Number of edits recorded since the outermost undo group was opened.
+    grouped: usize,
 }
 
 impl ViewResource {
@@ -33,9 +59,20 @@ impl ViewResource {
             layers: vec![(Default::default(), LayerResource::new(pixels, extent))]
                 .drain(..)
                 .collect(),
-            history: NonEmpty::new(Edit::Initial),
+            history: NonEmpty::new(HistoryNode {
+                edit: Edit::Initial,
+                parent: None,
+                children: Vec::new(),
+                redo: None,
+                grouped: false,
+                time: time::Instant::now(),
+                snapshots: Vec::new(),
+            }),
             cursor: 0,
             extent,
+            checkpoints: BTreeMap::new(),
+            group: 0,
+            grouped: 0,
         }
     }
 
@@ -56,9 +93,20 @@ impl ViewResource {
     }
 
     pub fn add_layer(&mut self, layer_id: LayerId, extent: ViewExtent, pixels: Vec<Rgba8>) {
-        self.layers
-            .insert(layer_id, LayerResource::new(pixels, extent));
-        self.history_record(Edit::LayerAdded(layer_id));
+        // A hidden layer with the same id may exist on another branch of the
+        // history. Keep its snapshots around, so that the branch can be restored.
+        let snapshots = if let Some(layer) = self.layers.get_mut(&layer_id) {
+            let (before, after) = layer.push_snapshot(pixels, extent);
+            layer.hidden = false;
+
+            vec![(layer_id, before, after)]
+        } else {
+            self.layers
+                .insert(layer_id, LayerResource::new(pixels, extent));
+
+            vec![(layer_id, 0, 0)]
+        };
+        self.history_record(Edit::LayerAdded(layer_id), snapshots);
     }
 
     pub fn save_layer<P: AsRef<Path>>(
@@ -66,6 +114,7 @@ impl ViewResource {
         layer_id: LayerId,
         rect: Rect<u32>,
         path: P,
+        meta: &image::Metadata,
     ) -> io::Result<(EditId, usize)> {
         let (_, pixels) = self
             .layer(layer_id)
@@ -73,130 +122,313 @@ impl ViewResource {
             .expect("rect should be within view");
         let (w, h) = (rect.width(), rect.height());
 
-        image::save_as(path, w, h, 1, &pixels)?;
+        image::save_as(path, w, h, 1, &pixels, meta)?;
 
         Ok((self.cursor, (w * h) as usize))
     }
 
     pub fn record_view_resized(&mut self, layers: Vec<(LayerId, Vec<Rgba8>)>, extent: ViewExtent) {
-        self.history_record(Edit::ViewResized(
+        let edit = Edit::ViewResized(
             layers.iter().map(|(l, _)| *l).collect(),
             self.extent,
             extent,
-        ));
-        self.extent = extent;
+        );
+        let snapshots = self.push_snapshots(layers, extent);
 
-        for (id, pixels) in layers.into_iter() {
-            self.layer_mut(id).push_snapshot(pixels, extent);
-        }
+        self.extent = extent;
+        self.history_record(edit, snapshots);
     }
 
-    pub fn record_view_painted(&mut self, layers: Vec<(LayerId, Vec<Rgba8>)>) {
-        let extent = self.extent;
-        self.history_record(Edit::ViewPainted(layers.iter().map(|(l, _)| *l).collect()));
+    /// Record the view's layers as painted. The extent is the view's current one,
+    /// which can differ from the recorded one if the frames were re-sliced.
+    pub fn record_view_painted(&mut self, layers: Vec<(LayerId, Vec<Rgba8>)>, extent: ViewExtent) {
+        let edit = Edit::ViewPainted(layers.iter().map(|(l, _)| *l).collect());
+        let snapshots = self.push_snapshots(layers, extent);
 
-        for (id, pixels) in layers.into_iter() {
-            self.layer_mut(id).push_snapshot(pixels, extent);
-        }
+        self.extent = extent;
+        self.history_record(edit, snapshots);
     }
 
     pub fn record_layer_painted(&mut self, layer: LayerId, pixels: Vec<Rgba8>, extent: ViewExtent) {
-        self.history_record(Edit::LayerPainted(layer));
-        self.layer_mut(layer).push_snapshot(pixels, extent);
+        let snapshots = self.push_snapshots(vec![(layer, pixels)], extent);
+        self.history_record(Edit::LayerPainted(layer), snapshots);
     }
 
-    pub fn history_truncate(&mut self) {
-        if self.cursor != self.history.len() - 1 {
-            self.history.truncate(self.cursor + 1);
-            self.cursor = self.history.len() - 1;
-        }
+    fn push_snapshots(
+        &mut self,
+        layers: Vec<(LayerId, Vec<Rgba8>)>,
+        extent: ViewExtent,
+    ) -> Vec<(LayerId, usize, usize)> {
+        layers
+            .into_iter()
+            .map(|(id, pixels)| {
+                let (before, after) = self.layer_mut(id).push_snapshot(pixels, extent);
+                (id, before, after)
+            })
+            .collect()
     }
 
-    pub fn history_record(&mut self, edit: Edit) {
+    /// Record an edit as a child of the current edit. Existing children are kept,
+    /// so that edits made after an undo start a new branch of the history.
+    fn history_record(&mut self, edit: Edit, snapshots: Vec<(LayerId, usize, usize)>) {
         debug!("edit: {:?}", edit);
 
-        // If we try to add an edit when we're not at the
-        // latest, we have to clear the list forward.
-        self.history_truncate();
-        self.cursor += 1;
-
-        self.history.push(edit);
+        let id = self.history.len();
+        let parent = self.cursor;
+        let grouped = if self.group > 0 {
+            self.grouped += 1;
+            self.grouped > 1
+        } else {
+            false
+        };
+
+        self.history.push(HistoryNode {
+            edit,
+            parent: Some(parent),
+            children: Vec::new(),
+            redo: None,
+            grouped,
+            time: time::Instant::now(),
+            snapshots,
+        });
+        if let Some(node) = self.history.get_mut(parent) {
+            node.children.push(id);
+            node.redo = Some(id);
+        }
+        self.cursor = id;
     }
 
     pub fn current_snapshot(&self, layer: LayerId) -> Option<(&Snapshot, &[Rgba8])> {
         self.layers.get(&layer).map(|l| l.current_snapshot())
     }
 
-    pub fn history_prev(&mut self) -> Option<(usize, Edit)> {
-        if self.cursor == 0 {
-            return None;
+    /// Step back to the parent of the current edit.
+    pub fn history_prev(&mut self) -> Option<(EditId, Edit)> {
+        let node = self.history.get(self.cursor).cloned()?;
+        let parent = node.parent?;
+
+        match node.edit {
+            Edit::LayerAdded(id) => {
+                self.layer_mut(id).hidden = true;
+            }
+            Edit::ViewResized(_, from, _) => {
+                self.extent = from;
+            }
+            _ => {}
         }
+        for (id, before, _) in node.snapshots.iter() {
+            self.layer_mut(*id).restore_snapshot(*before);
+        }
+        self.cursor = parent;
 
-        if let Some(edit) = self.history.get(self.cursor).cloned() {
-            match edit {
-                Edit::LayerPainted(id) => {
-                    self.layer_mut(id).prev_snapshot();
-                }
-                Edit::LayerAdded(id) => {
-                    self.layer_mut(id).hidden = true;
-                }
-                Edit::ViewResized(ref layers, from, _) => {
-                    self.extent = from;
+        Some((self.cursor, node.edit))
+    }
 
-                    for id in layers.iter() {
-                        self.layer_mut(*id).prev_snapshot();
-                    }
-                }
-                Edit::ViewPainted(ref layers) => {
-                    for id in layers.iter() {
-                        self.layer_mut(*id).prev_snapshot();
-                    }
-                }
-                _ => return None,
-            }
-            self.cursor -= 1;
+    /// Step forward to the redo child of the current edit.
+    pub fn history_next(&mut self) -> Option<(EditId, Edit)> {
+        let child = self.history.get(self.cursor)?.redo?;
+        let node = self.history.get(child).cloned()?;
 
-            Some((self.cursor, edit))
-        } else {
-            None
+        match node.edit {
+            Edit::LayerAdded(id) => {
+                self.layer_mut(id).hidden = false;
+            }
+            Edit::ViewResized(_, _, to) => {
+                self.extent = to;
+            }
+            _ => {}
         }
+        for (id, _, after) in node.snapshots.iter() {
+            self.layer_mut(*id).restore_snapshot(*after);
+        }
+        self.cursor = child;
+
+        Some((self.cursor, node.edit))
     }
 
-    pub fn history_next(&mut self) -> Option<(usize, Edit)> {
-        if let Some(edit) = self.history.get(self.cursor + 1).cloned() {
-            self.cursor += 1;
+    /// Area of a layer that changed with the given edit, if known.
+    pub fn history_dirty(&self, edit: EditId, layer_id: LayerId) -> Option<Rect<u32>> {
+        let node = self.history.get(edit)?;
+        let (_, _, after) = node.snapshots.iter().find(|(id, _, _)| *id == layer_id)?;
 
-            match edit {
-                Edit::LayerPainted(id) => {
-                    self.layer_mut(id).next_snapshot();
-                }
-                Edit::LayerAdded(id) => {
-                    self.layer_mut(id).hidden = false;
-                }
-                Edit::ViewResized(ref layers, _, to) => {
-                    self.extent = to;
+        self.layers.get(&layer_id)?.snapshots.get(*after)?.dirty
+    }
 
-                    for id in layers.iter() {
-                        self.layer_mut(*id).next_snapshot();
-                    }
-                }
-                Edit::ViewPainted(ref layers) => {
-                    for id in layers.iter() {
-                        self.layer_mut(*id).next_snapshot();
-                    }
-                }
-                _ => return None,
+    /// Make the given edit the one followed when redoing from its parent.
+    pub fn history_select(&mut self, edit: EditId) {
+        if let Some(parent) = self.history.get(edit).and_then(|n| n.parent) {
+            if let Some(node) = self.history.get_mut(parent) {
+                node.redo = Some(edit);
             }
-            Some((self.cursor, edit))
-        } else {
-            None
         }
     }
 
+    /// The given edit followed by its ancestors, up to the initial edit.
+    /// Returns an empty list if the edit doesn't exist.
+    pub fn history_ancestors(&self, edit: EditId) -> Vec<EditId> {
+        let mut ancestors = Vec::new();
+        let mut current = self.history.get(edit).map(|_| edit);
+
+        while let Some(id) = current {
+            ancestors.push(id);
+            current = self.history.get(id).and_then(|n| n.parent);
+        }
+        ancestors
+    }
+
+    /// Edits without children, ie. the tips of the history branches.
+    pub fn history_leaves(&self) -> Vec<EditId> {
+        self.history
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.children.is_empty())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
     pub fn current_edit(&self) -> EditId {
         self.cursor
     }
 
+    /// Start grouping edits, so that the edits recorded until the group is
+    /// ended are undone and redone as one. Groups can be nested, in which case
+    /// only the outermost group counts.
+    pub fn begin_group(&mut self) {
+        if self.group == 0 {
+            self.grouped = 0;
+        }
+        self.group += 1;
+    }
+
+    /// End the innermost undo group. Returns `false` if no group was open.
+    pub fn end_group(&mut self) -> bool {
+        if self.group == 0 {
+            return false;
+        }
+        self.group -= 1;
+        true
+    }
+
+    /// Whether the given edit belongs to the same undo group as its parent.
+    pub fn history_grouped(&self, edit: EditId) -> bool {
+        self.history.get(edit).is_some_and(|n| n.grouped)
+    }
+
+    /// The edit followed when redoing from the current edit.
+    pub fn history_redo(&self) -> Option<EditId> {
+        self.history.get(self.cursor).and_then(|n| n.redo)
+    }
+
+    /// When the edit that would be undone was recorded, if there is one.
+    pub fn undo_time(&self) -> Option<time::Instant> {
+        let node = self.history.get(self.cursor)?;
+        node.parent.map(|_| node.time)
+    }
+
+    /// When the edit that would be redone was recorded, if there is one.
+    pub fn redo_time(&self) -> Option<time::Instant> {
+        self.history_redo()
+            .and_then(|e| self.history.get(e))
+            .map(|n| n.time)
+    }
+
+    /// Create a named checkpoint at the current edit.
+    pub fn checkpoint(&mut self, name: &str) {
+        let layers = self
+            .layers()
+            .map(|(id, l)| (*id, l.current_snapshot().1.to_vec()))
+            .collect();
+
+        self.checkpoints.insert(
+            name.to_owned(),
+            Checkpoint {
+                edit: self.cursor,
+                extent: self.extent,
+                layers,
+            },
+        );
+    }
+
+    /// Coordinates of the pixels of a layer that changed since the given checkpoint.
+    /// Returns `None` if the checkpoint doesn't exist, or if the view was resized since.
+    pub fn checkpoint_diff(&self, name: &str, layer_id: LayerId) -> Option<Vec<LayerCoords<u32>>> {
+        let checkpoint = self.checkpoints.get(name)?;
+
+        if checkpoint.extent != self.extent {
+            return None;
+        }
+        let before = checkpoint.layers.get(&layer_id)?;
+        let (snapshot, after) = self.layers.get(&layer_id)?.current_snapshot();
+        let (w, h) = (snapshot.width(), snapshot.height());
+
+        Some(
+            before
+                .iter()
+                .zip(after.iter())
+                .enumerate()
+                .filter(|(_, (a, b))| a != b)
+                .map(|(i, _)| {
+                    let i = i as u32;
+                    // Pixel rows are stored top to bottom.
+                    LayerCoords::new(i % w, h - 1 - i / w)
+                })
+                .collect(),
+        )
+    }
+
+    /// Compute statistics on the current pixels and history of the view.
+    pub fn stats(&self) -> Stats {
+        let mut counts: BTreeMap<Rgba8, usize> = BTreeMap::new();
+        let mut stats = Stats {
+            colors: Vec::new(),
+            pixels: 0,
+            transparent: 0,
+            snapshots: 0,
+            memory: 0,
+        };
+
+        for layer in self.layers.values() {
+            let (_, pixels) = layer.current_snapshot();
+
+            for p in pixels {
+                if p.a == 0 {
+                    stats.transparent += 1;
+                } else {
+                    *counts.entry(*p).or_default() += 1;
+                }
+            }
+            stats.pixels += pixels.len();
+            stats.snapshots += layer.snapshots.len();
+            stats.memory += layer.memory();
+        }
+        stats.colors = counts.into_iter().collect();
+        stats
+            .colors
+            .sort_by_key(|(c, n)| (std::cmp::Reverse(*n), *c));
+
+        stats
+    }
+
+    /// Find the pixels of a layer with the given color, in row order, from the top.
+    pub fn find_color(&self, layer_id: LayerId, color: Rgba8) -> Vec<LayerCoords<u32>> {
+        let (snapshot, pixels) = match self.current_snapshot(layer_id) {
+            Some(s) => s,
+            None => return Vec::new(),
+        };
+        let (w, h) = (snapshot.width(), snapshot.height());
+
+        pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c == color)
+            .map(|(i, _)| {
+                let i = i as u32;
+                // Pixel rows are stored top to bottom.
+                LayerCoords::new(i % w, h - 1 - i / w)
+            })
+            .collect()
+    }
+
     pub fn save_archive<P: AsRef<Path>>(&self, path: P) -> io::Result<usize> {
         use std::io::Write;
         use zip::write::FileOptions;
@@ -237,7 +469,14 @@ impl ViewResource {
                     .expect("the rect is within the view");
 
                 buffer.clear();
-                image::write(&mut buffer, rect.width(), rect.height(), 1, &pixels)?;
+                image::write(
+                    &mut buffer,
+                    rect.width(),
+                    rect.height(),
+                    1,
+                    &pixels,
+                    &image::Metadata::default(),
+                )?;
 
                 let path = path
                     .join("frames")
@@ -256,144 +495,450 @@ impl ViewResource {
         Ok(written)
     }
 
-    pub fn save_png<P: AsRef<Path>>(
-        &self,
-        layer_id: LayerId,
-        path: P,
-        scale: u32,
-    ) -> io::Result<usize> {
-        let (snapshot, pixels) = self.layer(layer_id).current_snapshot();
-        let (w, h) = (snapshot.width(), snapshot.height());
-
-        image::save_as(path, w, h, scale, pixels)?;
-
-        Ok((w * h * scale) as usize)
+    /// Copy of the current pixels and extent of a layer, for exporting.
+    pub fn export_snapshot(&self, layer_id: LayerId) -> Option<(ViewExtent, Vec<Rgba8>)> {
+        self.current_snapshot(layer_id)
+            .map(|(snapshot, pixels)| (snapshot.extent, pixels.to_vec()))
     }
+}
 
-    pub fn save_svg<P: AsRef<Path>>(
-        &self,
-        layer_id: LayerId,
-        path: P,
-        scale: u32,
-    ) -> io::Result<usize> {
-        use std::io::Write;
+/// Save layer pixels as a PNG, optionally arranged in a sprite sheet. If a `sheet`
+/// size is given, the image is padded to that size.
+pub fn save_png<P: AsRef<Path>>(
+    path: P,
+    extent: ViewExtent,
+    pixels: &[Rgba8],
+    scale: u32,
+    layout: &SheetLayout,
+    meta: &image::Metadata,
+    sheet: Option<(u32, u32)>,
+) -> io::Result<usize> {
+    if *layout == SheetLayout::default() && sheet.is_none() {
+        let (w, h) = (extent.width(), extent.height());
+        image::save_as(path, w, h, scale, pixels, meta)?;
+
+        return Ok((w * h * scale) as usize);
+    }
 
-        let (snapshot, pixels) = self
-            .current_snapshot(layer_id)
-            .ok_or(io::ErrorKind::InvalidInput)?;
-        let (w, h) = (snapshot.width(), snapshot.height());
+    let (cols, rows) = layout.grid(extent.nframes);
+    if (cols as u64 * rows as u64) < extent.nframes as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "a {}x{} layout can't fit {} frames",
+                cols, rows, extent.nframes
+            ),
+        ));
+    }
+    let too_big = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "a {}x{} layout of {}x{} frames at scale {} is too big",
+                cols, rows, extent.fw, extent.fh, scale
+            ),
+        )
+    };
+    let (fw, fh) = match (extent.fw.checked_mul(scale), extent.fh.checked_mul(scale)) {
+        (Some(fw), Some(fh)) => (fw, fh),
+        _ => return Err(too_big()),
+    };
+    pixels::sheet_size(fw, fh, (cols, rows), layout.padding, layout.spacing).ok_or_else(too_big)?;
+
+    // Scale first, so that padding and spacing are in output pixels.
+    let pixels = if scale > 1 {
+        pixels::scale(pixels, extent.width(), extent.height(), scale)
+    } else {
+        pixels.to_vec()
+    };
+    let (pixels, w, h) = pixels::arrange(
+        &pixels,
+        fw,
+        fh,
+        extent.nframes,
+        (cols, rows),
+        layout.padding,
+        layout.spacing,
+    );
+    let (pixels, w, h) = match sheet {
+        Some((sw, sh)) if w > sw || h > sh => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}x{} image doesn't fit in a {}x{} sheet", w, h, sw, sh),
+            ));
+        }
+        Some(size) => (pixels::pad(&pixels, w, h, size), size.0, size.1),
+        None => (pixels, w, h),
+    };
+    image::save_as(path, w, h, 1, &pixels, &image::Metadata::default())?;
 
-        let f = File::create(path.as_ref())?;
-        let out = &mut io::BufWriter::new(f);
+    Ok((w * h) as usize)
+}
 
-        writeln!(
-            out,
-            r#"<svg width="{}" height="{}" viewBox="0 0 {} {}" fill="none" xmlns="http://www.w3.org/2000/svg">"#,
-            w * scale,
-            h * scale,
-            w * scale,
-            h * scale,
-        )?;
+/// Save layer palette indices as raw tile data.
+pub fn save_tiles<P: AsRef<Path>>(
+    path: P,
+    extent: ViewExtent,
+    indices: &[u8],
+    format: TileFormat,
+) -> io::Result<usize> {
+    let (w, h) = (extent.width(), extent.height());
+    let tiles = format.encode(w, h, indices)?;
 
-        for (i, rgba) in pixels.iter().cloned().enumerate().filter(|(_, c)| c.a > 0) {
-            let rgb: Rgb8 = rgba.into();
+    std::fs::write(path, tiles)?;
 
-            let x = (i as u32 % w) * scale;
-            let y = (i as u32 / h) * scale;
+    Ok((w * h) as usize)
+}
 
+/// Save layer pixels as a C source array of RGBA bytes, or as a Rust one if the file
+/// extension is `rs`. If a palette is given, `data` holds palette indices, and the
+/// palette is saved as a second array. Arrays are named after the file.
+pub fn save_array<P: AsRef<Path>>(
+    path: P,
+    extent: ViewExtent,
+    data: &[u8],
+    palette: Option<&[Rgba8]>,
+) -> io::Result<usize> {
+    use std::io::Write;
+
+    let path = path.as_ref();
+    let rust = path.extension().is_some_and(|e| e == "rs");
+    let name = self::identifier(path);
+    let (w, h) = (extent.width(), extent.height());
+
+    let f = File::create(path)?;
+    let out = &mut io::BufWriter::new(f);
+
+    let array = |out: &mut io::BufWriter<File>, name: &str, bytes: &[u8]| {
+        if rust {
             writeln!(
                 out,
-                r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
-                x, y, scale, scale, rgb
+                "pub static {}: [u8; {}] = [",
+                name.to_uppercase(),
+                bytes.len()
             )?;
+        } else {
+            writeln!(out, "const unsigned char {}[{}] = {{", name, bytes.len())?;
+        }
+        for line in bytes.chunks(16) {
+            let line: Vec<String> = line.iter().map(|b| format!("0x{:02x},", b)).collect();
+            writeln!(out, "    {}", line.join(" "))?;
         }
+        writeln!(out, "{}", if rust { "];" } else { "};" })
+    };
+
+    let upper = name.to_uppercase();
+    if rust {
+        writeln!(out, "pub const {}_WIDTH: usize = {};", upper, w)?;
+        writeln!(out, "pub const {}_HEIGHT: usize = {};", upper, h)?;
+    } else {
+        writeln!(out, "#define {}_WIDTH {}", upper, w)?;
+        writeln!(out, "#define {}_HEIGHT {}", upper, h)?;
+    }
+    writeln!(out)?;
+    array(out, &name, data)?;
 
-        writeln!(out, "</svg>")?;
+    if let Some(palette) = palette {
+        let bytes: Vec<u8> = palette
+            .iter()
+            .flat_map(|c| vec![c.r, c.g, c.b, c.a])
+            .collect();
 
-        Ok((w * h * scale) as usize)
+        writeln!(out)?;
+        array(out, &format!("{}_palette", name), &bytes)?;
     }
+    Ok((w * h) as usize)
+}
 
-    pub fn save_gif<P: AsRef<Path>>(
-        &self,
-        layer_id: LayerId,
-        path: P,
-        frame_delay: time::Duration,
-        palette: &[Rgba8],
-        scale: u32,
-    ) -> io::Result<usize> {
-        assert!(scale >= 1);
-
-        // The gif encoder expects the frame delay in units of 10ms.
-        let frame_delay = frame_delay.as_millis() / 10;
-        // If the passed in delay is larger than a `u16` can hold,
-        // we ensure it doesn't overflow.
-        let frame_delay = u128::min(frame_delay, u16::max_value() as u128) as u16;
-
-        let (snapshot, pixels) = self
-            .current_snapshot(layer_id)
-            .ok_or(io::ErrorKind::InvalidInput)?;
-        let extent = snapshot.extent;
-        let nframes = extent.nframes;
-
-        // Create a color palette for the gif, where the zero index is used
-        // for transparency.
-        let transparent: u8 = 0;
-        let mut palette = palette.to_vec();
-        palette.push(Rgba8::TRANSPARENT);
-        palette.sort();
-
-        assert!(palette[transparent as usize] == Rgba8::TRANSPARENT);
-        assert!(palette.len() <= 256);
-
-        // Convert RGBA pixels into indexed pixels.
-        let mut image: Vec<u8> = Vec::with_capacity(snapshot.size);
-        for rgba in pixels.iter().cloned() {
-            if let Ok(index) = palette.binary_search(&rgba) {
-                image.push(index as u8);
-            } else {
-                image.push(transparent);
-            }
+/// Save layer pixels as a Godot `SpriteFrames` resource, with a single animation
+/// named `default`. The frames are saved next to it, as a PNG strip with the same
+/// name. The playback mode is kept by ordering the frames accordingly.
+///
+/// Views don't have frame tags, and tags from other editors, eg. Aseprite, aren't
+/// imported, so there's no way to split the frames into several named animations.
+pub fn save_sprite_frames<P: AsRef<Path>>(
+    path: P,
+    extent: ViewExtent,
+    pixels: &[Rgba8],
+    scale: u32,
+    delay: time::Duration,
+    playback: Playback,
+) -> io::Result<usize> {
+    use std::io::Write;
+
+    let path = path.as_ref();
+    let png = path.with_extension("png");
+    let texture = png
+        .file_name()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid file name"))?;
+    let written = self::save_png(
+        &png,
+        extent,
+        pixels,
+        scale,
+        &SheetLayout::default(),
+        &image::Metadata::default(),
+        None,
+    )?;
+
+    let n = extent.nframes;
+    let frames: Vec<usize> = match playback {
+        Playback::Forward => (0..n).collect(),
+        Playback::Reverse => (0..n).rev().collect(),
+        Playback::PingPong => (0..n).chain((1..n.saturating_sub(1)).rev()).collect(),
+    };
+    let (fw, fh) = (extent.fw * scale, extent.fh * scale);
+    let speed = 1000. / delay.as_millis().max(1) as f64;
+
+    let f = File::create(path)?;
+    let out = &mut io::BufWriter::new(f);
+
+    writeln!(
+        out,
+        "[gd_resource type=\"SpriteFrames\" load_steps={} format=3]",
+        n + 2
+    )?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "[ext_resource type=\"Texture2D\" path=\"{}\" id=\"1\"]",
+        texture
+    )?;
+
+    for i in 0..n {
+        writeln!(out)?;
+        writeln!(
+            out,
+            "[sub_resource type=\"AtlasTexture\" id=\"AtlasTexture_{}\"]",
+            i
+        )?;
+        writeln!(out, "atlas = ExtResource(\"1\")")?;
+        writeln!(out, "region = Rect2({}, 0, {}, {})", i as u32 * fw, fw, fh)?;
+    }
+
+    let frames: Vec<String> = frames
+        .iter()
+        .map(|i| {
+            format!(
+                "{{\n\"duration\": 1.0,\n\"texture\": SubResource(\"AtlasTexture_{}\")\n}}",
+                i
+            )
+        })
+        .collect();
+
+    writeln!(out)?;
+    writeln!(out, "[resource]")?;
+    writeln!(out, "animations = [{{")?;
+    writeln!(out, "\"frames\": [{}],", frames.join(", "))?;
+    writeln!(out, "\"loop\": true,")?;
+    writeln!(out, "\"name\": &\"default\",")?;
+    writeln!(out, "\"speed\": {:.1}", speed)?;
+    writeln!(out, "}}]")?;
+
+    Ok(written)
+}
+
+/// Save layer pixels as an XPM image, named after the file.
+pub fn save_xpm<P: AsRef<Path>>(
+    path: P,
+    extent: ViewExtent,
+    pixels: &[Rgba8],
+) -> io::Result<usize> {
+    use std::io::Write;
+
+    // Printable characters, except for the quote and backslash.
+    const CHARS: &[u8] =
+        b" .+@#$%&*=-;>,')!~{]^/(_:<[}|1234567890abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ`";
+
+    let path = path.as_ref();
+    let (w, h) = (extent.width(), extent.height());
+
+    let mut colors: Vec<Rgba8> = Vec::new();
+    for c in pixels {
+        let c = if c.a == 0 { Rgba8::TRANSPARENT } else { *c };
+        if !colors.contains(&c) {
+            colors.push(c);
         }
-        if scale > 1 {
-            image = pixels::scale(&image, extent.width(), extent.height(), scale);
+    }
+    // Characters per pixel.
+    let (mut cpp, mut n) = (1, CHARS.len());
+    while n < colors.len() {
+        cpp += 1;
+        n *= CHARS.len();
+    }
+    let key = |i: usize| -> String {
+        (0..cpp)
+            .map(|k| CHARS[i / CHARS.len().pow(k) % CHARS.len()] as char)
+            .collect()
+    };
+
+    let f = File::create(path)?;
+    let out = &mut io::BufWriter::new(f);
+
+    writeln!(out, "/* XPM */")?;
+    writeln!(out, "static char *{}[] = {{", self::identifier(path))?;
+    writeln!(out, "\"{} {} {} {}\",", w, h, colors.len(), cpp)?;
+
+    for (i, c) in colors.iter().enumerate() {
+        if c.a == 0 {
+            writeln!(out, "\"{} c None\",", key(i))?;
+        } else {
+            writeln!(out, "\"{} c {}\",", key(i), Rgb8::from(*c))?;
         }
+    }
+    for (y, row) in pixels.chunks(w as usize).enumerate() {
+        let row: String = row
+            .iter()
+            .map(|c| {
+                let c = if c.a == 0 { Rgba8::TRANSPARENT } else { *c };
+                key(colors.iter().position(|p| *p == c).unwrap_or_default())
+            })
+            .collect();
+        let end = if y + 1 == h as usize { "" } else { "," };
+
+        writeln!(out, "\"{}\"{}", row, end)?;
+    }
+    writeln!(out, "}};")?;
 
-        let (fw, fh) = ((extent.fw * scale) as usize, (extent.fh * scale) as usize);
-        let mut frames: Vec<Vec<u8>> = Vec::with_capacity(nframes);
-        frames.resize(nframes, Vec::with_capacity(fw * fh));
+    Ok((w * h) as usize)
+}
 
-        {
-            // Convert animation strip into discrete frames for gif encoder.
-            let nrows = fh as usize * nframes;
-            let row_nbytes = fw as usize;
+/// A C identifier made from a file name.
+fn identifier(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    match name.chars().next() {
+        Some(c) if !c.is_ascii_digit() => name,
+        _ => format!("_{}", name),
+    }
+}
 
-            for i in 0..nrows {
-                let offset = i * row_nbytes;
-                let row = &image[offset..offset + row_nbytes];
+/// Save layer pixels as an SVG, with one rectangle per pixel.
+pub fn save_svg<P: AsRef<Path>>(
+    path: P,
+    extent: ViewExtent,
+    pixels: &[Rgba8],
+    scale: u32,
+) -> io::Result<usize> {
+    use std::io::Write;
 
-                frames[i % nframes].extend_from_slice(row);
-            }
+    let (w, h) = (extent.width(), extent.height());
+
+    let f = File::create(path.as_ref())?;
+    let out = &mut io::BufWriter::new(f);
+
+    writeln!(
+        out,
+        r#"<svg width="{}" height="{}" viewBox="0 0 {} {}" fill="none" xmlns="http://www.w3.org/2000/svg">"#,
+        w * scale,
+        h * scale,
+        w * scale,
+        h * scale,
+    )?;
+
+    for (i, rgba) in pixels.iter().cloned().enumerate().filter(|(_, c)| c.a > 0) {
+        let rgb: Rgb8 = rgba.into();
+
+        let x = (i as u32 % w) * scale;
+        let y = (i as u32 / h) * scale;
+
+        writeln!(
+            out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            x, y, scale, scale, rgb
+        )?;
+    }
+
+    writeln!(out, "</svg>")?;
+
+    Ok((w * h * scale) as usize)
+}
+
+/// Save layer pixels as an animated GIF, with one GIF frame per view frame.
+pub fn save_gif<P: AsRef<Path>>(
+    path: P,
+    extent: ViewExtent,
+    pixels: &[Rgba8],
+    frame_delay: time::Duration,
+    palette: &[Rgba8],
+    scale: u32,
+) -> io::Result<usize> {
+    assert!(scale >= 1);
+
+    // The gif encoder expects the frame delay in units of 10ms.
+    let frame_delay = frame_delay.as_millis() / 10;
+    // If the passed in delay is larger than a `u16` can hold,
+    // we ensure it doesn't overflow.
+    let frame_delay = u128::min(frame_delay, u16::max_value() as u128) as u16;
+
+    let nframes = extent.nframes;
+
+    // Create a color palette for the gif, where the zero index is used
+    // for transparency.
+    let transparent: u8 = 0;
+    let mut palette = palette.to_vec();
+    palette.push(Rgba8::TRANSPARENT);
+    palette.sort();
+
+    assert!(palette[transparent as usize] == Rgba8::TRANSPARENT);
+    assert!(palette.len() <= 256);
+
+    // Convert RGBA pixels into indexed pixels.
+    let mut image: Vec<u8> = Vec::with_capacity(pixels.len());
+    for rgba in pixels.iter().cloned() {
+        if let Ok(index) = palette.binary_search(&rgba) {
+            image.push(index as u8);
+        } else {
+            image.push(transparent);
         }
+    }
+    if scale > 1 {
+        image = pixels::scale(&image, extent.width(), extent.height(), scale);
+    }
 
-        // Discard alpha channel and convert to a `&[u8]`.
-        let palette: Vec<Rgb8> = palette.into_iter().map(Rgb8::from).collect();
-        let palette = util::align_u8(&palette);
+    let (fw, fh) = ((extent.fw * scale) as usize, (extent.fh * scale) as usize);
+    let mut frames: Vec<Vec<u8>> = Vec::with_capacity(nframes);
+    frames.resize(nframes, Vec::with_capacity(fw * fh));
 
-        let mut f = File::create(path.as_ref())?;
-        let mut encoder = gif::Encoder::new(&mut f, fw as u16, fh as u16, palette)?;
-        encoder.set(gif::Repeat::Infinite)?;
+    {
+        // Convert animation strip into discrete frames for gif encoder.
+        let nrows = fh as usize * nframes;
+        let row_nbytes = fw as usize;
 
-        for frame in frames.iter_mut() {
-            let mut frame =
-                gif::Frame::from_indexed_pixels(fw as u16, fh as u16, frame, Some(transparent));
-            frame.delay = frame_delay;
-            frame.dispose = gif::DisposalMethod::Background;
+        for i in 0..nrows {
+            let offset = i * row_nbytes;
+            let row = &image[offset..offset + row_nbytes];
 
-            encoder.write_frame(&frame)?;
+            frames[i % nframes].extend_from_slice(row);
         }
+    }
 
-        Ok(fw * fh * nframes)
+    // Discard alpha channel and convert to a `&[u8]`.
+    let palette: Vec<Rgb8> = palette.into_iter().map(Rgb8::from).collect();
+    let palette = util::align_u8(&palette);
+
+    let mut f = File::create(path.as_ref())?;
+    let mut encoder = gif::Encoder::new(&mut f, fw as u16, fh as u16, palette)?;
+    encoder.set(gif::Repeat::Infinite)?;
+
+    for frame in frames.iter_mut() {
+        let mut frame =
+            gif::Frame::from_indexed_pixels(fw as u16, fh as u16, frame, Some(transparent));
+        frame.delay = frame_delay;
+        frame.dispose = gif::DisposalMethod::Background;
+
+        encoder.write_frame(&frame)?;
     }
+
+    Ok(fw * fh * nframes)
 }
 
 #[derive(Debug)]
@@ -404,6 +949,9 @@ pub struct LayerResource {
     snapshot: usize,
     /// Current layer pixels. We keep a separate decompressed
     /// cache of the view pixels for performance reasons.
+    ///
+    /// Unlike snapshots, these aren't tiled: they're one contiguous buffer,
+    /// since that's what the renderers and exports read.
     pixels: Vec<Rgba8>,
     /// Whether this layer should be hidden.
     hidden: bool,
@@ -473,38 +1021,58 @@ impl LayerResource {
         Some((snapshot, buffer))
     }
 
-    pub fn push_snapshot(&mut self, pixels: Vec<Rgba8>, extent: ViewExtent) {
+    /// Add a snapshot and make it current. Returns the previous and new
+    /// snapshot indices.
+    pub fn push_snapshot(&mut self, pixels: Vec<Rgba8>, extent: ViewExtent) -> (usize, usize) {
         // FIXME: If pixels match current snapshot exactly, don't add the snapshot.
 
-        // If we try to add a snapshot when we're not at the
-        // latest, we have to clear the list forward.
-        if self.snapshot != self.snapshots.len() - 1 {
-            self.snapshots.truncate(self.snapshot + 1);
-            self.snapshot = self.snapshots.len() - 1;
-        }
-        self.snapshot += 1;
-        self.snapshots
-            .push(Snapshot::new(SnapshotId(self.snapshot), &pixels, extent));
+        // Snapshots are never discarded, since they may be part of
+        // another branch of the view history.
+        let before = self.snapshot;
+        let id = SnapshotId(self.snapshots.len());
+        let (current, _) = self.current_snapshot();
+
+        let snapshot = if current.extent == extent {
+            let h = extent.height();
+            let rect = pixels::changed(&self.pixels, &pixels, extent.width()).unwrap_or_default();
+            let mut snapshot = Snapshot::from_parent(id, &pixels, extent, current, rect);
+
+            // Pixel rows are stored top to bottom.
+            snapshot.dirty = Some(Rect::new(rect.x1, h - rect.y2, rect.x2, h - rect.y1));
+            snapshot
+        } else {
+            Snapshot::new(id, &pixels, extent)
+        };
+        self.snapshot = self.snapshots.len();
+        self.snapshots.push(snapshot);
         self.pixels = pixels;
+
+        (before, self.snapshot)
     }
 
-    pub fn prev_snapshot(&mut self) -> Option<&Snapshot> {
-        if self.snapshot == 0 {
-            return None;
-        }
-        if let Some(snapshot) = self.snapshots.get(self.snapshot - 1) {
-            self.snapshot -= 1;
-            self.pixels = snapshot.pixels();
+    /// Memory used by the layer snapshots and pixel cache, in bytes. Tiles that
+    /// are shared between snapshots are only counted once.
+    fn memory(&self) -> usize {
+        let mut seen = HashSet::new();
+        let mut size = self.pixels.len() * std::mem::size_of::<Rgba8>();
 
-            Some(snapshot)
-        } else {
-            None
+        for snapshot in self.snapshots.iter() {
+            for tile in snapshot.tiles.tiles.iter().flatten() {
+                if seen.insert(Rc::as_ptr(tile)) {
+                    size += tile.0.len();
+                }
+            }
         }
+        size
     }
 
-    pub fn next_snapshot(&mut self) -> Option<&Snapshot> {
-        if let Some(snapshot) = self.snapshots.get(self.snapshot + 1) {
-            self.snapshot += 1;
+    /// Make the snapshot at the given index current.
+    pub fn restore_snapshot(&mut self, index: usize) -> Option<&Snapshot> {
+        if index == self.snapshot {
+            return self.snapshots.get(index);
+        }
+        if let Some(snapshot) = self.snapshots.get(index) {
+            self.snapshot = index;
             self.pixels = snapshot.pixels();
 
             Some(snapshot)
@@ -525,6 +1093,35 @@ pub enum Edit {
 
 pub type EditId = usize;
 
+/// A node in the history tree.
+#[derive(Debug, Clone)]
+pub struct HistoryNode {
+    pub edit: Edit,
+    /// Edit this one was made on top of. Only the initial edit has no parent.
+    pub parent: Option<EditId>,
+    /// Edits made on top of this one, oldest first.
+    pub children: Vec<EditId>,
+    /// Child followed when redoing. This is the most recently visited branch.
+    pub redo: Option<EditId>,
+    /// Whether this edit is undone and redone together with its parent.
+    pub grouped: bool,
+    /// When the edit was recorded.
+    pub time: time::Instant,
+    /// Layer snapshot indices, before and after the edit.
+    snapshots: Vec<(LayerId, usize, usize)>,
+}
+
+/// A named point in the view history, which can be reverted to.
+#[derive(Debug)]
+pub struct Checkpoint {
+    /// Edit the checkpoint was created at.
+    pub edit: EditId,
+    /// View extent at the checkpoint.
+    pub extent: ViewExtent,
+    /// Layer pixels at the checkpoint.
+    pub layers: BTreeMap<LayerId, Vec<Rgba8>>,
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
 pub struct SnapshotId(usize);
 
@@ -538,9 +1135,11 @@ impl fmt::Display for SnapshotId {
 pub struct Snapshot {
     pub id: SnapshotId,
     pub extent: ViewExtent,
+    /// Area that changed since the snapshot this one was created from, in
+    /// layer coordinates. `None` if unknown, eg. if the extent changed.
+    pub dirty: Option<Rect<u32>>,
 
-    size: usize,
-    pixels: Compressed<Box<[u8]>>,
+    tiles: Tiles,
 }
 
 impl Snapshot {
@@ -554,20 +1153,41 @@ impl Snapshot {
 
 impl Snapshot {
     pub fn new(id: SnapshotId, pixels: &[Rgba8], extent: ViewExtent) -> Self {
+        Self::with_tiles(id, pixels, extent, None)
+    }
+
+    /// Create a snapshot that shares the tiles outside of `changed` with its parent.
+    /// The `changed` area is in image coordinates, ie. with the first row at `y = 0`.
+    pub fn from_parent(
+        id: SnapshotId,
+        pixels: &[Rgba8],
+        extent: ViewExtent,
+        parent: &Snapshot,
+        changed: Rect<u32>,
+    ) -> Self {
+        Self::with_tiles(id, pixels, extent, Some((&parent.tiles, changed)))
+    }
+
+    fn with_tiles(
+        id: SnapshotId,
+        pixels: &[Rgba8],
+        extent: ViewExtent,
+        parent: Option<(&Tiles, Rect<u32>)>,
+    ) -> Self {
         let size = pixels.len();
-        let pixels =
-            Compressed::from(pixels).expect("compressing snapshot shouldn't result in an error");
 
         debug_assert!(
             (extent.fw * extent.fh) as usize * extent.nframes == size,
             "the pixel buffer has the expected size"
         );
+        let width = extent.fw * extent.nframes as u32;
+        let tiles = Tiles::new(pixels, width, extent.fh, parent);
 
         Self {
             id,
             extent,
-            size,
-            pixels,
+            dirty: None,
+            tiles,
         }
     }
 
@@ -582,11 +1202,104 @@ impl Snapshot {
     ////////////////////////////////////////////////////////////////////////////
 
     fn pixels(&self) -> Vec<Rgba8> {
-        let bytes = self
-            .pixels
-            .decompress()
-            .expect("decompressing snapshot shouldn't result in an error");
-        Rgba8::align(&bytes).into()
+        self.tiles.pixels()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Snapshot pixels, split into square tiles. Tiles are allocated on demand:
+/// fully transparent tiles aren't stored, and tiles that didn't change are
+/// shared with the parent snapshot. Only the undo history is stored this way;
+/// the current pixels of a layer are kept whole.
+#[derive(Debug)]
+struct Tiles {
+    width: u32,
+    height: u32,
+    tiles: Vec<Option<Rc<Compressed<Box<[u8]>>>>>,
+}
+
+impl Tiles {
+    /// Tile width and height, in pixels.
+    const SIZE: u32 = 64;
+
+    fn new(pixels: &[Rgba8], width: u32, height: u32, parent: Option<(&Tiles, Rect<u32>)>) -> Self {
+        let parent = parent.filter(|(p, _)| p.width == width && p.height == height);
+        let (cols, rows) = Self::grid(width, height);
+        let mut tiles = Vec::with_capacity((cols * rows) as usize);
+        let mut buffer = Vec::with_capacity((Self::SIZE * Self::SIZE) as usize);
+
+        for (i, rect) in Self::rects(width, height).enumerate() {
+            if let Some((parent, changed)) = parent {
+                if !rect.intersects(changed) {
+                    tiles.push(parent.tiles[i].clone());
+                    continue;
+                }
+            }
+            buffer.clear();
+
+            for y in rect.y1..rect.y2 {
+                let offset = (y * width + rect.x1) as usize;
+                buffer.extend_from_slice(&pixels[offset..offset + rect.width() as usize]);
+            }
+
+            if buffer.iter().all(|p| *p == Rgba8::TRANSPARENT) {
+                tiles.push(None);
+            } else {
+                tiles.push(Some(Rc::new(
+                    Compressed::from(&buffer)
+                        .expect("compressing snapshot shouldn't result in an error"),
+                )));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            tiles,
+        }
+    }
+
+    fn pixels(&self) -> Vec<Rgba8> {
+        let mut pixels = vec![Rgba8::TRANSPARENT; (self.width * self.height) as usize];
+
+        for (tile, rect) in self.tiles.iter().zip(Self::rects(self.width, self.height)) {
+            if let Some(tile) = tile {
+                let bytes = tile
+                    .decompress()
+                    .expect("decompressing snapshot shouldn't result in an error");
+                let texels = Rgba8::align(&bytes);
+                let w = rect.width() as usize;
+
+                for (row, y) in (rect.y1..rect.y2).enumerate() {
+                    let offset = (y * self.width + rect.x1) as usize;
+                    pixels[offset..offset + w].copy_from_slice(&texels[row * w..(row + 1) * w]);
+                }
+            }
+        }
+        pixels
+    }
+
+    /// Number of tile columns and rows.
+    fn grid(width: u32, height: u32) -> (u32, u32) {
+        (width.div_ceil(Self::SIZE), height.div_ceil(Self::SIZE))
+    }
+
+    /// Tile areas, row by row.
+    fn rects(width: u32, height: u32) -> impl Iterator<Item = Rect<u32>> {
+        let (cols, rows) = Self::grid(width, height);
+
+        (0..rows).flat_map(move |ty| {
+            (0..cols).map(move |tx| {
+                let (x, y) = (tx * Self::SIZE, ty * Self::SIZE);
+                Rect::new(
+                    x,
+                    y,
+                    (x + Self::SIZE).min(width),
+                    (y + Self::SIZE).min(height),
+                )
+            })
+        })
     }
 }
 
@@ -607,3 +1320,213 @@ impl Compressed<Box<[u8]>> {
         dec.decompress_vec(&self.0)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_checkpoints() {
+        let layer = LayerId::default();
+        let red = Rgba8::new(0xff, 0, 0, 0xff);
+        let mut res = ViewResource::new(vec![Rgba8::TRANSPARENT; 2], ViewExtent::new(2, 1, 1));
+
+        res.checkpoint("a");
+        res.record_view_painted(vec![(layer, vec![red, Rgba8::TRANSPARENT])], res.extent);
+
+        assert_eq!(
+            res.checkpoint_diff("a", layer),
+            Some(vec![LayerCoords::new(0, 0)])
+        );
+        assert_eq!(res.checkpoint_diff("b", layer), None);
+
+        // Checkpoints on another branch of the history are kept.
+        res.checkpoint("b");
+        res.history_prev();
+        res.record_view_painted(vec![(layer, vec![Rgba8::TRANSPARENT, red])], res.extent);
+
+        assert!(res.checkpoints.contains_key("a"));
+        assert!(res.checkpoints.contains_key("b"));
+    }
+
+    #[test]
+    fn test_history_branches() {
+        let layer = LayerId::default();
+        let (red, blue) = (Rgba8::new(0xff, 0, 0, 0xff), Rgba8::new(0, 0, 0xff, 0xff));
+        let mut res = ViewResource::new(vec![Rgba8::TRANSPARENT], ViewExtent::new(1, 1, 1));
+        let pixel = |res: &ViewResource| res.layer(layer).current_snapshot().1[0];
+
+        res.record_layer_painted(layer, vec![red], ViewExtent::new(1, 1, 1));
+        res.history_prev();
+        res.record_layer_painted(layer, vec![blue], ViewExtent::new(1, 1, 1));
+
+        // Both branches are kept, and redo follows the latest one.
+        assert_eq!(res.history_leaves(), vec![1, 2]);
+        assert_eq!(res.history_ancestors(2), vec![2, 0]);
+        assert_eq!(pixel(&res), blue);
+
+        res.history_prev();
+        assert_eq!(pixel(&res), Rgba8::TRANSPARENT);
+
+        res.history_select(1);
+        res.history_next();
+        assert_eq!(res.current_edit(), 1);
+        assert_eq!(pixel(&res), red);
+    }
+
+    #[test]
+    fn test_history_groups() {
+        let layer = LayerId::default();
+        let extent = ViewExtent::new(1, 1, 1);
+        let (red, blue) = (Rgba8::new(0xff, 0, 0, 0xff), Rgba8::new(0, 0, 0xff, 0xff));
+        let mut res = ViewResource::new(vec![Rgba8::TRANSPARENT], extent);
+
+        res.begin_group();
+        res.record_layer_painted(layer, vec![red], extent);
+        res.begin_group();
+        res.record_layer_painted(layer, vec![blue], extent);
+        assert!(res.end_group());
+        res.record_layer_painted(layer, vec![red], extent);
+        assert!(res.end_group());
+        assert!(!res.end_group());
+        res.record_layer_painted(layer, vec![blue], extent);
+
+        // The first edit of a group starts a new undo step.
+        assert!(!res.history_grouped(1));
+        assert!(res.history_grouped(2));
+        assert!(res.history_grouped(3));
+        assert!(!res.history_grouped(4));
+
+        res.history_prev();
+        assert_eq!(res.history_redo(), Some(4));
+    }
+
+    #[test]
+    fn test_snapshot_tiles() {
+        let extent = ViewExtent::new(100, 70, 1);
+        let red = Rgba8::new(0xff, 0, 0, 0xff);
+        let mut pixels = vec![Rgba8::TRANSPARENT; 100 * 70];
+        pixels[0] = red;
+
+        let parent = Snapshot::new(SnapshotId(0), &pixels, extent);
+        // Only the top-left tile is allocated.
+        assert_eq!(parent.tiles.tiles.iter().filter(|t| t.is_some()).count(), 1);
+        assert_eq!(parent.pixels(), pixels);
+
+        // Paint the bottom-right pixel.
+        pixels[100 * 70 - 1] = red;
+        let (x, y) = (99, 69);
+        let changed = Rect::new(x, y, x + 1, y + 1);
+        let child = Snapshot::from_parent(SnapshotId(1), &pixels, extent, &parent, changed);
+
+        // The unchanged tile is shared with the parent.
+        assert!(Rc::ptr_eq(
+            parent.tiles.tiles[0].as_ref().unwrap(),
+            child.tiles.tiles[0].as_ref().unwrap()
+        ));
+        assert_eq!(child.pixels(), pixels);
+    }
+
+    #[test]
+    fn test_stats() {
+        let extent = ViewExtent::new(2, 2, 1);
+        let (red, blue) = (Rgba8::new(0xff, 0, 0, 0xff), Rgba8::new(0, 0, 0xff, 0xff));
+        let mut resource = ViewResource::new(vec![Rgba8::TRANSPARENT; 4], extent);
+        let layer = LayerId::default();
+
+        resource.record_layer_painted(layer, vec![red, blue, red, Rgba8::TRANSPARENT], extent);
+
+        let stats = resource.stats();
+        assert_eq!(stats.colors, vec![(red, 2), (blue, 1)]);
+        assert_eq!((stats.pixels, stats.transparent), (4, 1));
+        assert_eq!(stats.snapshots, 2);
+        assert!(stats.memory >= 4 * 4);
+    }
+
+    #[test]
+    fn test_save_array() {
+        let tmp = tempfile::tempdir().unwrap();
+        let extent = ViewExtent::new(2, 1, 1);
+        let palette = [Rgba8::RED];
+
+        let path = tmp.path().join("my-sprite.h");
+        save_array(&path, extent, &[0, 0], Some(&palette)).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "#define MY_SPRITE_WIDTH 2\n\
+             #define MY_SPRITE_HEIGHT 1\n\
+             \n\
+             const unsigned char my_sprite[2] = {\n    \
+             0x00, 0x00,\n\
+             };\n\
+             \n\
+             const unsigned char my_sprite_palette[4] = {\n    \
+             0xff, 0x00, 0x00, 0xff,\n\
+             };\n"
+        );
+
+        let path = tmp.path().join("1up.rs");
+        save_array(&path, extent, &[0xff; 8], None).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "pub const _1UP_WIDTH: usize = 2;\n\
+             pub const _1UP_HEIGHT: usize = 1;\n\
+             \n\
+             pub static _1UP: [u8; 8] = [\n    \
+             0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,\n\
+             ];\n"
+        );
+    }
+
+    #[test]
+    fn test_save_xpm() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("sprite.xpm");
+        let t = Rgba8::new(0xff, 0xff, 0xff, 0);
+
+        // Transparent pixels share a color, whatever their channels.
+        save_xpm(
+            &path,
+            ViewExtent::new(3, 1, 1),
+            &[t, Rgba8::RED, Rgba8::TRANSPARENT],
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "/* XPM */\n\
+             static char *sprite[] = {\n\
+             \"3 1 2 1\",\n\
+             \"  c None\",\n\
+             \". c #FF0000\",\n\
+             \" . \"\n\
+             };\n"
+        );
+    }
+
+    #[test]
+    fn test_save_sprite_frames() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("hero.tres");
+        let extent = ViewExtent::new(4, 2, 3);
+        let pixels = vec![Rgba8::TRANSPARENT; 12 * 2];
+        let delay = time::Duration::from_millis(100);
+
+        save_sprite_frames(&path, extent, &pixels, 2, delay, Playback::PingPong).unwrap();
+
+        let tres = std::fs::read_to_string(&path).unwrap();
+        let texture = |i: usize| format!("SubResource(\"AtlasTexture_{}\")", i);
+        let order = tres
+            .match_indices("SubResource")
+            .map(|(i, _)| &tres[i..i + texture(0).len()])
+            .collect::<Vec<_>>();
+
+        assert!(tres.starts_with("[gd_resource type=\"SpriteFrames\" load_steps=5 format=3]"));
+        assert!(tres.contains("[ext_resource type=\"Texture2D\" path=\"hero.png\" id=\"1\"]"));
+        assert!(tres.contains("region = Rect2(16, 0, 8, 4)"));
+        assert!(tres.contains("\"speed\": 10.0"));
+        assert_eq!(order, vec![texture(0), texture(1), texture(2), texture(1)]);
+
+        let (_, w, h, _) = image::load(tmp.path().join("hero.png")).unwrap();
+        assert_eq!((w, h), (24, 4));
+    }
+}
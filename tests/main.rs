@@ -171,6 +171,8 @@ fn run(name: &str) -> io::Result<()> {
         exec: ExecutionMode::Replay(path.clone(), DigestMode::Verify),
         glyphs,
         debug: false,
+        readonly: false,
+        frames: false,
     };
 
     {